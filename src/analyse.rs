@@ -6,10 +6,22 @@ impl Function {
         self.compute_successors();
         self.compute_postorder(); // Depends on successors.
         self.compute_dominators(); // Depends on predecessors.
+        self.compute_loops(); // Depends on successors and dominators.
         self.compute_liveness();
         self
     }
 
+    // Recomputes all analyses that depend on the shape of the CFG.
+    pub fn compute_cfg(&mut self) {
+        self.compute_predecessors();
+        self.compute_successors();
+        self.compute_postorder(); // Depends on successors.
+        self.compute_preorder(); // Depends on successors.
+        self.compute_reverse_postorder_number(); // Depends on postorder.
+        self.compute_dominators(); // Depends on predecessors.
+        self.compute_loops(); // Depends on successors and dominators.
+    }
+
     pub fn with_predecessors(mut self) -> Self {
         self.compute_predecessors();
         self
@@ -35,6 +47,11 @@ impl Function {
         self
     }
 
+    pub fn with_loops(mut self) -> Self {
+        self.compute_loops();
+        self
+    }
+
     pub fn with_liveness(mut self) -> Self {
         self.compute_liveness();
         self
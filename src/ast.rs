@@ -4,14 +4,56 @@ pub type Name = String;
 
 pub type LocalId = String;
 
+// Functions that can call each other, and the traits that types implement with some of them.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub traits: Vec<Trait>,
+    pub impls: Vec<Impl>,
+}
+
+// `trait Foo { fn get(&self) -> &i32; }`, whose methods take a receiver `self` of type `Self`,
+// or a reference to it.
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub id: Name,
+    pub methods: Vec<Signature>,
+}
+
+// The signature of a method of a trait, where `Self` is a type parameter.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub id: Name,
+    pub lifetimes: Vec<Lifetime>,
+    pub params: Vec<Local>,
+    pub ty: Type,
+}
+
+// `impl Foo for S { .. }`. Its methods are functions of the program, see `function_id`.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub trait_id: Name,
+    pub ty: Type,
+    pub methods: Vec<Name>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Function {
     pub id: Name,
+    pub lifetimes: Vec<Lifetime>,
+    pub type_params: Vec<Name>,
     pub params: Vec<Local>,
     pub ty: Type,
     pub block: Block,
 }
 
+// A lifetime parameter `'a: 'b + 'c`, which outlives its bounds.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Lifetime {
+    pub id: Name,
+    pub bounds: Vec<Name>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Local {
     pub id: LocalId,
@@ -26,8 +68,14 @@ pub struct Block {
 }
 
 impl Block {
+    // A block without a final expression has the never type if one of its statements does, since
+    // its end is never reached.
     pub fn ty(&self) -> &Type {
-        self.expr.as_ref().map_or(&Type::Unit, |e| e.ty())
+        match &self.expr {
+            Some(e) => e.ty(),
+            None if self.stmts.iter().any(|s| s.ty() == &Type::Never) => &Type::Never,
+            None => &Type::Unit,
+        }
     }
 }
 
@@ -37,29 +85,101 @@ pub enum Stmt {
     Expr(Expr),
 }
 
+impl Stmt {
+    pub fn ty(&self) -> &Type {
+        match self {
+            Stmt::Let(_, Some(e)) | Stmt::Expr(e) => e.ty(),
+            Stmt::Let(_, None) => &Type::Unit,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Expr {
     IfElse(Type, Rc<Expr>, Rc<Block>, Rc<Block>),
-    While(Type, Rc<Expr>, Rc<Block>),
+    While(Type, Option<usize>, Rc<Expr>, Rc<Block>),
     Loop(Type, Option<usize>, Rc<Block>),
     Tuple(Type, Vec<Expr>),
+    // An array of the values of the expressions, e.g., `[1, 2, 3]`.
+    Array(Type, Vec<Expr>),
+    Struct(Type, Vec<(Name, Expr)>),
+    // A variant of an enum with its payload, e.g., `Option::Some(x)`.
+    Variant(Type, Name, Vec<Expr>),
+    Match(Type, Place, Vec<Arm>),
     Ref(Type, Place),
     RefMut(Type, Place),
     Seq(Type, Rc<Expr>, Rc<Expr>),
     Assign(Type, Place, Rc<Expr>),
     Place(Type, Place),
-    Add(Type, Rc<Expr>, Rc<Expr>),
-    Int(Type, i32),
+    // `add(a, b)`, `sub(a, b)` or `mul(a, b)` of integers of the same type.
+    Arith(Type, ArithOp, Rc<Expr>, Rc<Expr>),
+    // An integer literal, whose type is that of its suffix, if it has one.
+    Int(Type, i128),
+    // Converts an integer or bool to the integer type of the expression, like `as` in Rust.
+    Cast(Type, Rc<Expr>),
     Bool(Type, bool),
     String(Type, String),
     Print(Type, Rc<Expr>),
+    // Moves a value into a new heap allocation.
+    Box(Type, Rc<Expr>),
+    // A call of a function of the program.
+    Call(Type, Name, Vec<Expr>),
+    // A call of a method, e.g., `x.get()`, which inference resolves to a call of the function of
+    // an impl, with the receiver passed explicitly.
+    MethodCall(Type, Place, Name, Vec<Expr>),
+    // A closure, e.g., `|x: i32| add(x, y)` or `move || print(s)`, which captures the places of
+    // the enclosing function that its body uses.
+    Closure(Type, Rc<Lambda>),
+    // A call of the closure that a local holds, e.g., `c(1)`.
+    CallClosure(Type, Place, Vec<Expr>),
     Unit(Type),
     Return(Type, Rc<Expr>),
     Continue(Type, Option<usize>),
-    Break(Type, Option<usize>),
+    // Leaves a loop, which then has the value of the expression.
+    Break(Type, Option<usize>, Option<Rc<Expr>>),
     Block(Type, Rc<Block>),
 }
 
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub is_move: bool,
+    pub params: Vec<Local>,
+    pub body: Expr,
+    // The values that the closure holds, in the order of the captures of its type, e.g., `&x.f`
+    // for a place that it captures by reference. Found by inference, see `closures.rs`.
+    pub captures: Vec<Expr>,
+    // The body as a function of the program, which takes the closure as its first parameter.
+    // Lifted once the types of the body are known.
+    pub function: Option<Rc<Function>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub block: Rc<Block>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // `_`
+    Wildcard,
+    // `Enum::Variant(p0, p1, ..)` where a `None` binding is `_`.
+    Variant(Name, Name, Vec<Option<Binding>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub mode: BindingMode,
+    pub local: Local,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingMode {
+    Value,
+    Ref,
+    RefMut,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Place {
     pub local: Local,
@@ -68,8 +188,20 @@ pub struct Place {
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum PlaceElem {
-    Index(usize),
+    // A field of a tuple.
+    TupleIndex(usize),
+    Field(Name),
+    // The payload of a variant of an enum, which is a tuple.
+    Downcast(Name),
     Deref,
+    // An element of an array or slice at an index that is known statically, e.g., `a[1]`.
+    ConstantIndex(usize),
+    // An element of an array or slice at the index held by a local of type usize, e.g., `a[i]`,
+    // which is only known at runtime.
+    Index(Local),
+    // The elements `from..to` of an array or slice, which are a slice, e.g., `a[1..3]`. Without
+    // `to` it reaches to the end, e.g., `a[1..]`.
+    Subslice { from: usize, to: Option<usize> },
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -80,8 +212,152 @@ pub enum Type {
     String,
     Unknown,
     Tuple(Vec<Type>),
+    Struct(Rc<Struct>),
+    Enum(Rc<Enum>),
     Ref(Vec<Loan>, Rc<Type>),
     RefMut(Vec<Loan>, Rc<Type>),
+    // An owning pointer to a heap allocation, which is freed when the box is dropped.
+    Box(Rc<Type>),
+    // A type parameter of a generic function, which is replaced by a concrete type when the
+    // function is monomorphized.
+    Param(Name),
+    // A type variable of inference, which stands for a type that is not known yet. Unification
+    // finds the type, and no variable is left once a function has been inferred.
+    Var(usize),
+    // The type of expressions that never produce a value, e.g., `return` and `break`, which can
+    // be used as a value of any type.
+    Never,
+    // An integer type other than i32, which is `Int`. Use `Type::int` to construct one.
+    Integer(IntTy),
+    // An array `[T; N]` of a fixed number of elements.
+    Array(Rc<Type>, usize),
+    // A slice `[T]` of a number of elements that is only known at runtime. It is unsized, so it
+    // can only be reached through a reference, e.g., `&[T]`.
+    Slice(Rc<Type>),
+    // The type of a closure, which is unique to its expression. Like a tuple, it consists of
+    // what the closure captures, so it carries the loans of the places it captures by reference.
+    Closure(Rc<Closure>),
+    // A `Cell<T>` or `RefCell<T>`, whose contents can be mutated through a shared reference.
+    Cell(CellKind, Rc<Type>),
+    // A guard of a borrow of the contents of a `RefCell`, like `Ref<T>` and `RefMut<T>` in Rust,
+    // which is dereferenced like a reference. Its loans are shared borrows of the `RefCell`,
+    // since the exclusivity of a mutable borrow is only checked at runtime.
+    Guard(Vec<Loan>, Rc<Type>),
+    GuardMut(Vec<Loan>, Rc<Type>),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Closure {
+    pub id: Name,
+    pub kind: ClosureKind,
+    pub params: Vec<Type>,
+    pub ty: Type,
+    // The types of the captures, e.g., `&{shared(x.f)} i32` for `x.f` captured by reference.
+    pub captures: Vec<Type>,
+}
+
+// How a closure is called, like the `Fn`, `FnMut` and `FnOnce` traits of Rust: through a shared
+// reference if its body only reads what it captures, through a mutable reference if it mutates
+// it, and by value if it moves out of it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ClosureKind {
+    Fn,
+    FnMut,
+    FnOnce,
+}
+
+// Which of the cells of Rust a `Type::Cell` is. A `Cell` is read and written by copying values in
+// and out of it, while a `RefCell` is borrowed, and counts its borrows at runtime.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CellKind {
+    Cell,
+    RefCell,
+}
+
+// An arithmetic operation on integers, which is lowered to a call of the builtin of its name.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+// A projection of a place in which the elements of an array or slice are the range `lo..hi` of
+// positions they may be at.
+#[derive(Debug, Clone, PartialEq)]
+enum Projection {
+    Elem(PlaceElem),
+    Range(usize, Bound),
+}
+
+// The end of a range of positions in an array or slice, counted from its start or from its end,
+// since the length of a slice is only known at runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Start(usize),
+    End(usize),
+}
+
+impl Bound {
+    // Whether the bound is at most the other bound for every length.
+    fn le(self, other: Bound) -> bool {
+        match (self, other) {
+            (Bound::Start(i), Bound::Start(j)) => i <= j,
+            (Bound::End(i), Bound::End(j)) => i >= j,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IntTy {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+}
+
+// A struct with named fields. The type of a struct value carries the types of its fields, which
+// for a struct literal are the types of its field expressions, so that loans are not lost.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Struct {
+    pub id: Name,
+    pub fields: Vec<(Name, Type)>,
+}
+
+impl Struct {
+    // Returns the position and type of a field.
+    pub fn field(&self, name: &str) -> Option<(usize, &Type)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .find(|(_, (id, _))| id == name)
+            .map(|(i, (_, t))| (i, t))
+    }
+}
+
+// An enum whose variants carry tuples. Like for structs, the payload types of an enum value carry
+// the loans of the values it was built from.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Enum {
+    pub id: Name,
+    pub variants: Vec<(Name, Type)>,
+}
+
+impl Enum {
+    // Returns the discriminant and payload type of a variant.
+    pub fn variant(&self, name: &str) -> Option<(usize, &Type)> {
+        self.variants
+            .iter()
+            .enumerate()
+            .find(|(_, (id, _))| id == name)
+            .map(|(i, (_, t))| (i, t))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -90,6 +366,50 @@ pub struct Loan {
     pub mutable: bool,
 }
 
+impl Loan {
+    // A loan for a lifetime parameter `'a` is a loan of something outside the function. It is
+    // represented as a loan of a place named after the lifetime, which is disjoint from every
+    // place of the function.
+    pub fn lifetime(id: &str, mutable: bool) -> Loan {
+        Loan {
+            place: Place {
+                local: Local {
+                    id: id.to_string(),
+                    ty: Type::Unit,
+                    mutable: false,
+                },
+                elems: vec![],
+            },
+            mutable,
+        }
+    }
+
+    pub fn lifetime_id(&self) -> Option<&Name> {
+        let id = &self.place.local.id;
+        (id.starts_with('\'') && self.place.elems.is_empty()).then_some(id)
+    }
+}
+
+impl Program {
+    pub fn function(&self, id: &str) -> Option<&Function> {
+        self.functions.iter().find(|f| f.id == id)
+    }
+}
+
+impl Impl {
+    // The function that implements a method, e.g., `<S as Foo>::get`, like Rust's fully
+    // qualified path to it.
+    pub fn function_id(&self, method: &str) -> Name {
+        format!("<{} as {}>::{}", self.ty, self.trait_id, method)
+    }
+}
+
+impl Function {
+    pub fn lifetime(&self, id: &str) -> Option<&Lifetime> {
+        self.lifetimes.iter().find(|l| l.id == id)
+    }
+}
+
 impl Place {
     pub fn is_prefix_of(&self, other: &Place) -> bool {
         if self.local.id != other.local.id {
@@ -110,51 +430,156 @@ impl Place {
             }
         }
     }
+
+    // Whether two places may denote overlapping memory, i.e., whether one may be a prefix of the
+    // other. Elements of arrays and slices are compared by the ranges of positions they may be
+    // at, e.g., `a[0..2]` and `a[2]` are disjoint while `a[0..2]` and `a[1..]` overlap. An index
+    // that is only known at runtime may be any position of what it indexes into.
+    pub fn may_overlap(&self, other: &Place) -> bool {
+        self.local.id == other.local.id
+            && self
+                .projections()
+                .iter()
+                .zip(&other.projections())
+                .all(|(p1, p2)| match (p1, p2) {
+                    (Projection::Range(lo1, hi1), Projection::Range(lo2, hi2)) => {
+                        !hi1.le(Bound::Start(*lo2)) && !hi2.le(Bound::Start(*lo1))
+                    }
+                    (p1, p2) => p1 == p2,
+                })
+    }
+
+    // The projections of the place, where every run of indices and subslices into an array or
+    // slice becomes the range of positions it may be at, e.g., `a[1..3][0]` becomes `1..2` like
+    // `a[1]`.
+    fn projections(&self) -> Vec<Projection> {
+        let mut projections = vec![];
+        // The range of the array or slice the place is at, if it is at a subslice.
+        let mut window: Option<(usize, Bound)> = None;
+        for (k, elem) in self.elems.iter().enumerate() {
+            let mut range = || {
+                window.take().unwrap_or_else(|| {
+                    let prefix = Place {
+                        local: self.local.clone(),
+                        elems: self.elems[..k].to_vec(),
+                    };
+                    match prefix.ty() {
+                        Type::Array(_, n) => (0, Bound::Start(n)),
+                        _ => (0, Bound::End(0)),
+                    }
+                })
+            };
+            match elem {
+                PlaceElem::ConstantIndex(i) => {
+                    let (lo, _) = range();
+                    projections.push(Projection::Range(lo + i, Bound::Start(lo + i + 1)));
+                }
+                PlaceElem::Index(_) => {
+                    let (lo, hi) = range();
+                    projections.push(Projection::Range(lo, hi));
+                }
+                PlaceElem::Subslice { from, to } => {
+                    let (lo, hi) = range();
+                    let hi = to.map_or(hi, |to| Bound::Start(lo + to));
+                    window = Some((lo + from, hi));
+                }
+                elem => projections.push(Projection::Elem(elem.clone())),
+            }
+        }
+        if let Some((lo, hi)) = window {
+            projections.push(Projection::Range(lo, hi));
+        }
+        projections
+    }
+
+    // The locals that hold the runtime indices of the place, which are read to find it.
+    pub fn index_locals(&self) -> impl Iterator<Item = &Local> {
+        self.elems.iter().filter_map(|e| match e {
+            PlaceElem::Index(l) => Some(l),
+            _ => None,
+        })
+    }
 }
 
 impl Expr {
     pub fn ty(&self) -> &Type {
         match self {
             Expr::IfElse(ty, _, _, _) => ty,
-            Expr::While(ty, _, _) => ty,
+            Expr::While(ty, _, _, _) => ty,
             Expr::Tuple(ty, _) => ty,
+            Expr::Array(ty, _) => ty,
+            Expr::Struct(ty, _) => ty,
+            Expr::Variant(ty, _, _) => ty,
+            Expr::Match(ty, _, _) => ty,
             Expr::Ref(ty, _) => ty,
             Expr::RefMut(ty, _) => ty,
             Expr::Seq(ty, _, _) => ty,
             Expr::Assign(ty, _, _) => ty,
             Expr::Place(ty, _) => ty,
-            Expr::Add(ty, _, _) => ty,
+            Expr::Arith(ty, _, _, _) => ty,
+            Expr::Cast(ty, _) => ty,
             Expr::Int(ty, _) => ty,
             Expr::Bool(ty, _) => ty,
             Expr::String(ty, _) => ty,
             Expr::Block(ty, _) => ty,
             Expr::Unit(ty) => ty,
             Expr::Print(ty, _) => ty,
+            Expr::Box(ty, _) => ty,
+            Expr::Call(ty, _, _) => ty,
+            Expr::MethodCall(ty, _, _, _) => ty,
+            Expr::Closure(ty, _) => ty,
+            Expr::CallClosure(ty, _, _) => ty,
             Expr::Return(ty, _) => ty,
             Expr::Loop(ty, _, _) => ty,
             Expr::Continue(ty, _) => ty,
-            Expr::Break(ty, _) => ty,
+            Expr::Break(ty, _, _) => ty,
         }
     }
 }
 
 impl Place {
-    pub fn ty(&self) -> &Type {
+    pub fn ty(&self) -> Type {
         let mut t = &self.local.ty;
-        for elem in self.elems.iter().rev() {
+        for elem in self.elems.iter() {
             t = match elem {
-                PlaceElem::Index(i) => match t {
+                PlaceElem::TupleIndex(i) => match t {
                     Type::Tuple(ts) => &ts[*i],
+                    Type::Closure(c) => &c.captures[*i],
+                    _ => &Type::Unknown,
+                },
+                PlaceElem::Field(f) => match t {
+                    Type::Struct(s) => s.field(f).map_or(&Type::Unknown, |(_, t)| t),
+                    _ => &Type::Unknown,
+                },
+                PlaceElem::Downcast(v) => match t {
+                    Type::Enum(e) => e.variant(v).map_or(&Type::Unknown, |(_, t)| t),
                     _ => &Type::Unknown,
                 },
                 PlaceElem::Deref => match t {
                     Type::Ref(_, ty) => ty.as_ref(),
                     Type::RefMut(_, ty) => ty.as_ref(),
+                    Type::Box(ty) => ty.as_ref(),
+                    Type::Guard(_, ty) | Type::GuardMut(_, ty) => ty.as_ref(),
+                    _ => &Type::Unknown,
+                },
+                PlaceElem::ConstantIndex(_) | PlaceElem::Index(_) => match t {
+                    Type::Array(ty, _) | Type::Slice(ty) => ty.as_ref(),
+                    _ => &Type::Unknown,
+                },
+                // A subslice is a slice of the elements, which is only a type of its own at the
+                // end of the place, since anything after it projects out an element again.
+                PlaceElem::Subslice { .. } => match t {
+                    Type::Array(..) | Type::Slice(_) => t,
                     _ => &Type::Unknown,
                 },
             };
         }
-        t
+        match (self.elems.last(), t) {
+            (Some(PlaceElem::Subslice { .. }), Type::Array(ty, _) | Type::Slice(ty)) => {
+                Type::Slice(ty.clone())
+            }
+            _ => t.clone(),
+        }
     }
 
     pub fn is_mutable(&self) -> bool {
@@ -166,15 +591,40 @@ impl Place {
 
     pub fn is_mutable_rec(&self) -> bool {
         let mut t = &self.local.ty;
-        for elem in self.elems.iter().rev() {
+        for elem in self.elems.iter() {
             t = match elem {
-                PlaceElem::Index(i) => match t {
+                PlaceElem::TupleIndex(i) => match t {
                     Type::Tuple(ts) => &ts[*i],
+                    Type::Closure(c) => &c.captures[*i],
+                    _ => return false,
+                },
+                PlaceElem::Field(f) => match t {
+                    Type::Struct(s) => match s.field(f) {
+                        Some((_, t)) => t,
+                        None => return false,
+                    },
+                    _ => return false,
+                },
+                PlaceElem::Downcast(v) => match t {
+                    Type::Enum(e) => match e.variant(v) {
+                        Some((_, t)) => t,
+                        None => return false,
+                    },
                     _ => return false,
                 },
                 PlaceElem::Deref => match t {
-                    Type::Ref(_, _) => return false,
-                    Type::RefMut(_, ty) => ty,
+                    Type::Ref(_, _) | Type::Guard(_, _) => return false,
+                    Type::RefMut(_, ty) | Type::GuardMut(_, ty) => ty,
+                    // The contents of a box are owned like the fields of a tuple.
+                    Type::Box(ty) => ty,
+                    _ => return false,
+                },
+                PlaceElem::ConstantIndex(_) | PlaceElem::Index(_) => match t {
+                    Type::Array(ty, _) | Type::Slice(ty) => ty,
+                    _ => return false,
+                },
+                PlaceElem::Subslice { .. } => match t {
+                    Type::Array(..) | Type::Slice(_) => t,
                     _ => return false,
                 },
             };
@@ -192,11 +642,17 @@ impl Type {
 
     fn loans_acc(&self, loans: &mut Vec<Loan>) {
         match self {
-            Type::Ref(loans2, t) | Type::RefMut(loans2, t) => {
+            Type::Ref(loans2, t)
+            | Type::RefMut(loans2, t)
+            | Type::Guard(loans2, t)
+            | Type::GuardMut(loans2, t) => {
                 loans.extend(loans2.clone());
                 t.loans_acc(loans);
             }
-            Type::Int => {}
+            Type::Box(t) | Type::Array(t, _) | Type::Slice(t) | Type::Cell(_, t) => {
+                t.loans_acc(loans)
+            }
+            Type::Int | Type::Integer(_) => {}
             Type::Bool => {}
             Type::Unit => {}
             Type::Unknown => {}
@@ -205,20 +661,127 @@ impl Type {
                     t.loans_acc(loans);
                 }
             }
+            Type::Struct(s) => {
+                for (_, t) in &s.fields {
+                    t.loans_acc(loans);
+                }
+            }
+            // A live closure keeps the places that it captures borrowed.
+            Type::Closure(c) => {
+                for t in &c.captures {
+                    t.loans_acc(loans);
+                }
+            }
+            Type::Enum(e) => {
+                for (_, t) in &e.variants {
+                    t.loans_acc(loans);
+                }
+            }
             Type::String => {}
+            Type::Param(_) => {}
+            Type::Var(_) => {}
+            Type::Never => {}
         }
     }
 
     pub fn is_copy(&self) -> bool {
         match self {
-            Type::Int => true,
+            Type::Int | Type::Integer(_) => true,
             Type::Bool => true,
             Type::Unit => true,
             Type::String => false,
             Type::Unknown => false,
             Type::Tuple(ts) => ts.iter().all(|t| t.is_copy()),
+            Type::Array(t, _) => t.is_copy(),
+            // A slice is unsized, so it is never a value of its own.
+            Type::Slice(_) => false,
+            // Like structs in Rust that do not derive Copy.
+            Type::Struct(_) => false,
+            Type::Enum(_) => false,
+            // Like in Rust, a closure can be copied if what it captures can.
+            Type::Closure(c) => c.captures.iter().all(|t| t.is_copy()),
             Type::Ref(_, _) => true,
             Type::RefMut(_, _) => false,
+            Type::Box(_) => false,
+            // Like in Rust, where a `Cell` is not Copy even if its contents are.
+            Type::Cell(..) => false,
+            Type::Guard(..) | Type::GuardMut(..) => false,
+            // A generic function can only move a value whose type is a parameter.
+            Type::Param(_) => false,
+            Type::Var(_) => false,
+            Type::Never => true,
+        }
+    }
+
+    // Whether a value of this type owns something that must be dropped, i.e., a string, a box or
+    // the guard of a borrow of a `RefCell`, which releases the borrow. A value of a type parameter
+    // may, and is dropped once the function is monomorphized if it does.
+    pub fn needs_drop(&self) -> bool {
+        match self {
+            Type::String | Type::Box(_) | Type::Param(_) => true,
+            Type::Guard(..) | Type::GuardMut(..) => true,
+            Type::Cell(_, t) => t.needs_drop(),
+            Type::Tuple(ts) => ts.iter().any(|t| t.needs_drop()),
+            Type::Array(t, n) => *n > 0 && t.needs_drop(),
+            Type::Struct(s) => s.fields.iter().any(|(_, t)| t.needs_drop()),
+            Type::Enum(e) => e.variants.iter().any(|(_, t)| t.needs_drop()),
+            Type::Closure(c) => c.captures.iter().any(|t| t.needs_drop()),
+            _ => false,
+        }
+    }
+
+    // Compares types while ignoring loans. Unknown types, type variables and the never type are
+    // compatible with everything.
+    pub fn same_shape(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown | Type::Var(_) | Type::Never, _)
+            | (_, Type::Unknown | Type::Var(_) | Type::Never) => true,
+            (Type::Tuple(ts1), Type::Tuple(ts2)) => {
+                ts1.len() == ts2.len() && ts1.iter().zip(ts2).all(|(t1, t2)| t1.same_shape(t2))
+            }
+            (Type::Struct(s1), Type::Struct(s2)) => {
+                s1.id == s2.id
+                    && s1.fields.len() == s2.fields.len()
+                    && s1
+                        .fields
+                        .iter()
+                        .zip(&s2.fields)
+                        .all(|((f1, t1), (f2, t2))| f1 == f2 && t1.same_shape(t2))
+            }
+            (Type::Enum(e1), Type::Enum(e2)) => {
+                e1.id == e2.id
+                    && e1.variants.len() == e2.variants.len()
+                    && e1
+                        .variants
+                        .iter()
+                        .zip(&e2.variants)
+                        .all(|((v1, t1), (v2, t2))| v1 == v2 && t1.same_shape(t2))
+            }
+            (Type::Ref(_, t1), Type::Ref(_, t2)) => t1.same_shape(t2),
+            (Type::RefMut(_, t1), Type::RefMut(_, t2)) => t1.same_shape(t2),
+            (Type::Box(t1), Type::Box(t2)) => t1.same_shape(t2),
+            (Type::Array(t1, n1), Type::Array(t2, n2)) => n1 == n2 && t1.same_shape(t2),
+            (Type::Slice(t1), Type::Slice(t2)) => t1.same_shape(t2),
+            (Type::Closure(c1), Type::Closure(c2)) => c1.id == c2.id,
+            (Type::Cell(k1, t1), Type::Cell(k2, t2)) => k1 == k2 && t1.same_shape(t2),
+            (Type::Guard(_, t1), Type::Guard(_, t2)) => t1.same_shape(t2),
+            (Type::GuardMut(_, t1), Type::GuardMut(_, t2)) => t1.same_shape(t2),
+            (t1, t2) => t1 == t2,
+        }
+    }
+
+    pub fn int(t: IntTy) -> Type {
+        match t {
+            IntTy::I32 => Type::Int,
+            t => Type::Integer(t),
+        }
+    }
+
+    pub fn int_ty(&self) -> Option<IntTy> {
+        match self {
+            Type::Int => Some(IntTy::I32),
+            Type::Integer(t) => Some(*t),
+            _ => None,
         }
     }
 
@@ -231,6 +794,149 @@ impl Type {
     }
 }
 
+impl IntTy {
+    pub const ALL: [IntTy; 9] = [
+        IntTy::I8,
+        IntTy::I16,
+        IntTy::I32,
+        IntTy::I64,
+        IntTy::U8,
+        IntTy::U16,
+        IntTy::U32,
+        IntTy::U64,
+        IntTy::Usize,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntTy::I8 => "i8",
+            IntTy::I16 => "i16",
+            IntTy::I32 => "i32",
+            IntTy::I64 => "i64",
+            IntTy::U8 => "u8",
+            IntTy::U16 => "u16",
+            IntTy::U32 => "u32",
+            IntTy::U64 => "u64",
+            IntTy::Usize => "usize",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IntTy> {
+        IntTy::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    // usize is as wide as on the 64-bit machines that the interpreter runs on.
+    pub fn bits(self) -> u32 {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::I64 | IntTy::U64 | IntTy::Usize => 64,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, IntTy::I8 | IntTy::I16 | IntTy::I32 | IntTy::I64)
+    }
+
+    pub fn min(self) -> i128 {
+        if self.is_signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.is_signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+
+    pub fn contains(self, i: i128) -> bool {
+        self.min() <= i && i <= self.max()
+    }
+
+    // Adds like Rust in debug builds, where overflow is an error.
+    pub fn checked_add(self, a: i128, b: i128) -> Option<i128> {
+        Some(a + b).filter(|&i| self.contains(i))
+    }
+
+    pub fn checked_sub(self, a: i128, b: i128) -> Option<i128> {
+        Some(a - b).filter(|&i| self.contains(i))
+    }
+
+    // The product of two u64s may not fit in an i128.
+    pub fn checked_mul(self, a: i128, b: i128) -> Option<i128> {
+        a.checked_mul(b).filter(|&i| self.contains(i))
+    }
+
+    pub fn checked(self, op: ArithOp, a: i128, b: i128) -> Option<i128> {
+        match op {
+            ArithOp::Add => self.checked_add(a, b),
+            ArithOp::Sub => self.checked_sub(a, b),
+            ArithOp::Mul => self.checked_mul(a, b),
+        }
+    }
+
+    // Truncates an integer to the bits of the type, like `as` does.
+    pub fn wrap(self, i: i128) -> i128 {
+        let i = i & ((1 << self.bits()) - 1);
+        if i > self.max() {
+            i - (1 << self.bits())
+        } else {
+            i
+        }
+    }
+}
+
+impl ArithOp {
+    pub const ALL: [ArithOp; 3] = [ArithOp::Add, ArithOp::Sub, ArithOp::Mul];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArithOp::Add => "add",
+            ArithOp::Sub => "sub",
+            ArithOp::Mul => "mul",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ArithOp> {
+        ArithOp::ALL.into_iter().find(|op| op.name() == name)
+    }
+
+    // The operator of Rust and C.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+        }
+    }
+}
+
+impl CellKind {
+    pub const ALL: [CellKind; 2] = [CellKind::Cell, CellKind::RefCell];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CellKind::Cell => "Cell",
+            CellKind::RefCell => "RefCell",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CellKind> {
+        CellKind::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    // The kind of cell that `Cell::new` or `RefCell::new` constructs.
+    pub fn constructor(id: &str) -> Option<CellKind> {
+        CellKind::from_name(id.strip_suffix("::new")?)
+    }
+}
+
 impl Local {
     pub fn into_expr(self) -> Expr {
         Expr::Place(
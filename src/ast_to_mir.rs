@@ -1,12 +1,18 @@
 use std::rc::Rc;
 
 use crate::ast;
+use crate::ast::Closure;
+use crate::ast::ClosureKind;
+use crate::ast::Enum;
 use crate::ast::Expr;
+use crate::ast::IntTy;
 use crate::ast::Loan;
 use crate::ast::Local;
 use crate::ast::Place;
 use crate::ast::PlaceElem;
+use crate::ast::Struct;
 use crate::ast::Type;
+use crate::loops::LoopForest;
 use crate::mir;
 use crate::mir::BasicBlock;
 use crate::mir::BlockId;
@@ -16,13 +22,29 @@ use crate::mir::Operation;
 use crate::mir::Rvalue;
 use crate::mir::Stmt;
 use crate::mir::Terminator;
+use crate::monomorphize::monomorphize;
 use crate::set::Set;
 
 pub struct Context {
     func: mir::Function,
     temp_counter: usize,
     stack: Vec<Scope>,
-    loops: Vec<(BlockId, BlockId)>,
+    // Every renaming so far, including those of scopes that have ended.
+    renamed: Vec<(Local, Local)>,
+    loops: Vec<Loop>,
+    // The functions that the bodies of the closures so far are lowered to.
+    closures: Vec<mir::Function>,
+}
+
+#[derive(Clone)]
+struct Loop {
+    label: Option<usize>,
+    b_continue: BlockId,
+    b_break: BlockId,
+    // The depth of the scope stack when entering the loop.
+    depth: usize,
+    // The local that the value of the loop is written to.
+    result: Option<Local>,
 }
 
 #[derive(Debug)]
@@ -37,15 +59,31 @@ impl Context {
             func: function,
             temp_counter: 0,
             stack: vec![],
+            renamed: vec![],
             loops: vec![],
+            closures: vec![],
         }
     }
 }
 
 impl ast::Function {
+    // A function with closures must be lowered with `lower`, since their bodies become functions of
+    // their own.
     pub fn into_mir(self) -> mir::Function {
+        let id = self.id.clone();
+        let mut functions = self.lower();
+        if functions.len() > 1 {
+            panic!("Closure: the closures of {} are lowered to functions of their own", id);
+        }
+        functions.remove(0)
+    }
+
+    // Lowers the function, followed by the functions of its closures.
+    pub fn lower(self) -> Vec<mir::Function> {
         let func = mir::Function {
             id: self.id,
+            lifetimes: self.lifetimes,
+            type_params: self.type_params,
             params: self.params,
             locals: vec![],
             ty: self.ty.clone(),
@@ -63,6 +101,7 @@ impl ast::Function {
             postorder: vec![],
             preorder: vec![],
             reverse_postorder_number: vec![],
+            loops: LoopForest::default(),
         };
         let mut ctx = Context::new(func);
         let l0 = ctx.new_local(self.ty);
@@ -77,7 +116,17 @@ impl ast::Function {
                 .get_or_insert(Terminator::Return);
             (b1, o1)
         });
-        ctx.func
+        // Drops are inserted where storage ends, once it is known what has been moved out of.
+        let mut functions = vec![ctx.func.with_elaborated_drops()];
+        functions.extend(ctx.closures);
+        functions
+    }
+}
+
+impl ast::Program {
+    // Generic functions are replaced by their instances, so every function is concrete.
+    pub fn into_mir(self) -> Vec<mir::Function> {
+        monomorphize(self.functions.into_iter().flat_map(|f| f.lower()).collect())
     }
 }
 
@@ -89,12 +138,30 @@ impl Context {
         });
     }
 
-    fn push_loop(&mut self, b_continue: BlockId, b_break: BlockId) {
-        self.loops.push((b_continue, b_break))
+    fn push_loop(
+        &mut self,
+        label: Option<usize>,
+        b_continue: BlockId,
+        b_break: BlockId,
+        result: Option<Local>,
+    ) {
+        self.loops.push(Loop {
+            label,
+            b_continue,
+            b_break,
+            depth: self.stack.len(),
+            result,
+        })
     }
 
-    fn get_loop(&mut self) -> (BlockId, BlockId) {
-        *self.loops.last().unwrap()
+    // Returns the innermost loop, or the one with the label if there is one.
+    fn get_loop(&mut self, label: Option<usize>) -> Loop {
+        self.loops
+            .iter()
+            .rev()
+            .find(|l| label.is_none() || l.label == label)
+            .unwrap_or_else(|| panic!("Loop: undeclared label {:?}", label))
+            .clone()
     }
 
     fn get_return_local(&mut self) -> &Local {
@@ -106,16 +173,47 @@ impl Context {
     }
 
     fn rename(&mut self, l1: Local, l2: Local) {
+        self.renamed.push((l1.clone(), l2.clone()));
         self.stack.last_mut().unwrap().subst.push((l1, l2));
     }
 
+    // Finds the local that a source variable was renamed to. Loans can outlive the scope of the
+    // variable they borrow, so the most recent renaming is used if it is not in scope anymore.
     fn lookup(&self, l0: Local) -> Option<&Local> {
-        self.stack.iter().rev().find_map(|scope| {
-            scope
-                .subst
-                .iter()
-                .find_map(|(l1, l2)| if l0.id == *l1.id { Some(l2) } else { None })
-        })
+        self.stack
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.subst.iter())
+            .chain(self.renamed.iter().rev())
+            .find_map(|(l1, l2)| if l0.id == *l1.id { Some(l2) } else { None })
+    }
+
+    // The local that holds the result of branches is declared before them, so its type can have
+    // loans of variables which are declared within the branches. Resolves its type again once the
+    // branches have been lowered, and updates the statements that mention it.
+    fn retype(&mut self, l0: Local) -> Local {
+        let l1 = Local {
+            ty: self.resolve_type(l0.ty.clone()),
+            ..l0.clone()
+        };
+        let update = |l: &mut Local| {
+            if l.id == l0.id {
+                *l = l1.clone();
+            }
+        };
+        self.func.locals.iter_mut().for_each(update);
+        self.stack
+            .iter_mut()
+            .flat_map(|scope| scope.locals.iter_mut())
+            .for_each(update);
+        for stmt in self.func.blocks.iter_mut().flat_map(|b| b.stmts.iter_mut()) {
+            match &mut stmt.op {
+                Operation::StorageLive(l) | Operation::StorageDead(l) => update(l),
+                Operation::Assign(dest, _) => update(&mut dest.local),
+                _ => {}
+            }
+        }
+        l1
     }
 
     fn scoped(&mut self, f: impl FnOnce(&mut Self) -> (BlockId, Operand)) -> (BlockId, Operand) {
@@ -125,6 +223,20 @@ impl Context {
         (b, o)
     }
 
+    // Ends the storage of all locals in scopes above `depth` when jumping out of them.
+    fn exit_scopes(&mut self, depth: usize, b: BlockId) {
+        let locals = self.stack[depth..]
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.locals.iter().rev().cloned())
+            .collect::<Vec<_>>();
+        for l in locals {
+            self.func.blocks[b]
+                .stmts
+                .push(Stmt::new(Operation::StorageDead(l)));
+        }
+    }
+
     fn pop_scope(&mut self, b: mir::BlockId) {
         let scope = self.stack.pop().unwrap();
         for l in scope.locals.into_iter().rev() {
@@ -147,11 +259,15 @@ impl Context {
                     )));
                     b1
                 } else {
-                    todo!()
+                    let l1 = self.new_storage_local(l0.ty.clone(), b1);
+                    self.rename(l0.clone(), l1);
+                    b1
                 }
             }
+            // The temporaries of a statement, such as the guard of a borrow of a RefCell, are
+            // dropped at the end of it, like in Rust.
             ast::Stmt::Expr(e) => {
-                let (b1, _) = self.lower_expr(e, b1);
+                let (b1, _) = self.scoped(|ctx| ctx.lower_expr(e, b1));
                 b1
             }
         });
@@ -166,14 +282,17 @@ impl Context {
     /// Returns the current block and the local that holds the result of the expression.
     pub fn lower_expr(&mut self, e: &Expr, b0: BlockId) -> (BlockId, Operand) {
         match e {
-            Expr::Int(_, v) => (b0, Operand::Constant(Constant::Int(*v))),
-            Expr::Add(t, e0, e1) => {
+            Expr::Int(t, v) => {
+                let t = t.int_ty().unwrap_or(IntTy::I32);
+                (b0, Operand::Constant(Constant::int(t, *v)))
+            }
+            Expr::Arith(t, op, e0, e1) => {
                 let (b0, a0) = self.lower_expr(e0, b0);
                 let (b1, a1) = self.lower_expr(e1, b0);
                 let l2 = self.new_storage_local(t.clone(), b1);
                 self.func.blocks[b1].stmts.push(Stmt::new(Operation::Call {
                     dest: Place::from(l2.clone()),
-                    func: Operand::Function("add".to_string()),
+                    func: Operand::Function(op.name().to_string()),
                     args: vec![a0, a1],
                 }));
                 let a2 = Operand::from(l2);
@@ -214,14 +333,14 @@ impl Context {
                     (b2, l2)
                 });
 
-                (b3, Operand::from(l3))
+                (b3, Operand::from(self.retype(l3)))
             }
-            Expr::While(_, e0, e1) => {
+            Expr::While(_, label, e0, e1) => {
                 let b_header = self.new_block();
                 let b_body = self.new_block();
                 let b_after = self.new_block();
 
-                self.push_loop(b_header, b_after);
+                self.push_loop(*label, b_header, b_after, None);
 
                 self.func.blocks[b0]
                     .terminator
@@ -245,11 +364,15 @@ impl Context {
 
                 (b_after, Operand::Constant(Constant::Unit))
             }
-            Expr::Loop(_, _, e1) => {
+            Expr::Loop(t, label, e1) => {
                 let b_body = self.new_block();
                 let b_after = self.new_block();
+                // Like the result of an `if`, the value that the loop breaks with is written to a
+                // local which is declared before it.
+                let l0 = (!matches!(t, Type::Unit | Type::Never))
+                    .then(|| self.new_storage_local(t.clone(), b0));
 
-                self.push_loop(b_body, b_after);
+                self.push_loop(*label, b_body, b_after, l0.clone());
 
                 self.func.blocks[b0]
                     .terminator
@@ -265,7 +388,10 @@ impl Context {
 
                 self.pop_loop();
 
-                (b_after, Operand::Constant(Constant::Unit))
+                match l0 {
+                    Some(l0) => (b_after, Operand::from(self.retype(l0))),
+                    None => (b_after, Operand::Constant(Constant::Unit)),
+                }
             }
             Expr::Tuple(t, es) => {
                 let l0 = self.new_storage_local(t.clone(), b0);
@@ -274,7 +400,7 @@ impl Context {
                     self.func.blocks[b1].stmts.push(Stmt::new(Operation::Assign(
                         Place {
                             local: l0.clone(),
-                            elems: vec![PlaceElem::Index(i)],
+                            elems: vec![PlaceElem::TupleIndex(i)],
                         },
                         Rvalue::Use(l1),
                     )));
@@ -282,6 +408,152 @@ impl Context {
                 });
                 (b0, Operand::from(l0))
             }
+            Expr::Array(t, es) => {
+                let l0 = self.new_storage_local(t.clone(), b0);
+                let b0 = es.iter().enumerate().fold(b0, |b0, (i, e)| {
+                    let (b1, l1) = self.lower_expr(e, b0);
+                    self.func.blocks[b1].stmts.push(Stmt::new(Operation::Assign(
+                        Place {
+                            local: l0.clone(),
+                            elems: vec![PlaceElem::ConstantIndex(i)],
+                        },
+                        Rvalue::Use(l1),
+                    )));
+                    b1
+                });
+                (b0, Operand::from(l0))
+            }
+            Expr::Struct(t, fields) => {
+                let l0 = self.new_storage_local(t.clone(), b0);
+                let b0 = fields.iter().fold(b0, |b0, (f, e)| {
+                    let (b1, l1) = self.lower_expr(e, b0);
+                    self.func.blocks[b1].stmts.push(Stmt::new(Operation::Assign(
+                        Place {
+                            local: l0.clone(),
+                            elems: vec![PlaceElem::Field(f.clone())],
+                        },
+                        Rvalue::Use(l1),
+                    )));
+                    b1
+                });
+                (b0, Operand::from(l0))
+            }
+            Expr::Variant(t, v, es) => {
+                let Type::Enum(def) = t else {
+                    unreachable!("Variant of {:?}", t)
+                };
+                let (discr, _) = def.variant(v).unwrap();
+                let mut b0 = b0;
+                let mut os = vec![];
+                for e in es {
+                    let (b1, o1) = self.lower_expr(e, b0);
+                    b0 = b1;
+                    os.push(o1);
+                }
+                let l0 = self.new_storage_local(t.clone(), b0);
+                self.func.blocks[b0]
+                    .stmts
+                    .push(Stmt::new(Operation::SetDiscriminant(
+                        Place::from(l0.clone()),
+                        discr,
+                    )));
+                for (i, o) in os.into_iter().enumerate() {
+                    self.func.blocks[b0].stmts.push(Stmt::new(Operation::Assign(
+                        Place {
+                            local: l0.clone(),
+                            elems: vec![PlaceElem::Downcast(v.clone()), PlaceElem::TupleIndex(i)],
+                        },
+                        Rvalue::Use(o),
+                    )));
+                }
+                (b0, Operand::from(l0))
+            }
+            Expr::Match(t, p0, arms) => {
+                let p0 = self.resolve_place(p0.clone());
+                let Type::Enum(def) = p0.ty().clone() else {
+                    unreachable!("Match on {:?}", p0.ty())
+                };
+                let l_discr = self.new_storage_local(Type::Int, b0);
+                self.func.blocks[b0].stmts.push(Stmt::new(Operation::Assign(
+                    Place::from(l_discr.clone()),
+                    Rvalue::Discriminant(p0.clone()),
+                )));
+                let l_result = self.new_storage_local(t.clone(), b0);
+                let b_after = self.new_block();
+
+                // Each arm gets its own block. A wildcard arm, or else the last arm, is the
+                // fallback of the switch.
+                let mut targets = vec![];
+                let mut otherwise = None;
+                for arm in arms.iter() {
+                    let b_arm = self.new_block();
+                    match &arm.pattern {
+                        ast::Pattern::Variant(_, v, _) => {
+                            let (discr, _) = def.variant(v).unwrap();
+                            targets.push((discr as i32, b_arm));
+                        }
+                        ast::Pattern::Wildcard => {
+                            otherwise.get_or_insert(b_arm);
+                        }
+                    }
+                    self.scoped(|ctx| {
+                        if let ast::Pattern::Variant(_, v, bindings) = &arm.pattern {
+                            for (i, b) in bindings.iter().enumerate() {
+                                let Some(b) = b else { continue };
+                                let place = Place {
+                                    local: p0.local.clone(),
+                                    elems: p0
+                                        .elems
+                                        .iter()
+                                        .cloned()
+                                        .chain([
+                                            PlaceElem::Downcast(v.clone()),
+                                            PlaceElem::TupleIndex(i),
+                                        ])
+                                        .collect(),
+                                };
+                                let rvalue = match b.mode {
+                                    ast::BindingMode::Value => Rvalue::Use(Operand::from(place)),
+                                    ast::BindingMode::Ref => Rvalue::Ref {
+                                        mutable: false,
+                                        place,
+                                    },
+                                    ast::BindingMode::RefMut => Rvalue::Ref {
+                                        mutable: true,
+                                        place,
+                                    },
+                                };
+                                let l = ctx.new_storage_local(b.local.ty.clone(), b_arm);
+                                ctx.rename(b.local.clone(), l.clone());
+                                ctx.func.blocks[b_arm]
+                                    .stmts
+                                    .push(Stmt::new(Operation::Assign(Place::from(l), rvalue)));
+                            }
+                        }
+                        let (b1, o1) = ctx.lower_block(&arm.block, b_arm);
+                        ctx.func.blocks[b1].stmts.push(Stmt::new(Operation::Assign(
+                            Place::from(l_result.clone()),
+                            Rvalue::Use(o1.clone()),
+                        )));
+                        ctx.func.blocks[b1]
+                            .terminator
+                            .get_or_insert(Terminator::Goto(b_after));
+                        (b1, o1)
+                    });
+                }
+                let otherwise = match otherwise {
+                    Some(b) => b,
+                    None => targets.pop().unwrap().1,
+                };
+                self.func.blocks[b0]
+                    .terminator
+                    .get_or_insert(Terminator::SwitchInt(
+                        Operand::from(l_discr),
+                        targets,
+                        otherwise,
+                    ));
+                (b_after, Operand::from(self.retype(l_result)))
+            }
             Expr::Ref(t, p0) => {
                 let l = self.new_storage_local(t.clone(), b0);
                 let p1 = self.resolve_place(p0.clone());
@@ -324,7 +596,19 @@ impl Context {
             }
             Expr::Bool(_, v) => (b0, Operand::Constant(Constant::Bool(*v))),
             Expr::String(_, v) => (b0, Operand::Constant(Constant::String(v.clone()))),
-            Expr::Block(_, b) => self.scoped(|ctx| ctx.lower_block(b, b0)),
+            // Like a branch, the block's result outlives the locals declared in it.
+            Expr::Block(t, b) => {
+                let l0 = self.new_storage_local(t.clone(), b0);
+                let (b1, _) = self.scoped(|ctx| {
+                    let (b1, o1) = ctx.lower_block(b, b0);
+                    ctx.func.blocks[b1].stmts.push(Stmt::new(Operation::Assign(
+                        Place::from(l0.clone()),
+                        Rvalue::Use(o1.clone()),
+                    )));
+                    (b1, o1)
+                });
+                (b1, Operand::from(self.retype(l0)))
+            }
             Expr::Unit(_) => (b0, Operand::Constant(Constant::Unit)),
             Expr::Print(t, e0) => {
                 let (b0, l0) = self.lower_expr(e0, b0);
@@ -336,6 +620,110 @@ impl Context {
                 }));
                 (b0, Operand::Constant(Constant::Unit))
             }
+            // Like `add`, a cast is a builtin, which converts to the type of its destination.
+            Expr::Cast(t, e0) => {
+                let (b0, l0) = self.lower_expr(e0, b0);
+                let l1 = self.new_storage_local(t.clone(), b0);
+                self.func.blocks[b0].stmts.push(Stmt::new(Operation::Call {
+                    dest: Place::from(l1.clone()),
+                    func: Operand::Function("cast".to_string()),
+                    args: vec![l0],
+                }));
+                (b0, Operand::from(l1))
+            }
+            Expr::Box(t, e0) => {
+                let (b0, l0) = self.lower_expr(e0, b0);
+                let l1 = self.new_storage_local(t.clone(), b0);
+                self.func.blocks[b0].stmts.push(Stmt::new(Operation::Call {
+                    dest: Place::from(l1.clone()),
+                    func: Operand::Function("box".to_string()),
+                    args: vec![l0],
+                }));
+                (b0, Operand::from(l1))
+            }
+            Expr::MethodCall(_, p, m, _) => unreachable!("Method call {}.{} is not resolved", p, m),
+            Expr::Call(t, id, es) => {
+                let mut args = vec![];
+                let b0 = es.iter().fold(b0, |b0, e| {
+                    let (b1, o) = self.lower_expr(e, b0);
+                    args.push(o);
+                    b1
+                });
+                let l1 = self.new_storage_local(t.clone(), b0);
+                self.func.blocks[b0].stmts.push(Stmt::new(Operation::Call {
+                    dest: Place::from(l1.clone()),
+                    func: Operand::Function(id.clone()),
+                    args,
+                }));
+                (b0, Operand::from(l1))
+            }
+            // A closure holds its captures like a tuple. They are taken before the closure is
+            // live, since its type holds their loans.
+            Expr::Closure(t, lambda) => {
+                let mut ops = vec![];
+                let b0 = lambda.captures.iter().fold(b0, |b0, e| {
+                    let (b1, l1) = self.lower_expr(e, b0);
+                    ops.push(l1);
+                    b1
+                });
+                let l0 = self.new_storage_local(t.clone(), b0);
+                for (i, l1) in ops.into_iter().enumerate() {
+                    self.func.blocks[b0].stmts.push(Stmt::new(Operation::Assign(
+                        Place {
+                            local: l0.clone(),
+                            elems: vec![PlaceElem::TupleIndex(i)],
+                        },
+                        Rvalue::Use(l1),
+                    )));
+                }
+                let function = lambda.function.as_ref().expect("Closure: not lifted");
+                self.closures.extend(function.as_ref().clone().lower());
+                (b0, Operand::from(l0))
+            }
+            // The closure is passed to the function of its body, borrowed unless it is FnOnce, so
+            // borrowck sees the borrow.
+            Expr::CallClosure(t, p0, es) => {
+                let Type::Closure(c) = p0.ty() else {
+                    unreachable!("Call of {}", p0)
+                };
+                let mut args = vec![];
+                let b0 = es.iter().fold(b0, |b0, e| {
+                    let (b1, o) = self.lower_expr(e, b0);
+                    args.push(o);
+                    b1
+                });
+                let env = match c.kind {
+                    ClosureKind::FnOnce => Operand::from(self.resolve_place(p0.clone())),
+                    kind => {
+                        let mutable = kind == ClosureKind::FnMut;
+                        let loan = Loan {
+                            place: p0.clone(),
+                            mutable,
+                        };
+                        let t = Rc::new(p0.ty());
+                        let t = if mutable {
+                            Type::RefMut(vec![loan], t)
+                        } else {
+                            Type::Ref(vec![loan], t)
+                        };
+                        let l = self.new_storage_local(t, b0);
+                        let place = self.resolve_place(p0.clone());
+                        self.func.blocks[b0].stmts.push(Stmt::new(Operation::Assign(
+                            Place::from(l.clone()),
+                            Rvalue::Ref { mutable, place },
+                        )));
+                        Operand::from(l)
+                    }
+                };
+                args.insert(0, env);
+                let l1 = self.new_storage_local(t.clone(), b0);
+                self.func.blocks[b0].stmts.push(Stmt::new(Operation::Call {
+                    dest: Place::from(l1.clone()),
+                    func: Operand::Function(c.id.clone()),
+                    args,
+                }));
+                (b0, Operand::from(l1))
+            }
             Expr::Return(_, e0) => {
                 let (b0, l0) = self.lower_expr(e0, b0);
                 let l1 = self.get_return_local().clone();
@@ -343,37 +731,66 @@ impl Context {
                     Place::from(l1),
                     Rvalue::from(l0),
                 )));
+                self.exit_scopes(0, b0);
                 self.func.blocks[b0]
                     .terminator
                     .get_or_insert(Terminator::Return);
-                (b0, Operand::Constant(Constant::Unit))
+                // Anything after a jump is unreachable.
+                (self.new_block(), Operand::Constant(Constant::Unit))
             }
-            Expr::Continue(_, _) => {
-                let (b_continue, _) = self.get_loop();
+            Expr::Continue(_, label) => {
+                let l = self.get_loop(*label);
+                self.exit_scopes(l.depth, b0);
                 self.func.blocks[b0]
                     .terminator
-                    .get_or_insert(Terminator::Goto(b_continue));
-                (b0, Operand::Constant(Constant::Unit))
+                    .get_or_insert(Terminator::Goto(l.b_continue));
+                (self.new_block(), Operand::Constant(Constant::Unit))
             }
-            Expr::Break(_, _) => {
-                let (_, b_break) = self.get_loop();
+            Expr::Break(_, label, e0) => {
+                let l = self.get_loop(*label);
+                let b0 = match e0 {
+                    Some(e0) => {
+                        let (b0, o0) = self.lower_expr(e0, b0);
+                        if let Some(l0) = l.result {
+                            self.func.blocks[b0].stmts.push(Stmt::new(Operation::Assign(
+                                Place::from(l0),
+                                Rvalue::Use(o0),
+                            )));
+                        }
+                        b0
+                    }
+                    None => b0,
+                };
+                self.exit_scopes(l.depth, b0);
                 self.func.blocks[b0]
                     .terminator
-                    .get_or_insert(Terminator::Goto(b_break));
-                (b0, Operand::Constant(Constant::Unit))
+                    .get_or_insert(Terminator::Goto(l.b_break));
+                (self.new_block(), Operand::Constant(Constant::Unit))
             }
         }
     }
 
     fn resolve_place(&mut self, p0: Place) -> Place {
+        let elems = p0
+            .elems
+            .iter()
+            .map(|e| match e {
+                PlaceElem::Index(l) => {
+                    PlaceElem::Index(self.lookup(l.clone()).unwrap_or(l).clone())
+                }
+                e => e.clone(),
+            })
+            .collect();
         if let Some(l) = self.lookup(p0.local.clone()) {
-            let p1 = Place {
+            Place {
                 local: l.clone(),
-                elems: p0.elems.clone(),
-            };
-            p1.clone()
+                elems,
+            }
         } else {
-            p0.clone()
+            Place {
+                local: p0.local,
+                elems,
+            }
         }
     }
 
@@ -383,6 +800,28 @@ impl Context {
                 let ts = ts.into_iter().map(|t| self.resolve_type(t)).collect();
                 Type::Tuple(ts)
             }
+            Type::Struct(s) => {
+                let fields = s
+                    .fields
+                    .iter()
+                    .map(|(f, t)| (f.clone(), self.resolve_type(t.clone())))
+                    .collect();
+                Type::Struct(Rc::new(Struct {
+                    id: s.id.clone(),
+                    fields,
+                }))
+            }
+            Type::Enum(e) => {
+                let variants = e
+                    .variants
+                    .iter()
+                    .map(|(v, t)| (v.clone(), self.resolve_type(t.clone())))
+                    .collect();
+                Type::Enum(Rc::new(Enum {
+                    id: e.id.clone(),
+                    variants,
+                }))
+            }
             Type::Ref(loans, t) => {
                 let loans = loans
                     .into_iter()
@@ -406,6 +845,42 @@ impl Context {
                 let t = self.resolve_type(t.as_ref().clone());
                 Type::RefMut(loans, Rc::new(t))
             }
+            Type::Guard(loans, t) => {
+                let loans = loans
+                    .into_iter()
+                    .map(|l| Loan {
+                        place: self.resolve_place(l.place),
+                        mutable: l.mutable,
+                    })
+                    .collect();
+                let t = self.resolve_type(t.as_ref().clone());
+                Type::Guard(loans, Rc::new(t))
+            }
+            Type::GuardMut(loans, t) => {
+                let loans = loans
+                    .into_iter()
+                    .map(|l| Loan {
+                        place: self.resolve_place(l.place),
+                        mutable: l.mutable,
+                    })
+                    .collect();
+                let t = self.resolve_type(t.as_ref().clone());
+                Type::GuardMut(loans, Rc::new(t))
+            }
+            Type::Box(t) => Type::Box(Rc::new(self.resolve_type(t.as_ref().clone()))),
+            Type::Cell(k, t) => Type::Cell(k, Rc::new(self.resolve_type(t.as_ref().clone()))),
+            Type::Array(t, n) => Type::Array(Rc::new(self.resolve_type(t.as_ref().clone())), n),
+            Type::Slice(t) => Type::Slice(Rc::new(self.resolve_type(t.as_ref().clone()))),
+            Type::Closure(c) => {
+                let mut resolve = |t: &Type| self.resolve_type(t.clone());
+                Type::Closure(Rc::new(Closure {
+                    id: c.id.clone(),
+                    kind: c.kind,
+                    params: c.params.iter().map(&mut resolve).collect(),
+                    ty: resolve(&c.ty),
+                    captures: c.captures.iter().map(&mut resolve).collect(),
+                }))
+            }
             _ => t,
         }
     }
@@ -0,0 +1,948 @@
+use std::fmt::Display;
+
+use crate::ast::BindingMode;
+use crate::ast::Block;
+use crate::ast::CellKind;
+use crate::ast::Enum;
+use crate::ast::Expr;
+use crate::ast::Function;
+use crate::ast::Impl;
+use crate::ast::Lifetime;
+use crate::ast::Loan;
+use crate::ast::Local;
+use crate::ast::Name;
+use crate::ast::Pattern;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Program;
+use crate::ast::Stmt;
+use crate::ast::Struct;
+use crate::ast::Trait;
+use crate::ast::Type;
+
+// Prints a typed AST as Rust source that rustc accepts if and only if it borrow checks, e.g.,
+// `assign(x.index(0), add(a, 1))` becomes `x.0 = (a + 1)`. Loans are not printed, so references
+// get their lifetimes from elision and inference, unless they borrow for a lifetime parameter.
+struct Printer<'a, 'b> {
+    f: &'a mut std::fmt::Formatter<'b>,
+    indent_level: usize,
+}
+
+impl<'a, 'b> Printer<'a, 'b> {
+    fn new(f: &'a mut std::fmt::Formatter<'b>) -> Printer<'a, 'b> {
+        Printer { f, indent_level: 0 }
+    }
+
+    fn lit(&mut self, s: impl Display) -> std::fmt::Result {
+        write!(self.f, "{}", s)
+    }
+
+    fn space(&mut self) -> std::fmt::Result {
+        self.lit(" ")
+    }
+
+    fn indent(&mut self) -> std::fmt::Result {
+        for _ in 0..self.indent_level {
+            self.lit("    ")?;
+        }
+        Ok(())
+    }
+
+    fn newline(&mut self) -> std::fmt::Result {
+        self.lit("\n")?;
+        self.indent()
+    }
+
+    fn ty(&mut self, t: &Type) -> std::fmt::Result {
+        self.ty_with(t, "'_")
+    }
+
+    // Prints a type whose references have the given lifetime. Only struct and enum declarations
+    // name their lifetime, elsewhere lifetimes are elided.
+    fn ty_with(&mut self, t: &Type, lifetime: &str) -> std::fmt::Result {
+        match t {
+            Type::Int => self.lit("i32"),
+            Type::Integer(t) => self.lit(t.name()),
+            Type::Bool => self.lit("bool"),
+            Type::Unit => self.lit("()"),
+            Type::Never => self.lit("!"),
+            Type::String => self.lit("String"),
+            Type::Unknown | Type::Var(_) | Type::Closure(_) => self.lit("_"),
+            Type::Param(id) => self.lit(id),
+            Type::Tuple(tys) => {
+                self.lit("(")?;
+                for (i, t) in tys.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.ty_with(t, lifetime)?;
+                }
+                // A tuple with one element needs a trailing comma.
+                if tys.len() == 1 {
+                    self.lit(",")?;
+                }
+                self.lit(")")
+            }
+            Type::Struct(_) | Type::Enum(_) => {
+                self.lit(decl_id(t).unwrap())?;
+                if has_ref(t) {
+                    self.lit("<")?;
+                    self.lit(lifetime)?;
+                    self.lit(">")?;
+                }
+                Ok(())
+            }
+            Type::Ref(loans, t) => {
+                self.lit("&")?;
+                if let Some(lifetime) = named_lifetime(loans, lifetime) {
+                    self.lit(lifetime)?;
+                    self.space()?;
+                }
+                self.ty_with(t, lifetime)
+            }
+            Type::RefMut(loans, t) => {
+                self.lit("&")?;
+                if let Some(lifetime) = named_lifetime(loans, lifetime) {
+                    self.lit(lifetime)?;
+                    self.space()?;
+                }
+                self.lit("mut")?;
+                self.space()?;
+                self.ty_with(t, lifetime)
+            }
+            Type::Box(t) => {
+                self.lit("Box<")?;
+                self.ty_with(t, lifetime)?;
+                self.lit(">")
+            }
+            Type::Cell(kind, t) => {
+                self.lit("std::cell::")?;
+                self.lit(kind.name())?;
+                self.lit("<")?;
+                self.ty_with(t, lifetime)?;
+                self.lit(">")
+            }
+            Type::Guard(loans, t) => self.guard("Ref", loans, t, lifetime),
+            Type::GuardMut(loans, t) => self.guard("RefMut", loans, t, lifetime),
+            Type::Array(t, n) => {
+                self.lit("[")?;
+                self.ty_with(t, lifetime)?;
+                self.lit(";")?;
+                self.space()?;
+                self.lit(n.to_string())?;
+                self.lit("]")
+            }
+            Type::Slice(t) => {
+                self.lit("[")?;
+                self.ty_with(t, lifetime)?;
+                self.lit("]")
+            }
+        }
+    }
+
+    fn guard(&mut self, id: &str, loans: &[Loan], t: &Type, lifetime: &str) -> std::fmt::Result {
+        self.lit("std::cell::")?;
+        self.lit(id)?;
+        self.lit("<")?;
+        self.lit(named_lifetime(loans, lifetime).unwrap_or("'_"))?;
+        self.lit(",")?;
+        self.space()?;
+        self.ty_with(t, lifetime)?;
+        self.lit(">")
+    }
+
+    fn place(&mut self, place: &Place) -> std::fmt::Result {
+        let mut s = place.local.id.clone();
+        for elem in &place.elems {
+            s = match elem {
+                PlaceElem::TupleIndex(i) => format!("{}.{}", s, i),
+                PlaceElem::Field(f) => format!("{}.{}", s, f),
+                PlaceElem::Deref => format!("(*{})", s),
+                PlaceElem::ConstantIndex(i) => format!("{}[{}]", s, i),
+                PlaceElem::Index(l) => format!("{}[{}]", s, l.id),
+                PlaceElem::Subslice { from, to: None } => format!("{}[{}..]", s, from),
+                PlaceElem::Subslice { from, to: Some(to) } => format!("{}[{}..{}]", s, from, to),
+                // Rust only reaches into a variant through a pattern.
+                PlaceElem::Downcast(v) => panic!("Cannot print a downcast to {} in Rust", v),
+            };
+        }
+        self.lit(s)
+    }
+
+    fn local(&mut self, l: &Local) -> std::fmt::Result {
+        if l.mutable {
+            self.lit("mut")?;
+            self.space()?;
+        }
+        self.lit(&l.id)?;
+        self.lit(":")?;
+        self.space()?;
+        self.ty(&l.ty)
+    }
+
+    // Declares a struct. Structs which contain references get a single lifetime parameter.
+    fn struct_decl(&mut self, s: &Struct) -> std::fmt::Result {
+        self.lit("struct")?;
+        self.space()?;
+        self.lit(&s.id)?;
+        let lifetime = if s.fields.iter().any(|(_, t)| has_ref(t)) {
+            self.lit("<'a>")?;
+            "'a"
+        } else {
+            "'_"
+        };
+        self.space()?;
+        self.lit("{")?;
+        self.indent_level += 1;
+        for (f, t) in &s.fields {
+            self.newline()?;
+            self.lit(f)?;
+            self.lit(":")?;
+            self.space()?;
+            self.ty_with(t, lifetime)?;
+            self.lit(",")?;
+        }
+        self.indent_level -= 1;
+        self.newline()?;
+        self.lit("}")
+    }
+
+    // Declares an enum like a struct. Variants without a payload have no parentheses.
+    fn enum_decl(&mut self, e: &Enum) -> std::fmt::Result {
+        self.lit("enum")?;
+        self.space()?;
+        self.lit(&e.id)?;
+        let lifetime = if e.variants.iter().any(|(_, t)| has_ref(t)) {
+            self.lit("<'a>")?;
+            "'a"
+        } else {
+            "'_"
+        };
+        self.space()?;
+        self.lit("{")?;
+        self.indent_level += 1;
+        for (v, t) in &e.variants {
+            self.newline()?;
+            self.lit(v)?;
+            if let Type::Tuple(tys) = t {
+                if !tys.is_empty() {
+                    self.lit("(")?;
+                    for (i, t) in tys.iter().enumerate() {
+                        if i > 0 {
+                            self.lit(",")?;
+                            self.space()?;
+                        }
+                        self.ty_with(t, lifetime)?;
+                    }
+                    self.lit(")")?;
+                }
+            }
+            self.lit(",")?;
+        }
+        self.indent_level -= 1;
+        self.newline()?;
+        self.lit("}")
+    }
+
+    fn pattern(&mut self, p: &Pattern) -> std::fmt::Result {
+        match p {
+            Pattern::Wildcard => self.lit("_"),
+            Pattern::Variant(e, v, bindings) => {
+                self.lit(e)?;
+                self.lit("::")?;
+                self.lit(v)?;
+                if bindings.is_empty() {
+                    return Ok(());
+                }
+                self.lit("(")?;
+                for (i, b) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    match b {
+                        Some(b) => {
+                            match b.mode {
+                                BindingMode::Value => {}
+                                BindingMode::Ref => {
+                                    self.lit("ref")?;
+                                    self.space()?;
+                                }
+                                BindingMode::RefMut => {
+                                    self.lit("ref mut")?;
+                                    self.space()?;
+                                }
+                            }
+                            if b.local.mutable && b.mode == BindingMode::Value {
+                                self.lit("mut")?;
+                                self.space()?;
+                            }
+                            self.lit(&b.local.id)?;
+                        }
+                        None => self.lit("_")?,
+                    }
+                }
+                self.lit(")")
+            }
+        }
+    }
+
+    fn function(&mut self, f: &Function) -> std::fmt::Result {
+        self.decls(std::slice::from_ref(f))?;
+        self.signature_and_body(f)
+    }
+
+    // Prints the traits, then the impls with their methods, then the other functions.
+    fn program(&mut self, p: &Program) -> std::fmt::Result {
+        self.decls(&p.functions)?;
+        let mut items = 0;
+        let mut separate = |printer: &mut Self| {
+            items += 1;
+            if items > 1 {
+                printer.newline()?;
+                printer.newline()?;
+            }
+            Ok(())
+        };
+        for t in &p.traits {
+            separate(self)?;
+            self.trait_decl(t)?;
+        }
+        for i in &p.impls {
+            separate(self)?;
+            self.impl_decl(i, p)?;
+        }
+        let methods = p
+            .impls
+            .iter()
+            .flat_map(|i| i.methods.iter().map(|m| i.function_id(m)))
+            .collect::<Vec<_>>();
+        for f in p.functions.iter().filter(|f| !methods.contains(&f.id)) {
+            separate(self)?;
+            self.signature_and_body(f)?;
+        }
+        Ok(())
+    }
+
+    fn trait_decl(&mut self, t: &Trait) -> std::fmt::Result {
+        self.lit("trait")?;
+        self.space()?;
+        self.lit(&t.id)?;
+        self.space()?;
+        self.lit("{")?;
+        self.indent_level += 1;
+        for s in &t.methods {
+            self.newline()?;
+            self.signature(&s.id, &s.lifetimes, &[], &s.params, &s.ty)?;
+            self.lit(";")?;
+        }
+        self.indent_level -= 1;
+        self.newline()?;
+        self.lit("}")
+    }
+
+    // Calls of the methods print as calls of their fully qualified paths, e.g., `<S as Foo>::get`,
+    // which is what the functions of the program are named.
+    fn impl_decl(&mut self, i: &Impl, p: &Program) -> std::fmt::Result {
+        self.lit("impl")?;
+        self.space()?;
+        self.lit(&i.trait_id)?;
+        self.space()?;
+        self.lit("for")?;
+        self.space()?;
+        self.ty(&i.ty)?;
+        self.space()?;
+        self.lit("{")?;
+        self.indent_level += 1;
+        for m in &i.methods {
+            let f = p.function(&i.function_id(m)).unwrap();
+            self.newline()?;
+            self.signature(m, &f.lifetimes, &f.type_params, &f.params, &f.ty)?;
+            self.space()?;
+            self.block(&f.block)?;
+        }
+        self.indent_level -= 1;
+        self.newline()?;
+        self.lit("}")
+    }
+
+    // Declares the structs and enums that the functions use.
+    fn decls(&mut self, functions: &[Function]) -> std::fmt::Result {
+        let mut decls = vec![];
+        for f in functions {
+            for l in &f.params {
+                collect_decls(&l.ty, &mut decls);
+            }
+            collect_decls(&f.ty, &mut decls);
+            collect_decls_block(&f.block, &mut decls);
+        }
+        for t in &decls {
+            match t {
+                Type::Struct(s) => self.struct_decl(s)?,
+                Type::Enum(e) => self.enum_decl(e)?,
+                _ => unreachable!(),
+            }
+            self.newline()?;
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn signature_and_body(&mut self, f: &Function) -> std::fmt::Result {
+        self.signature(&f.id, &f.lifetimes, &f.type_params, &f.params, &f.ty)?;
+        self.space()?;
+        self.block(&f.block)
+    }
+
+    fn signature(
+        &mut self,
+        id: &str,
+        lifetimes: &[Lifetime],
+        type_params: &[Name],
+        params: &[Local],
+        ty: &Type,
+    ) -> std::fmt::Result {
+        self.lit("fn")?;
+        self.space()?;
+        self.lit(id)?;
+        if !lifetimes.is_empty() || !type_params.is_empty() {
+            self.lit("<")?;
+            for (i, l) in lifetimes.iter().enumerate() {
+                if i > 0 {
+                    self.lit(",")?;
+                    self.space()?;
+                }
+                self.lit(&l.id)?;
+                if !l.bounds.is_empty() {
+                    self.lit(":")?;
+                    self.space()?;
+                    self.lit(l.bounds.join(" + "))?;
+                }
+            }
+            for (i, id) in type_params.iter().enumerate() {
+                if i > 0 || !lifetimes.is_empty() {
+                    self.lit(",")?;
+                    self.space()?;
+                }
+                self.lit(id)?;
+            }
+            self.lit(">")?;
+        }
+        self.lit("(")?;
+        for (i, l) in params.iter().enumerate() {
+            if i > 0 {
+                self.lit(",")?;
+                self.space()?;
+            }
+            self.local(l)?;
+        }
+        self.lit(")")?;
+        self.space()?;
+        self.lit("->")?;
+        self.space()?;
+        self.ty(ty)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> std::fmt::Result {
+        match stmt {
+            Stmt::Let(l, Some(e)) => {
+                self.lit("let")?;
+                self.space()?;
+                self.local(l)?;
+                self.space()?;
+                self.lit("=")?;
+                self.space()?;
+                self.expr(e)
+            }
+            Stmt::Let(l, None) => {
+                // Variables declared without a value are assigned later, which rustc only allows
+                // more than once for mutable variables.
+                self.lit("let")?;
+                self.space()?;
+                self.lit("mut")?;
+                self.space()?;
+                self.lit(&l.id)?;
+                self.lit(":")?;
+                self.space()?;
+                self.ty(&l.ty)
+            }
+            Stmt::Expr(e) => self.expr(e),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> std::fmt::Result {
+        self.lit("{")?;
+        self.indent_level += 1;
+        for stmt in &block.stmts {
+            self.newline()?;
+            self.stmt(stmt)?;
+            self.lit(";")?;
+        }
+        if let Some(e) = &block.expr {
+            self.newline()?;
+            self.expr(e)?;
+        }
+        self.indent_level -= 1;
+        self.newline()?;
+        self.lit("}")
+    }
+
+    fn label(&mut self, l: &Option<usize>) -> std::fmt::Result {
+        if let Some(l) = l {
+            self.space()?;
+            self.lit("'l")?;
+            self.lit(l)?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, e: &Expr) -> std::fmt::Result {
+        match e {
+            Expr::IfElse(_, e1, b2, b3) => {
+                self.lit("if")?;
+                self.space()?;
+                self.expr(e1)?;
+                self.space()?;
+                self.block(b2)?;
+                self.space()?;
+                self.lit("else")?;
+                self.space()?;
+                self.block(b3)
+            }
+            Expr::While(_, l, e, b) => {
+                if let Some(l) = l {
+                    self.lit("'l")?;
+                    self.lit(l)?;
+                    self.lit(":")?;
+                    self.space()?;
+                }
+                self.lit("while")?;
+                self.space()?;
+                self.expr(e)?;
+                self.space()?;
+                self.block(b)
+            }
+            Expr::Loop(_, l, b) => {
+                if let Some(l) = l {
+                    self.lit("'l")?;
+                    self.lit(l)?;
+                    self.lit(":")?;
+                    self.space()?;
+                }
+                self.lit("loop")?;
+                self.space()?;
+                self.block(b)
+            }
+            Expr::Tuple(_, es) => {
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                if es.len() == 1 {
+                    self.lit(",")?;
+                }
+                self.lit(")")
+            }
+            Expr::Array(_, es) => {
+                self.lit("[")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit("]")
+            }
+            Expr::Struct(t, fields) => {
+                if let Type::Struct(s) = t {
+                    self.lit(&s.id)?;
+                    self.space()?;
+                }
+                self.lit("{")?;
+                for (i, (f, e)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                    }
+                    self.space()?;
+                    self.lit(f)?;
+                    self.lit(":")?;
+                    self.space()?;
+                    self.expr(e)?;
+                }
+                self.space()?;
+                self.lit("}")
+            }
+            Expr::Variant(t, v, es) => {
+                if let Type::Enum(e) = t {
+                    self.lit(&e.id)?;
+                    self.lit("::")?;
+                }
+                self.lit(v)?;
+                if !es.is_empty() {
+                    self.lit("(")?;
+                    for (i, e) in es.iter().enumerate() {
+                        if i > 0 {
+                            self.lit(",")?;
+                            self.space()?;
+                        }
+                        self.expr(e)?;
+                    }
+                    self.lit(")")?;
+                }
+                Ok(())
+            }
+            Expr::Match(_, place, arms) => {
+                self.lit("match")?;
+                self.space()?;
+                self.place(place)?;
+                self.space()?;
+                self.lit("{")?;
+                self.indent_level += 1;
+                for arm in arms {
+                    self.newline()?;
+                    self.pattern(&arm.pattern)?;
+                    self.space()?;
+                    self.lit("=>")?;
+                    self.space()?;
+                    self.block(&arm.block)?;
+                }
+                self.indent_level -= 1;
+                self.newline()?;
+                self.lit("}")
+            }
+            Expr::Ref(_, place) => {
+                self.lit("&")?;
+                self.place(place)
+            }
+            Expr::RefMut(_, place) => {
+                self.lit("&mut")?;
+                self.space()?;
+                self.place(place)
+            }
+            Expr::Seq(_, e1, e2) => {
+                self.lit("{")?;
+                self.space()?;
+                self.expr(e1)?;
+                self.lit(";")?;
+                self.space()?;
+                self.expr(e2)?;
+                self.space()?;
+                self.lit("}")
+            }
+            Expr::Assign(_, place, e) => {
+                self.place(place)?;
+                self.space()?;
+                self.lit("=")?;
+                self.space()?;
+                self.expr(e)
+            }
+            Expr::Place(_, p) => self.place(p),
+            // Parenthesized so that the operands bind correctly whatever they are.
+            Expr::Arith(_, op, e1, e2) => {
+                self.lit("(")?;
+                self.expr(e1)?;
+                self.space()?;
+                self.lit(op.symbol())?;
+                self.space()?;
+                self.expr(e2)?;
+                self.lit(")")
+            }
+            Expr::Int(t, i) => {
+                self.lit(i)?;
+                match t {
+                    Type::Integer(t) => self.lit(t.name()),
+                    _ => Ok(()),
+                }
+            }
+            Expr::Cast(t, e) => {
+                self.lit("(")?;
+                self.expr(e)?;
+                self.space()?;
+                self.lit("as")?;
+                self.space()?;
+                self.ty(t)?;
+                self.lit(")")
+            }
+            Expr::Bool(_, b) => self.lit(b),
+            Expr::String(_, s) => {
+                self.lit("String::from(")?;
+                self.lit(format!("{:?}", s))?;
+                self.lit(")")
+            }
+            Expr::Box(_, e) => {
+                self.lit("Box::new(")?;
+                self.expr(e)?;
+                self.lit(")")
+            }
+            Expr::Unit(_) => self.lit("()"),
+            Expr::Print(_, e) => {
+                self.lit("println!(\"{}\",")?;
+                self.space()?;
+                self.expr(e)?;
+                self.lit(")")
+            }
+            Expr::Return(_, e) => {
+                self.lit("return")?;
+                self.space()?;
+                self.expr(e)
+            }
+            Expr::MethodCall(_, p, m, es) => {
+                self.place(p)?;
+                self.lit(".")?;
+                self.lit(m)?;
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")
+            }
+            Expr::Call(_, id, es) => {
+                // The methods of cells are those of the standard library.
+                let path = id.split("::").next().unwrap_or(id);
+                if CellKind::from_name(path).is_some() {
+                    self.lit("std::cell::")?;
+                }
+                self.lit(id)?;
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")
+            }
+            Expr::Closure(_, lambda) => {
+                if lambda.is_move {
+                    self.lit("move")?;
+                    self.space()?;
+                }
+                self.lit("|")?;
+                for (i, l) in lambda.params.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.local(l)?;
+                }
+                self.lit("|")?;
+                self.space()?;
+                self.expr(&lambda.body)
+            }
+            // A closure stored in a field is called as `(s.f)(..)`.
+            Expr::CallClosure(_, p, es) => {
+                if p.elems.is_empty() {
+                    self.place(p)?;
+                } else {
+                    self.lit("(")?;
+                    self.place(p)?;
+                    self.lit(")")?;
+                }
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")
+            }
+            Expr::Continue(_, l) => {
+                self.lit("continue")?;
+                self.label(l)
+            }
+            Expr::Break(_, l, e) => {
+                self.lit("break")?;
+                self.label(l)?;
+                if let Some(e) = e {
+                    self.space()?;
+                    self.expr(e)?;
+                }
+                Ok(())
+            }
+            Expr::Block(_, b) => self.block(b),
+        }
+    }
+}
+
+// The lifetime to print for a reference: that of the enclosing declaration, or else the lifetime
+// parameter that the reference borrows for, if it is the only one.
+fn named_lifetime<'a>(loans: &'a [Loan], lifetime: &'a str) -> Option<&'a str> {
+    if lifetime != "'_" {
+        return Some(lifetime);
+    }
+    let mut ids = loans.iter().map(|l| l.lifetime_id());
+    match ids.next() {
+        Some(Some(id)) if ids.all(|id2| id2 == Some(id)) => Some(id),
+        _ => None,
+    }
+}
+
+fn has_ref(t: &Type) -> bool {
+    match t {
+        Type::Ref(..) | Type::RefMut(..) | Type::Guard(..) | Type::GuardMut(..) => true,
+        Type::Tuple(ts) => ts.iter().any(has_ref),
+        Type::Box(t) | Type::Array(t, _) | Type::Slice(t) | Type::Cell(_, t) => has_ref(t),
+        Type::Struct(s) => s.fields.iter().any(|(_, t)| has_ref(t)),
+        Type::Enum(e) => e.variants.iter().any(|(_, t)| has_ref(t)),
+        _ => false,
+    }
+}
+
+fn decl_id(t: &Type) -> Option<&str> {
+    match t {
+        Type::Struct(s) => Some(&s.id),
+        Type::Enum(e) => Some(&e.id),
+        _ => None,
+    }
+}
+
+// Collects the structs and enums that occur in a type, each once and nested ones first.
+fn collect_decls(t: &Type, out: &mut Vec<Type>) {
+    match t {
+        Type::Tuple(ts) => ts.iter().for_each(|t| collect_decls(t, out)),
+        Type::Closure(c) => c.captures.iter().for_each(|t| collect_decls(t, out)),
+        Type::Ref(_, t)
+        | Type::RefMut(_, t)
+        | Type::Box(t)
+        | Type::Array(t, _)
+        | Type::Slice(t)
+        | Type::Cell(_, t)
+        | Type::Guard(_, t)
+        | Type::GuardMut(_, t) => collect_decls(t, out),
+        Type::Struct(s) => {
+            for (_, t) in &s.fields {
+                collect_decls(t, out);
+            }
+            push_decl(t, out);
+        }
+        Type::Enum(e) => {
+            for (_, t) in &e.variants {
+                collect_decls(t, out);
+            }
+            push_decl(t, out);
+        }
+        _ => {}
+    }
+}
+
+fn push_decl(t: &Type, out: &mut Vec<Type>) {
+    if !out.iter().any(|t2| decl_id(t2) == decl_id(t)) {
+        out.push(t.clone());
+    }
+}
+
+fn collect_decls_block(b: &Block, out: &mut Vec<Type>) {
+    for stmt in &b.stmts {
+        match stmt {
+            Stmt::Let(l, e) => {
+                collect_decls(&l.ty, out);
+                if let Some(e) = e {
+                    collect_decls_expr(e, out);
+                }
+            }
+            Stmt::Expr(e) => collect_decls_expr(e, out),
+        }
+    }
+    if let Some(e) = &b.expr {
+        collect_decls_expr(e, out);
+    }
+}
+
+fn collect_decls_expr(e: &Expr, out: &mut Vec<Type>) {
+    collect_decls(e.ty(), out);
+    match e {
+        Expr::IfElse(_, e1, b2, b3) => {
+            collect_decls_expr(e1, out);
+            collect_decls_block(b2, out);
+            collect_decls_block(b3, out);
+        }
+        Expr::While(_, _, e, b) => {
+            collect_decls_expr(e, out);
+            collect_decls_block(b, out);
+        }
+        Expr::Loop(_, _, b) | Expr::Block(_, b) => collect_decls_block(b, out),
+        Expr::Tuple(_, es) | Expr::Array(_, es) => {
+            es.iter().for_each(|e| collect_decls_expr(e, out))
+        }
+        Expr::Struct(_, fields) => fields.iter().for_each(|(_, e)| collect_decls_expr(e, out)),
+        Expr::Variant(_, _, es)
+        | Expr::Call(_, _, es)
+        | Expr::CallClosure(_, _, es)
+        | Expr::MethodCall(_, _, _, es) => es.iter().for_each(|e| collect_decls_expr(e, out)),
+        Expr::Closure(_, lambda) => {
+            for l in &lambda.params {
+                collect_decls(&l.ty, out);
+            }
+            collect_decls_expr(&lambda.body, out);
+        }
+        Expr::Match(_, place, arms) => {
+            collect_decls(&place.local.ty, out);
+            for arm in arms {
+                if let Pattern::Variant(_, _, bindings) = &arm.pattern {
+                    for b in bindings.iter().flatten() {
+                        collect_decls(&b.local.ty, out);
+                    }
+                }
+                collect_decls_block(&arm.block, out);
+            }
+        }
+        Expr::Seq(_, e1, e2) | Expr::Arith(_, _, e1, e2) => {
+            collect_decls_expr(e1, out);
+            collect_decls_expr(e2, out);
+        }
+        Expr::Assign(_, _, e)
+        | Expr::Print(_, e)
+        | Expr::Box(_, e)
+        | Expr::Return(_, e)
+        | Expr::Cast(_, e)
+        | Expr::Break(_, _, Some(e)) => collect_decls_expr(e, out),
+        Expr::Ref(..)
+        | Expr::RefMut(..)
+        | Expr::Place(..)
+        | Expr::Int(..)
+        | Expr::Bool(..)
+        | Expr::String(..)
+        | Expr::Unit(_)
+        | Expr::Continue(..)
+        | Expr::Break(..) => {}
+    }
+}
+
+pub struct Rust<T>(T);
+
+impl Function {
+    pub fn rust(&self) -> Rust<&Function> {
+        Rust(self)
+    }
+}
+
+impl std::fmt::Display for Rust<&Function> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Printer::new(f).function(self.0)
+    }
+}
+
+impl Program {
+    pub fn rust(&self) -> Rust<&Program> {
+        Rust(self)
+    }
+}
+
+impl std::fmt::Display for Rust<&Program> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Printer::new(f).program(self.0)
+    }
+}
@@ -1,21 +1,28 @@
 use crate::ast::Loan;
 use crate::ast::Place;
 use crate::mir::Function;
+use crate::mir::Operand;
 use crate::mir::Operation;
 use crate::mir::Rvalue;
 
 pub struct Context<'a> {
     function: &'a Function,
+    // The move paths that may be uninitialized before each statement of each block.
+    uninit: Vec<Vec<Vec<Place>>>,
 }
 
 impl<'a> Context<'a> {
     pub fn new(function: &'a Function) -> Context<'a> {
-        Context { function }
+        Context {
+            function,
+            uninit: function.maybe_uninit(),
+        }
     }
 
     fn check(&self) {
-        for block in &self.function.blocks {
-            for stmt in &block.stmts {
+        for (b, block) in self.function.blocks.iter().enumerate() {
+            for (i, stmt) in block.stmts.iter().enumerate() {
+                let uninit = &self.uninit[b][i];
                 match &stmt.op {
                     Operation::Assign(lhs, rhs) => {
                         let loan = Loan {
@@ -26,8 +33,12 @@ impl<'a> Context<'a> {
                             panic!("Borrowck error");
                         }
                         match rhs {
-                            Rvalue::Use(_) => {}
+                            Rvalue::Use(op) => {
+                                self.check_init(uninit, op);
+                                self.check_move(&stmt.live_out, op);
+                            }
                             Rvalue::Ref { mutable, place } => {
+                                self.check_init_place(uninit, place);
                                 let loan = Loan {
                                     place: place.clone(),
                                     mutable: *mutable,
@@ -36,11 +47,33 @@ impl<'a> Context<'a> {
                                     panic!("Borrowck error");
                                 }
                             }
+                            // Matching on a place reads it, which a mutable loan forbids.
+                            Rvalue::Discriminant(place) => {
+                                self.check_init_place(uninit, place);
+                                let loan = Loan {
+                                    place: place.clone(),
+                                    mutable: false,
+                                };
+                                if !self.permits(&stmt.live_in, &loan) {
+                                    panic!("Borrowck error");
+                                }
+                            }
+                        }
+                    }
+                    // Dropping frees what the place owns, so nothing borrowed from it may be used
+                    // afterwards.
+                    Operation::SetDiscriminant(dest, _) | Operation::Drop(dest) => {
+                        let loan = Loan {
+                            place: dest.clone(),
+                            mutable: true,
+                        };
+                        if !self.permits(&stmt.live_out, &loan) {
+                            panic!("Borrowck error");
                         }
                     }
                     Operation::StorageLive(..) => {}
                     Operation::StorageDead(..) => {}
-                    Operation::Call { dest, .. } => {
+                    Operation::Call { dest, args, .. } => {
                         let loan = Loan {
                             place: dest.clone(),
                             mutable: true,
@@ -48,6 +81,10 @@ impl<'a> Context<'a> {
                         if !self.permits(&stmt.live_in, &loan) {
                             panic!("Borrowck error");
                         }
+                        for arg in args {
+                            self.check_init(uninit, arg);
+                            self.check_move(&stmt.live_out, arg);
+                        }
                     }
                     Operation::Noop => {}
                 }
@@ -55,6 +92,37 @@ impl<'a> Context<'a> {
         }
     }
 
+    // A place cannot be used if it or a part of it may have been moved out of, e.g., a FnOnce
+    // closure that is called in a loop, or a local that a `move` closure captured.
+    fn check_init(&self, uninit: &[Place], op: &Operand) {
+        if let Operand::Copy(place) | Operand::Move(place) = op {
+            self.check_init_place(uninit, place);
+        }
+    }
+
+    fn check_init_place(&self, uninit: &[Place], place: &Place) {
+        if uninit
+            .iter()
+            .any(|p| p.is_prefix_of(place) || place.is_prefix_of(p))
+        {
+            panic!("Borrowck error");
+        }
+    }
+
+    // A value cannot be moved while something borrowed from it is still used, e.g., a box while
+    // a reference to its contents is live.
+    fn check_move(&self, live_out: &[Place], op: &Operand) {
+        if let Operand::Move(place) = op {
+            let loan = Loan {
+                place: place.clone(),
+                mutable: true,
+            };
+            if !self.permits(live_out, &loan) {
+                panic!("Borrowck error");
+            }
+        }
+    }
+
     fn permits(&self, live_out: &[Place], loan1: &Loan) -> bool {
         for place in live_out {
             for loan2 in place.local.ty.loans() {
@@ -71,7 +139,7 @@ impl<'a> Context<'a> {
     }
 
     fn disjoint(&self, p1: &Place, p2: &Place) -> bool {
-        !p1.is_prefix_of(p2) && !p2.is_prefix_of(p1)
+        !p1.may_overlap(p2)
     }
 }
 
@@ -0,0 +1,492 @@
+use std::rc::Rc;
+
+use crate::ast::Arm;
+use crate::ast::Binding;
+use crate::ast::BindingMode;
+use crate::ast::Block;
+use crate::ast::Closure;
+use crate::ast::ClosureKind;
+use crate::ast::Enum;
+use crate::ast::Expr;
+use crate::ast::Function;
+use crate::ast::Lambda;
+use crate::ast::Lifetime;
+use crate::ast::Loan;
+use crate::ast::Local;
+use crate::ast::Name;
+use crate::ast::Pattern;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Stmt;
+use crate::ast::Struct;
+use crate::ast::Type;
+
+// Closures capture the places of the enclosing function that their bodies use, like in Rust 2021,
+// where a closure that uses `x.f` captures only `x.f`, so the other fields of `x` can still be used
+// while the closure is live. A place is captured
+// - by reference if it is only read, which includes copying it,
+// - by mutable reference if it is assigned or borrowed mutably, and
+// - by value if it is moved out of, or if the closure is a `move` closure.
+// A place is captured up to its first index or downcast, and a place captured by value up to its
+// first dereference, since nothing can be moved out of a reference. A captured place within
+// another is captured with it, in the stronger of the two modes.
+//
+// The body of a closure is lifted to a function of the program, whose first parameter `self` is
+// the closure, or a reference to it unless the closure is FnOnce. Uses of the captured places
+// become uses of the captures, e.g., `x.f.g` becomes `self.deref.index(0).deref.g` if `x.f` is the
+// first capture and is captured by reference.
+
+// Finds the places that a closure captures, in the order of their first use, and its kind.
+pub fn captures(lambda: &Lambda, is_copy: impl Fn(&Type) -> bool) -> (Vec<Capture>, ClosureKind) {
+    let mut uses = vec![];
+    let mut visit = |p: &Place, mode: BindingMode| {
+        let mode = match mode {
+            BindingMode::Value if is_copy(&p.ty()) => BindingMode::Ref,
+            mode => mode,
+        };
+        uses.push((p.clone(), mode));
+        p.clone()
+    };
+    Walker::new(&lambda.params, &mut visit, &|_| None).expr(&lambda.body);
+    let kind = if uses.iter().any(|(_, m)| *m == BindingMode::Value) {
+        ClosureKind::FnOnce
+    } else if uses.iter().any(|(_, m)| *m == BindingMode::RefMut) {
+        ClosureKind::FnMut
+    } else {
+        ClosureKind::Fn
+    };
+    let mut captures: Vec<Capture> = vec![];
+    for (p, mode) in uses {
+        let mode = if lambda.is_move {
+            BindingMode::Value
+        } else {
+            mode
+        };
+        let p = truncate(&p, mode);
+        if let Some(c) = captures.iter_mut().find(|c| c.place.is_prefix_of(&p)) {
+            c.mode = stronger(c.mode, mode);
+            continue;
+        }
+        let within = |c: &Capture| p.is_prefix_of(&c.place);
+        let mode = captures
+            .iter()
+            .filter(|c| within(c))
+            .fold(mode, |m, c| stronger(m, c.mode));
+        let i = captures.iter().position(within).unwrap_or(captures.len());
+        captures.retain(|c| !within(c));
+        captures.insert(i, Capture { place: p, mode });
+    }
+    (captures, kind)
+}
+
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub place: Place,
+    pub mode: BindingMode,
+}
+
+impl Capture {
+    // The value that the closure holds for the capture.
+    pub fn into_expr(self) -> Expr {
+        let ty = Rc::new(self.place.ty());
+        let loan = Loan {
+            place: self.place.clone(),
+            mutable: self.mode == BindingMode::RefMut,
+        };
+        match self.mode {
+            BindingMode::Value => Expr::Place(ty.as_ref().clone(), self.place),
+            BindingMode::Ref => Expr::Ref(Type::Ref(vec![loan], ty), self.place),
+            BindingMode::RefMut => Expr::RefMut(Type::RefMut(vec![loan], ty), self.place),
+        }
+    }
+}
+
+// Lifts the body of a closure of type `ty` to a function, once the types of the body are known.
+// The function has the type parameters of the enclosing function that the closure mentions.
+// The locals that index into places within the body are copied into locals of the function, which
+// the body must not assign to. Loans of captured places in the types of the body become loans of
+// the captures.
+pub fn lift(lambda: &Lambda, ty: &Type, type_params: &[Name]) -> Function {
+    let Type::Closure(c) = ty else {
+        unreachable!("Closure of type {}", ty)
+    };
+    let captures = lambda
+        .captures
+        .iter()
+        .map(|e| match e {
+            Expr::Place(_, p) => (p, BindingMode::Value),
+            Expr::Ref(_, p) => (p, BindingMode::Ref),
+            Expr::RefMut(_, p) => (p, BindingMode::RefMut),
+            e => unreachable!("Capture {:?}", e),
+        })
+        .collect::<Vec<_>>();
+    let (env, lifetimes) = match c.kind {
+        ClosureKind::Fn => (
+            Type::Ref(vec![Loan::lifetime("'env", false)], Rc::new(ty.clone())),
+            vec!["'env"],
+        ),
+        ClosureKind::FnMut => (
+            Type::RefMut(vec![Loan::lifetime("'env", true)], Rc::new(ty.clone())),
+            vec!["'env"],
+        ),
+        ClosureKind::FnOnce => (ty.clone(), vec![]),
+    };
+    let env = Local {
+        id: "self".to_string(),
+        ty: env,
+        mutable: c.kind == ClosureKind::FnOnce,
+    };
+    let rewrite = |p: &Place| {
+        let (k, (q, mode)) = captures
+            .iter()
+            .enumerate()
+            .find(|(_, (q, _))| q.is_prefix_of(p))
+            .unwrap_or_else(|| panic!("Closure: {} is not captured", p));
+        let mut elems = vec![];
+        if c.kind != ClosureKind::FnOnce {
+            elems.push(PlaceElem::Deref);
+        }
+        elems.push(PlaceElem::TupleIndex(k));
+        if *mode != BindingMode::Value {
+            elems.push(PlaceElem::Deref);
+        }
+        elems.extend(p.elems[q.elems.len()..].iter().cloned());
+        Place {
+            local: env.clone(),
+            elems,
+        }
+    };
+    let mut assigned = vec![];
+    let mut visit = |p: &Place, mode: BindingMode| {
+        if mode == BindingMode::RefMut && p.elems.is_empty() {
+            assigned.push(p.local.id.clone());
+        }
+        rewrite(p)
+    };
+    let captured = |p: &Place| captures.iter().any(|(q, _)| q.is_prefix_of(p));
+    let loan = |p: &Place| captured(p).then(|| rewrite(p));
+    let mut walker = Walker::new(&lambda.params, &mut visit, &loan);
+    let body = walker.expr(&lambda.body);
+    let indices = std::mem::take(&mut walker.indices);
+    let stmts = indices
+        .into_iter()
+        .map(|l| {
+            if assigned.contains(&l.id) {
+                panic!(
+                    "Closure: cannot index with {}, which the closure assigns to",
+                    l.id
+                );
+            }
+            let p = rewrite(&Place::from(l.clone()));
+            Stmt::Let(l, Some(Expr::Place(p.ty(), p)))
+        })
+        .collect();
+    Function {
+        id: c.id.clone(),
+        lifetimes: lifetimes
+            .into_iter()
+            .map(|id| Lifetime {
+                id: id.to_string(),
+                bounds: vec![],
+            })
+            .collect(),
+        type_params: type_params
+            .iter()
+            .filter(|id| mentions_param(ty, id))
+            .cloned()
+            .collect(),
+        params: [env].into_iter().chain(lambda.params.clone()).collect(),
+        ty: c.ty.clone(),
+        block: Block {
+            stmts,
+            expr: Some(body),
+        },
+    }
+}
+
+// Replaces the loans in a type.
+fn map_loans(t: &Type, f: &dyn Fn(&Loan) -> Loan) -> Type {
+    let map = |t: &Type| map_loans(t, f);
+    match t {
+        Type::Ref(ls, t) => Type::Ref(ls.iter().map(f).collect(), Rc::new(map(t))),
+        Type::RefMut(ls, t) => Type::RefMut(ls.iter().map(f).collect(), Rc::new(map(t))),
+        Type::Guard(ls, t) => Type::Guard(ls.iter().map(f).collect(), Rc::new(map(t))),
+        Type::GuardMut(ls, t) => Type::GuardMut(ls.iter().map(f).collect(), Rc::new(map(t))),
+        Type::Box(t) => Type::Box(Rc::new(map(t))),
+        Type::Cell(k, t) => Type::Cell(*k, Rc::new(map(t))),
+        Type::Array(t, n) => Type::Array(Rc::new(map(t)), *n),
+        Type::Slice(t) => Type::Slice(Rc::new(map(t))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(map).collect()),
+        Type::Struct(s) => Type::Struct(Rc::new(Struct {
+            id: s.id.clone(),
+            fields: s.fields.iter().map(|(n, t)| (n.clone(), map(t))).collect(),
+        })),
+        Type::Enum(e) => Type::Enum(Rc::new(Enum {
+            id: e.id.clone(),
+            variants: e
+                .variants
+                .iter()
+                .map(|(n, t)| (n.clone(), map(t)))
+                .collect(),
+        })),
+        Type::Closure(c) => Type::Closure(Rc::new(Closure {
+            captures: c.captures.iter().map(map).collect(),
+            ..c.as_ref().clone()
+        })),
+        _ => t.clone(),
+    }
+}
+
+// Cuts a place off where it stops being captured precisely.
+fn truncate(p: &Place, mode: BindingMode) -> Place {
+    let n = p
+        .elems
+        .iter()
+        .position(|e| match e {
+            PlaceElem::TupleIndex(_) | PlaceElem::Field(_) => false,
+            PlaceElem::Deref => mode == BindingMode::Value,
+            _ => true,
+        })
+        .unwrap_or(p.elems.len());
+    Place {
+        local: p.local.clone(),
+        elems: p.elems[..n].to_vec(),
+    }
+}
+
+fn stronger(m1: BindingMode, m2: BindingMode) -> BindingMode {
+    let rank = |m| match m {
+        BindingMode::Ref => 0,
+        BindingMode::RefMut => 1,
+        BindingMode::Value => 2,
+    };
+    if rank(m1) >= rank(m2) {
+        m1
+    } else {
+        m2
+    }
+}
+
+fn mentions_param(t: &Type, id: &Name) -> bool {
+    match t {
+        Type::Param(p) => p == id,
+        Type::Ref(_, t)
+        | Type::RefMut(_, t)
+        | Type::Guard(_, t)
+        | Type::GuardMut(_, t)
+        | Type::Box(t)
+        | Type::Array(t, _)
+        | Type::Slice(t)
+        | Type::Cell(_, t) => mentions_param(t, id),
+        Type::Tuple(ts) => ts.iter().any(|t| mentions_param(t, id)),
+        Type::Closure(c) => c
+            .params
+            .iter()
+            .chain(&c.captures)
+            .chain([&c.ty])
+            .any(|t| mentions_param(t, id)),
+        _ => false,
+    }
+}
+
+// Visits the places of an expression that are not of locals declared within it, with how they
+// are used, and rebuilds the expression with the places that the visitor returns. Of a nested
+// closure, only its captures are visited, which use what its body does. The locals that index
+// into places are visited as reads, and collected. Loans of places that are not of locals declared
+// within the expression are replaced by loans of the places that `loan` returns, if any.
+struct Walker<'a> {
+    // The locals declared within the expression that are in scope.
+    inner: Vec<Name>,
+    visit: &'a mut dyn FnMut(&Place, BindingMode) -> Place,
+    loan: &'a dyn Fn(&Place) -> Option<Place>,
+    indices: Vec<Local>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(
+        params: &[Local],
+        visit: &'a mut dyn FnMut(&Place, BindingMode) -> Place,
+        loan: &'a dyn Fn(&Place) -> Option<Place>,
+    ) -> Self {
+        Walker {
+            inner: params.iter().map(|l| l.id.clone()).collect(),
+            visit,
+            loan,
+            indices: vec![],
+        }
+    }
+
+    fn ty(&self, t: &Type) -> Type {
+        map_loans(t, &|l: &Loan| {
+            if self.inner.contains(&l.place.local.id) {
+                return l.clone();
+            }
+            match (self.loan)(&l.place) {
+                Some(place) => Loan {
+                    place,
+                    mutable: l.mutable,
+                },
+                None => l.clone(),
+            }
+        })
+    }
+
+    fn local(&self, l: &Local) -> Local {
+        Local {
+            ty: self.ty(&l.ty),
+            ..l.clone()
+        }
+    }
+
+    fn place(&mut self, p: &Place, mode: BindingMode) -> Place {
+        for l in p.index_locals() {
+            if !self.inner.contains(&l.id) {
+                (self.visit)(&Place::from(l.clone()), BindingMode::Ref);
+                if !self.indices.iter().any(|l2| l2.id == l.id) {
+                    self.indices.push(l.clone());
+                }
+            }
+        }
+        if self.inner.contains(&p.local.id) {
+            Place {
+                local: self.local(&p.local),
+                elems: p.elems.clone(),
+            }
+        } else {
+            (self.visit)(p, mode)
+        }
+    }
+
+    fn rc(&mut self, e: &Expr) -> Rc<Expr> {
+        Rc::new(self.expr(e))
+    }
+
+    fn exprs(&mut self, es: &[Expr]) -> Vec<Expr> {
+        es.iter().map(|e| self.expr(e)).collect()
+    }
+
+    fn block(&mut self, b: &Block) -> Rc<Block> {
+        let depth = self.inner.len();
+        let stmts = b
+            .stmts
+            .iter()
+            .map(|s| match s {
+                Stmt::Let(l, e) => {
+                    let e = e.as_ref().map(|e| self.expr(e));
+                    let l = self.local(l);
+                    self.inner.push(l.id.clone());
+                    Stmt::Let(l, e)
+                }
+                Stmt::Expr(e) => Stmt::Expr(self.expr(e)),
+            })
+            .collect();
+        let expr = b.expr.as_ref().map(|e| self.expr(e));
+        self.inner.truncate(depth);
+        Rc::new(Block { stmts, expr })
+    }
+
+    fn expr(&mut self, e: &Expr) -> Expr {
+        match e {
+            Expr::IfElse(t, e0, b1, b2) => {
+                Expr::IfElse(self.ty(t), self.rc(e0), self.block(b1), self.block(b2))
+            }
+            Expr::While(t, l, e0, b) => Expr::While(self.ty(t), *l, self.rc(e0), self.block(b)),
+            Expr::Loop(t, l, b) => Expr::Loop(self.ty(t), *l, self.block(b)),
+            Expr::Tuple(t, es) => Expr::Tuple(self.ty(t), self.exprs(es)),
+            Expr::Array(t, es) => Expr::Array(self.ty(t), self.exprs(es)),
+            Expr::Struct(t, fields) => Expr::Struct(
+                self.ty(t),
+                fields
+                    .iter()
+                    .map(|(f, e)| (f.clone(), self.expr(e)))
+                    .collect(),
+            ),
+            Expr::Variant(t, v, es) => Expr::Variant(self.ty(t), v.clone(), self.exprs(es)),
+            // Matching reads the place, and a binding uses the part of the payload it binds like
+            // its binding mode says.
+            Expr::Match(t, p, arms) => {
+                for arm in arms {
+                    if let Pattern::Variant(_, v, bindings) = &arm.pattern {
+                        for (k, b) in bindings.iter().enumerate() {
+                            let Some(b) = b else { continue };
+                            let mut q = p.clone();
+                            q.elems
+                                .extend([PlaceElem::Downcast(v.clone()), PlaceElem::TupleIndex(k)]);
+                            self.place(&q, b.mode);
+                        }
+                    }
+                }
+                let p = self.place(p, BindingMode::Ref);
+                let arms = arms
+                    .iter()
+                    .map(|arm| {
+                        let depth = self.inner.len();
+                        let pattern = match &arm.pattern {
+                            Pattern::Variant(e, v, bindings) => {
+                                let bindings = bindings
+                                    .iter()
+                                    .map(|b| {
+                                        b.as_ref().map(|b| Binding {
+                                            mode: b.mode,
+                                            local: self.local(&b.local),
+                                        })
+                                    })
+                                    .collect::<Vec<_>>();
+                                self.inner
+                                    .extend(bindings.iter().flatten().map(|b| b.local.id.clone()));
+                                Pattern::Variant(e.clone(), v.clone(), bindings)
+                            }
+                            Pattern::Wildcard => Pattern::Wildcard,
+                        };
+                        let block = self.block(&arm.block);
+                        self.inner.truncate(depth);
+                        Arm { pattern, block }
+                    })
+                    .collect();
+                Expr::Match(self.ty(t), p, arms)
+            }
+            Expr::Ref(t, p) => Expr::Ref(self.ty(t), self.place(p, BindingMode::Ref)),
+            Expr::RefMut(t, p) => Expr::RefMut(self.ty(t), self.place(p, BindingMode::RefMut)),
+            Expr::Seq(t, e0, e1) => Expr::Seq(self.ty(t), self.rc(e0), self.rc(e1)),
+            Expr::Assign(t, p, e0) => {
+                let p = self.place(p, BindingMode::RefMut);
+                Expr::Assign(self.ty(t), p, self.rc(e0))
+            }
+            Expr::Place(t, p) => Expr::Place(self.ty(t), self.place(p, BindingMode::Value)),
+            Expr::Arith(t, op, e0, e1) => Expr::Arith(self.ty(t), *op, self.rc(e0), self.rc(e1)),
+            Expr::Cast(t, e0) => Expr::Cast(self.ty(t), self.rc(e0)),
+            Expr::Print(t, e0) => Expr::Print(self.ty(t), self.rc(e0)),
+            Expr::Box(t, e0) => Expr::Box(self.ty(t), self.rc(e0)),
+            Expr::Call(t, id, es) => Expr::Call(self.ty(t), id.clone(), self.exprs(es)),
+            Expr::MethodCall(_, p, m, _) => unreachable!("Method call {}.{} is not resolved", p, m),
+            Expr::Closure(t, lambda) => {
+                let captures = self.exprs(&lambda.captures);
+                Expr::Closure(
+                    self.ty(t),
+                    Rc::new(Lambda {
+                        captures,
+                        ..lambda.as_ref().clone()
+                    }),
+                )
+            }
+            // Calling a closure uses it like its kind says.
+            Expr::CallClosure(t, p, es) => {
+                let es = self.exprs(es);
+                let mode = match p.ty() {
+                    Type::Closure(c) if c.kind == ClosureKind::FnMut => BindingMode::RefMut,
+                    Type::Closure(c) if c.kind == ClosureKind::FnOnce => BindingMode::Value,
+                    _ => BindingMode::Ref,
+                };
+                Expr::CallClosure(self.ty(t), self.place(p, mode), es)
+            }
+            Expr::Return(t, e0) => Expr::Return(self.ty(t), self.rc(e0)),
+            Expr::Break(t, l, e0) => Expr::Break(self.ty(t), *l, e0.as_ref().map(|e| self.rc(e))),
+            Expr::Block(t, b) => Expr::Block(self.ty(t), self.block(b)),
+            Expr::Int(..)
+            | Expr::Bool(..)
+            | Expr::String(..)
+            | Expr::Unit(_)
+            | Expr::Continue(..) => e.clone(),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::ast::ArithOp;
 use crate::mir::Constant;
 use crate::mir::Function;
 use crate::mir::Operand;
@@ -8,8 +9,10 @@ use crate::mir::Rvalue;
 use crate::mir::Terminator;
 
 impl Function {
-    pub fn constant_propagation(&mut self) {
+    // Returns whether any operation or terminator was folded.
+    pub fn constant_propagation(&mut self) -> bool {
         let mut map = HashMap::new();
+        let mut changed = false;
 
         for b in 0..self.blocks.len() {
             for stmt in &mut self.blocks[b].stmts {
@@ -23,14 +26,49 @@ impl Function {
                                 p.clone(),
                                 Rvalue::Use(Operand::Constant(c.clone())),
                             );
+                            changed = true;
                         }
                     }
                     Operation::Assign(p, _) => {
                         map.remove(p);
                     }
+                    // Arithmetic on constants is folded, except when it overflows, which is left
+                    // for the interpreter to report at runtime.
+                    Operation::Call {
+                        dest,
+                        func: Operand::Function(name),
+                        args,
+                    } if ArithOp::from_name(name).is_some() => {
+                        let op = ArithOp::from_name(name).unwrap();
+                        let ints = args
+                            .iter()
+                            .map(|a| match a {
+                                Operand::Constant(c) => c.as_int(),
+                                Operand::Copy(p) => map.get(p).and_then(Constant::as_int),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>();
+                        let folded = match ints[..] {
+                            [Some((t, a)), Some((_, b))] => {
+                                t.checked(op, a, b).map(|i| Constant::int(t, i))
+                            }
+                            _ => None,
+                        };
+                        if let Some(c) = folded {
+                            map.insert(dest.clone(), c.clone());
+                            stmt.op =
+                                Operation::Assign(dest.clone(), Rvalue::Use(Operand::Constant(c)));
+                            changed = true;
+                        } else {
+                            map.remove(dest);
+                        }
+                    }
                     Operation::Call { dest, .. } => {
                         map.remove(dest);
                     }
+                    Operation::SetDiscriminant(dest, _) => {
+                        map.retain(|p, _| !dest.is_prefix_of(p));
+                    }
                     _ => {}
                 }
             }
@@ -45,6 +83,7 @@ impl Function {
                                 } else {
                                     Terminator::Goto(*false_block)
                                 };
+                                changed = true;
                             }
                         }
                     }
@@ -52,5 +91,6 @@ impl Function {
                 }
             }
         }
+        changed
     }
 }
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::ast::LocalId;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::mir::BlockId;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+impl Function {
+    // Replaces uses of `_n` with `p` when `_n = copy p` is the only assignment to `_n`, `p` is
+    // never reassigned, and neither `_n` nor `p` is borrowed. Elements at runtime indices are not
+    // forwarded, since the index may be reassigned. A use is only rewritten if the
+    // storage of `p` is definitely live at that point.
+    //
+    // Operands are rewritten wherever they are read, i.e., in uses, call arguments and the operands
+    // of terminators, and so are the places whose discriminant is read.
+    //
+    // The copies themselves are left behind for dead store elimination to remove. Returns whether
+    // any operand was rewritten.
+    pub fn copy_propagation(&mut self) -> bool {
+        let mut assignments: HashMap<LocalId, usize> = HashMap::new();
+        let mut copies: HashMap<LocalId, Place> = HashMap::new();
+        let mut borrowed: HashSet<LocalId> = HashSet::new();
+
+        for p in &self.params {
+            *assignments.entry(p.id.clone()).or_default() += 1;
+        }
+        for l in self.params.iter().chain(self.locals.iter()) {
+            for loan in l.ty.loans() {
+                borrowed.insert(loan.place.local.id.clone());
+            }
+        }
+        for block in &self.blocks {
+            for stmt in &block.stmts {
+                match &stmt.op {
+                    Operation::Assign(dest, rvalue) => {
+                        *assignments.entry(dest.local.id.clone()).or_default() += 1;
+                        match rvalue {
+                            Rvalue::Use(Operand::Copy(src)) if dest.elems.is_empty() => {
+                                copies.insert(dest.local.id.clone(), src.clone());
+                            }
+                            Rvalue::Ref { place, .. } => {
+                                borrowed.insert(place.local.id.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                    Operation::Call { dest, .. } => {
+                        *assignments.entry(dest.local.id.clone()).or_default() += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let single = |id: &LocalId| assignments.get(id).copied().unwrap_or(0) <= 1;
+        let copies: HashMap<LocalId, Place> = copies
+            .into_iter()
+            .filter(|(dest, src)| {
+                single(dest)
+                    && single(&src.local.id)
+                    && !borrowed.contains(dest)
+                    && !borrowed.contains(&src.local.id)
+                    && !src.elems.contains(&PlaceElem::Deref)
+                    && src.index_locals().next().is_none()
+                    && *dest != src.local.id
+            })
+            .collect();
+
+        if copies.is_empty() {
+            return false;
+        }
+
+        // Resolve chains such as `_2 = copy _1; _3 = copy _2` so that `_3` forwards to `_1`.
+        let copies: HashMap<LocalId, Place> = copies
+            .iter()
+            .map(|(dest, src)| {
+                let mut src = src.clone();
+                for _ in 0..copies.len() {
+                    match copies.get(&src.local.id) {
+                        Some(next) => {
+                            let mut elems = next.elems.clone();
+                            elems.extend(src.elems);
+                            src = Place {
+                                local: next.local.clone(),
+                                elems,
+                            };
+                        }
+                        None => break,
+                    }
+                }
+                (dest.clone(), src)
+            })
+            .collect();
+
+        let dead_on_entry = self.maybe_storage_dead();
+        let mut changed = false;
+
+        for (b, dead) in dead_on_entry.into_iter().enumerate() {
+            let mut dead = dead;
+            let block = &mut self.blocks[b];
+            for stmt in &mut block.stmts {
+                match &mut stmt.op {
+                    Operation::Assign(_, Rvalue::Use(op)) => changed |= forward(op, &copies, &dead),
+                    Operation::Assign(_, Rvalue::Discriminant(p)) => {
+                        if let Some(src) = forwarded(p, &copies, &dead) {
+                            *p = src;
+                            changed = true;
+                        }
+                    }
+                    Operation::Call { func, args, .. } => {
+                        changed |= forward(func, &copies, &dead);
+                        for arg in args {
+                            changed |= forward(arg, &copies, &dead);
+                        }
+                    }
+                    Operation::StorageLive(l) => {
+                        dead.remove(&l.id);
+                    }
+                    Operation::StorageDead(l) => {
+                        dead.insert(l.id.clone());
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(
+                Terminator::ConditionalGoto(cond, _, _) | Terminator::SwitchInt(cond, _, _),
+            ) = &mut block.terminator
+            {
+                changed |= forward(cond, &copies, &dead);
+            }
+        }
+        changed
+    }
+
+    // b ↦ locals whose storage may be dead on entry to b, i.e., for which there is a path from the
+    // entry with a StorageDead that is not followed by a StorageLive.
+    fn maybe_storage_dead(&self) -> Vec<HashSet<LocalId>> {
+        let mut entry = vec![HashSet::new(); self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 0..self.blocks.len() {
+                let mut dead = entry[b].clone();
+                for stmt in &self.blocks[b].stmts {
+                    match &stmt.op {
+                        Operation::StorageLive(l) => {
+                            dead.remove(&l.id);
+                        }
+                        Operation::StorageDead(l) => {
+                            dead.insert(l.id.clone());
+                        }
+                        _ => {}
+                    }
+                }
+                let succs: Vec<BlockId> = match &self.blocks[b].terminator {
+                    Some(Terminator::Goto(b1)) => vec![*b1],
+                    Some(Terminator::ConditionalGoto(_, b1, b2)) => vec![*b1, *b2],
+                    Some(Terminator::SwitchInt(_, targets, otherwise)) => targets
+                        .iter()
+                        .map(|(_, b)| *b)
+                        .chain([*otherwise])
+                        .collect(),
+                    _ => vec![],
+                };
+                for succ in succs {
+                    for l in &dead {
+                        changed |= entry[succ].insert(l.clone());
+                    }
+                }
+            }
+        }
+        entry
+    }
+
+    pub fn with_copy_propagation(mut self) -> Self {
+        self.copy_propagation();
+        self
+    }
+}
+
+// Returns whether `op` was rewritten.
+fn forward(op: &mut Operand, copies: &HashMap<LocalId, Place>, dead: &HashSet<LocalId>) -> bool {
+    if let Operand::Copy(p) | Operand::Move(p) = op {
+        if let Some(src) = forwarded(p, copies, dead) {
+            *op = Operand::Copy(src);
+            return true;
+        }
+    }
+    false
+}
+
+// The place that `p` reads if its local is a copy of a place whose storage is live.
+fn forwarded(
+    p: &Place,
+    copies: &HashMap<LocalId, Place>,
+    dead: &HashSet<LocalId>,
+) -> Option<Place> {
+    let src = copies.get(&p.local.id)?;
+    if dead.contains(&src.local.id) {
+        return None;
+    }
+    let mut elems = src.elems.clone();
+    elems.extend(p.elems.iter().cloned());
+    Some(Place {
+        local: src.local.clone(),
+        elems,
+    })
+}
@@ -0,0 +1,34 @@
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::mir::Function;
+use crate::mir::Operation;
+
+impl Function {
+    // Removes assignments whose destination is not live afterwards. Calls are kept since they may
+    // have side effects, and writes through a dereference are kept since they may be observed
+    // through another reference.
+    //
+    // Depends on liveness. Returns whether any assignment was removed.
+    pub fn dead_store_elimination(&mut self) -> bool {
+        let ret = Place::from(self.locals[0].clone());
+        let mut changed = false;
+        for block in &mut self.blocks {
+            let len = block.stmts.len();
+            block.stmts.retain(|stmt| match &stmt.op {
+                Operation::Assign(dest, _) => {
+                    dest.elems.contains(&PlaceElem::Deref)
+                        || ret.is_prefix_of(dest)
+                        || stmt.live_out.iter().any(|p| dest.may_overlap(p))
+                }
+                _ => true,
+            });
+            changed |= block.stmts.len() != len;
+        }
+        changed
+    }
+
+    pub fn with_dead_store_elimination(mut self) -> Self {
+        self.dead_store_elimination();
+        self
+    }
+}
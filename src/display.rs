@@ -1,9 +1,13 @@
 use std::fmt::Display;
 
 use crate::ast;
+use crate::ast::BindingMode;
 use crate::ast::Expr;
+use crate::ast::Lifetime;
 use crate::ast::Loan;
 use crate::ast::Local;
+use crate::ast::Name;
+use crate::ast::Pattern;
 use crate::ast::Place;
 use crate::ast::Type;
 use crate::mir;
@@ -53,8 +57,11 @@ impl<'a, 'b> Printer<'a, 'b> {
     fn ty(&mut self, t: &Type) -> std::fmt::Result {
         match t {
             Type::Int => self.lit("i32"),
+            Type::Integer(t) => self.lit(t.name()),
             Type::Bool => self.lit("bool"),
             Type::Unit => self.lit("()"),
+            Type::Never => self.lit("!"),
+            Type::Param(id) => self.lit(id),
             Type::Tuple(tys) => {
                 self.lit("(")?;
                 for (i, ty) in tys.iter().enumerate() {
@@ -68,6 +75,11 @@ impl<'a, 'b> Printer<'a, 'b> {
             }
             Type::Ref(loans, ty) => {
                 self.lit("&")?;
+                if let Some(id) = single_lifetime(loans, false) {
+                    self.lit(id)?;
+                    self.space()?;
+                    return self.ty(ty);
+                }
                 self.lit("{")?;
                 for (i, loan) in loans.iter().enumerate() {
                     if i > 0 {
@@ -82,6 +94,13 @@ impl<'a, 'b> Printer<'a, 'b> {
             }
             Type::RefMut(loans, ty) => {
                 self.lit("&")?;
+                if let Some(id) = single_lifetime(loans, true) {
+                    self.lit(id)?;
+                    self.space()?;
+                    self.lit("mut")?;
+                    self.space()?;
+                    return self.ty(ty);
+                }
                 self.lit("{")?;
                 for (i, loan) in loans.iter().enumerate() {
                     if i > 0 {
@@ -96,7 +115,54 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.space()?;
                 self.ty(ty)
             }
+            Type::Struct(s) => self.lit(&s.id),
+            Type::Enum(e) => self.lit(&e.id),
+            Type::Box(ty) => {
+                self.lit("Box<")?;
+                self.ty(ty)?;
+                self.lit(">")
+            }
+            Type::Cell(kind, ty) => {
+                self.lit(kind.name())?;
+                self.lit("<")?;
+                self.ty(ty)?;
+                self.lit(">")
+            }
+            // E.g., `RefMut<{shared(c)} i32>`.
+            Type::Guard(loans, ty) | Type::GuardMut(loans, ty) => {
+                self.lit(if matches!(t, Type::Guard(..)) {
+                    "Ref<{"
+                } else {
+                    "RefMut<{"
+                })?;
+                for (i, loan) in loans.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.loan(loan)?;
+                }
+                self.lit("}")?;
+                self.space()?;
+                self.ty(ty)?;
+                self.lit(">")
+            }
+            Type::Array(ty, n) => {
+                self.lit("[")?;
+                self.ty(ty)?;
+                self.lit(";")?;
+                self.space()?;
+                self.lit(n)?;
+                self.lit("]")
+            }
+            Type::Slice(ty) => {
+                self.lit("[")?;
+                self.ty(ty)?;
+                self.lit("]")
+            }
+            Type::Closure(c) => self.lit(&c.id),
             Type::Unknown => self.lit("?"),
+            Type::Var(v) => self.lit(format!("?{}", v)),
             Type::String => self.lit("String"),
         }
     }
@@ -108,10 +174,39 @@ impl<'a, 'b> Printer<'a, 'b> {
             self.lit("shared")?;
         }
         self.lit("(")?;
-        self.place(&loan.place)?;
+        match loan.lifetime_id() {
+            Some(id) => self.lit(id)?,
+            None => self.place(&loan.place)?,
+        }
         self.lit(")")
     }
 
+    fn generics(&mut self, lifetimes: &[Lifetime], type_params: &[Name]) -> std::fmt::Result {
+        if lifetimes.is_empty() && type_params.is_empty() {
+            return Ok(());
+        }
+        self.lit("<")?;
+        for (i, l) in lifetimes.iter().enumerate() {
+            if i > 0 {
+                self.lit(",")?;
+                self.space()?;
+            }
+            self.lit(&l.id)?;
+            for (j, bound) in l.bounds.iter().enumerate() {
+                self.lit(if j == 0 { ": " } else { " + " })?;
+                self.lit(bound)?;
+            }
+        }
+        for (i, id) in type_params.iter().enumerate() {
+            if i > 0 || !lifetimes.is_empty() {
+                self.lit(",")?;
+                self.space()?;
+            }
+            self.lit(id)?;
+        }
+        self.lit(">")
+    }
+
     fn place(&mut self, place: &Place) -> std::fmt::Result {
         if self.verbose {
             self.lit("(")?;
@@ -124,14 +219,44 @@ impl<'a, 'b> Printer<'a, 'b> {
         }
         for elem in &place.elems {
             match elem {
-                ast::PlaceElem::Index(i) => {
+                ast::PlaceElem::TupleIndex(i) => {
                     self.lit(".")?;
                     self.lit(&i)?;
                 }
+                ast::PlaceElem::Field(f) => {
+                    self.lit(".")?;
+                    self.lit(f)?;
+                }
+                ast::PlaceElem::Downcast(v) => {
+                    self.lit(".")?;
+                    self.lit("downcast")?;
+                    self.lit("(")?;
+                    self.lit(v)?;
+                    self.lit(")")?;
+                }
                 ast::PlaceElem::Deref => {
                     self.lit(".")?;
                     self.lit("deref")?;
                 }
+                ast::PlaceElem::ConstantIndex(i) => {
+                    self.lit("[")?;
+                    self.lit(i)?;
+                    self.lit("]")?;
+                }
+                ast::PlaceElem::Index(l) => {
+                    self.lit("[")?;
+                    self.lit(&l.id)?;
+                    self.lit("]")?;
+                }
+                ast::PlaceElem::Subslice { from, to } => {
+                    self.lit("[")?;
+                    self.lit(from)?;
+                    self.lit("..")?;
+                    if let Some(to) = to {
+                        self.lit(to)?;
+                    }
+                    self.lit("]")?;
+                }
             }
         }
         Ok(())
@@ -141,6 +266,7 @@ impl<'a, 'b> Printer<'a, 'b> {
         self.lit("fn")?;
         self.space()?;
         self.lit(&f.id)?;
+        self.generics(&f.lifetimes, &f.type_params)?;
         self.lit("(")?;
         self.locals(&f.params)?;
         self.lit(")")?;
@@ -178,6 +304,7 @@ impl<'a, 'b> Printer<'a, 'b> {
         self.lit("fn")?;
         self.space()?;
         self.lit(&f.id)?;
+        self.generics(&f.lifetimes, &f.type_params)?;
         self.lit("(")?;
         self.locals(&f.params)?;
         self.lit(")")?;
@@ -276,6 +403,22 @@ impl<'a, 'b> Printer<'a, 'b> {
                 }
                 self.lit(")")?;
             }
+            Operation::SetDiscriminant(place, v) => {
+                self.lit("discriminant")?;
+                self.lit("(")?;
+                self.place(place)?;
+                self.lit(")")?;
+                self.space()?;
+                self.lit("=")?;
+                self.space()?;
+                self.lit(v)?;
+            }
+            Operation::Drop(place) => {
+                self.lit("Drop")?;
+                self.lit("(")?;
+                self.place(place)?;
+                self.lit(")")?;
+            }
             Operation::Noop => {}
         }
         self.lit(";")?;
@@ -388,6 +531,30 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.lit("bb")?;
                 self.lit(&block_id2)
             }
+            Terminator::SwitchInt(discr, targets, otherwise) => {
+                self.lit("switchInt")?;
+                self.lit("(")?;
+                self.operand(discr)?;
+                self.lit(")")?;
+                self.space()?;
+                self.lit("->")?;
+                self.space()?;
+                self.lit("[")?;
+                for (value, block_id) in targets {
+                    self.lit(value)?;
+                    self.lit(":")?;
+                    self.space()?;
+                    self.lit("bb")?;
+                    self.lit(block_id)?;
+                    self.lit(",")?;
+                    self.space()?;
+                }
+                self.lit("otherwise:")?;
+                self.space()?;
+                self.lit("bb")?;
+                self.lit(otherwise)?;
+                self.lit("]")
+            }
         }
     }
 
@@ -402,6 +569,12 @@ impl<'a, 'b> Printer<'a, 'b> {
                 }
                 self.place(place)
             }
+            Rvalue::Discriminant(place) => {
+                self.lit("discriminant")?;
+                self.lit("(")?;
+                self.place(place)?;
+                self.lit(")")
+            }
         }
     }
 
@@ -431,6 +604,10 @@ impl<'a, 'b> Printer<'a, 'b> {
             Constant::Int(i) => {
                 self.lit(i)?;
             }
+            Constant::Integer(t, i) => {
+                self.lit(i)?;
+                self.lit(t.name())?;
+            }
             Constant::Bool(b) => {
                 self.lit(b)?;
             }
@@ -446,6 +623,51 @@ impl<'a, 'b> Printer<'a, 'b> {
         Ok(())
     }
 
+    fn pattern(&mut self, pattern: &Pattern) -> std::fmt::Result {
+        match pattern {
+            Pattern::Wildcard => self.lit("_"),
+            Pattern::Variant(e, v, bindings) => {
+                self.lit(e)?;
+                self.lit("::")?;
+                self.lit(v)?;
+                if bindings.is_empty() {
+                    return Ok(());
+                }
+                self.lit("(")?;
+                for (i, b) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    match b {
+                        None => self.lit("_")?,
+                        Some(b) => {
+                            match b.mode {
+                                BindingMode::Value if b.local.mutable => {
+                                    self.lit("mut")?;
+                                    self.space()?;
+                                }
+                                BindingMode::Value => {}
+                                BindingMode::Ref => {
+                                    self.lit("ref")?;
+                                    self.space()?;
+                                }
+                                BindingMode::RefMut => {
+                                    self.lit("ref")?;
+                                    self.space()?;
+                                    self.lit("mut")?;
+                                    self.space()?;
+                                }
+                            }
+                            self.lit(&b.local.id)?;
+                        }
+                    }
+                }
+                self.lit(")")
+            }
+        }
+    }
+
     fn expr(&mut self, e: &Expr) -> std::fmt::Result {
         if self.verbose {
             self.lit("(")?;
@@ -462,7 +684,13 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.space()?;
                 self.ast_block(b3)?;
             }
-            Expr::While(_, e, b) => {
+            Expr::While(_, l, e, b) => {
+                if let Some(l) = l {
+                    self.lit("'l")?;
+                    self.lit(l)?;
+                    self.lit(":")?;
+                    self.space()?;
+                }
                 self.lit("while")?;
                 self.space()?;
                 self.expr(e)?;
@@ -482,6 +710,73 @@ impl<'a, 'b> Printer<'a, 'b> {
                 }
                 self.lit(")")?;
             }
+            Expr::Array(_, es) => {
+                self.lit("[")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit("]")?;
+            }
+            Expr::Struct(t, fields) => {
+                if let Type::Struct(s) = t {
+                    self.lit(&s.id)?;
+                    self.space()?;
+                }
+                self.lit("{")?;
+                for (i, (f, e)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                    }
+                    self.space()?;
+                    self.lit(f)?;
+                    self.lit(":")?;
+                    self.space()?;
+                    self.expr(e)?;
+                }
+                self.space()?;
+                self.lit("}")?;
+            }
+            Expr::Variant(t, v, es) => {
+                if let Type::Enum(e) = t {
+                    self.lit(&e.id)?;
+                    self.lit("::")?;
+                }
+                self.lit(v)?;
+                if !es.is_empty() {
+                    self.lit("(")?;
+                    for (i, e) in es.iter().enumerate() {
+                        if i > 0 {
+                            self.lit(",")?;
+                            self.space()?;
+                        }
+                        self.expr(e)?;
+                    }
+                    self.lit(")")?;
+                }
+            }
+            Expr::Match(_, place, arms) => {
+                self.lit("match")?;
+                self.space()?;
+                self.place(place)?;
+                self.space()?;
+                self.lit("{")?;
+                self.indent_level += 1;
+                for arm in arms {
+                    self.newline()?;
+                    self.pattern(&arm.pattern)?;
+                    self.space()?;
+                    self.lit("=>")?;
+                    self.space()?;
+                    self.ast_block(&arm.block)?;
+                }
+                self.indent_level -= 1;
+                self.newline()?;
+                self.lit("}")?;
+            }
             Expr::Ref(_, place) => {
                 self.lit("&")?;
                 self.place(place)?;
@@ -511,8 +806,8 @@ impl<'a, 'b> Printer<'a, 'b> {
             Expr::Place(_, p) => {
                 self.place(p)?;
             }
-            Expr::Add(_, e1, e2) => {
-                self.lit("add")?;
+            Expr::Arith(_, op, e1, e2) => {
+                self.lit(op.name())?;
                 self.lit("(")?;
                 self.expr(e1)?;
                 self.lit(",")?;
@@ -520,8 +815,19 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.expr(e2)?;
                 self.lit(")")?;
             }
-            Expr::Int(_, i) => {
+            Expr::Int(t, i) => {
                 self.lit(i)?;
+                if let Type::Integer(t) = t {
+                    self.lit(t.name())?;
+                }
+            }
+            Expr::Cast(t, e) => {
+                self.lit("cast(")?;
+                self.expr(e)?;
+                self.lit(",")?;
+                self.space()?;
+                self.ty(t)?;
+                self.lit(")")?;
             }
             Expr::Bool(_, b) => {
                 self.lit(b)?;
@@ -543,18 +849,89 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.expr(e)?;
                 self.lit(")")?;
             }
+            Expr::Box(_, e) => {
+                self.lit("box")?;
+                self.lit("(")?;
+                self.expr(e)?;
+                self.lit(")")?;
+            }
+            Expr::MethodCall(_, p, m, es) => {
+                self.place(p)?;
+                self.lit(".")?;
+                self.lit(m)?;
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")?;
+            }
+            Expr::Call(_, id, es) => {
+                self.lit(id)?;
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")?;
+            }
+            Expr::Closure(_, lambda) => {
+                if lambda.is_move {
+                    self.lit("move")?;
+                    self.space()?;
+                }
+                self.lit("|")?;
+                for (i, l) in lambda.params.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    // Parameters whose type is left out are printed the same way.
+                    if l.ty == Type::Unknown {
+                        if l.mutable {
+                            self.lit("mut")?;
+                            self.space()?;
+                        }
+                        self.lit(&l.id)?;
+                    } else {
+                        self.local(l)?;
+                    }
+                }
+                self.lit("|")?;
+                self.space()?;
+                self.expr(&lambda.body)?;
+            }
+            Expr::CallClosure(_, p, es) => {
+                self.place(p)?;
+                self.lit("(")?;
+                for (i, e) in es.iter().enumerate() {
+                    if i > 0 {
+                        self.lit(",")?;
+                        self.space()?;
+                    }
+                    self.expr(e)?;
+                }
+                self.lit(")")?;
+            }
             Expr::Return(_, e) => {
                 self.lit("return")?;
                 self.space()?;
                 self.expr(e)?;
             }
             Expr::Loop(_, l, b) => {
-                self.lit("loop")?;
                 if let Some(l) = l {
-                    self.space()?;
-                    self.lit("'")?;
+                    self.lit("'l")?;
                     self.lit(&l)?;
+                    self.lit(":")?;
+                    self.space()?;
                 }
+                self.lit("loop")?;
                 self.space()?;
                 self.ast_block(b)?;
             }
@@ -562,17 +939,21 @@ impl<'a, 'b> Printer<'a, 'b> {
                 self.lit("continue")?;
                 if let Some(l) = l {
                     self.space()?;
-                    self.lit("'")?;
+                    self.lit("'l")?;
                     self.lit(&l)?;
                 }
             }
-            Expr::Break(_, l) => {
+            Expr::Break(_, l, e) => {
                 self.lit("break")?;
                 if let Some(l) = l {
                     self.space()?;
-                    self.lit("'")?;
+                    self.lit("'l")?;
                     self.lit(&l)?;
                 }
+                if let Some(e) = e {
+                    self.space()?;
+                    self.expr(e)?;
+                }
             }
         }
         if self.verbose {
@@ -590,6 +971,19 @@ impl std::fmt::Display for ast::Function {
     }
 }
 
+impl std::fmt::Display for ast::Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+                writeln!(f)?;
+            }
+            Printer::new(f).ast_function(function)?;
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Display for mir::Function {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         Printer::new(f).mir_function(self)
@@ -602,6 +996,12 @@ impl<'a> std::fmt::Display for Loan {
     }
 }
 
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Printer::new(f).ty(self)
+    }
+}
+
 impl<'a> std::fmt::Display for Place {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         Printer::new(f).place(self)
@@ -706,3 +1106,12 @@ impl mir::Function {
         self
     }
 }
+
+// The lifetime of a reference that borrows for a lifetime parameter only, which is printed as
+// `&'a T`.
+fn single_lifetime(loans: &[Loan], mutable: bool) -> Option<&str> {
+    match loans {
+        [loan] if loan.mutable == mutable => loan.lifetime_id().map(|id| id.as_str()),
+        _ => None,
+    }
+}
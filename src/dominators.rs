@@ -79,6 +79,12 @@ impl Function {
                         preds[*b1].push(b0);
                         preds[*b2].push(b0);
                     }
+                    Terminator::SwitchInt(_, targets, otherwise) => {
+                        for (_, b1) in targets {
+                            preds[*b1].push(b0);
+                        }
+                        preds[*otherwise].push(b0);
+                    }
                     _ => {}
                 }
             }
@@ -99,6 +105,12 @@ impl Function {
                         succs[b0].push(*b1);
                         succs[b0].push(*b2);
                     }
+                    Terminator::SwitchInt(_, targets, otherwise) => {
+                        for (_, b1) in targets {
+                            succs[b0].push(*b1);
+                        }
+                        succs[b0].push(*otherwise);
+                    }
                     _ => {}
                 }
             }
@@ -0,0 +1,439 @@
+use crate::ast::Local;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Type;
+use crate::mir::BasicBlock;
+use crate::mir::BlockId;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Stmt;
+use crate::mir::Terminator;
+use crate::set::Set;
+
+// Whether a move path is initialized on some path to a point, and whether it is uninitialized on
+// some path to it. A move path that is neither is unreachable.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct State {
+    maybe_init: bool,
+    maybe_uninit: bool,
+}
+
+impl State {
+    fn new(init: bool) -> State {
+        State {
+            maybe_init: init,
+            maybe_uninit: !init,
+        }
+    }
+
+    fn join(self, other: State) -> State {
+        State {
+            maybe_init: self.maybe_init || other.maybe_init,
+            maybe_uninit: self.maybe_uninit || other.maybe_uninit,
+        }
+    }
+
+    fn is_init(self) -> bool {
+        self.maybe_init && !self.maybe_uninit
+    }
+}
+
+struct Elaborator {
+    // The owned places whose initialization is tracked.
+    paths: Vec<Place>,
+    // The state of every move path on entry to each block.
+    entry: Vec<Vec<State>>,
+}
+
+impl Function {
+    // Inserts a drop before the storage of a local ends, and for parameters before returning, for
+    // the parts of it that are initialized. Parts that have been moved out of are not dropped, and
+    // parts that are initialized on some paths but not on others are dropped under a drop flag,
+    // which is a boolean local that is set whenever the part is initialized and cleared whenever
+    // it is moved out of or dropped. Returns whether any drop was inserted.
+    pub fn elaborate_drops(&mut self) -> bool {
+        let elaborator = Elaborator::new(self);
+
+        // Decide what to drop before every StorageDead and Return, before changing any block.
+        let mut plans = vec![];
+        let mut flagged = vec![false; elaborator.paths.len()];
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut state = elaborator.entry[b].clone();
+            let mut plan = |i: usize, places: Vec<Place>, state: &[State]| {
+                let mut drops = vec![];
+                for p in places {
+                    elaborator.open_drop(p, state, &mut drops);
+                }
+                for (_, flag) in &drops {
+                    if let Some(k) = flag {
+                        flagged[*k] = true;
+                    }
+                }
+                plans.push(((b, i), drops));
+            };
+            for (i, stmt) in block.stmts.iter().enumerate() {
+                if let Operation::StorageDead(l) = &stmt.op {
+                    if l.ty.needs_drop() {
+                        plan(i, vec![Place::from(l.clone())], &state);
+                    }
+                }
+                elaborator.apply(&stmt.op, &mut state);
+            }
+            if let Some(Terminator::Return) = block.terminator {
+                let params = self.params.iter().filter(|l| l.ty.needs_drop());
+                plan(
+                    block.stmts.len(),
+                    params.map(|l| Place::from(l.clone())).collect(),
+                    &state,
+                );
+            }
+        }
+
+        let mut next = self
+            .params
+            .iter()
+            .chain(self.locals.iter())
+            .filter_map(|l| l.id.strip_prefix('_')?.parse::<usize>().ok())
+            .max()
+            .map_or(0, |n| n + 1);
+        let flags = flagged
+            .iter()
+            .map(|flagged| {
+                flagged.then(|| {
+                    let l = Local {
+                        id: format!("_{}", next),
+                        ty: Type::Bool,
+                        mutable: true,
+                    };
+                    next += 1;
+                    l
+                })
+            })
+            .collect::<Vec<_>>();
+        // A flag is already clear when the storage of its local starts or ends, since it is
+        // cleared whenever the part is moved out of or dropped.
+        let set_flags = |op: &Operation, stmts: &mut Vec<Stmt>| {
+            if matches!(op, Operation::StorageLive(_) | Operation::StorageDead(_)) {
+                return;
+            }
+            for (k, init) in elaborator.effects(op) {
+                if let Some(flag) = &flags[k] {
+                    stmts.push(set_flag(flag, init));
+                }
+            }
+        };
+
+        let changed = plans.iter().any(|(_, drops)| !drops.is_empty());
+        let mut plans = plans.into_iter().peekable();
+        for b in 0..self.blocks.len() {
+            let stmts = std::mem::take(&mut self.blocks[b].stmts);
+            let terminator = self.blocks[b].terminator.take();
+            let mut current = b;
+            let mut new_stmts = vec![];
+            let stmts = stmts.into_iter().map(Some).chain([None]);
+            for (i, stmt) in stmts.enumerate() {
+                if let Some((_, drops)) = plans.next_if(|(at, _)| *at == (b, i)) {
+                    for (place, flag) in drops {
+                        let drop = Operation::Drop(place);
+                        match flag.and_then(|k| flags[k].as_ref()) {
+                            None => {
+                                new_stmts.push(Stmt::new(drop.clone()));
+                                set_flags(&drop, &mut new_stmts);
+                            }
+                            Some(flag) => {
+                                let b_drop = self.blocks.len();
+                                let b_after = b_drop + 1;
+                                self.blocks[current].stmts = std::mem::take(&mut new_stmts);
+                                self.blocks[current].terminator =
+                                    Some(Terminator::ConditionalGoto(
+                                        Operand::Copy(Place::from(flag.clone())),
+                                        b_drop,
+                                        b_after,
+                                    ));
+                                let mut drop_stmts = vec![Stmt::new(drop.clone())];
+                                set_flags(&drop, &mut drop_stmts);
+                                self.blocks.push(new_block(
+                                    b_drop,
+                                    drop_stmts,
+                                    Some(Terminator::Goto(b_after)),
+                                ));
+                                self.blocks.push(new_block(b_after, vec![], None));
+                                current = b_after;
+                            }
+                        }
+                    }
+                }
+                if let Some(stmt) = stmt {
+                    let op = stmt.op.clone();
+                    new_stmts.push(stmt);
+                    set_flags(&op, &mut new_stmts);
+                }
+            }
+            self.blocks[current].stmts = new_stmts;
+            self.blocks[current].terminator = terminator;
+        }
+
+        // Drop flags live for the whole call, and start out set for parameters only.
+        let mut init = vec![];
+        for (k, flag) in flags.into_iter().enumerate() {
+            if let Some(flag) = flag {
+                let local = &elaborator.paths[k].local;
+                let param = self.params.iter().any(|l| l.id == local.id);
+                init.push(set_flag(&flag, param));
+                self.locals.push(flag);
+            }
+        }
+        self.blocks[0].stmts.splice(0..0, init);
+        changed
+    }
+
+    pub fn with_elaborated_drops(mut self) -> Self {
+        self.elaborate_drops();
+        self
+    }
+
+    // The move paths that may be uninitialized before each statement of each block, because they
+    // have not been initialized or have been moved out of on some path to it, including through a
+    // back edge of a loop.
+    pub(crate) fn maybe_uninit(&self) -> Vec<Vec<Vec<Place>>> {
+        let elaborator = Elaborator::new(self);
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(b, block)| {
+                let mut state = elaborator.entry[b].clone();
+                block
+                    .stmts
+                    .iter()
+                    .map(|stmt| {
+                        let uninit = elaborator
+                            .paths
+                            .iter()
+                            .zip(&state)
+                            .filter(|(_, s)| s.maybe_uninit)
+                            .map(|(p, _)| p.clone())
+                            .collect();
+                        elaborator.apply(&stmt.op, &mut state);
+                        uninit
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Elaborator {
+    fn new(f: &Function) -> Elaborator {
+        let mut paths = vec![];
+        for l in f.params.iter().filter(|l| l.ty.needs_drop()) {
+            move_paths(Place::from(l.clone()), &mut paths);
+        }
+        let params = paths.len();
+        for l in f.locals.iter().filter(|l| l.ty.needs_drop()) {
+            move_paths(Place::from(l.clone()), &mut paths);
+        }
+
+        // Only the parameters are initialized on entry.
+        let mut entry = vec![vec![State::default(); paths.len()]; f.blocks.len()];
+        entry[0] = (0..paths.len()).map(|k| State::new(k < params)).collect();
+        let mut worklist = vec![0];
+        let mut elaborator = Elaborator { paths, entry };
+        while let Some(b) = worklist.pop() {
+            let mut state = elaborator.entry[b].clone();
+            for stmt in &f.blocks[b].stmts {
+                elaborator.apply(&stmt.op, &mut state);
+            }
+            for target in targets(&f.blocks[b].terminator) {
+                let joined = elaborator.entry[target]
+                    .iter()
+                    .zip(&state)
+                    .map(|(s1, s2)| s1.join(*s2))
+                    .collect::<Vec<_>>();
+                if joined != elaborator.entry[target] {
+                    elaborator.entry[target] = joined;
+                    worklist.push(target);
+                }
+            }
+        }
+        elaborator
+    }
+
+    // The move paths that an operation initializes or uninitializes, in order.
+    fn effects(&self, op: &Operation) -> Vec<(usize, bool)> {
+        let within = |p: &Place| {
+            (0..self.paths.len())
+                .filter(|k| p.is_prefix_of(&self.paths[*k]))
+                .collect::<Vec<_>>()
+        };
+        let mut effects = vec![];
+        let moved = |o: &Operand, effects: &mut Vec<(usize, bool)>| {
+            if let Operand::Move(p) = o {
+                effects.extend(within(p).into_iter().map(|k| (k, false)));
+            }
+        };
+        // Writing into a part of a move path, such as the payload of an enum, initializes it too.
+        let assigned = |p: &Place, effects: &mut Vec<(usize, bool)>| {
+            effects.extend(
+                (0..self.paths.len())
+                    .filter(|k| p.is_prefix_of(&self.paths[*k]) || self.paths[*k].is_prefix_of(p))
+                    .map(|k| (k, true)),
+            );
+        };
+        match op {
+            Operation::Assign(dest, rvalue) => {
+                if let Rvalue::Use(o) = rvalue {
+                    moved(o, &mut effects);
+                }
+                assigned(dest, &mut effects);
+            }
+            Operation::Call { dest, args, .. } => {
+                for arg in args {
+                    moved(arg, &mut effects);
+                }
+                assigned(dest, &mut effects);
+            }
+            Operation::SetDiscriminant(dest, _) => assigned(dest, &mut effects),
+            Operation::Drop(place) => {
+                effects.extend(within(place).into_iter().map(|k| (k, false)));
+            }
+            Operation::StorageLive(l) | Operation::StorageDead(l) => {
+                effects.extend(
+                    within(&Place::from(l.clone()))
+                        .into_iter()
+                        .map(|k| (k, false)),
+                );
+            }
+            Operation::Noop => {}
+        }
+        effects
+    }
+
+    fn apply(&self, op: &Operation, state: &mut [State]) {
+        for (k, init) in self.effects(op) {
+            state[k] = State::new(init);
+        }
+    }
+
+    // Decides how to drop a place, given the states of the move paths within it. A place that is
+    // initialized is dropped as a whole, which also drops the parts of it that are not tracked,
+    // such as the payload of an enum. Otherwise, the fields of a tuple or struct are dropped one
+    // by one, and anything else is dropped if its flag is set.
+    fn open_drop(&self, p: Place, state: &[State], drops: &mut Vec<(Place, Option<usize>)>) {
+        let within = (0..self.paths.len())
+            .filter(|k| p.is_prefix_of(&self.paths[*k]))
+            .collect::<Vec<_>>();
+        if within.iter().all(|k| !state[*k].maybe_init) {
+            return;
+        }
+        if within.iter().all(|k| state[*k].is_init()) {
+            drops.push((p, None));
+            return;
+        }
+        match p.ty().clone() {
+            Type::Tuple(ts) => {
+                for (i, t) in ts.iter().enumerate() {
+                    if t.needs_drop() {
+                        self.open_drop(project(&p, PlaceElem::TupleIndex(i)), state, drops);
+                    }
+                }
+            }
+            Type::Closure(c) => {
+                for (i, t) in c.captures.iter().enumerate() {
+                    if t.needs_drop() {
+                        self.open_drop(project(&p, PlaceElem::TupleIndex(i)), state, drops);
+                    }
+                }
+            }
+            Type::Struct(s) => {
+                for (f, t) in &s.fields {
+                    if t.needs_drop() {
+                        self.open_drop(project(&p, PlaceElem::Field(f.clone())), state, drops);
+                    }
+                }
+            }
+            _ => {
+                let k = within
+                    .iter()
+                    .copied()
+                    .find(|k| self.paths[*k] == p)
+                    .unwrap();
+                if state[k].is_init() {
+                    drops.push((p, None));
+                } else if state[k].maybe_init {
+                    drops.push((p, Some(k)));
+                }
+            }
+        }
+    }
+}
+
+// Collects the move paths of an owned place. Strings, enums and boxes are move paths, and so is
+// the value a box points to, while tuples and structs are tracked through their fields. A value of
+// a type parameter can only be moved as a whole, and so can an array, since its elements cannot be
+// moved out of. A closure is tracked through its captures, which its body may move out of.
+fn move_paths(p: Place, paths: &mut Vec<Place>) {
+    match p.ty().clone() {
+        Type::String | Type::Param(_) | Type::Guard(..) | Type::GuardMut(..) => paths.push(p),
+        Type::Enum(_) | Type::Array(..) | Type::Cell(..) if p.ty().needs_drop() => paths.push(p),
+        Type::Box(_) => {
+            paths.push(p.clone());
+            move_paths(project(&p, PlaceElem::Deref), paths);
+        }
+        Type::Tuple(ts) => {
+            for i in 0..ts.len() {
+                move_paths(project(&p, PlaceElem::TupleIndex(i)), paths);
+            }
+        }
+        Type::Closure(c) => {
+            for i in 0..c.captures.len() {
+                move_paths(project(&p, PlaceElem::TupleIndex(i)), paths);
+            }
+        }
+        Type::Struct(s) => {
+            for (f, _) in &s.fields {
+                move_paths(project(&p, PlaceElem::Field(f.clone())), paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn project(p: &Place, elem: PlaceElem) -> Place {
+    let mut p = p.clone();
+    p.elems.push(elem);
+    p
+}
+
+fn set_flag(flag: &Local, value: bool) -> Stmt {
+    Stmt::new(Operation::Assign(
+        Place::from(flag.clone()),
+        Rvalue::Use(Operand::Constant(Constant::Bool(value))),
+    ))
+}
+
+fn new_block(id: BlockId, stmts: Vec<Stmt>, terminator: Option<Terminator>) -> BasicBlock {
+    BasicBlock {
+        id,
+        stmts,
+        terminator,
+        live_in: Set::new(),
+        live_out: Set::new(),
+        dom: Set::new(),
+    }
+}
+
+fn targets(t: &Option<Terminator>) -> Vec<BlockId> {
+    match t {
+        Some(Terminator::Goto(b1)) => vec![*b1],
+        Some(Terminator::ConditionalGoto(_, b1, b2)) => vec![*b1, *b2],
+        Some(Terminator::SwitchInt(_, targets, otherwise)) => targets
+            .iter()
+            .map(|(_, b)| *b)
+            .chain([*otherwise])
+            .collect(),
+        _ => vec![],
+    }
+}
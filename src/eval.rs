@@ -0,0 +1,556 @@
+use crate::ast::BindingMode;
+use crate::ast::Block;
+use crate::ast::ClosureKind;
+use crate::ast::Expr;
+use crate::ast::Function;
+use crate::ast::IntTy;
+use crate::ast::LocalId;
+use crate::ast::Pattern;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Stmt;
+use crate::ast::Type;
+use crate::interpreter::borrow;
+use crate::interpreter::check_index;
+use crate::interpreter::elem_index;
+use crate::interpreter::elements;
+use crate::interpreter::flag_path;
+use crate::interpreter::release;
+use crate::interpreter::subslice;
+use crate::interpreter::through_reference;
+use crate::interpreter::AllocId;
+use crate::interpreter::Elements;
+use crate::interpreter::Error;
+use crate::interpreter::Path;
+use crate::interpreter::Pointer;
+use crate::interpreter::Value;
+
+// Reference semantics of the typed AST, used to check the MIR lowering and passes against.
+pub struct Evaluator {
+    // Values of variables. A slot is None after its variable has gone out of scope.
+    slots: Vec<Option<Value>>,
+    scopes: Vec<Vec<(LocalId, AllocId)>>,
+    // The functions that can be called.
+    functions: Vec<Function>,
+    fuel: usize,
+    pub stdout: String,
+}
+
+// Non-local control flow, propagated as the error of `Result`.
+enum Control {
+    Break(Option<usize>, Value),
+    Continue(Option<usize>),
+    Return(Value),
+    Error(Error),
+}
+
+impl From<Error> for Control {
+    fn from(e: Error) -> Control {
+        Control::Error(e)
+    }
+}
+
+impl Function {
+    pub fn eval(&self, args: Vec<Value>) -> Result<Value, Error> {
+        Evaluator::new().run(self, args)
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator {
+            slots: vec![],
+            scopes: vec![],
+            functions: vec![],
+            fuel: 1_000_000,
+            stdout: String::new(),
+        }
+    }
+
+    pub fn with_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    pub fn with_functions(mut self, functions: &[Function]) -> Self {
+        self.functions = functions.to_vec();
+        self
+    }
+
+    pub fn run(&mut self, f: &Function, args: Vec<Value>) -> Result<Value, Error> {
+        self.scopes.push(vec![]);
+        for (param, arg) in f.params.iter().zip(args) {
+            self.bind(&param.id, arg);
+        }
+        let result = self.block(&f.block);
+        self.pop_scope();
+        match result {
+            Ok(v) | Err(Control::Return(v)) => Ok(v),
+            Err(Control::Error(e)) => Err(e),
+            Err(Control::Break(_, _)) | Err(Control::Continue(_)) => Err(Error::TypeError(
+                "break or continue outside of loop".to_string(),
+            )),
+        }
+    }
+
+    fn bind(&mut self, id: &LocalId, v: Value) {
+        let a = self.slots.len();
+        self.slots.push(Some(v));
+        self.scopes.last_mut().unwrap().push((id.clone(), a));
+    }
+
+    // Like drops in the MIR, the end of a scope ends the borrows of the guards in it.
+    fn pop_scope(&mut self) {
+        for (_, a) in self.scopes.pop().unwrap().into_iter().rev() {
+            if let Some(v) = self.slots[a].take() {
+                self.release(&v);
+            }
+        }
+    }
+
+    fn release(&mut self, v: &Value) {
+        match v {
+            Value::Tuple(vs) => vs.iter().for_each(|v| self.release(v)),
+            Value::Variant(_, v) => self.release(v),
+            Value::Guard(p, mutable) => {
+                if let Ok(flag) = self.get_mut(p.alloc, &flag_path(p)) {
+                    if let Ok(n) = release(flag, *mutable) {
+                        *flag = n;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A value that is not used lives until the end of the scope, like a temporary of the MIR.
+    fn discard(&mut self, v: Value) {
+        self.bind(&LocalId::new(), v);
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(vec![]);
+        let result = f(self);
+        self.pop_scope();
+        result
+    }
+
+    fn block(&mut self, b: &Block) -> Result<Value, Control> {
+        self.scoped(|ctx| {
+            for s in &b.stmts {
+                match s {
+                    Stmt::Let(l, Some(e)) => {
+                        let v = ctx.expr(e)?;
+                        ctx.bind(&l.id, v);
+                    }
+                    Stmt::Let(l, None) => ctx.bind(&l.id, Value::uninit(&l.ty)),
+                    Stmt::Expr(e) => ctx.scoped(|ctx| -> Result<(), Control> {
+                        let v = ctx.expr(e)?;
+                        ctx.discard(v);
+                        Ok(())
+                    })?,
+                }
+            }
+            match &b.expr {
+                Some(e) => ctx.expr(e),
+                None => Ok(Value::Unit),
+            }
+        })
+    }
+
+    fn expr(&mut self, e: &Expr) -> Result<Value, Control> {
+        if self.fuel == 0 {
+            return Err(Error::StepLimit.into());
+        }
+        self.fuel -= 1;
+        match e {
+            Expr::Int(t, i) => Ok(Value::int(t.int_ty().unwrap_or(IntTy::I32), *i)),
+            Expr::Cast(t, e) => Ok(self.expr(e)?.cast(t)?),
+            Expr::Bool(_, b) => Ok(Value::Bool(*b)),
+            Expr::String(_, s) => Ok(Value::String(s.clone())),
+            Expr::Unit(_) => Ok(Value::Unit),
+            Expr::Tuple(_, es) => {
+                let vs = es
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(vs))
+            }
+            // Arrays are stored like tuples.
+            Expr::Array(_, es) => {
+                let vs = es
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(vs))
+            }
+            // Fields are evaluated in source order but stored in declaration order.
+            Expr::Struct(ty, fields) => {
+                let Type::Struct(s) = ty else {
+                    return Err(Error::TypeError(format!("struct literal of {:?}", ty)).into());
+                };
+                let mut vs = vec![Value::Uninit; s.fields.len()];
+                for (f, e) in fields {
+                    let v = self.expr(e)?;
+                    let (i, _) = s
+                        .field(f)
+                        .ok_or_else(|| Error::TypeError(format!("no field {} in {}", f, s.id)))?;
+                    vs[i] = v;
+                }
+                Ok(Value::Tuple(vs))
+            }
+            Expr::Variant(ty, v, es) => {
+                let Type::Enum(e) = ty else {
+                    return Err(Error::TypeError(format!("variant of {:?}", ty)).into());
+                };
+                let (d, _) = e
+                    .variant(v)
+                    .ok_or_else(|| Error::TypeError(format!("no variant {} in {}", v, e.id)))?;
+                let vs = es
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Variant(d, Box::new(Value::Tuple(vs))))
+            }
+            Expr::Match(ty, p, arms) => {
+                let (a, path) = self.resolve(p)?;
+                let Value::Variant(d, payload) = self.get(a, &path)? else {
+                    return Err(Error::TypeError(format!("match on {:?}", ty)).into());
+                };
+                let Type::Enum(e) = p.ty() else {
+                    return Err(Error::TypeError(format!("match on {:?}", p.ty())).into());
+                };
+                let arm = arms
+                    .iter()
+                    .find(|arm| match &arm.pattern {
+                        Pattern::Wildcard => true,
+                        Pattern::Variant(_, v, _) => e.variant(v).map(|(i, _)| i) == Some(d),
+                    })
+                    .ok_or_else(|| Error::TypeError("no matching arm".to_string()))?;
+                self.scoped(|ctx| {
+                    if let Pattern::Variant(_, _, bindings) = &arm.pattern {
+                        for (k, b) in bindings.iter().enumerate() {
+                            let Some(b) = b else { continue };
+                            let v = match b.mode {
+                                BindingMode::Value => payload.get(&[k])?.clone(),
+                                BindingMode::Ref | BindingMode::RefMut => {
+                                    let mut path = path.clone();
+                                    path.extend([d, k]);
+                                    Value::Ref(Pointer {
+                                        alloc: a,
+                                        path,
+                                        tag: 0,
+                                    })
+                                }
+                            };
+                            ctx.bind(&b.local.id, v);
+                        }
+                    }
+                    ctx.block(&arm.block)
+                })
+            }
+            Expr::Arith(_, op, e0, e1) => {
+                let (a, b) = (self.expr(e0)?, self.expr(e1)?);
+                match (a.as_int(), b.as_int()) {
+                    (Some((t, i)), Some((t2, j))) if t == t2 => Ok(Value::int(
+                        t,
+                        t.checked(*op, i, j)
+                            .ok_or(Control::Error(Error::Overflow))?,
+                    )),
+                    _ => Err(
+                        Error::TypeError(format!("cannot {} {:?} and {:?}", op.name(), a, b))
+                            .into(),
+                    ),
+                }
+            }
+            // Moves are not tracked, except those of guards, which must not end a borrow twice.
+            Expr::Place(_, p) => {
+                let (a, path) = self.resolve(p)?;
+                let v = self.get(a, &path)?;
+                if v.is_uninit() {
+                    return Err(Error::UseOfUninit(p.clone()).into());
+                }
+                if holds_guard(&v) && !through_reference(p) {
+                    *self.get_mut(a, &path)? = Value::Uninit;
+                }
+                Ok(v)
+            }
+            Expr::Ref(_, p) | Expr::RefMut(_, p) => {
+                let (alloc, path, slice) = self.resolve_slice(p)?;
+                let ptr = Pointer {
+                    alloc,
+                    path,
+                    tag: 0,
+                };
+                Ok(match slice {
+                    Some((start, len)) => Value::Slice(ptr, start, len),
+                    None => Value::Ref(ptr),
+                })
+            }
+            Expr::Assign(_, p, e) => {
+                let v = self.expr(e)?;
+                let (a, path) = self.resolve(p)?;
+                *self.get_mut(a, &path)? = v;
+                Ok(Value::Unit)
+            }
+            Expr::Seq(_, e0, e1) => {
+                let v = self.expr(e0)?;
+                self.discard(v);
+                self.expr(e1)
+            }
+            Expr::Print(_, e) => {
+                let v = match self.expr(e)? {
+                    Value::Ref(p) => self.get(p.alloc, &p.path)?,
+                    v => v,
+                };
+                self.stdout.push_str(&v.to_string());
+                self.stdout.push('\n');
+                Ok(Value::Unit)
+            }
+            // Boxes are never freed, since the evaluator does not track which values have been
+            // moved.
+            Expr::Box(_, e) => {
+                let v = self.expr(e)?;
+                let a = self.slots.len();
+                self.slots.push(Some(v));
+                Ok(Value::Box(a))
+            }
+            Expr::Block(_, b) => self.block(b),
+            Expr::IfElse(_, e0, b1, b2) => match self.expr(e0)? {
+                Value::Bool(true) => self.block(b1),
+                Value::Bool(false) => self.block(b2),
+                v => Err(Error::TypeError(format!("expected bool, found {:?}", v)).into()),
+            },
+            Expr::While(_, label, e0, b) => {
+                loop {
+                    match self.expr(e0)? {
+                        Value::Bool(true) => {}
+                        Value::Bool(false) => break,
+                        v => {
+                            return Err(
+                                Error::TypeError(format!("expected bool, found {:?}", v)).into()
+                            )
+                        }
+                    }
+                    match self.block(b) {
+                        Ok(_) => {}
+                        Err(Control::Break(l, _)) if targets(l, *label) => break,
+                        Err(Control::Continue(l)) if targets(l, *label) => {}
+                        Err(c) => return Err(c),
+                    }
+                }
+                Ok(Value::Unit)
+            }
+            Expr::Loop(_, label, b) => loop {
+                match self.block(b) {
+                    Ok(_) => {}
+                    Err(Control::Break(l, v)) if targets(l, *label) => return Ok(v),
+                    Err(Control::Continue(l)) if targets(l, *label) => {}
+                    Err(c) => return Err(c),
+                }
+            },
+            Expr::Break(_, l, e) => {
+                let v = match e {
+                    Some(e) => self.expr(e)?,
+                    None => Value::Unit,
+                };
+                Err(Control::Break(*l, v))
+            }
+            Expr::Continue(_, l) => Err(Control::Continue(*l)),
+            Expr::Return(_, e) => {
+                let v = self.expr(e)?;
+                Err(Control::Return(v))
+            }
+            // The callee only sees its own variables, but references to those of the caller stay
+            // valid since slots outlive scopes.
+            Expr::Call(_, id, es) => {
+                let args = es
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(self.call(id, args)?)
+            }
+            Expr::MethodCall(_, p, m, _) => {
+                Err(Error::TypeError(format!("method call {}.{} that is not resolved", p, m)).into())
+            }
+            // A closure holds its captures like a tuple, and its body can be called from then on.
+            Expr::Closure(_, lambda) => {
+                let vs = lambda
+                    .captures
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let function = lambda
+                    .function
+                    .as_ref()
+                    .ok_or_else(|| Error::TypeError("closure that is not lifted".to_string()))?;
+                if !self.functions.iter().any(|f| f.id == function.id) {
+                    self.functions.push(function.as_ref().clone());
+                }
+                Ok(Value::Tuple(vs))
+            }
+            // Like in the MIR, the closure is passed after the arguments are evaluated.
+            Expr::CallClosure(_, p, es) => {
+                let Type::Closure(c) = p.ty() else {
+                    return Err(Error::TypeError(format!("call of {}", p)).into());
+                };
+                let mut args = es
+                    .iter()
+                    .map(|e| self.expr(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (alloc, path) = self.resolve(p)?;
+                let env = match c.kind {
+                    ClosureKind::FnOnce => self.get(alloc, &path)?,
+                    ClosureKind::Fn | ClosureKind::FnMut => Value::Ref(Pointer {
+                        alloc,
+                        path,
+                        tag: 0,
+                    }),
+                };
+                args.insert(0, env);
+                Ok(self.call(&c.id, args)?)
+            }
+        }
+    }
+
+    fn call(&mut self, id: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let Some(callee) = self.functions.iter().find(|f| f.id == id).cloned() else {
+            return self.builtin(id, args);
+        };
+        let scopes = std::mem::take(&mut self.scopes);
+        let result = self.run(&callee, args);
+        self.scopes = scopes;
+        result
+    }
+
+    // The methods of cells, which are the only functions that are not in the program.
+    fn builtin(&mut self, id: &str, args: Vec<Value>) -> Result<Value, Error> {
+        match (id, args.as_slice()) {
+            ("Cell::new", [v]) => Ok(v.clone()),
+            ("RefCell::new", [v]) => Ok(Value::Tuple(vec![Value::Int(0), v.clone()])),
+            ("Cell::get", [Value::Ref(p)]) => self.get(p.alloc, &p.path),
+            ("Cell::set", [Value::Ref(p), v]) => {
+                *self.get_mut(p.alloc, &p.path)? = v.clone();
+                Ok(Value::Unit)
+            }
+            ("RefCell::borrow" | "RefCell::borrow_mut", [Value::Ref(p)]) => {
+                let mutable = id == "RefCell::borrow_mut";
+                let ptr = Pointer {
+                    alloc: p.alloc,
+                    path: [p.path.clone(), vec![1]].concat(),
+                    tag: 0,
+                };
+                let flag = self.get_mut(p.alloc, &flag_path(&ptr))?;
+                *flag = borrow(flag, mutable)?;
+                Ok(Value::Guard(ptr, mutable))
+            }
+            _ => Err(Error::UnknownFunction(id.to_string())),
+        }
+    }
+
+    fn resolve(&self, p: &Place) -> Result<(AllocId, Path), Error> {
+        let (a, path, _) = self.resolve_slice(p)?;
+        Ok((a, path))
+    }
+
+    // Like `resolve`, but a place that is a slice also resolves to the elements `start..start +
+    // len` of the array at the path that it denotes.
+    fn resolve_slice(&self, p: &Place) -> Result<(AllocId, Path, Option<Elements>), Error> {
+        let a = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.iter().rev().find(|(id, _)| *id == p.local.id))
+            .map(|(_, a)| *a)
+            .ok_or_else(|| Error::DeadLocal(p.local.id.clone()))?;
+        let (mut a, mut path) = (a, vec![]);
+        let mut slice = None;
+        for (k, elem) in p.elems.iter().enumerate() {
+            match elem {
+                PlaceElem::TupleIndex(i) => path.push(*i),
+                PlaceElem::Field(_) | PlaceElem::Downcast(_) => path.push(elem_index(p, k)?),
+                PlaceElem::ConstantIndex(i) => {
+                    let (start, len) = elements(p, k, slice.take())?;
+                    path.push(start + check_index(*i as i128, len)?);
+                }
+                PlaceElem::Index(l) => {
+                    let (start, len) = elements(p, k, slice.take())?;
+                    let (a, path_i) = self.resolve(&Place::from(l.clone()))?;
+                    let i = self.get(a, &path_i)?;
+                    let Some((_, i)) = i.as_int() else {
+                        return Err(Error::TypeError(format!("index {:?}", i)));
+                    };
+                    path.push(start + check_index(i, len)?);
+                }
+                PlaceElem::Subslice { from, to } => {
+                    slice = Some(subslice(elements(p, k, slice.take())?, *from, *to)?);
+                }
+                PlaceElem::Deref => match self.get(a, &path)? {
+                    Value::Ref(ptr) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                    }
+                    Value::Slice(ptr, start, len) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                        slice = Some((start, len));
+                    }
+                    Value::Guard(ptr, _) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                    }
+                    Value::Box(b) => {
+                        a = b;
+                        path = vec![];
+                    }
+                    v => return Err(Error::TypeError(format!("deref of {:?}", v))),
+                },
+            }
+        }
+        Ok((a, path, slice))
+    }
+
+    fn get(&self, a: AllocId, path: &Path) -> Result<Value, Error> {
+        let mut v = self.slots[a].as_ref().ok_or(Error::UseAfterFree(a))?;
+        for i in path {
+            v = match v {
+                Value::Tuple(vs) if *i < vs.len() => &vs[*i],
+                Value::Variant(d, v) if d == i => v.as_ref(),
+                _ => return Err(Error::TypeError(format!("cannot index at {}", i))),
+            };
+        }
+        Ok(v.clone())
+    }
+
+    fn get_mut(&mut self, a: AllocId, path: &Path) -> Result<&mut Value, Error> {
+        let mut v = self.slots[a].as_mut().ok_or(Error::UseAfterFree(a))?;
+        for i in path {
+            v = match v {
+                Value::Tuple(vs) if *i < vs.len() => &mut vs[*i],
+                Value::Variant(d, v) if d == i => v.as_mut(),
+                _ => return Err(Error::TypeError(format!("cannot index at {}", i))),
+            };
+        }
+        Ok(v)
+    }
+}
+
+// Whether a break or continue with label `l` targets the loop with label `label`.
+fn targets(l: Option<usize>, label: Option<usize>) -> bool {
+    l.is_none() || l == label
+}
+
+fn holds_guard(v: &Value) -> bool {
+    match v {
+        Value::Guard(..) => true,
+        Value::Tuple(vs) => vs.iter().any(holds_guard),
+        Value::Variant(_, v) => holds_guard(v),
+        _ => false,
+    }
+}
@@ -1,18 +1,60 @@
 use std::rc::Rc;
 
+use crate::ast::ArithOp;
+use crate::ast::Arm;
+use crate::ast::Binding;
+use crate::ast::BindingMode;
 use crate::ast::Block;
+use crate::ast::CellKind;
+use crate::ast::Closure;
+use crate::ast::ClosureKind;
+use crate::ast::Enum;
 /// Simple type inference
 use crate::ast::Expr;
 use crate::ast::Function;
+use crate::ast::Impl;
+use crate::ast::IntTy;
+use crate::ast::Lambda;
 use crate::ast::Loan;
 use crate::ast::Local;
 use crate::ast::LocalId;
+use crate::ast::Name;
+use crate::ast::Pattern;
 use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Program;
 use crate::ast::Stmt;
+use crate::ast::Struct;
 use crate::ast::Type;
+use crate::closures::captures;
+use crate::closures::lift;
 
+// Types are inferred by unification. Each expression is given a type from the types of its
+// subexpressions, and every constraint on types, e.g., that the condition of an `if` is a `bool`
+// or that an assigned value has the type of its place, is solved as soon as it is met by unifying
+// the two types. A `let` without an initializer or annotation gives its local a type variable,
+// which later uses of the local determine. Once a function has been inferred, the type variables
+// are replaced with the types they were unified with.
 struct Context {
     pub stack: Vec<Scope>,
+    // The functions that can be called, of which only the signatures are used, and the impls
+    // whose methods they include.
+    pub functions: Vec<Function>,
+    pub impls: Vec<Impl>,
+    // What each type variable has been unified with, if anything yet.
+    pub vars: Vec<Option<Type>>,
+    // The type variables of integer literals without a suffix, which can only be unified with
+    // integer types, and are i32 if nothing else is known, like in Rust.
+    pub ints: Vec<usize>,
+    // The return type of the current function, and the types of the values it returns early.
+    pub ret: Type,
+    pub returns: Vec<Type>,
+    // The label of each enclosing loop and the type of its value, where a `while` has none.
+    pub loops: Vec<(Option<usize>, Option<Type>)>,
+    // The current function, after which its closures are named, and the number of them so far.
+    pub id: Name,
+    pub type_params: Vec<Name>,
+    pub closures: usize,
 }
 
 pub struct Scope {
@@ -20,8 +62,20 @@ pub struct Scope {
 }
 
 impl Context {
-    pub fn new() -> Context {
-        Context { stack: vec![] }
+    pub fn new(functions: Vec<Function>) -> Context {
+        Context {
+            stack: vec![],
+            functions,
+            impls: vec![],
+            vars: vec![],
+            ints: vec![],
+            ret: Type::Unit,
+            returns: vec![],
+            loops: vec![],
+            id: Name::new(),
+            type_params: vec![],
+            closures: 0,
+        }
     }
 
     pub fn add_binding(&mut self, l: Local) {
@@ -36,17 +90,35 @@ impl Context {
     }
 
     pub fn infer_function(&mut self, f: &Function) -> Function {
+        f.check_lifetimes();
         self.stack.push(Scope { bindings: vec![] });
         for l in &f.params {
             self.add_binding(l.clone());
         }
+        self.ret = f.ty.clone();
+        self.returns.clear();
+        self.id = f.id.clone();
+        self.type_params = f.type_params.clone();
+        self.closures = 0;
         let body = self.infer_block(&f.block);
-        if *body.ty() != f.ty {
-            panic!("Function: mismatched types: {:?} != {:?}", body.ty(), f.ty);
+        self.unify("Function", &f.ty, body.ty());
+        self.default_ints();
+        let body = self.zonk_block(&body);
+        if !matches!(body.ty(), Type::Never) && *body.ty() != f.ty {
+            f.check_return(body.ty());
+        }
+        // Values that are returned early must live as long as the tail expression.
+        for t in std::mem::take(&mut self.returns) {
+            let t = self.resolve(&t);
+            if !matches!(t, Type::Never) && t != f.ty {
+                f.check_return(&t);
+            }
         }
         self.stack.pop();
         Function {
             id: f.id.clone(),
+            lifetimes: f.lifetimes.clone(),
+            type_params: f.type_params.clone(),
             params: f.params.clone(),
             ty: f.ty.clone(),
             block: body,
@@ -55,70 +127,223 @@ impl Context {
 
     pub fn infer_expr(&mut self, e: &Expr) -> Expr {
         match e {
-            Expr::Int(_, i) => Expr::Int(Type::Int, *i),
+            Expr::Int(t, i) => match t {
+                Type::Unknown => Expr::Int(self.fresh_int(), *i),
+                t => Expr::Int(t.clone(), *i),
+            },
+            // Whether the operand can be cast is only known once its type is, see `zonk_expr`.
+            Expr::Cast(t, e) => {
+                if t.int_ty().is_none() {
+                    panic!("Cast: cannot cast to {}", t);
+                }
+                Expr::Cast(t.clone(), Rc::new(self.infer_expr(e)))
+            }
             Expr::Bool(_, b) => Expr::Bool(Type::Bool, *b),
             Expr::Place(_, id) => {
                 let p = self.infer_place(id);
                 Expr::Place(p.ty().clone(), p)
             }
-            Expr::Add(_, e1, e2) => {
+            Expr::Arith(_, op, e1, e2) => {
                 let e1 = self.infer_expr(e1);
                 let e2 = self.infer_expr(e2);
-                let t = Type::Int;
-                Expr::Add(t, Rc::new(e1), Rc::new(e2))
+                let t = self.fresh_int();
+                let what = match op {
+                    ArithOp::Add => "Add",
+                    ArithOp::Sub => "Sub",
+                    ArithOp::Mul => "Mul",
+                };
+                self.unify(what, &t, e1.ty());
+                self.unify(what, &t, e2.ty());
+                Expr::Arith(t, *op, Rc::new(e1), Rc::new(e2))
             }
             Expr::IfElse(_, e0, e1, e2) => {
                 let e0 = self.infer_expr(e0);
-                if *e0.ty() != Type::Bool {
-                    panic!("IfElse: expected bool, found {:?}", e0.ty());
-                }
+                self.unify("IfElse", &Type::Bool, e0.ty());
                 let b1 = self.infer_block(e1);
                 let b2 = self.infer_block(e2);
-                let ty = match (b1.ty(), b2.ty()) {
-                    (Type::Ref(loans1, t1), Type::Ref(loans2, t2)) => {
-                        if t1 != t2 {
-                            panic!("IfElse (Ref): mismatched types: {:?} != {:?}", t1, t2);
-                        }
-                        let loans = loans1
-                            .iter()
-                            .chain(loans2.iter())
-                            .cloned()
-                            .collect::<Vec<_>>();
-                        Type::Ref(loans, t1.clone())
-                    }
-                    (Type::RefMut(loans1, t1), Type::RefMut(loans2, t2)) => {
-                        if t1 != t2 {
-                            panic!("IfElse (RefMut) mismatched types: {:?} != {:?}", t1, t2);
-                        }
-                        let loans = loans1
-                            .iter()
-                            .chain(loans2.iter())
-                            .cloned()
-                            .collect::<Vec<_>>();
-                        Type::RefMut(loans, t1.clone())
-                    }
-                    (t1, t2) => {
-                        if t1 != t2 {
-                            panic!("IfElse (Type) mismatched types: {:?} != {:?}", t1, t2);
-                        }
-                        t1.clone()
-                    }
-                };
+                let ty = self.join("IfElse", b1.ty(), b2.ty());
                 Expr::IfElse(ty, Rc::new(e0), Rc::new(b1), Rc::new(b2))
             }
-            Expr::While(_, e, b) => {
+            Expr::While(_, l, e, b) => {
                 let e = self.infer_expr(e);
-                if *e.ty() != Type::Bool {
-                    panic!("expected bool, found {:?}", e.ty());
-                }
+                self.unify("While", &Type::Bool, e.ty());
+                self.loops.push((*l, None));
                 let b = self.infer_block(b);
-                Expr::While(Type::Unit, Rc::new(e), Rc::new(b))
+                self.loops.pop();
+                Expr::While(Type::Unit, *l, Rc::new(e), Rc::new(b))
             }
             Expr::Tuple(_, es) => {
                 let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
                 let ts = es.iter().map(|e| e.ty().clone()).collect();
                 Expr::Tuple(Type::Tuple(ts), es)
             }
+            // The elements have one type, which carries the loans of all of them.
+            Expr::Array(_, es) => {
+                let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
+                let mut t = self.fresh();
+                for e in &es {
+                    t = self.join("Array", &t, e.ty());
+                }
+                Expr::Array(Type::Array(Rc::new(t), es.len()), es)
+            }
+            Expr::Struct(ty, fields) => {
+                let Type::Struct(def) = ty else {
+                    panic!("Struct: expected struct type, found {:?}", ty);
+                };
+                let fields = fields
+                    .iter()
+                    .map(|(f, e)| (f.clone(), self.infer_expr(e)))
+                    .collect::<Vec<_>>();
+                // Every field must be given exactly once, and the struct's field types are
+                // refined with the loans of the field expressions.
+                let tys = def
+                    .fields
+                    .iter()
+                    .map(|(f, t)| {
+                        let mut es = fields.iter().filter(|(g, _)| g == f);
+                        let (Some((_, e)), None) = (es.next(), es.next()) else {
+                            panic!("Struct: field {} of {} must be given once", f, def.id);
+                        };
+                        self.unify("Struct", t, e.ty());
+                        (f.clone(), e.ty().clone())
+                    })
+                    .collect::<Vec<_>>();
+                if let Some((f, _)) = fields.iter().find(|(f, _)| def.field(f).is_none()) {
+                    panic!("Struct: {} has no field {}", def.id, f);
+                }
+                let ty = Type::Struct(Rc::new(Struct {
+                    id: def.id.clone(),
+                    fields: tys,
+                }));
+                Expr::Struct(ty, fields)
+            }
+            Expr::Variant(ty, v, es) => {
+                let Type::Enum(def) = ty else {
+                    panic!("Variant: expected enum type, found {:?}", ty);
+                };
+                let Some((_, Type::Tuple(ts))) = def.variant(v) else {
+                    panic!("Variant: {} has no variant {}", def.id, v);
+                };
+                if ts.len() != es.len() {
+                    panic!("Variant: {}::{} takes {} values", def.id, v, ts.len());
+                }
+                let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
+                for (e, t) in es.iter().zip(ts) {
+                    self.unify("Variant", t, e.ty());
+                }
+                // Only the payload of the constructed variant is refined with loans.
+                let variants = def
+                    .variants
+                    .iter()
+                    .map(|(name, t)| {
+                        if name == v {
+                            let ts = es.iter().map(|e| e.ty().clone()).collect();
+                            (name.clone(), Type::Tuple(ts))
+                        } else {
+                            (name.clone(), t.clone())
+                        }
+                    })
+                    .collect();
+                let ty = Type::Enum(Rc::new(Enum {
+                    id: def.id.clone(),
+                    variants,
+                }));
+                Expr::Variant(ty, v.clone(), es)
+            }
+            Expr::Match(_, p, arms) => {
+                let p = self.infer_place(p);
+                let Type::Enum(def) = self.resolve(&p.ty()) else {
+                    panic!("Match: expected enum, found {}", self.resolve(&p.ty()));
+                };
+                let mut covered = vec![false; def.variants.len()];
+                let mut ty: Option<Type> = None;
+                let arms = arms
+                    .iter()
+                    .map(|arm| {
+                        self.stack.push(Scope { bindings: vec![] });
+                        let pattern = match &arm.pattern {
+                            Pattern::Wildcard => {
+                                covered.iter_mut().for_each(|c| *c = true);
+                                Pattern::Wildcard
+                            }
+                            Pattern::Variant(e, v, bindings) => {
+                                let Some((i, Type::Tuple(ts))) = def.variant(v) else {
+                                    panic!("Match: {} has no variant {}", def.id, v);
+                                };
+                                if *e != def.id || ts.len() != bindings.len() {
+                                    panic!("Match: pattern {}::{} does not fit {}", e, v, def.id);
+                                }
+                                covered[i] = true;
+                                let bindings = bindings
+                                    .iter()
+                                    .zip(ts)
+                                    .enumerate()
+                                    .map(|(k, (b, t))| {
+                                        let b = b.as_ref()?;
+                                        let place = Place {
+                                            local: p.local.clone(),
+                                            elems: p
+                                                .elems
+                                                .iter()
+                                                .cloned()
+                                                .chain([
+                                                    PlaceElem::Downcast(v.clone()),
+                                                    PlaceElem::TupleIndex(k),
+                                                ])
+                                                .collect(),
+                                        };
+                                        let ty = match b.mode {
+                                            BindingMode::Value => t.clone(),
+                                            BindingMode::Ref => Type::Ref(
+                                                vec![Loan {
+                                                    place,
+                                                    mutable: false,
+                                                }],
+                                                Rc::new(t.clone()),
+                                            ),
+                                            BindingMode::RefMut => Type::RefMut(
+                                                vec![Loan {
+                                                    place,
+                                                    mutable: true,
+                                                }],
+                                                Rc::new(t.clone()),
+                                            ),
+                                        };
+                                        let local = Local {
+                                            id: b.local.id.clone(),
+                                            ty,
+                                            mutable: b.local.mutable,
+                                        };
+                                        self.add_binding(local.clone());
+                                        Some(Binding {
+                                            mode: b.mode,
+                                            local,
+                                        })
+                                    })
+                                    .collect();
+                                Pattern::Variant(e.clone(), v.clone(), bindings)
+                            }
+                        };
+                        let block = self.infer_block(&arm.block);
+                        self.stack.pop();
+                        ty = Some(match &ty {
+                            Some(t) => self.join("Match", t, block.ty()),
+                            None => block.ty().clone(),
+                        });
+                        Arm {
+                            pattern,
+                            block: Rc::new(block),
+                        }
+                    })
+                    .collect();
+                if let Some(i) = covered.iter().position(|c| !c) {
+                    panic!(
+                        "Match: variant {} of {} is not covered",
+                        def.variants[i].0, def.id
+                    );
+                }
+                Expr::Match(ty.unwrap_or(Type::Unit), p, arms)
+            }
             Expr::Ref(_, p) => {
                 let p = self.infer_place(p);
                 let loan = Loan {
@@ -148,8 +373,12 @@ impl Context {
                     panic!("cannot assign to immutable variable {:?}", p);
                 }
                 let e = self.infer_expr(e);
-                if p.ty() != e.ty() {
-                    panic!("Assign: mismatched types: {:?} != {:?}", p.ty(), e.ty());
+                self.unify("Assign", &p.ty(), e.ty());
+                // A place whose type is not being inferred keeps its loans, which must be those of
+                // the value.
+                let (t1, t2) = (self.resolve(&p.ty()), self.resolve(e.ty()));
+                if !matches!(p.ty(), Type::Var(_)) && !matches!(t2, Type::Never) && t1 != t2 {
+                    panic!("Assign: mismatched loans: expected {}, found {}", t1, t2);
                 }
                 Expr::Assign(Type::Unit, p, Rc::new(e))
             }
@@ -161,23 +390,158 @@ impl Context {
             Expr::Unit(_) => Expr::Unit(Type::Unit),
             Expr::Print(_, e) => {
                 let e = self.infer_expr(e);
-                if let Type::Ref(_, t) = e.ty().downgrade() {
-                    if *t.as_ref() == Type::String {
-                        return Expr::Print(Type::Unit, Rc::new(e));
+                let t = self.resolve(e.ty()).downgrade();
+                if let Type::Ref(_, t) = &t {
+                    self.unify("Print", &Type::String, t);
+                } else {
+                    self.unify("Print", &Type::Ref(vec![], Rc::new(Type::String)), &t);
+                }
+                Expr::Print(Type::Unit, Rc::new(e))
+            }
+            Expr::Box(_, e) => {
+                let e = self.infer_expr(e);
+                Expr::Box(Type::Box(Rc::new(e.ty().clone())), Rc::new(e))
+            }
+            // A local shadows the functions of the program, like in Rust.
+            Expr::Call(_, id, es) if self.lookup(id).is_some() => {
+                let p = Place::from(self.lookup(id).unwrap().clone());
+                self.infer_call_closure(&p, es)
+            }
+            Expr::CallClosure(_, p, es) => self.infer_call_closure(p, es),
+            Expr::Call(_, id, es) if CellKind::constructor(id).is_some() => {
+                let kind = CellKind::constructor(id).unwrap();
+                let [e] = es.as_slice() else {
+                    panic!("Call: {} takes 1 arguments", id);
+                };
+                let e = self.infer_expr(e);
+                let t = Type::Cell(kind, Rc::new(e.ty().clone()));
+                Expr::Call(t, id.clone(), vec![e])
+            }
+            Expr::MethodCall(_, p, m, es) => self.infer_method_call(p, m, es),
+            // The body is inferred with its own return type and without the loops around it. What
+            // the closure captures is found from the uses of places in it, as far as their types
+            // are known by then.
+            Expr::Closure(_, lambda) => {
+                let id = format!("{}::{{closure#{}}}", self.id, self.closures);
+                self.closures += 1;
+                self.stack.push(Scope { bindings: vec![] });
+                let params = lambda
+                    .params
+                    .iter()
+                    .map(|l| Local {
+                        id: l.id.clone(),
+                        ty: match &l.ty {
+                            Type::Unknown => self.fresh(),
+                            t => t.clone(),
+                        },
+                        mutable: l.mutable,
+                    })
+                    .collect::<Vec<_>>();
+                for l in &params {
+                    self.add_binding(l.clone());
+                }
+                let ret = self.fresh();
+                let ret0 = std::mem::replace(&mut self.ret, ret.clone());
+                let returns = std::mem::take(&mut self.returns);
+                let loops = std::mem::take(&mut self.loops);
+                let body = self.infer_expr(&lambda.body);
+                self.unify("Closure", &ret, body.ty());
+                self.ret = ret0;
+                self.returns = returns;
+                self.loops = loops;
+                self.stack.pop();
+                let lambda = Lambda {
+                    is_move: lambda.is_move,
+                    params,
+                    body,
+                    captures: vec![],
+                    function: None,
+                };
+                let (captures, kind) = captures(&lambda, |t| self.is_copy(t));
+                let captures = captures
+                    .into_iter()
+                    .map(|c| c.into_expr())
+                    .collect::<Vec<_>>();
+                let ty = Type::Closure(Rc::new(Closure {
+                    id,
+                    kind,
+                    params: lambda.params.iter().map(|l| l.ty.clone()).collect(),
+                    ty: ret,
+                    captures: captures.iter().map(|e| e.ty().clone()).collect(),
+                }));
+                Expr::Closure(ty, Rc::new(Lambda { captures, ..lambda }))
+            }
+            Expr::Call(_, id, es) => {
+                let Some(callee) = self.functions.iter().find(|f| f.id == *id).cloned() else {
+                    panic!("Call: unknown function {}", id);
+                };
+                if callee.params.len() != es.len() {
+                    panic!("Call: {} takes {} arguments", id, callee.params.len());
+                }
+                let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
+                let ts = es.iter().map(|e| self.resolve(e.ty())).collect::<Vec<_>>();
+                // The type arguments are variables, which take the loans of every argument they
+                // are unified with.
+                let type_args = callee
+                    .type_params
+                    .iter()
+                    .map(|_| self.fresh())
+                    .collect::<Vec<_>>();
+                for (e, param) in es.iter().zip(&callee.params) {
+                    let t = param.ty.with_type_args(&callee.type_params, &type_args);
+                    self.unify("Call", &t, e.ty());
+                }
+                for (t, id) in type_args.iter().zip(&callee.type_params) {
+                    if let Type::Var(v) = t {
+                        if self.vars[*v].is_none() {
+                            panic!("Call: cannot infer type parameter {}", id);
+                        }
                     }
                 }
-                panic!("Print: expected string, found {:?}", e.ty());
+                // Lifetimes are instantiated first, so that those of the caller which the type
+                // arguments carry are not taken for those of the callee.
+                let t = callee
+                    .instantiate(&ts)
+                    .with_type_args(&callee.type_params, &type_args);
+                Expr::Call(t, id.clone(), es)
             }
             Expr::Return(_, e) => {
                 let e = self.infer_expr(e);
-                Expr::Return(e.ty().clone(), Rc::new(e))
+                let ret = self.ret.clone();
+                self.unify("Return", &ret, e.ty());
+                self.returns.push(e.ty().clone());
+                Expr::Return(Type::Never, Rc::new(e))
             }
+            // The value of a loop is that of the breaks out of it. A loop that is never broken out
+            // of does not have a value.
             Expr::Loop(_, l, b) => {
+                let t = self.fresh();
+                self.loops.push((*l, Some(t.clone())));
                 let b = self.infer_block(b);
-                Expr::Loop(b.ty().clone(), *l, Rc::new(b))
+                self.loops.pop();
+                self.unify("Loop", &Type::Unit, b.ty());
+                let Type::Var(v) = t else { unreachable!() };
+                let t = if self.vars[v].is_none() {
+                    Type::Never
+                } else {
+                    t
+                };
+                Expr::Loop(t, *l, Rc::new(b))
+            }
+            Expr::Continue(_, l) => {
+                self.target("Continue", *l);
+                Expr::Continue(Type::Never, *l)
+            }
+            Expr::Break(_, l, e) => {
+                let e = e.as_ref().map(|e| Rc::new(self.infer_expr(e)));
+                let found = e.as_ref().map_or(Type::Unit, |e| e.ty().clone());
+                match self.target("Break", *l) {
+                    None if e.is_some() => panic!("Break: only a loop can break with a value"),
+                    None => {}
+                    Some(t) => self.unify("Break", &t, &found),
+                }
+                Expr::Break(Type::Never, *l, e)
             }
-            Expr::Continue(_, _) => Expr::Continue(Type::Unit, None),
-            Expr::Break(_, _) => Expr::Break(Type::Unit, None),
         }
     }
 
@@ -188,18 +552,25 @@ impl Context {
             .iter()
             .map(|s| match s {
                 Stmt::Let(l, e) => {
-                    if let Some(e) = e {
-                        let e = self.infer_expr(e);
-                        let l = Local {
-                            id: l.id.clone(),
-                            ty: e.ty().clone(),
-                            mutable: l.mutable,
-                        };
-                        self.add_binding(l.clone());
-                        Stmt::Let(l, Some(e))
-                    } else {
-                        todo!()
-                    }
+                    // The local takes the type of its value, which carries its loans, after it is
+                    // checked against the annotation.
+                    let e = e.as_ref().map(|e| self.infer_expr(e));
+                    let ty = match (&l.ty, &e) {
+                        (Type::Unknown, Some(e)) => e.ty().clone(),
+                        (Type::Unknown, None) => self.fresh(),
+                        (t, Some(e)) => {
+                            self.unify("Let", t, e.ty());
+                            e.ty().clone()
+                        }
+                        (t, None) => t.clone(),
+                    };
+                    let l = Local {
+                        id: l.id.clone(),
+                        ty,
+                        mutable: l.mutable,
+                    };
+                    self.add_binding(l.clone());
+                    Stmt::Let(l, e)
                 }
                 Stmt::Expr(e) => {
                     let e = self.infer_expr(e);
@@ -212,20 +583,804 @@ impl Context {
         Block { stmts, expr }
     }
 
-    pub fn infer_place(&self, p: &Place) -> Place {
+    // A place of a local whose type is being inferred can only be projected once the type is
+    // known. The locals that index arrays must be usizes, and constant indices must be in bounds.
+    pub fn infer_place(&mut self, p: &Place) -> Place {
+        let mut ty = self.lookup(&p.local.id).unwrap().ty.clone();
+        if !p.elems.is_empty() {
+            ty = self.resolve(&ty);
+            if let Type::Var(_) = ty {
+                panic!(
+                    "Place: cannot infer the type of {}, a type annotation is needed",
+                    p.local.id
+                );
+            }
+        }
         let local = Local {
             id: p.local.id.clone(),
-            ty: self.lookup(&p.local.id).unwrap().ty.clone(),
+            ty,
             mutable: p.local.mutable,
         };
-        let elems = p.elems.clone();
+        let elems = p
+            .elems
+            .iter()
+            .map(|e| match e {
+                PlaceElem::Index(l) => {
+                    let l = self.lookup(&l.id).unwrap().clone();
+                    self.unify("Index", &Type::int(IntTy::Usize), &l.ty);
+                    PlaceElem::Index(l)
+                }
+                e => e.clone(),
+            })
+            .collect::<Vec<_>>();
+        for k in 0..elems.len() {
+            let prefix = Place {
+                local: local.clone(),
+                elems: elems[..k].to_vec(),
+            };
+            let Type::Array(_, n) = self.resolve(&prefix.ty()) else {
+                continue;
+            };
+            match elems[k] {
+                PlaceElem::ConstantIndex(i) if i >= n => {
+                    panic!("Index: index {} is out of bounds of {}", i, prefix);
+                }
+                PlaceElem::Subslice { from, to } if from > to.unwrap_or(n) || to > Some(n) => {
+                    let to = to.map_or(String::new(), |to| to.to_string());
+                    panic!(
+                        "Index: range {}..{} is out of bounds of {}",
+                        from, to, prefix
+                    );
+                }
+                _ => {}
+            }
+        }
         Place { local, elems }
     }
+
+    // A closure is called through a reference to it unless it is FnOnce, so one that mutates what
+    // it captures cannot be called through a shared reference.
+    fn infer_call_closure(&mut self, p: &Place, es: &[Expr]) -> Expr {
+        let p = self.infer_place(p);
+        let Type::Closure(c) = self.resolve(&p.ty()) else {
+            panic!("Call: {} is not a closure", p);
+        };
+        if c.params.len() != es.len() {
+            panic!("Call: {} takes {} arguments", p, c.params.len());
+        }
+        if c.kind == ClosureKind::FnMut && !p.is_mutable() {
+            panic!(
+                "Call: cannot borrow {} as mutable, since it mutates what it captures",
+                p
+            );
+        }
+        let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
+        for (e, t) in es.iter().zip(&c.params) {
+            self.unify("Call", t, e.ty());
+        }
+        Expr::CallClosure(c.ty.clone(), p, es)
+    }
+
+    // A method is looked up in the impls for the type of the receiver, and then for the types it
+    // dereferences to, like in Rust. The call is one of the function of the impl, which is passed
+    // a reference to the receiver if it takes one, so that the borrow is explicit.
+    fn infer_method_call(&mut self, p: &Place, m: &Name, es: &[Expr]) -> Expr {
+        let mut p = p.clone();
+        let id = loop {
+            let t = self.infer_place(&p).ty();
+            let t = self.resolve(&t);
+            if let Type::Var(_) = t {
+                panic!(
+                    "Method: cannot infer the type of {}, a type annotation is needed",
+                    p
+                );
+            }
+            if let Type::Cell(kind, t) = t {
+                return self.infer_cell_method(kind, t, p, m, es);
+            }
+            let mut impls = self
+                .impls
+                .iter()
+                .filter(|i| i.methods.contains(m) && i.ty.same_shape(&t));
+            match (impls.next(), impls.next()) {
+                (Some(i), None) => break i.function_id(m),
+                (Some(_), Some(_)) => panic!("Method: {}.{} is ambiguous", p, m),
+                (None, _)
+                    if matches!(
+                        t,
+                        Type::Ref(..)
+                            | Type::RefMut(..)
+                            | Type::Box(_)
+                            | Type::Guard(..)
+                            | Type::GuardMut(..)
+                    ) =>
+                {
+                    p.elems.push(PlaceElem::Deref);
+                }
+                (None, _) => panic!("Method: no method {} for {}", m, t),
+            }
+        };
+        let callee = self.functions.iter().find(|f| f.id == id).unwrap();
+        let receiver = match callee.params.first().cloned() {
+            Some(l) if l.id == "self" => match l.ty {
+                Type::Ref(..) => Expr::Ref(Type::Unknown, p),
+                Type::RefMut(..) => {
+                    if !self.infer_place(&p).is_mutable() {
+                        panic!("Method: cannot borrow {} as mutable", p);
+                    }
+                    Expr::RefMut(Type::Unknown, p)
+                }
+                _ => Expr::Place(Type::Unknown, p),
+            },
+            _ => panic!("Method: {} does not take self", id),
+        };
+        let es = std::iter::once(receiver)
+            .chain(es.iter().cloned())
+            .collect();
+        self.infer_expr(&Expr::Call(Type::Unknown, id, es))
+    }
+
+    // The methods of a cell are builtins that take it by shared reference, since the cell checks
+    // the accesses to its contents itself: a Cell by only copying them in and out, and a RefCell
+    // by counting its borrows at runtime. The guard of a borrow holds the loan of the reference,
+    // so the RefCell cannot be moved or assigned while the guard is live.
+    fn infer_cell_method(
+        &mut self,
+        kind: CellKind,
+        t: Rc<Type>,
+        p: Place,
+        m: &Name,
+        es: &[Expr],
+    ) -> Expr {
+        let receiver = self.infer_expr(&Expr::Ref(Type::Unknown, p));
+        let Type::Ref(loans, _) = receiver.ty().clone() else {
+            unreachable!()
+        };
+        let (params, ty) = match (kind, m.as_str()) {
+            (CellKind::Cell, "get") => {
+                if !self.is_copy(&t) {
+                    panic!(
+                        "Method: Cell::get needs a Copy type, found {}",
+                        self.resolve(&t)
+                    );
+                }
+                (vec![], t.as_ref().clone())
+            }
+            (CellKind::Cell, "set") => (vec![t.as_ref().clone()], Type::Unit),
+            (CellKind::RefCell, "borrow") => (vec![], Type::Guard(loans, t)),
+            (CellKind::RefCell, "borrow_mut") => (vec![], Type::GuardMut(loans, t)),
+            _ => panic!(
+                "Method: no method {} for {}",
+                m,
+                self.resolve(&Type::Cell(kind, t))
+            ),
+        };
+        let id = format!("{}::{}", kind.name(), m);
+        if params.len() != es.len() {
+            panic!("Call: {} takes {} arguments", id, params.len());
+        }
+        let es = es.iter().map(|e| self.infer_expr(e)).collect::<Vec<_>>();
+        for (e, t) in es.iter().zip(&params) {
+            self.unify("Call", t, e.ty());
+        }
+        let es = std::iter::once(receiver).chain(es).collect();
+        Expr::Call(ty, id, es)
+    }
+
+    // Returns the type of the value of the loop that a break or continue leaves, which is the
+    // innermost one unless it has a label.
+    fn target(&self, what: &str, label: Option<usize>) -> Option<Type> {
+        match self
+            .loops
+            .iter()
+            .rev()
+            .find(|(l, _)| label.is_none() || *l == label)
+        {
+            Some((_, t)) => t.clone(),
+            None if label.is_some() => panic!("{what}: undeclared label"),
+            None => panic!("{what}: outside of a loop"),
+        }
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.vars.push(None);
+        Type::Var(self.vars.len() - 1)
+    }
+
+    fn fresh_int(&mut self) -> Type {
+        self.vars.push(None);
+        self.ints.push(self.vars.len() - 1);
+        Type::Var(self.vars.len() - 1)
+    }
+
+    // Binds the integer variables that nothing else is known about to i32.
+    fn default_ints(&mut self) {
+        for v in self.ints.clone() {
+            if let Type::Var(v) = self.shallow(&Type::Var(v)) {
+                self.vars[v] = Some(Type::Int);
+            }
+        }
+    }
+
+    // Whether a type is known to be Copy by now, where an integer variable is, whatever integer
+    // type it ends up as.
+    fn is_copy(&self, t: &Type) -> bool {
+        match self.shallow(t) {
+            Type::Var(v) => self
+                .ints
+                .iter()
+                .any(|i| self.shallow(&Type::Var(*i)) == Type::Var(v)),
+            Type::Tuple(ts) => ts.iter().all(|t| self.is_copy(t)),
+            Type::Array(t, _) => self.is_copy(&t),
+            t => self.resolve(&t).is_copy(),
+        }
+    }
+
+    // Follows the bindings of a type variable to a type that is not a bound variable.
+    fn shallow(&self, t: &Type) -> Type {
+        match t {
+            Type::Var(v) => match &self.vars[*v] {
+                Some(t) => self.shallow(t),
+                None => t.clone(),
+            },
+            _ => t.clone(),
+        }
+    }
+
+    // Replaces the type variables in a type with what they are bound to, as far as that is known.
+    fn resolve(&self, t: &Type) -> Type {
+        let loans = |loans: &[Loan]| {
+            loans
+                .iter()
+                .map(|l| Loan {
+                    place: self.resolve_place(&l.place),
+                    mutable: l.mutable,
+                })
+                .collect()
+        };
+        match t {
+            Type::Var(v) => match &self.vars[*v] {
+                Some(t) => self.resolve(t),
+                None => t.clone(),
+            },
+            Type::Ref(ls, t) => Type::Ref(loans(ls), Rc::new(self.resolve(t))),
+            Type::RefMut(ls, t) => Type::RefMut(loans(ls), Rc::new(self.resolve(t))),
+            Type::Guard(ls, t) => Type::Guard(loans(ls), Rc::new(self.resolve(t))),
+            Type::GuardMut(ls, t) => Type::GuardMut(loans(ls), Rc::new(self.resolve(t))),
+            Type::Box(t) => Type::Box(Rc::new(self.resolve(t))),
+            Type::Cell(k, t) => Type::Cell(*k, Rc::new(self.resolve(t))),
+            Type::Array(t, n) => Type::Array(Rc::new(self.resolve(t)), *n),
+            Type::Slice(t) => Type::Slice(Rc::new(self.resolve(t))),
+            Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| self.resolve(t)).collect()),
+            Type::Closure(c) => Type::Closure(Rc::new(Closure {
+                id: c.id.clone(),
+                kind: c.kind,
+                params: c.params.iter().map(|t| self.resolve(t)).collect(),
+                ty: self.resolve(&c.ty),
+                captures: c.captures.iter().map(|t| self.resolve(t)).collect(),
+            })),
+            Type::Struct(s) => Type::Struct(Rc::new(Struct {
+                id: s.id.clone(),
+                fields: s
+                    .fields
+                    .iter()
+                    .map(|(f, t)| (f.clone(), self.resolve(t)))
+                    .collect(),
+            })),
+            Type::Enum(e) => Type::Enum(Rc::new(Enum {
+                id: e.id.clone(),
+                variants: e
+                    .variants
+                    .iter()
+                    .map(|(v, t)| (v.clone(), self.resolve(t)))
+                    .collect(),
+            })),
+            _ => t.clone(),
+        }
+    }
+
+    fn resolve_local(&self, l: &Local) -> Local {
+        Local {
+            id: l.id.clone(),
+            ty: self.resolve(&l.ty),
+            mutable: l.mutable,
+        }
+    }
+
+    fn resolve_place(&self, p: &Place) -> Place {
+        let elems = p
+            .elems
+            .iter()
+            .map(|e| match e {
+                PlaceElem::Index(l) => PlaceElem::Index(self.resolve_local(l)),
+                e => e.clone(),
+            })
+            .collect();
+        Place {
+            local: self.resolve_local(&p.local),
+            elems,
+        }
+    }
+
+    // Unifies the type that is expected with the type that is found, or panics with both.
+    fn unify(&mut self, what: &str, expected: &Type, found: &Type) {
+        if !self.unifies(expected, found) {
+            self.default_ints();
+            panic!(
+                "{}: mismatched types: expected {}, found {}",
+                what,
+                self.resolve(expected),
+                self.resolve(found)
+            );
+        }
+    }
+
+    // Loans are ignored, except that a variable which is already bound takes the loans of what it
+    // is unified with. The type of a local that is inferred thereby carries the loans of every
+    // value which is assigned to it.
+    fn unifies(&mut self, expected: &Type, found: &Type) -> bool {
+        if let Type::Var(v) = expected {
+            if let Some(t) = self.vars[*v].clone() {
+                if !self.unifies(&t, found) {
+                    return false;
+                }
+                if !matches!(t, Type::Var(_)) {
+                    let t = join("Unify", &self.resolve(&t), &self.resolve(found));
+                    self.vars[*v] = Some(t);
+                }
+                return true;
+            }
+        }
+        match (self.shallow(expected), self.shallow(found)) {
+            // Code that does not return has every type.
+            (Type::Never, _) | (_, Type::Never) => true,
+            (Type::Var(a), Type::Var(b)) if a == b => true,
+            (Type::Var(v), t) | (t, Type::Var(v)) => {
+                let t = self.resolve(&t);
+                if mentions(&t, v) {
+                    panic!("Unify: infinite type: {} contains itself", t);
+                }
+                if self.ints.contains(&v) {
+                    match t {
+                        Type::Var(w) => self.ints.push(w),
+                        Type::Unknown => {}
+                        _ if t.int_ty().is_some() => {}
+                        _ => return false,
+                    }
+                }
+                self.vars[v] = Some(t);
+                true
+            }
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Ref(_, t1), Type::Ref(_, t2))
+            | (Type::RefMut(_, t1), Type::RefMut(_, t2))
+            | (Type::Guard(_, t1), Type::Guard(_, t2))
+            | (Type::GuardMut(_, t1), Type::GuardMut(_, t2))
+            | (Type::Box(t1), Type::Box(t2)) => self.unifies(&t1, &t2),
+            (Type::Cell(k1, t1), Type::Cell(k2, t2)) => k1 == k2 && self.unifies(&t1, &t2),
+            (Type::Array(t1, n1), Type::Array(t2, n2)) => n1 == n2 && self.unifies(&t1, &t2),
+            (Type::Slice(t1), Type::Slice(t2)) => self.unifies(&t1, &t2),
+            (Type::Closure(c1), Type::Closure(c2)) => c1.id == c2.id,
+            (Type::Tuple(ts1), Type::Tuple(ts2)) => {
+                ts1.len() == ts2.len() && ts1.iter().zip(&ts2).all(|(t1, t2)| self.unifies(t1, t2))
+            }
+            (Type::Struct(s1), Type::Struct(s2)) => {
+                s1.id == s2.id
+                    && s1
+                        .fields
+                        .iter()
+                        .zip(&s2.fields)
+                        .all(|((_, t1), (_, t2))| self.unifies(t1, t2))
+            }
+            (Type::Enum(e1), Type::Enum(e2)) => {
+                e1.id == e2.id
+                    && e1
+                        .variants
+                        .iter()
+                        .zip(&e2.variants)
+                        .all(|((_, t1), (_, t2))| self.unifies(t1, t2))
+            }
+            (t1, t2) => t1 == t2,
+        }
+    }
+
+    // Joins the types of branches once they have been unified.
+    fn join(&mut self, what: &str, t1: &Type, t2: &Type) -> Type {
+        self.unify(what, t1, t2);
+        join(what, &self.resolve(t1), &self.resolve(t2))
+    }
+
+    // Replaces every type variable in a block with its type. A `let` whose type is still not
+    // known is an error.
+    fn zonk_block(&self, b: &Block) -> Block {
+        let stmts = b
+            .stmts
+            .iter()
+            .map(|s| match s {
+                Stmt::Let(l, e) => {
+                    let l = self.resolve_local(l);
+                    if mentions_var(&l.ty) {
+                        panic!(
+                            "Let: cannot infer the type of {}, a type annotation is needed",
+                            l.id
+                        );
+                    }
+                    Stmt::Let(l, e.as_ref().map(|e| self.zonk_expr(e)))
+                }
+                Stmt::Expr(e) => Stmt::Expr(self.zonk_expr(e)),
+            })
+            .collect();
+        let expr = b.expr.as_ref().map(|e| self.zonk_expr(e));
+        Block { stmts, expr }
+    }
+
+    fn zonk_expr(&self, e: &Expr) -> Expr {
+        let t = self.resolve(e.ty());
+        let rc = |e: &Rc<Expr>| Rc::new(self.zonk_expr(e));
+        let block = |b: &Rc<Block>| Rc::new(self.zonk_block(b));
+        let exprs = |es: &[Expr]| es.iter().map(|e| self.zonk_expr(e)).collect();
+        match e {
+            Expr::IfElse(_, e0, b1, b2) => Expr::IfElse(t, rc(e0), block(b1), block(b2)),
+            Expr::While(_, l, e0, b) => Expr::While(t, *l, rc(e0), block(b)),
+            Expr::Loop(_, l, b) => Expr::Loop(t, *l, block(b)),
+            Expr::Tuple(_, es) => Expr::Tuple(t, exprs(es)),
+            Expr::Array(_, es) => Expr::Array(t, exprs(es)),
+            Expr::Struct(_, fields) => Expr::Struct(
+                t,
+                fields
+                    .iter()
+                    .map(|(f, e)| (f.clone(), self.zonk_expr(e)))
+                    .collect(),
+            ),
+            Expr::Variant(_, v, es) => Expr::Variant(t, v.clone(), exprs(es)),
+            Expr::Match(_, p, arms) => Expr::Match(
+                t,
+                self.resolve_place(p),
+                arms.iter()
+                    .map(|arm| Arm {
+                        pattern: match &arm.pattern {
+                            Pattern::Wildcard => Pattern::Wildcard,
+                            Pattern::Variant(e, v, bindings) => Pattern::Variant(
+                                e.clone(),
+                                v.clone(),
+                                bindings
+                                    .iter()
+                                    .map(|b| {
+                                        b.as_ref().map(|b| Binding {
+                                            mode: b.mode,
+                                            local: self.resolve_local(&b.local),
+                                        })
+                                    })
+                                    .collect(),
+                            ),
+                        },
+                        block: block(&arm.block),
+                    })
+                    .collect(),
+            ),
+            Expr::Ref(_, p) => Expr::Ref(t, self.resolve_place(p)),
+            Expr::RefMut(_, p) => Expr::RefMut(t, self.resolve_place(p)),
+            Expr::Seq(_, e0, e1) => Expr::Seq(t, rc(e0), rc(e1)),
+            Expr::Assign(_, p, e0) => Expr::Assign(t, self.resolve_place(p), rc(e0)),
+            // Like in Rust, an element of an array can only be read if it can be copied, since
+            // moving it out would leave a hole in the array. A slice is unsized, so it can only
+            // be used through a reference.
+            Expr::Place(_, p) => {
+                let p = self.resolve_place(p);
+                if let Type::Slice(_) = t {
+                    panic!(
+                        "Place: {} is a slice, which can only be used through a reference",
+                        p
+                    );
+                }
+                let indexed = p
+                    .elems
+                    .iter()
+                    .any(|e| matches!(e, PlaceElem::ConstantIndex(_) | PlaceElem::Index(_)));
+                if indexed && !t.is_copy() {
+                    panic!(
+                        "Place: cannot move out of {}, which is an element of an array",
+                        p
+                    );
+                }
+                Expr::Place(t, p)
+            }
+            Expr::Arith(_, op, e0, e1) => Expr::Arith(t, *op, rc(e0), rc(e1)),
+            Expr::Int(_, i) => {
+                if let Some(it) = t.int_ty() {
+                    if !it.contains(*i) {
+                        panic!("Int: literal {} out of range for {}", i, t);
+                    }
+                }
+                Expr::Int(t, *i)
+            }
+            Expr::Cast(_, e0) => {
+                let e0 = self.zonk_expr(e0);
+                if e0.ty().int_ty().is_none() && *e0.ty() != Type::Bool {
+                    panic!("Cast: cannot cast {} to {}", e0.ty(), t);
+                }
+                Expr::Cast(t, Rc::new(e0))
+            }
+            Expr::Bool(_, b) => Expr::Bool(t, *b),
+            Expr::String(_, s) => Expr::String(t, s.clone()),
+            Expr::Print(_, e0) => Expr::Print(t, rc(e0)),
+            Expr::Box(_, e0) => Expr::Box(t, rc(e0)),
+            Expr::Call(_, id, es) => Expr::Call(t, id.clone(), exprs(es)),
+            Expr::CallClosure(_, p, es) => Expr::CallClosure(t, self.resolve_place(p), exprs(es)),
+            Expr::MethodCall(..) => unreachable!("Method calls are resolved by infer_expr"),
+            // Once the types of its body are known, the closure is lifted to a function.
+            Expr::Closure(_, lambda) => {
+                let lambda = Lambda {
+                    is_move: lambda.is_move,
+                    params: lambda
+                        .params
+                        .iter()
+                        .map(|l| self.resolve_local(l))
+                        .collect(),
+                    body: self.zonk_expr(&lambda.body),
+                    captures: exprs(&lambda.captures),
+                    function: None,
+                };
+                let function = lift(&lambda, &t, &self.type_params);
+                Expr::Closure(
+                    t,
+                    Rc::new(Lambda {
+                        function: Some(Rc::new(function)),
+                        ..lambda
+                    }),
+                )
+            }
+            Expr::Unit(_) => Expr::Unit(t),
+            Expr::Return(_, e0) => Expr::Return(t, rc(e0)),
+            Expr::Continue(_, l) => Expr::Continue(t, *l),
+            Expr::Break(_, l, e0) => Expr::Break(t, *l, e0.as_ref().map(rc)),
+            Expr::Block(_, b) => Expr::Block(t, block(b)),
+        }
+    }
+}
+
+// Whether a type mentions a type variable, including in the types of the places it borrows.
+fn mentions(t: &Type, v: usize) -> bool {
+    match t {
+        Type::Var(w) => *w == v,
+        Type::Ref(loans, t)
+        | Type::RefMut(loans, t)
+        | Type::Guard(loans, t)
+        | Type::GuardMut(loans, t) => {
+            loans.iter().any(|l| mentions(&l.place.local.ty, v)) || mentions(t, v)
+        }
+        Type::Box(t) | Type::Array(t, _) | Type::Slice(t) | Type::Cell(_, t) => mentions(t, v),
+        Type::Tuple(ts) => ts.iter().any(|t| mentions(t, v)),
+        Type::Struct(s) => s.fields.iter().any(|(_, t)| mentions(t, v)),
+        Type::Enum(e) => e.variants.iter().any(|(_, t)| mentions(t, v)),
+        Type::Closure(c) => c
+            .params
+            .iter()
+            .chain(&c.captures)
+            .chain([&c.ty])
+            .any(|t| mentions(t, v)),
+        _ => false,
+    }
+}
+
+fn mentions_var(t: &Type) -> bool {
+    match t {
+        Type::Var(_) => true,
+        Type::Ref(_, t)
+        | Type::RefMut(_, t)
+        | Type::Guard(_, t)
+        | Type::GuardMut(_, t)
+        | Type::Box(t)
+        | Type::Array(t, _)
+        | Type::Slice(t)
+        | Type::Cell(_, t) => mentions_var(t),
+        Type::Tuple(ts) => ts.iter().any(mentions_var),
+        Type::Struct(s) => s.fields.iter().any(|(_, t)| mentions_var(t)),
+        Type::Enum(e) => e.variants.iter().any(|(_, t)| mentions_var(t)),
+        Type::Closure(c) => c
+            .params
+            .iter()
+            .chain(&c.captures)
+            .chain([&c.ty])
+            .any(mentions_var),
+        _ => false,
+    }
+}
+
+// Infers the type arguments of a call from the types of its arguments. A type parameter which is
+// the type of several arguments carries the loans of all of them.
+pub fn infer_type_args(type_params: &[Name], params: &[Local], args: &[Type]) -> Vec<Type> {
+    let mut bound = vec![None; type_params.len()];
+    for (param, arg) in params.iter().zip(args) {
+        bind(type_params, &param.ty, arg, &mut bound);
+    }
+    bound
+        .into_iter()
+        .zip(type_params)
+        .map(|(t, id)| t.unwrap_or_else(|| panic!("Call: cannot infer type parameter {}", id)))
+        .collect()
+}
+
+fn bind(type_params: &[Name], param: &Type, arg: &Type, bound: &mut [Option<Type>]) {
+    match (param, arg) {
+        (Type::Param(id), _) => {
+            let i = type_params.iter().position(|p| p == id).unwrap();
+            bound[i] = Some(match &bound[i] {
+                Some(t) => join("Call", t, arg),
+                None => arg.clone(),
+            });
+        }
+        (Type::Ref(_, t1), Type::Ref(_, t2))
+        | (Type::RefMut(_, t1), Type::RefMut(_, t2))
+        | (Type::Guard(_, t1), Type::Guard(_, t2))
+        | (Type::GuardMut(_, t1), Type::GuardMut(_, t2))
+        | (Type::Box(t1), Type::Box(t2))
+        | (Type::Cell(_, t1), Type::Cell(_, t2))
+        | (Type::Array(t1, _), Type::Array(t2, _))
+        | (Type::Slice(t1), Type::Slice(t2)) => bind(type_params, t1, t2, bound),
+        (Type::Tuple(ts1), Type::Tuple(ts2)) => {
+            for (t1, t2) in ts1.iter().zip(ts2) {
+                bind(type_params, t1, t2, bound);
+            }
+        }
+        (Type::Closure(c1), Type::Closure(c2)) => {
+            for (t1, t2) in c1.captures.iter().zip(&c2.captures) {
+                bind(type_params, t1, t2, bound);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Returns the type of an expression which is either of two types, where a reference may carry the
+// loans of both. Aggregates are joined component-wise, e.g., the variants of an enum which is built
+// from different variants in different branches.
+fn join(what: &str, t1: &Type, t2: &Type) -> Type {
+    match (t1, t2) {
+        (Type::Never, t) | (t, Type::Never) => t.clone(),
+        (Type::Tuple(ts1), Type::Tuple(ts2)) if ts1.len() == ts2.len() => Type::Tuple(
+            ts1.iter()
+                .zip(ts2)
+                .map(|(t1, t2)| join(what, t1, t2))
+                .collect(),
+        ),
+        (Type::Enum(e1), Type::Enum(e2)) if t1.same_shape(t2) => Type::Enum(Rc::new(Enum {
+            id: e1.id.clone(),
+            variants: e1
+                .variants
+                .iter()
+                .zip(&e2.variants)
+                .map(|((v, t1), (_, t2))| (v.clone(), join(what, t1, t2)))
+                .collect(),
+        })),
+        (Type::Struct(s1), Type::Struct(s2)) if t1.same_shape(t2) => {
+            Type::Struct(Rc::new(Struct {
+                id: s1.id.clone(),
+                fields: s1
+                    .fields
+                    .iter()
+                    .zip(&s2.fields)
+                    .map(|((f, t1), (_, t2))| (f.clone(), join(what, t1, t2)))
+                    .collect(),
+            }))
+        }
+        (Type::Ref(loans1, t1), Type::Ref(loans2, t2)) => {
+            if t1 != t2 {
+                panic!("{} (Ref): mismatched types: {:?} != {:?}", what, t1, t2);
+            }
+            let loans = loans1
+                .iter()
+                .chain(loans2.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            Type::Ref(loans, t1.clone())
+        }
+        (Type::Box(t1), Type::Box(t2)) => Type::Box(Rc::new(join(what, t1, t2))),
+        (Type::Cell(k1, t1), Type::Cell(k2, t2)) if k1 == k2 => {
+            Type::Cell(*k1, Rc::new(join(what, t1, t2)))
+        }
+        // A guard carries the loans of both, like a reference.
+        (Type::Guard(loans1, u1), Type::Guard(loans2, u2))
+        | (Type::GuardMut(loans1, u1), Type::GuardMut(loans2, u2)) => {
+            if u1 != u2 {
+                panic!("{} (Guard): mismatched types: {:?} != {:?}", what, u1, u2);
+            }
+            let loans = loans1.iter().chain(loans2).cloned().collect::<Vec<_>>();
+            if let Type::Guard(..) = t1 {
+                Type::Guard(loans, u1.clone())
+            } else {
+                Type::GuardMut(loans, u1.clone())
+            }
+        }
+        (Type::Array(t1, n1), Type::Array(t2, n2)) if n1 == n2 => {
+            Type::Array(Rc::new(join(what, t1, t2)), *n1)
+        }
+        (Type::Slice(t1), Type::Slice(t2)) => Type::Slice(Rc::new(join(what, t1, t2))),
+        (Type::RefMut(loans1, t1), Type::RefMut(loans2, t2)) => {
+            if t1 != t2 {
+                panic!("{} (RefMut) mismatched types: {:?} != {:?}", what, t1, t2);
+            }
+            let loans = loans1
+                .iter()
+                .chain(loans2.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            Type::RefMut(loans, t1.clone())
+        }
+        (t1, t2) => {
+            if t1 != t2 {
+                panic!("{} (Type) mismatched types: {:?} != {:?}", what, t1, t2);
+            }
+            t1.clone()
+        }
+    }
 }
 
 impl Function {
     pub fn infer(&self) -> Function {
-        let mut ctx = Context::new();
+        let mut ctx = Context::new(vec![self.clone()]);
         ctx.infer_function(self)
     }
 }
+
+impl Program {
+    pub fn infer(&self) -> Program {
+        self.check_impls();
+        let mut ctx = Context::new(self.functions.clone());
+        ctx.impls = self.impls.clone();
+        Program {
+            functions: self
+                .functions
+                .iter()
+                .map(|f| ctx.infer_function(f))
+                .collect(),
+            traits: self.traits.clone(),
+            impls: self.impls.clone(),
+        }
+    }
+
+    // Each impl must be of a trait, at most once for a type, and have exactly the methods of the
+    // trait, with its signatures once `Self` is the type. Loans are not compared, since the
+    // lifetimes of the methods are checked like those of any function.
+    fn check_impls(&self) {
+        for (k, imp) in self.impls.iter().enumerate() {
+            let Some(tr) = self.traits.iter().find(|t| t.id == imp.trait_id) else {
+                panic!("Impl: unknown trait {}", imp.trait_id);
+            };
+            if self.impls[..k]
+                .iter()
+                .any(|i| i.trait_id == imp.trait_id && i.ty.same_shape(&imp.ty))
+            {
+                panic!("Impl: {} is implemented twice for {}", tr.id, imp.ty);
+            }
+            if let Some(m) = imp
+                .methods
+                .iter()
+                .find(|m| !tr.methods.iter().any(|s| s.id == **m))
+            {
+                panic!("Impl: {} is not a method of {}", m, tr.id);
+            }
+            for s in &tr.methods {
+                if !imp.methods.contains(&s.id) {
+                    panic!(
+                        "Impl: method {} of {} is missing for {}",
+                        s.id, tr.id, imp.ty
+                    );
+                }
+                let f = self.function(&imp.function_id(&s.id)).unwrap();
+                let self_ty = |t: &Type| {
+                    t.with_type_args(&["Self".to_string()], std::slice::from_ref(&imp.ty))
+                };
+                if s.params.len() != f.params.len()
+                    || s.params.iter().zip(&f.params).any(|(l1, l2)| {
+                        (l1.id == "self") != (l2.id == "self")
+                            || !self_ty(&l1.ty).same_shape(&l2.ty)
+                    })
+                    || !self_ty(&s.ty).same_shape(&f.ty)
+                {
+                    panic!("Impl: {} does not match its signature in {}", f.id, tr.id);
+                }
+            }
+        }
+    }
+}
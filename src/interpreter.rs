@@ -0,0 +1,826 @@
+use std::collections::HashMap;
+
+use crate::ast::ArithOp;
+use crate::ast::CellKind;
+use crate::ast::IntTy;
+use crate::ast::Local;
+use crate::ast::LocalId;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Type;
+use crate::mir::BlockId;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+pub type AllocId = usize;
+pub type Tag = usize;
+
+// Paths are sequences of tuple indices from the root of an allocation.
+pub type Path = Vec<usize>;
+
+// The elements `start..start + len` of an array, which a slice consists of.
+pub type Elements = (usize, usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    // An integer of a type other than i32. Use `Value::int` to construct one.
+    Integer(IntTy, i128),
+    Bool(bool),
+    String(String),
+    Unit,
+    Tuple(Vec<Value>),
+    // A variant of an enum, by discriminant, and its payload.
+    Variant(usize, Box<Value>),
+    Ref(Pointer),
+    // A reference to the elements `start..start + len` of the array that the pointer points to.
+    Slice(Pointer, usize, usize),
+    // An owning pointer to a heap allocation.
+    Box(AllocId),
+    // A borrow of the contents of a RefCell, which is mutable if the bool is true. The contents
+    // are the second element of the RefCell, after its borrow flag.
+    Guard(Pointer, bool),
+    Uninit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pointer {
+    pub alloc: AllocId,
+    pub path: Path,
+    pub tag: Tag,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // An access through a tag that has been removed from the borrow stack.
+    AliasingViolation { tag: Tag, write: bool },
+    // A write through a shared reference.
+    WriteThroughShared { tag: Tag },
+    UseAfterFree(AllocId),
+    DoubleFree(AllocId),
+    DeadLocal(LocalId),
+    UseOfUninit(Place),
+    Overflow,
+    IndexOutOfBounds { index: i128, len: usize },
+    RangeOutOfBounds { from: usize, to: usize, len: usize },
+    // A borrow of a RefCell while it is mutably borrowed.
+    BorrowError,
+    // A mutable borrow of a RefCell while it is borrowed.
+    BorrowMutError,
+    UnknownFunction(String),
+    TypeError(String),
+    StepLimit,
+    // A block without a terminator was reached, which only happens in malformed MIR.
+    MissingTerminator(BlockId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Permission {
+    Unique,
+    SharedReadOnly,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    tag: Tag,
+    perm: Permission,
+}
+
+// The tag of accesses that go directly through a local rather than through a reference.
+const OWNER: Tag = 0;
+
+// The tag of accesses through the guard of a RefCell, which its borrow flag checks instead of the
+// borrow stacks.
+const CELL: Tag = OWNER + 1;
+
+#[derive(Debug)]
+struct Allocation {
+    value: Value,
+    live: bool,
+    // Borrow stacks of parts of the allocation, keyed by path.
+    stacks: Vec<(Path, Vec<Item>)>,
+}
+
+pub struct Interpreter<'a> {
+    func: &'a Function,
+    // The functions that can be called besides the builtins.
+    functions: &'a [Function],
+    allocs: Vec<Allocation>,
+    frame: HashMap<LocalId, AllocId>,
+    next_tag: Tag,
+    fuel: usize,
+    pub stdout: String,
+}
+
+impl Function {
+    pub fn interpret(&self, args: Vec<Value>) -> Result<Value, Error> {
+        Interpreter::new(self).run(args)
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(func: &'a Function) -> Interpreter<'a> {
+        Interpreter {
+            func,
+            functions: &[],
+            allocs: vec![],
+            frame: HashMap::new(),
+            next_tag: CELL + 1,
+            fuel: 1_000_000,
+            stdout: String::new(),
+        }
+    }
+
+    pub fn with_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    pub fn with_functions(mut self, functions: &'a [Function]) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    pub fn run(&mut self, args: Vec<Value>) -> Result<Value, Error> {
+        self.exec(self.func, args)
+    }
+
+    // Runs a function in a new frame. The storage of its parameters and of its locals without
+    // storage statements ends when it returns.
+    fn call(&mut self, func: &'a Function, args: Vec<Value>) -> Result<Value, Error> {
+        let caller = std::mem::take(&mut self.frame);
+        let result = self.exec(func, args);
+        for (_, a) in std::mem::replace(&mut self.frame, caller) {
+            self.allocs[a].live = false;
+            self.allocs[a].stacks.clear();
+        }
+        result
+    }
+
+    fn exec(&mut self, func: &'a Function, args: Vec<Value>) -> Result<Value, Error> {
+        for (param, arg) in func.params.iter().zip(args) {
+            let a = self.allocate(param);
+            self.allocs[a].value = arg;
+        }
+        // Locals without storage statements, such as the return local, live for the whole call.
+        for l in &func.locals {
+            let has_storage = func
+                .blocks
+                .iter()
+                .flat_map(|b| &b.stmts)
+                .any(|s| matches!(&s.op, Operation::StorageLive(l1) if l1.id == l.id));
+            if !has_storage {
+                self.allocate(l);
+            }
+        }
+
+        let mut b: BlockId = 0;
+        loop {
+            let block = &func.blocks[b];
+            for stmt in &block.stmts {
+                self.tick()?;
+                self.step(&stmt.op)?;
+            }
+            self.tick()?;
+            match &block.terminator {
+                Some(Terminator::Return) => {
+                    let ret = Place::from(func.locals[0].clone());
+                    return self.read_place(&ret);
+                }
+                None => return Err(Error::MissingTerminator(b)),
+                Some(Terminator::Goto(b1)) => b = *b1,
+                Some(Terminator::ConditionalGoto(cond, b1, b2)) => {
+                    b = match self.operand(cond)? {
+                        Value::Bool(true) => *b1,
+                        Value::Bool(false) => *b2,
+                        v => return Err(Error::TypeError(format!("expected bool, found {:?}", v))),
+                    }
+                }
+                Some(Terminator::SwitchInt(discr, targets, otherwise)) => {
+                    let Value::Int(d) = self.operand(discr)? else {
+                        return Err(Error::TypeError("switch on a non-integer".to_string()));
+                    };
+                    b = targets
+                        .iter()
+                        .find(|(v, _)| *v == d)
+                        .map_or(*otherwise, |(_, b1)| *b1);
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self.fuel == 0 {
+            return Err(Error::StepLimit);
+        }
+        self.fuel -= 1;
+        Ok(())
+    }
+
+    fn step(&mut self, op: &Operation) -> Result<(), Error> {
+        match op {
+            Operation::Assign(dest, rvalue) => {
+                let v = match rvalue {
+                    Rvalue::Use(op) => self.operand(op)?,
+                    Rvalue::Ref { mutable, place } => self.reborrow(place, *mutable)?,
+                    // Only the discriminant needs to be initialized, not the payload.
+                    Rvalue::Discriminant(place) => match self.read_discriminant(place)? {
+                        Value::Variant(d, _) => Value::Int(d as i32),
+                        v => return Err(Error::TypeError(format!("discriminant of {:?}", v))),
+                    },
+                };
+                self.write_place(dest, v)
+            }
+            Operation::SetDiscriminant(dest, d) => {
+                let Type::Enum(e) = dest.ty() else {
+                    return Err(Error::TypeError(format!("set discriminant of {}", dest)));
+                };
+                let payload = Value::uninit(&e.variants[*d].1);
+                self.write_place(dest, Value::Variant(*d, Box::new(payload)))
+            }
+            Operation::Call { dest, func, args } => {
+                let Operand::Function(name) = func else {
+                    return Err(Error::TypeError("call of non-function".to_string()));
+                };
+                let args = args
+                    .iter()
+                    .map(|a| self.operand(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let v = match self.functions.iter().find(|f| f.id == *name) {
+                    Some(callee) => self.call(callee, args)?,
+                    None => self.builtin(name, args, &dest.ty())?,
+                };
+                self.write_place(dest, v)
+            }
+            Operation::Drop(place) => {
+                let (a, path, tag) = self.resolve(place)?;
+                self.access(a, &path, tag, true)?;
+                let v = std::mem::replace(
+                    self.allocs[a].value.get_mut(&path)?,
+                    Value::uninit(&place.ty()),
+                );
+                // Drop elaboration never drops a place that has been moved out of as a whole.
+                if v.is_moved() {
+                    return Err(Error::UseOfUninit(place.clone()));
+                }
+                self.drop_value(v)
+            }
+            Operation::StorageLive(l) => {
+                self.allocate(l);
+                Ok(())
+            }
+            Operation::StorageDead(l) => {
+                if let Some(a) = self.frame.remove(&l.id) {
+                    self.allocs[a].live = false;
+                    self.allocs[a].stacks.clear();
+                }
+                Ok(())
+            }
+            Operation::Noop => Ok(()),
+        }
+    }
+
+    // Frees the boxes a value owns. Uninitialized parts, e.g., those that have been moved out of,
+    // own nothing.
+    fn drop_value(&mut self, v: Value) -> Result<(), Error> {
+        match v {
+            Value::Box(a) => {
+                let alloc = &mut self.allocs[a];
+                if !alloc.live {
+                    return Err(Error::DoubleFree(a));
+                }
+                alloc.live = false;
+                alloc.stacks.clear();
+                let v = std::mem::replace(&mut alloc.value, Value::Uninit);
+                self.drop_value(v)
+            }
+            Value::Tuple(vs) => vs.into_iter().try_for_each(|v| self.drop_value(v)),
+            Value::Variant(_, v) => self.drop_value(*v),
+            // Dropping a guard ends its borrow of the RefCell.
+            Value::Guard(p, mutable) => {
+                if !self.allocs[p.alloc].live {
+                    return Err(Error::UseAfterFree(p.alloc));
+                }
+                let n = self.allocs[p.alloc].value.get_mut(&flag_path(&p))?;
+                *n = release(n, mutable)?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The result of a builtin has the type of its destination, which is what a cast converts to.
+    fn builtin(&mut self, name: &str, args: Vec<Value>, ty: &Type) -> Result<Value, Error> {
+        match (name, args.as_slice()) {
+            ("add" | "sub" | "mul", [a, b]) => {
+                let op = ArithOp::from_name(name).unwrap();
+                match (a.as_int(), b.as_int()) {
+                    (Some((t, a)), Some((t2, b))) if t == t2 => t
+                        .checked(op, a, b)
+                        .map(|i| Value::int(t, i))
+                        .ok_or(Error::Overflow),
+                    _ => Err(Error::TypeError(format!(
+                        "cannot {} {:?} and {:?}",
+                        name, a, b
+                    ))),
+                }
+            }
+            ("cast", [v]) => v.cast(ty),
+            ("print", [v]) => {
+                let s = match v {
+                    Value::Ref(p) => self.read(p.alloc, &p.path, p.tag)?,
+                    v => v.clone(),
+                };
+                self.stdout.push_str(&s.to_string());
+                self.stdout.push('\n');
+                Ok(Value::Unit)
+            }
+            ("box", [v]) => Ok(Value::Box(self.allocate_value(v.clone()))),
+            ("Cell::new", [v]) => Ok(v.clone()),
+            ("RefCell::new", [v]) => Ok(Value::Tuple(vec![Value::Int(0), v.clone()])),
+            // The cell is only read through the shared reference, and written to directly.
+            ("Cell::get", [Value::Ref(p)]) => self.read(p.alloc, &p.path, p.tag),
+            ("Cell::set", [Value::Ref(p), v]) => {
+                self.access(p.alloc, &p.path, p.tag, false)?;
+                let old =
+                    std::mem::replace(self.allocs[p.alloc].value.get_mut(&p.path)?, v.clone());
+                self.drop_value(old)?;
+                Ok(Value::Unit)
+            }
+            ("RefCell::borrow" | "RefCell::borrow_mut", [Value::Ref(p)]) => {
+                let mutable = name == "RefCell::borrow_mut";
+                let ptr = Pointer {
+                    alloc: p.alloc,
+                    path: [p.path.clone(), vec![1]].concat(),
+                    tag: CELL,
+                };
+                let flag = flag_path(&ptr);
+                let n = borrow(&self.read(p.alloc, &flag, p.tag)?, mutable)?;
+                *self.allocs[p.alloc].value.get_mut(&flag)? = n;
+                Ok(Value::Guard(ptr, mutable))
+            }
+            _ => Err(Error::UnknownFunction(name.to_string())),
+        }
+    }
+
+    fn allocate(&mut self, l: &Local) -> AllocId {
+        let a = self.allocate_value(Value::uninit(&l.ty));
+        self.frame.insert(l.id.clone(), a);
+        a
+    }
+
+    fn allocate_value(&mut self, value: Value) -> AllocId {
+        let a = self.allocs.len();
+        self.allocs.push(Allocation {
+            value,
+            live: true,
+            stacks: vec![(
+                vec![],
+                vec![Item {
+                    tag: OWNER,
+                    perm: Permission::Unique,
+                }],
+            )],
+        });
+        a
+    }
+
+    fn operand(&mut self, op: &Operand) -> Result<Value, Error> {
+        match op {
+            Operand::Constant(c) => Ok(Value::from(c)),
+            Operand::Copy(p) => self.read_place(p),
+            Operand::Move(p) => {
+                let v = self.read_place(p)?;
+                // Moving out of a local or a box leaves it uninitialized.
+                if !through_reference(p) {
+                    let (a, path, _) = self.resolve(p)?;
+                    *self.allocs[a].value.get_mut(&path)? = Value::Uninit;
+                }
+                Ok(v)
+            }
+            Operand::Function(name) => Err(Error::TypeError(format!("{} is not a value", name))),
+        }
+    }
+
+    fn read_place(&mut self, p: &Place) -> Result<Value, Error> {
+        let (a, path, tag) = self.resolve(p)?;
+        let v = self.read(a, &path, tag)?;
+        if v.is_uninit() {
+            return Err(Error::UseOfUninit(p.clone()));
+        }
+        Ok(v)
+    }
+
+    fn read_discriminant(&mut self, p: &Place) -> Result<Value, Error> {
+        let (a, path, tag) = self.resolve(p)?;
+        self.read(a, &path, tag)
+    }
+
+    fn write_place(&mut self, p: &Place, v: Value) -> Result<(), Error> {
+        let (a, path, tag) = self.resolve(p)?;
+        self.access(a, &path, tag, true)?;
+        *self.allocs[a].value.get_mut(&path)? = v;
+        Ok(())
+    }
+
+    fn read(&mut self, a: AllocId, path: &Path, tag: Tag) -> Result<Value, Error> {
+        self.access(a, path, tag, false)?;
+        self.allocs[a].value.get(path).cloned()
+    }
+
+    // Resolves a place into the allocation and path that it denotes, and the tag that grants
+    // access to it.
+    fn resolve(&mut self, p: &Place) -> Result<(AllocId, Path, Tag), Error> {
+        let (a, path, tag, _) = self.resolve_slice(p)?;
+        Ok((a, path, tag))
+    }
+
+    // Like `resolve`, but a place that is a slice also resolves to the elements `start..start +
+    // len` of the array at the path that it denotes.
+    fn resolve_slice(
+        &mut self,
+        p: &Place,
+    ) -> Result<(AllocId, Path, Tag, Option<Elements>), Error> {
+        let Some(&a) = self.frame.get(&p.local.id) else {
+            return Err(Error::DeadLocal(p.local.id.clone()));
+        };
+        let (mut a, mut path, mut tag) = (a, vec![], OWNER);
+        let mut slice = None;
+        for (k, elem) in p.elems.iter().enumerate() {
+            match elem {
+                PlaceElem::TupleIndex(i) => path.push(*i),
+                PlaceElem::Field(_) | PlaceElem::Downcast(_) => path.push(elem_index(p, k)?),
+                PlaceElem::ConstantIndex(i) => {
+                    let (start, len) = elements(p, k, slice.take())?;
+                    path.push(start + check_index(*i as i128, len)?);
+                }
+                PlaceElem::Index(l) => {
+                    let (start, len) = elements(p, k, slice.take())?;
+                    let i = self.read_place(&Place::from(l.clone()))?;
+                    let Some((_, i)) = i.as_int() else {
+                        return Err(Error::TypeError(format!("index {:?}", i)));
+                    };
+                    path.push(start + check_index(i, len)?);
+                }
+                PlaceElem::Subslice { from, to } => {
+                    slice = Some(subslice(elements(p, k, slice.take())?, *from, *to)?);
+                }
+                PlaceElem::Deref => match self.read(a, &path, tag)? {
+                    Value::Ref(ptr) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                        tag = ptr.tag;
+                    }
+                    Value::Slice(ptr, start, len) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                        tag = ptr.tag;
+                        slice = Some((start, len));
+                    }
+                    Value::Guard(ptr, _) => {
+                        a = ptr.alloc;
+                        path = ptr.path;
+                        tag = ptr.tag;
+                    }
+                    // The contents of a box are owned by it, so they are accessed like a local.
+                    Value::Box(b) => {
+                        a = b;
+                        path = vec![];
+                        tag = OWNER;
+                    }
+                    v => return Err(Error::TypeError(format!("deref of {:?}", v))),
+                },
+            }
+        }
+        Ok((a, path, tag, slice))
+    }
+
+    // A borrow of a slice borrows each of its elements, so that borrows of disjoint subslices of
+    // an array do not invalidate each other.
+    fn reborrow(&mut self, p: &Place, mutable: bool) -> Result<Value, Error> {
+        let (a, path, tag, slice) = self.resolve_slice(p)?;
+        let paths = match slice {
+            Some((start, len)) => (start..start + len)
+                .map(|i| [path.clone(), vec![i]].concat())
+                .collect(),
+            None => vec![path.clone()],
+        };
+        for path in &paths {
+            self.access(a, path, tag, mutable)?;
+        }
+        let new = self.next_tag;
+        self.next_tag += 1;
+        let item = Item {
+            tag: new,
+            perm: if mutable {
+                Permission::Unique
+            } else {
+                Permission::SharedReadOnly
+            },
+        };
+        let alloc = &mut self.allocs[a];
+        for path in paths {
+            // Give the borrowed path its own stack, so that borrows of disjoint paths do not
+            // invalidate each other. New stacks start out as a copy of the closest stack above.
+            if !alloc.stacks.iter().any(|(p, _)| *p == path) {
+                let parent = alloc
+                    .stacks
+                    .iter()
+                    .filter(|(p, _)| path.starts_with(p))
+                    .max_by_key(|(p, _)| p.len())
+                    .map(|(_, s)| s.clone())
+                    .unwrap_or_default();
+                alloc.stacks.push((path.clone(), parent));
+            }
+            for (p, stack) in &mut alloc.stacks {
+                if p.starts_with(&path) {
+                    stack.push(item);
+                }
+            }
+        }
+        let ptr = Pointer {
+            alloc: a,
+            path,
+            tag: new,
+        };
+        Ok(match slice {
+            Some((start, len)) => Value::Slice(ptr, start, len),
+            None => Value::Ref(ptr),
+        })
+    }
+
+    // Stacked Borrows: an access through `tag` is only allowed if `tag` is in the borrow stack.
+    // Reads remove all unique borrows above it, writes remove everything above it.
+    fn access(&mut self, a: AllocId, path: &Path, tag: Tag, write: bool) -> Result<(), Error> {
+        let alloc = &mut self.allocs[a];
+        if !alloc.live {
+            return Err(Error::UseAfterFree(a));
+        }
+        if tag == CELL {
+            return Ok(());
+        }
+        // The stacks of the accessed path and everything below it, or the closest stack above it
+        // if the path has no stack of its own.
+        let ancestor = if alloc.stacks.iter().any(|(p, _)| p == path) {
+            None
+        } else {
+            alloc
+                .stacks
+                .iter()
+                .enumerate()
+                .filter(|(_, (p, _))| path.starts_with(p))
+                .max_by_key(|(_, (p, _))| p.len())
+                .map(|(i, _)| i)
+        };
+        for (k, (p, stack)) in alloc.stacks.iter_mut().enumerate() {
+            if !p.starts_with(path) && ancestor != Some(k) {
+                continue;
+            }
+            let Some(i) = stack.iter().rposition(|item| item.tag == tag) else {
+                return Err(Error::AliasingViolation { tag, write });
+            };
+            if write {
+                if stack[i].perm == Permission::SharedReadOnly {
+                    return Err(Error::WriteThroughShared { tag });
+                }
+                stack.truncate(i + 1);
+            } else {
+                let mut j = i + 1;
+                while j < stack.len() {
+                    if stack[j].perm == Permission::Unique {
+                        stack.remove(j);
+                    } else {
+                        j += 1;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Structs are stored like tuples, so a field projection is an index into its struct's fields. A
+// downcast is a step into a variant, which only succeeds if it is the variant the enum holds.
+pub(crate) fn elem_index(p: &Place, k: usize) -> Result<usize, Error> {
+    let prefix = Place {
+        local: p.local.clone(),
+        elems: p.elems[..k].to_vec(),
+    };
+    match (&p.elems[k], prefix.ty()) {
+        (PlaceElem::Field(f), Type::Struct(s)) => s
+            .field(f)
+            .map(|(i, _)| i)
+            .ok_or_else(|| Error::TypeError(format!("no field {} in {}", f, s.id))),
+        (PlaceElem::Downcast(v), Type::Enum(e)) => e
+            .variant(v)
+            .map(|(i, _)| i)
+            .ok_or_else(|| Error::TypeError(format!("no variant {} in {}", v, e.id))),
+        (elem, t) => Err(Error::TypeError(format!("{:?} of {:?}", elem, t))),
+    }
+}
+
+// The elements `start..start + len` of the array that the `k`th element of the place indexes
+// into, which are all of them unless the place is at a slice of it.
+pub(crate) fn elements(p: &Place, k: usize, slice: Option<Elements>) -> Result<Elements, Error> {
+    let prefix = Place {
+        local: p.local.clone(),
+        elems: p.elems[..k].to_vec(),
+    };
+    match (slice, prefix.ty()) {
+        (Some(slice), _) => Ok(slice),
+        (None, Type::Array(_, len)) => Ok((0, len)),
+        (None, t) => Err(Error::TypeError(format!("index of {:?}", t))),
+    }
+}
+
+// Indices are checked to be in bounds, since the index or the length may only be known at
+// runtime.
+pub(crate) fn check_index(index: i128, len: usize) -> Result<usize, Error> {
+    if 0 <= index && index < len as i128 {
+        Ok(index as usize)
+    } else {
+        Err(Error::IndexOutOfBounds { index, len })
+    }
+}
+
+// The elements `from..to` of a slice, which must be within it.
+pub(crate) fn subslice(
+    (start, len): Elements,
+    from: usize,
+    to: Option<usize>,
+) -> Result<Elements, Error> {
+    let to = to.unwrap_or(len);
+    if from > to || to > len {
+        return Err(Error::RangeOutOfBounds { from, to, len });
+    }
+    Ok((start + from, to - from))
+}
+
+// A RefCell is stored as its borrow flag and its contents. The flag counts the shared borrows of
+// the contents, or is -1 while they are mutably borrowed.
+pub(crate) fn borrow(flag: &Value, mutable: bool) -> Result<Value, Error> {
+    match (mutable, flag) {
+        (false, Value::Int(n)) if *n >= 0 => Ok(Value::Int(n + 1)),
+        (false, Value::Int(_)) => Err(Error::BorrowError),
+        (true, Value::Int(0)) => Ok(Value::Int(-1)),
+        (true, Value::Int(_)) => Err(Error::BorrowMutError),
+        (_, v) => Err(Error::TypeError(format!("borrow flag {:?}", v))),
+    }
+}
+
+pub(crate) fn release(flag: &Value, mutable: bool) -> Result<Value, Error> {
+    match flag {
+        Value::Int(_) if mutable => Ok(Value::Int(0)),
+        Value::Int(n) => Ok(Value::Int(n - 1)),
+        v => Err(Error::TypeError(format!("borrow flag {:?}", v))),
+    }
+}
+
+// The path of the borrow flag of the RefCell whose contents a guard points to.
+pub(crate) fn flag_path(p: &Pointer) -> Path {
+    [&p.path[..p.path.len() - 1], &[0]].concat()
+}
+
+pub(crate) fn through_reference(p: &Place) -> bool {
+    (0..p.elems.len()).any(|k| {
+        let prefix = Place {
+            local: p.local.clone(),
+            elems: p.elems[..k].to_vec(),
+        };
+        p.elems[k] == PlaceElem::Deref
+            && matches!(
+                prefix.ty(),
+                Type::Ref(..) | Type::RefMut(..) | Type::Guard(..) | Type::GuardMut(..)
+            )
+    })
+}
+
+impl Value {
+    pub fn int(t: IntTy, i: i128) -> Value {
+        match t {
+            IntTy::I32 => Value::Int(i as i32),
+            t => Value::Integer(t, i),
+        }
+    }
+
+    pub fn as_int(&self) -> Option<(IntTy, i128)> {
+        match self {
+            Value::Int(i) => Some((IntTy::I32, *i as i128)),
+            Value::Integer(t, i) => Some((*t, *i)),
+            _ => None,
+        }
+    }
+
+    // Converts an integer or bool to an integer type like `as`, which truncates and never fails.
+    pub fn cast(&self, ty: &Type) -> Result<Value, Error> {
+        let i = match self {
+            Value::Bool(b) => *b as i128,
+            v => match v.as_int() {
+                Some((_, i)) => i,
+                None => return Err(Error::TypeError(format!("cannot cast {:?}", v))),
+            },
+        };
+        match ty.int_ty() {
+            Some(t) => Ok(Value::int(t, t.wrap(i))),
+            None => Err(Error::TypeError(format!("cannot cast to {}", ty))),
+        }
+    }
+
+    pub fn uninit(t: &Type) -> Value {
+        match t {
+            Type::Tuple(ts) => Value::Tuple(ts.iter().map(Value::uninit).collect()),
+            Type::Struct(s) => {
+                Value::Tuple(s.fields.iter().map(|(_, t)| Value::uninit(t)).collect())
+            }
+            // Arrays and closures are stored like tuples.
+            Type::Array(t, n) => Value::Tuple(vec![Value::uninit(t); *n]),
+            Type::Closure(c) => Value::Tuple(c.captures.iter().map(Value::uninit).collect()),
+            // A RefCell is stored as its borrow flag and its contents, and a Cell as its contents.
+            Type::Cell(CellKind::RefCell, t) => Value::Tuple(vec![Value::Uninit, Value::uninit(t)]),
+            Type::Cell(CellKind::Cell, t) => Value::uninit(t),
+            _ => Value::Uninit,
+        }
+    }
+
+    pub fn is_uninit(&self) -> bool {
+        match self {
+            Value::Uninit => true,
+            Value::Tuple(vs) => vs.iter().any(|v| v.is_uninit()),
+            Value::Variant(_, v) => v.is_uninit(),
+            _ => false,
+        }
+    }
+
+    // Whether no part of the value is initialized, as after it has been moved out of.
+    pub fn is_moved(&self) -> bool {
+        match self {
+            Value::Uninit => true,
+            Value::Tuple(vs) => vs.iter().all(|v| v.is_moved()),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn get(&self, path: &[usize]) -> Result<&Value, Error> {
+        path.iter().try_fold(self, |v, i| match v {
+            Value::Tuple(vs) if *i < vs.len() => Ok(&vs[*i]),
+            Value::Variant(d, v) if d == i => Ok(v.as_ref()),
+            v => Err(Error::TypeError(format!("cannot index {:?}", v))),
+        })
+    }
+
+    fn get_mut(&mut self, path: &[usize]) -> Result<&mut Value, Error> {
+        let mut v = self;
+        for i in path {
+            v = match v {
+                Value::Tuple(vs) if *i < vs.len() => &mut vs[*i],
+                Value::Variant(d, v) if d == i => v.as_mut(),
+                _ => return Err(Error::TypeError(format!("cannot index at {}", i))),
+            };
+        }
+        Ok(v)
+    }
+}
+
+impl From<&Constant> for Value {
+    fn from(c: &Constant) -> Value {
+        match c {
+            Constant::Int(i) => Value::Int(*i),
+            Constant::Integer(t, i) => Value::Integer(*t, *i),
+            Constant::Bool(b) => Value::Bool(*b),
+            Constant::String(s) => Value::String(s.clone()),
+            Constant::Unit => Value::Unit,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Integer(_, i) => write!(f, "{}", i),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Unit => write!(f, "()"),
+            Value::Tuple(vs) => {
+                write!(f, "(")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, ")")
+            }
+            Value::Variant(d, v) => write!(f, "#{}{}", d, v),
+            Value::Ref(p) => write!(f, "&{}", p.alloc),
+            Value::Slice(p, start, len) => write!(f, "&{}[{}..{}]", p.alloc, start, start + len),
+            Value::Box(a) => write!(f, "box {}", a),
+            Value::Guard(p, _) => write!(f, "&{}", p.alloc),
+            Value::Uninit => write!(f, "<uninit>"),
+        }
+    }
+}
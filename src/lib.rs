@@ -11,9 +11,23 @@ pub mod token;
 pub mod lexer;
 pub mod mir_to_ast;
 pub mod dominators;
+pub mod loops;
 pub mod analyse;
 pub mod merge_blocks;
 pub mod remove_unreachable;
 pub mod constant_propagation;
 pub mod remove_unused;
+pub mod copy_propagation;
+pub mod dead_store_elimination;
+pub mod drop_elaboration;
+pub mod pass_manager;
+pub mod validate;
+pub mod interpreter;
+pub mod eval;
+pub mod mir_to_c;
+pub mod mir_to_wat;
+pub mod ast_to_rust;
 pub mod cst;
+pub mod regions;
+pub mod monomorphize;
+pub mod closures;
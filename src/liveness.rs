@@ -1,5 +1,6 @@
 use crate::ast::Local;
 use crate::ast::Place;
+use crate::ast::PlaceElem;
 use crate::mir::Function;
 use crate::mir::Operand;
 use crate::mir::Operation;
@@ -8,22 +9,44 @@ use crate::mir::Terminator;
 use crate::set::Set;
 
 impl Operation {
+    // Accessing a place also reads the locals that index into it.
     fn used(&self) -> Vec<Place> {
+        let mut v = self.used_places();
+        let indices = v
+            .iter()
+            .chain(&self.defined())
+            .flat_map(|p| p.index_locals())
+            .map(|l| Place::from(l.clone()))
+            .collect::<Vec<_>>();
+        v.extend(indices);
+        v
+    }
+
+    fn used_places(&self) -> Vec<Place> {
         match self {
-            Operation::Assign(_, rv) => match rv {
-                Rvalue::Use(op) => op.used(),
-                Rvalue::Ref { place, .. } => {
-                    let mut v = vec![place.clone()];
-                    for loan in place.local.ty.loans() {
-                        v.push(loan.place.clone());
+            Operation::Assign(dest, rv) => {
+                let mut v = match rv {
+                    Rvalue::Use(op) => op.used(),
+                    Rvalue::Ref { place, .. } => {
+                        let mut v = vec![place.clone()];
+                        for loan in place.local.ty.loans() {
+                            v.push(loan.place.clone());
+                        }
+                        v
                     }
-                    v
-                }
-            },
-            Operation::Call { func, args, .. } => func
+                    Rvalue::Discriminant(place) => vec![place.clone()],
+                };
+                v.extend(dest.indirection());
+                v
+            }
+            Operation::SetDiscriminant(dest, _) => dest.indirection().into_iter().collect(),
+            // Like a box in Rust, dropping does not read the loans held by the value.
+            Operation::Drop(place) => place.indirection().into_iter().collect(),
+            Operation::Call { dest, func, args } => func
                 .used()
                 .into_iter()
                 .chain(args.iter().flat_map(|arg| arg.used().into_iter()))
+                .chain(dest.indirection())
                 .collect(),
             Operation::StorageLive(_) => vec![],
             Operation::StorageDead(_) => vec![],
@@ -42,7 +65,10 @@ impl Operation {
                     }
                     v
                 }
+                Rvalue::Discriminant(_) => vec![],
             },
+            Operation::SetDiscriminant(..) => vec![],
+            Operation::Drop(..) => vec![],
             Operation::Call { func, args, .. } => func
                 .moved()
                 .into_iter()
@@ -58,7 +84,14 @@ impl Operation {
         match self {
             Operation::Assign(p, _) => vec![p.clone()],
             Operation::Call { dest, .. } => vec![dest.clone()],
-            Operation::StorageLive(_) => vec![],
+            Operation::SetDiscriminant(dest, _) => vec![dest.clone()],
+            Operation::Drop(place) => vec![place.clone()],
+            // Aggregates are initialized one field at a time, so no single assignment kills the
+            // whole local. Nothing of it can be live before its storage is.
+            Operation::StorageLive(l) => vec![Place {
+                local: l.clone(),
+                elems: vec![],
+            }],
             Operation::StorageDead(_) => vec![],
             Operation::Noop => vec![],
         }
@@ -68,6 +101,8 @@ impl Operation {
         match self {
             Operation::Assign(..) => vec![],
             Operation::Call { .. } => vec![],
+            Operation::SetDiscriminant(..) => vec![],
+            Operation::Drop(..) => vec![],
             Operation::StorageLive(l) => vec![l.clone()],
             Operation::StorageDead(_) => vec![],
             Operation::Noop => vec![],
@@ -78,6 +113,8 @@ impl Operation {
         match self {
             Operation::Assign(_, _) => vec![],
             Operation::Call { .. } => vec![],
+            Operation::SetDiscriminant(..) => vec![],
+            Operation::Drop(..) => vec![],
             Operation::StorageLive(_) => vec![],
             Operation::StorageDead(l) => vec![l.clone()],
             Operation::Noop => vec![],
@@ -91,6 +128,7 @@ impl Operand {
             Operand::Constant(_) | Operand::Function(_) => vec![],
             Operand::Copy(p) | Operand::Move(p) => {
                 let mut v = vec![p.clone()];
+                v.extend(p.index_locals().map(|l| Place::from(l.clone())));
                 for loan in p.local.ty.loans() {
                     v.push(loan.place.clone());
                 }
@@ -108,6 +146,17 @@ impl Operand {
     }
 }
 
+impl Place {
+    // Writing through a dereference reads the reference, i.e., the place before the last deref.
+    fn indirection(&self) -> Option<Place> {
+        let i = self.elems.iter().rposition(|e| *e == PlaceElem::Deref)?;
+        Some(Place {
+            local: self.local.clone(),
+            elems: self.elems[..i].to_vec(),
+        })
+    }
+}
+
 impl Function {
     pub fn compute_liveness(&mut self) {
         let mut changed = true;
@@ -119,14 +168,35 @@ impl Function {
             for block in self.blocks.iter_mut().rev() {
                 let mut live_out = Set::new();
                 if let Some(terminator) = &block.terminator {
-                    // A block's live-out is the union of its successor's live-in
+                    // A block's live-out is the union of its successors' live-in
                     match terminator {
-                        Terminator::Goto(b) | Terminator::ConditionalGoto(_, b, _) => {
+                        Terminator::Goto(b) => {
                             live_out.extend(old_blocks[*b].live_in.iter().cloned());
                         }
+                        Terminator::ConditionalGoto(_, b0, b1) => {
+                            live_out.extend(old_blocks[*b0].live_in.iter().cloned());
+                            live_out.extend(old_blocks[*b1].live_in.iter().cloned());
+                        }
+                        Terminator::SwitchInt(_, targets, otherwise) => {
+                            for (_, b) in targets {
+                                live_out.extend(old_blocks[*b].live_in.iter().cloned());
+                            }
+                            live_out.extend(old_blocks[*otherwise].live_in.iter().cloned());
+                        }
                         Terminator::Return => {}
                     }
                 }
+                let old_block_live_in = block.live_in.clone();
+                block.live_out = live_out.clone();
+
+                // The terminator reads its operand after the last statement, so the operand is live
+                // into the terminator, but not out of the block into its successors.
+                if let Some(
+                    Terminator::ConditionalGoto(op, _, _) | Terminator::SwitchInt(op, _, _),
+                ) = &block.terminator
+                {
+                    live_out.extend(op.used());
+                }
 
                 // Compute live-out for each statement in reverse order
                 for stmt in block.stmts.iter_mut().rev() {
@@ -141,13 +211,17 @@ impl Function {
                     let moved = stmt.op.moved();
                     let defined = stmt.op.defined();
 
-                    // live_in = (live_out - (defined U moved)) U used
+                    // live_in = (live_out - (defined U moved)) U used, where places with an index
+                    // that is only known at runtime kill nothing, since they may denote any element.
+                    let kills = |p: &Place, v: &Place| {
+                        p.index_locals().next().is_none() && p.is_prefix_of(v)
+                    };
                     stmt.live_in = stmt
                         .live_out
                         .iter()
                         .filter(|v| {
-                            !defined.iter().any(|p| p.is_prefix_of(v))
-                                && !moved.iter().any(|p| p.is_prefix_of(v))
+                            !defined.iter().any(|p| kills(p, v))
+                                && !moved.iter().any(|p| kills(p, v))
                         })
                         .cloned()
                         .collect();
@@ -163,13 +237,11 @@ impl Function {
                     }
                 }
 
-                // Update block's live-in and live-out
-                block.live_out = live_out.clone();
-                block.live_in = block
-                    .stmts
-                    .iter()
-                    .flat_map(|stmt| stmt.live_in.iter().cloned())
-                    .collect();
+                // The block's live-in is the live-in of its first statement
+                block.live_in = live_out;
+                if old_block_live_in != block.live_in {
+                    changed = true;
+                }
             }
         }
     }
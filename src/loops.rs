@@ -0,0 +1,177 @@
+use crate::mir::BlockId;
+use crate::mir::Function;
+use crate::set::Set;
+
+pub type LoopId = usize;
+
+#[derive(Debug, Clone, Default)]
+pub struct LoopForest {
+    pub loops: Vec<Loop>,
+    // b ↦ innermost loop containing b.
+    pub innermost: Vec<Option<LoopId>>,
+    // Retreating edges (source, target) whose target does not dominate the source.
+    pub irreducible: Vec<(BlockId, BlockId)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: BlockId,
+    // Sources of the back edges into the header.
+    pub latches: Vec<BlockId>,
+    // All blocks of the loop, including the header.
+    pub body: Set<BlockId>,
+    // Blocks outside of the loop that are targeted from inside of it.
+    pub exits: Vec<BlockId>,
+    pub parent: Option<LoopId>,
+    // 1 for outermost loops.
+    pub depth: usize,
+}
+
+impl LoopForest {
+    pub fn is_header(&self, b: BlockId) -> bool {
+        self.loops.iter().any(|l| l.header == b)
+    }
+
+    pub fn is_back_edge(&self, source: BlockId, target: BlockId) -> bool {
+        self.loops
+            .iter()
+            .any(|l| l.header == target && l.latches.contains(&source))
+    }
+
+    pub fn is_exit(&self, source: BlockId, target: BlockId) -> bool {
+        self.loops
+            .iter()
+            .any(|l| l.body.contains(&source) && l.exits.contains(&target))
+    }
+
+    pub fn loop_of(&self, b: BlockId) -> Option<&Loop> {
+        self.innermost
+            .get(b)
+            .copied()
+            .flatten()
+            .map(|l| &self.loops[l])
+    }
+
+    pub fn depth(&self, b: BlockId) -> usize {
+        self.loop_of(b).map_or(0, |l| l.depth)
+    }
+
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+}
+
+impl Function {
+    // A back edge is an edge s → h where h dominates s. The natural loop of h is h together with
+    // all blocks that can reach a latch s without passing through h.
+    //
+    // Depends on predecessors, successors and dominators.
+    pub fn compute_loops(&mut self) {
+        let n = self.blocks.len();
+        let mut loops: Vec<Loop> = Vec::new();
+
+        let (reachable, retreating) = self.retreating_edges();
+
+        let mut irreducible = Vec::new();
+        for (s, h) in retreating {
+            if !self.blocks[s].dom.contains(&h) {
+                irreducible.push((s, h));
+                continue;
+            }
+            match loops.iter_mut().find(|l| l.header == h) {
+                Some(l) => l.latches.push(s),
+                None => loops.push(Loop {
+                    header: h,
+                    latches: vec![s],
+                    body: Set::new(),
+                    exits: vec![],
+                    parent: None,
+                    depth: 0,
+                }),
+            }
+        }
+
+        for l in &mut loops {
+            l.body.add(l.header);
+            let mut worklist = l.latches.clone();
+            while let Some(b) = worklist.pop() {
+                if !reachable[b] || l.body.contains(&b) {
+                    continue;
+                }
+                l.body.add(b);
+                worklist.extend(self.predecessors[b].iter().copied());
+            }
+            for &b in l.body.iter() {
+                for &succ in &self.successors[b] {
+                    if !l.body.contains(&succ) && !l.exits.contains(&succ) {
+                        l.exits.push(succ);
+                    }
+                }
+            }
+        }
+
+        // Sort outermost-first so that parents are always resolved before their children.
+        loops.sort_by_key(|l| std::cmp::Reverse(l.body.len()));
+
+        for i in 0..loops.len() {
+            let parent = (0..i)
+                .rev()
+                .find(|&j| loops[j].body.contains(&loops[i].header));
+            loops[i].parent = parent;
+            loops[i].depth = parent.map_or(1, |j| loops[j].depth + 1);
+        }
+
+        let mut innermost = vec![None; n];
+        for (i, l) in loops.iter().enumerate() {
+            for &b in l.body.iter() {
+                innermost[b] = Some(i);
+            }
+        }
+
+        self.loops = LoopForest {
+            loops,
+            innermost,
+            irreducible,
+        };
+    }
+
+    // Returns which blocks are reachable from the entry, and all edges s → t of a depth-first
+    // traversal where t is still on the stack when the edge is visited.
+    fn retreating_edges(&self) -> (Vec<bool>, Vec<(BlockId, BlockId)>) {
+        let n = self.blocks.len();
+        let mut visited = vec![false; n];
+        let mut on_stack = vec![false; n];
+        let mut retreating = Vec::new();
+
+        fn dfs(
+            b: BlockId,
+            visited: &mut [bool],
+            on_stack: &mut [bool],
+            retreating: &mut Vec<(BlockId, BlockId)>,
+            successors: &[Vec<BlockId>],
+        ) {
+            visited[b] = true;
+            on_stack[b] = true;
+            for &s in &successors[b] {
+                if on_stack[s] {
+                    retreating.push((b, s));
+                } else if !visited[s] {
+                    dfs(s, visited, on_stack, retreating, successors);
+                }
+            }
+            on_stack[b] = false;
+        }
+
+        if n > 0 {
+            dfs(
+                0,
+                &mut visited,
+                &mut on_stack,
+                &mut retreating,
+                &self.successors,
+            );
+        }
+
+        (visited, retreating)
+    }
+}
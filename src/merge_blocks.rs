@@ -1,46 +1,105 @@
+use crate::mir::BlockId;
 use crate::mir::Function;
 use crate::mir::Terminator;
 
 impl Function {
-    pub fn merge_blocks(&mut self) {
-        let mut visited = vec![false; self.blocks.len()];
-
-        for i in 0..self.blocks.len() {
-            if visited[i] {
-                continue;
+    // Simplifies the CFG until nothing changes by:
+    // * Threading jumps through blocks that are empty and only contain a `Goto`.
+    // * Merging a block into its predecessor when it is the predecessor's only successor and the
+    //   predecessor is its only predecessor.
+    // Absorbed blocks are removed afterwards and the CFG analyses are recomputed. Returns whether
+    // the CFG changed.
+    pub fn merge_blocks(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            self.compute_predecessors();
+            let threaded = self.thread_jumps();
+            self.compute_predecessors();
+            let merged = self.merge_chains();
+            if !threaded && !merged {
+                break;
             }
+            changed = true;
+        }
+        changed |= self.remove_unreachable();
+        self.compute_cfg();
+        changed
+    }
 
-            let mut chain = Vec::new();
-            let mut pred = i;
-
-            while let &Some(Terminator::Goto(succ)) = &self.blocks[pred].terminator {
-                if self.predecessors[succ].len() == 1 && !visited[succ] {
-                    chain.push(pred);
-                    visited[pred] = true;
-                    pred = succ;
-                } else {
-                    break;
+    // Returns the block that is eventually reached when jumping to `b`.
+    fn jump_target(&self, b: BlockId) -> BlockId {
+        let mut target = b;
+        // Bounded, since a cycle of empty blocks never reaches a non-empty block.
+        for _ in 0..self.blocks.len() {
+            match &self.blocks[target].terminator {
+                Some(Terminator::Goto(next))
+                    if self.blocks[target].stmts.is_empty() && *next != target =>
+                {
+                    target = *next;
                 }
+                _ => break,
             }
+        }
+        target
+    }
 
-            if !chain.is_empty() {
-                chain.push(pred);
-                visited[pred] = true;
-
-                // Merge the chain into the first block
-                let &first = chain.first().unwrap();
-                let &second = chain.get(1).unwrap();
-                let &last = chain.last().unwrap();
-                self.successors[first] = std::mem::take(&mut self.successors[last]);
-                self.blocks[first].terminator = std::mem::take(&mut self.blocks[last].terminator);
-                self.predecessors[second].clear();
-
-                for &succ in chain.iter().skip(1) {
-                    let stmts = std::mem::take(&mut self.blocks[succ].stmts);
-                    self.blocks[first].stmts.extend(stmts);
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for b in 0..self.blocks.len() {
+            let terminator = match self.blocks[b].terminator.clone() {
+                Some(Terminator::Goto(b1)) => {
+                    let t1 = self.jump_target(b1);
+                    Terminator::Goto(t1)
+                }
+                Some(Terminator::ConditionalGoto(cond, b1, b2)) => {
+                    let t1 = self.jump_target(b1);
+                    let t2 = self.jump_target(b2);
+                    if t1 == t2 {
+                        Terminator::Goto(t1)
+                    } else {
+                        Terminator::ConditionalGoto(cond, t1, t2)
+                    }
+                }
+                Some(Terminator::SwitchInt(discr, targets, otherwise)) => {
+                    let targets = targets
+                        .into_iter()
+                        .map(|(v, b1)| (v, self.jump_target(b1)))
+                        .collect();
+                    Terminator::SwitchInt(discr, targets, self.jump_target(otherwise))
                 }
+                _ => continue,
+            };
+            if !same_terminator(self.blocks[b].terminator.as_ref(), &terminator) {
+                self.blocks[b].terminator = Some(terminator);
+                changed = true;
             }
         }
+        changed
+    }
+
+    fn merge_chains(&mut self) -> bool {
+        let mut touched = vec![false; self.blocks.len()];
+        let mut changed = false;
+        for first in 0..self.blocks.len() {
+            let Some(Terminator::Goto(second)) = self.blocks[first].terminator else {
+                continue;
+            };
+            if second == first
+                || second == 0
+                || touched[first]
+                || touched[second]
+                || self.predecessors[second] != [first]
+            {
+                continue;
+            }
+            touched[first] = true;
+            touched[second] = true;
+            let stmts = std::mem::take(&mut self.blocks[second].stmts);
+            self.blocks[first].stmts.extend(stmts);
+            self.blocks[first].terminator = std::mem::take(&mut self.blocks[second].terminator);
+            changed = true;
+        }
+        changed
     }
 
     pub fn with_merge_blocks(mut self) -> Self {
@@ -48,3 +107,16 @@ impl Function {
         self
     }
 }
+
+fn same_terminator(t1: Option<&Terminator>, t2: &Terminator) -> bool {
+    match (t1, t2) {
+        (Some(Terminator::Goto(b1)), Terminator::Goto(b2)) => b1 == b2,
+        (Some(Terminator::ConditionalGoto(_, b1, b2)), Terminator::ConditionalGoto(_, b3, b4)) => {
+            b1 == b3 && b2 == b4
+        }
+        (Some(Terminator::SwitchInt(_, ts1, o1)), Terminator::SwitchInt(_, ts2, o2)) => {
+            ts1 == ts2 && o1 == o2
+        }
+        _ => false,
+    }
+}
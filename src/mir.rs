@@ -1,6 +1,9 @@
+use crate::ast::IntTy;
+use crate::ast::Lifetime;
 use crate::ast::Local;
 use crate::ast::Place;
 use crate::ast::Type;
+use crate::loops::LoopForest;
 use crate::set::Set;
 
 pub type Name = String;
@@ -9,6 +12,8 @@ pub type BlockId = usize;
 #[derive(Debug, Clone)]
 pub struct Function {
     pub id: String,
+    pub lifetimes: Vec<Lifetime>,
+    pub type_params: Vec<Name>,
     pub params: Vec<Local>,
     pub locals: Vec<Local>,
     pub ty: Type,
@@ -19,6 +24,7 @@ pub struct Function {
     pub postorder: Vec<BlockId>,
     pub preorder: Vec<BlockId>,
     pub reverse_postorder_number: Vec<BlockId>,
+    pub loops: LoopForest,
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +69,11 @@ pub enum Operation {
         func: Operand,
         args: Vec<Operand>,
     },
+    // Makes a place hold the given variant of its enum, with an uninitialized payload.
+    SetDiscriminant(Place, usize),
+    // Drops the initialized parts of the value of a place, freeing the boxes it owns, and leaves
+    // the place uninitialized. Inserted by drop elaboration before the storage of a local ends.
+    Drop(Place),
     Noop,
 }
 
@@ -71,12 +82,17 @@ pub enum Terminator {
     Return,
     Goto(BlockId),
     ConditionalGoto(Operand, BlockId, BlockId),
+    // Jumps to the block of the first value equal to the operand, or to the last block if none
+    // is.
+    SwitchInt(Operand, Vec<(i32, BlockId)>, BlockId),
 }
 
 #[derive(Debug, Clone)]
 pub enum Rvalue {
     Use(Operand),
     Ref { mutable: bool, place: Place },
+    // Reads the discriminant of an enum as an `i32`.
+    Discriminant(Place),
 }
 
 #[derive(Debug, Clone)]
@@ -93,4 +109,23 @@ pub enum Constant {
     Bool(bool),
     String(String),
     Unit,
+    // An integer of a type other than i32. Use `Constant::int` to construct one.
+    Integer(IntTy, i128),
+}
+
+impl Constant {
+    pub fn int(t: IntTy, i: i128) -> Constant {
+        match t {
+            IntTy::I32 => Constant::Int(i as i32),
+            t => Constant::Integer(t, i),
+        }
+    }
+
+    pub fn as_int(&self) -> Option<(IntTy, i128)> {
+        match self {
+            Constant::Int(i) => Some((IntTy::I32, *i as i128)),
+            Constant::Integer(t, i) => Some((*t, *i)),
+            _ => None,
+        }
+    }
 }
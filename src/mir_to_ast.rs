@@ -1,8 +1,12 @@
 use std::rc::Rc;
 
 use crate::ast;
+use crate::ast::ArithOp;
 use crate::ast::Block;
 use crate::ast::Expr;
+use crate::ast::Pattern;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
 use crate::ast::Stmt;
 use crate::ast::Type;
 use crate::mir;
@@ -15,25 +19,43 @@ use crate::mir::Terminator;
 
 impl Function {
     fn do_tree(&self, b: BlockId, loops: &mut Vec<BlockId>) -> Block {
-        let merge_nodes = self.domtree[b]
-            .clone()
-            .into_iter()
-            .filter(|&b1| self.is_merge_node(b1))
-            .collect::<Vec<_>>();
-
-        if self.is_loop_header(b) {
+        if self.loops.is_header(b) {
             loops.push(b);
-            let block = self.node_within(b, merge_nodes, loops);
+            let block = self.node_within(b, self.merge_nodes(b, loops), loops);
             loops.pop();
-            Block {
-                stmts: vec![Stmt::Expr(Expr::Loop(Type::Unit, Some(b), Rc::new(block)))],
-                expr: None,
+            let mut stmts = vec![Stmt::Expr(Expr::Loop(Type::Unit, Some(b), Rc::new(block)))];
+            // The breaks out of the loop continue after it. Exits that also leave an enclosing
+            // loop follow that loop instead, and those that are not dominated by the header
+            // follow the code of the node that dominates them.
+            let exits = self.loops.loop_of(b).unwrap().exits.iter().filter(|&&x| {
+                self.blocks[x].dom.contains(&b) && loops.iter().all(|&h| self.in_loop(h, x))
+            });
+            match exits.collect::<Vec<_>>().as_slice() {
+                [] => {}
+                [x] => stmts.extend(self.do_tree(**x, loops).stmts),
+                _ => panic!("Cannot decompile a loop with several exits at {}", b),
             }
+            Block { stmts, expr: None }
         } else {
-            self.node_within(b, merge_nodes, loops)
+            self.node_within(b, self.merge_nodes(b, loops), loops)
         }
     }
 
+    // Returns the merge nodes that follow the code of `b`, which excludes those that are only
+    // reached by leaving a loop.
+    fn merge_nodes(&self, b: BlockId, loops: &[BlockId]) -> Vec<BlockId> {
+        self.domtree[b]
+            .iter()
+            .copied()
+            .filter(|&b1| self.is_merge_node(b1) && loops.iter().all(|&h| self.in_loop(h, b1)))
+            .collect()
+    }
+
+    // Whether a block is in the loop with header `h`, whose innermost loop is its own.
+    fn in_loop(&self, h: BlockId, b: BlockId) -> bool {
+        self.loops.loop_of(h).unwrap().body.contains(&b)
+    }
+
     fn node_within(
         &self,
         b: BlockId,
@@ -41,15 +63,49 @@ impl Function {
         loops: &mut Vec<BlockId>,
     ) -> Block {
         if let Some(merge_node) = merge_nodes.pop() {
-            let mut block1 = self.do_tree(merge_node, loops);
-            let block2 = self.node_within(b, merge_nodes, loops);
+            // The merge node follows the code of the node that dominates it.
+            let mut block1 = self.node_within(b, merge_nodes, loops);
+            let block2 = self.do_tree(merge_node, loops);
             block1.stmts.extend(block2.stmts);
             block1
         } else {
             let mut stmts = Vec::new();
 
-            for s in &self.blocks[b].stmts {
+            let mut ss = self.blocks[b].stmts.iter();
+            while let Some(s) = ss.next() {
                 match &s.op {
+                    // Reads of the discriminant are folded into the match of the switch.
+                    Operation::Assign(_, Rvalue::Discriminant(_)) => {}
+                    // The payload is written right after the discriminant is set, and together
+                    // they become a variant expression.
+                    Operation::SetDiscriminant(place, d) => {
+                        let Type::Enum(e) = place.ty() else {
+                            unreachable!()
+                        };
+                        let (v, Type::Tuple(ts)) = &e.variants[*d] else {
+                            unreachable!()
+                        };
+                        let es = (0..ts.len())
+                            .map(|i| match ss.next().map(|s| &s.op) {
+                                Some(Operation::Assign(dest, Rvalue::Use(op)))
+                                    if dest.local == place.local
+                                        && dest.elems[place.elems.len()..]
+                                            == [
+                                                PlaceElem::Downcast(v.clone()),
+                                                PlaceElem::TupleIndex(i),
+                                            ] =>
+                                {
+                                    self.operand_to_expr(op)
+                                }
+                                _ => panic!("Cannot decompile a partially initialized {}", e.id),
+                            })
+                            .collect();
+                        stmts.push(Stmt::Expr(Expr::Assign(
+                            Type::Unit,
+                            place.clone(),
+                            Rc::new(Expr::Variant(place.ty().clone(), v.clone(), es)),
+                        )));
+                    }
                     Operation::Assign(place, rvalue) => {
                         let rhs_expr = match rvalue {
                             Rvalue::Use(op) => self.operand_to_expr(op),
@@ -60,6 +116,7 @@ impl Function {
                                     Expr::Ref(place.ty().clone(), place.clone())
                                 }
                             }
+                            Rvalue::Discriminant(_) => unreachable!(),
                         };
                         stmts.push(Stmt::Expr(Expr::Assign(
                             Type::Unit,
@@ -70,7 +127,8 @@ impl Function {
                     Operation::StorageLive(l) => {
                         stmts.push(Stmt::Let(l.clone(), None));
                     }
-                    Operation::StorageDead(_) => {}
+                    // Like storage, drops are implicit at the end of a scope.
+                    Operation::StorageDead(_) | Operation::Drop(_) => {}
                     Operation::Call { dest, func, args } => {
                         let Operand::Function(func_name) = func else {
                             unreachable!()
@@ -86,18 +144,49 @@ impl Function {
                                     Rc::new(Expr::Print(Type::Unit, Rc::new(arg_exprs[0].clone()))),
                                 )));
                             }
-                            "add" => {
+                            "add" | "sub" | "mul" => {
                                 stmts.push(Stmt::Expr(Expr::Assign(
                                     Type::Unit,
                                     dest.clone(),
-                                    Rc::new(Expr::Add(
-                                        Type::Int,
+                                    Rc::new(Expr::Arith(
+                                        dest.ty().clone(),
+                                        ArithOp::from_name(func_name).unwrap(),
                                         Rc::new(arg_exprs[0].clone()),
                                         Rc::new(arg_exprs[1].clone()),
                                     )),
                                 )));
                             }
-                            _ => todo!(),
+                            "cast" => {
+                                stmts.push(Stmt::Expr(Expr::Assign(
+                                    Type::Unit,
+                                    dest.clone(),
+                                    Rc::new(Expr::Cast(
+                                        dest.ty().clone(),
+                                        Rc::new(arg_exprs[0].clone()),
+                                    )),
+                                )));
+                            }
+                            "box" => {
+                                stmts.push(Stmt::Expr(Expr::Assign(
+                                    Type::Unit,
+                                    dest.clone(),
+                                    Rc::new(Expr::Box(
+                                        dest.ty().clone(),
+                                        Rc::new(arg_exprs[0].clone()),
+                                    )),
+                                )));
+                            }
+                            _ => {
+                                stmts.push(Stmt::Expr(Expr::Assign(
+                                    Type::Unit,
+                                    dest.clone(),
+                                    Rc::new(Expr::Call(
+                                        dest.ty().clone(),
+                                        func_name.clone(),
+                                        arg_exprs,
+                                    )),
+                                )));
+                            }
                         }
                     }
                     Operation::Noop => {}
@@ -127,43 +216,77 @@ impl Function {
                         Rc::new(else_block),
                     )));
                 }
+                Terminator::SwitchInt(discr, targets, otherwise) => {
+                    let place = self.discriminant_of(&discr);
+                    let Type::Enum(e) = place.ty() else {
+                        unreachable!()
+                    };
+                    let mut arms = targets
+                        .iter()
+                        .map(|(d, t)| {
+                            let (v, Type::Tuple(ts)) = &e.variants[*d as usize] else {
+                                unreachable!()
+                            };
+                            ast::Arm {
+                                pattern: Pattern::Variant(
+                                    e.id.clone(),
+                                    v.clone(),
+                                    vec![None; ts.len()],
+                                ),
+                                block: Rc::new(self.do_branch(b, *t, loops)),
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    arms.push(ast::Arm {
+                        pattern: Pattern::Wildcard,
+                        block: Rc::new(self.do_branch(b, otherwise, loops)),
+                    });
+                    stmts.push(Stmt::Expr(Expr::Match(Type::Unit, place, arms)));
+                }
             };
 
             Block { stmts, expr: None }
         }
     }
 
-    fn is_loop_header(&self, b: BlockId) -> bool {
-        self.predecessors[b]
+    // Returns the place whose discriminant the operand of a switch holds.
+    fn discriminant_of(&self, discr: &Operand) -> Place {
+        let (Operand::Copy(p) | Operand::Move(p)) = discr else {
+            panic!("Cannot decompile a switch on {:?}", discr)
+        };
+        self.blocks
             .iter()
-            .any(|&pred| self.is_backward_edge(pred, b))
+            .flat_map(|b| &b.stmts)
+            .find_map(|s| match &s.op {
+                Operation::Assign(dest, Rvalue::Discriminant(place)) if dest == p => {
+                    Some(place.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("Cannot decompile a switch on {}", p))
     }
 
     fn is_merge_node(&self, b: BlockId) -> bool {
         self.predecessors[b].len() > 1
     }
 
-    fn is_backward_edge(&self, source: BlockId, target: BlockId) -> bool {
-        self.reverse_postorder_number[target] <= self.reverse_postorder_number[source]
-    }
-
     fn do_branch(&self, source: BlockId, target: BlockId, loops: &mut Vec<BlockId>) -> Block {
-        if self.is_backward_edge(source, target) {
+        if self.loops.is_back_edge(source, target) {
             Block {
                 stmts: vec![Stmt::Expr(Expr::Continue(Type::Unit, Some(target)))],
                 expr: None,
             }
+        } else if let Some(&h) = loops.iter().find(|&&h| !self.in_loop(h, target)) {
+            // Leaving the outermost loop that does not contain the target also leaves the loops
+            // within it.
+            Block {
+                stmts: vec![Stmt::Expr(Expr::Break(Type::Unit, Some(h), None))],
+                expr: None,
+            }
         } else if self.is_merge_node(target) {
-            if loops.contains(&target) {
-                Block {
-                    stmts: vec![Stmt::Expr(Expr::Break(Type::Unit, Some(target)))],
-                    expr: None,
-                }
-            } else {
-                Block {
-                    stmts: Vec::new(),
-                    expr: None,
-                }
+            Block {
+                stmts: Vec::new(),
+                expr: None,
             }
         } else {
             self.do_tree(target, loops)
@@ -173,7 +296,8 @@ impl Function {
     fn operand_to_expr(&self, op: &Operand) -> Expr {
         match op {
             Operand::Constant(c) => match c {
-                mir::Constant::Int(i) => Expr::Int(Type::Int, *i),
+                mir::Constant::Int(i) => Expr::Int(Type::Int, *i as i128),
+                mir::Constant::Integer(t, i) => Expr::Int(Type::Integer(*t), *i),
                 mir::Constant::Bool(b) => Expr::Bool(Type::Bool, *b),
                 mir::Constant::String(s) => Expr::String(Type::String, s.clone()),
                 mir::Constant::Unit => Expr::Unit(Type::Unit),
@@ -185,15 +309,36 @@ impl Function {
 }
 
 impl Function {
-    pub fn into_ast(self) -> ast::Function {
+    // The analyses of the CFG are computed here, since a pass may have rewired edges without
+    // changing the number of blocks, e.g., by folding a conditional jump. Blocks that nothing jumps
+    // to, such as those that follow a `return` when lowering, would be left out of the tree, so
+    // they are removed first.
+    pub fn into_ast(mut self) -> ast::Function {
+        self.remove_unreachable();
+        self.compute_cfg();
+        // Locals without storage statements, such as the return local and drop flags, are
+        // declared up front.
+        let has_storage = |l: &ast::Local| {
+            self.blocks
+                .iter()
+                .flat_map(|b| &b.stmts)
+                .any(|s| matches!(&s.op, Operation::StorageLive(l1) if l1.id == l.id))
+        };
         let mut block = Block {
-            stmts: vec![Stmt::Let(self.locals[0].clone(), None)],
+            stmts: self
+                .locals
+                .iter()
+                .filter(|l| !has_storage(l))
+                .map(|l| Stmt::Let(l.clone(), None))
+                .collect(),
             expr: None,
         };
         let mut env = Vec::new();
         block.stmts.extend(self.do_tree(0, &mut env).stmts);
         ast::Function {
             id: self.id.clone(),
+            lifetimes: self.lifetimes.clone(),
+            type_params: self.type_params.clone(),
             params: self.params.clone(),
             ty: self.ty.clone(),
             block,
@@ -0,0 +1,696 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::ast::ArithOp;
+use crate::ast::IntTy;
+use crate::ast::Local;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Type;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+// Support code which every generated file starts with. Arithmetic traps on overflow and indexing
+// out of bounds like in the interpreter.
+const PRELUDE: &str = r#"#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef unsigned char unit;
+
+static int32_t check_i32(int64_t c) {
+    if (c < INT32_MIN || c > INT32_MAX) {
+        fprintf(stderr, "overflow\n");
+        exit(101);
+    }
+    return (int32_t)c;
+}
+
+static int32_t add(int32_t a, int32_t b) {
+    return check_i32((int64_t)a + (int64_t)b);
+}
+
+static int32_t sub(int32_t a, int32_t b) {
+    return check_i32((int64_t)a - (int64_t)b);
+}
+
+static int32_t mul(int32_t a, int32_t b) {
+    return check_i32((int64_t)a * (int64_t)b);
+}
+
+static uint64_t check_index(uint64_t i, uint64_t n) {
+    if (i >= n) {
+        fprintf(stderr, "index out of bounds\n");
+        exit(101);
+    }
+    return i;
+}
+
+static uint64_t subslice_len(uint64_t from, uint64_t to, uint64_t n) {
+    if (from > to || to > n) {
+        fprintf(stderr, "range out of bounds\n");
+        exit(101);
+    }
+    return to - from;
+}
+"#;
+
+// Emits C for a MIR function. Every type is lowered as follows:
+// * `i32`, `bool` and `()` become `int32_t`, `bool` and `unit`. Other integer types become the
+//   integer types of `stdint.h` of their width, where `usize` is 64 bits like in the interpreter.
+// * `String` becomes `const char *`.
+// * Tuples become structs with fields `_0`, `_1`, ...
+// * Structs become structs named `struct_<id>` with fields `f_<name>`.
+// * Arrays become structs named `array_<mangle>` with a C array `a`, so that they can be assigned.
+// * Enums become structs named `enum_<id>` with a discriminant `tag` and a union `u` of their
+//   payloads with fields `v_<variant>`.
+// * References become pointers, except references to slices, which become structs named
+//   `slice_<mangle>` with a pointer `ptr` to the first element and the number of elements `len`.
+// * Boxes become pointers to memory from `malloc`.
+//
+// Values which own boxes are zeroed when they are moved out of or dropped, so that a drop of a
+// value that does not own its boxes anymore frees nothing. Locals that own boxes start out zeroed
+// for the same reason.
+struct Generator {
+    // Declarations of structs, print and drop functions, in dependency order.
+    decls: String,
+    declared: HashSet<String>,
+    body: String,
+}
+
+impl Function {
+    // Returns a self-contained C file which defines the function.
+    pub fn to_c(&self) -> String {
+        let mut g = Generator {
+            decls: String::new(),
+            declared: HashSet::new(),
+            body: String::new(),
+        };
+        g.function(self);
+        format!("{}{}{}", PRELUDE, g.decls, g.body)
+    }
+
+    // Like `to_c`, but also defines a `main` that calls the function with the given arguments.
+    pub fn to_c_with_main(&self, args: &[Constant]) -> String {
+        let args = args.iter().map(constant).collect::<Vec<_>>().join(", ");
+        format!(
+            "{}\nint main(void) {{\n    {}({});\n    return 0;\n}}\n",
+            self.to_c(),
+            function_name(&self.id),
+            args
+        )
+    }
+}
+
+impl Generator {
+    fn function(&mut self, f: &Function) {
+        let ret = self.ty(&f.ty);
+        let params = f
+            .params
+            .iter()
+            .map(|p| format!("{} {}", self.ty(&p.ty), local(p)))
+            .collect::<Vec<_>>();
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        let mut out = String::new();
+        writeln!(out, "\n{} {}({}) {{", ret, function_name(&f.id), params).unwrap();
+        for l in &f.locals {
+            if f.params.iter().any(|p| p.id == l.id) {
+                continue;
+            }
+            if l.ty.needs_drop() {
+                writeln!(out, "    {} {} = {{0}};", self.ty(&l.ty), local(l)).unwrap();
+            } else {
+                writeln!(out, "    {} {};", self.ty(&l.ty), local(l)).unwrap();
+            }
+        }
+        for block in &f.blocks {
+            writeln!(out, "bb{}:;", block.id).unwrap();
+            for stmt in &block.stmts {
+                self.operation(&mut out, &stmt.op);
+                for p in moved(&stmt.op) {
+                    if p.ty().needs_drop() {
+                        writeln!(out, "    memset(&{}, 0, sizeof {});", place(p), place(p))
+                            .unwrap();
+                    }
+                }
+            }
+            match &block.terminator {
+                Some(Terminator::Return) => {
+                    writeln!(out, "    return {};", local(&f.locals[0])).unwrap()
+                }
+                Some(Terminator::Goto(b)) => writeln!(out, "    goto bb{};", b).unwrap(),
+                Some(Terminator::ConditionalGoto(cond, b0, b1)) => writeln!(
+                    out,
+                    "    if ({}) goto bb{}; else goto bb{};",
+                    operand(cond),
+                    b0,
+                    b1
+                )
+                .unwrap(),
+                Some(Terminator::SwitchInt(discr, targets, otherwise)) => {
+                    writeln!(out, "    switch ({}) {{", operand(discr)).unwrap();
+                    for (v, b) in targets {
+                        writeln!(out, "    case {}: goto bb{};", v, b).unwrap();
+                    }
+                    writeln!(out, "    default: goto bb{};", otherwise).unwrap();
+                    writeln!(out, "    }}").unwrap();
+                }
+                None => writeln!(out, "    abort();").unwrap(),
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        self.body.push_str(&out);
+    }
+
+    fn operation(&mut self, out: &mut String, op: &Operation) {
+        match op {
+            Operation::Assign(dest, Rvalue::Use(o)) => {
+                writeln!(out, "    {} = {};", place(dest), operand(o)).unwrap();
+            }
+            Operation::Assign(dest, Rvalue::Ref { place: p, .. }) if slice(p).1.is_some() => {
+                let ty = self.ty(&dest.ty());
+                let (_, window) = slice(p);
+                let (ptr, len) = window.unwrap();
+                writeln!(out, "    {} = ({}){{{}, {}}};", place(dest), ty, ptr, len).unwrap();
+            }
+            Operation::Assign(dest, Rvalue::Ref { place: p, .. }) => {
+                writeln!(out, "    {} = &{};", place(dest), place(p)).unwrap();
+            }
+            Operation::Assign(dest, Rvalue::Discriminant(p)) => {
+                writeln!(out, "    {} = {}.tag;", place(dest), place(p)).unwrap();
+            }
+            Operation::SetDiscriminant(dest, d) => {
+                writeln!(out, "    {}.tag = {};", place(dest), d).unwrap();
+            }
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if name == "print" && args.len() == 1 => {
+                // Like in the interpreter, a reference is printed as its referent.
+                let (ty, arg) = match operand_ty(&args[0]) {
+                    Type::Ref(_, ty) | Type::RefMut(_, ty) => {
+                        (ty.as_ref().clone(), format!("*{}", operand(&args[0])))
+                    }
+                    ty => (ty, operand(&args[0])),
+                };
+                let print = self.print(&ty);
+                writeln!(out, "    {}({});", print, arg).unwrap();
+                writeln!(out, "    printf(\"\\n\");").unwrap();
+                writeln!(out, "    {} = 0;", place(dest)).unwrap();
+            }
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if name == "box" && args.len() == 1 => {
+                let dest = place(dest);
+                writeln!(out, "    {} = malloc(sizeof *{});", dest, dest).unwrap();
+                writeln!(out, "    *{} = {};", dest, operand(&args[0])).unwrap();
+            }
+            // The prelude only does arithmetic on i32s, other integers are checked by the compiler
+            // builtins.
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if ArithOp::from_name(name).is_some() && matches!(dest.ty(), Type::Integer(_)) => {
+                writeln!(
+                    out,
+                    "    if (__builtin_{}_overflow({}, {}, &{})) {{",
+                    name,
+                    operand(&args[0]),
+                    operand(&args[1]),
+                    place(dest)
+                )
+                .unwrap();
+                writeln!(out, "        fprintf(stderr, \"overflow\\n\");").unwrap();
+                writeln!(out, "        exit(101);").unwrap();
+                writeln!(out, "    }}").unwrap();
+            }
+            // Conversions to unsigned types wrap in C, and those to signed types do so in GCC and
+            // Clang, like `as`.
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if name == "cast" && args.len() == 1 => {
+                let ty = self.ty(&dest.ty());
+                writeln!(out, "    {} = ({}){};", place(dest), ty, operand(&args[0])).unwrap();
+            }
+            Operation::Call { dest, func, args } => {
+                let func = match func {
+                    Operand::Function(name) if ArithOp::from_name(name).is_some() => name.clone(),
+                    Operand::Function(name) => function_name(name),
+                    o => operand(o),
+                };
+                let args = args.iter().map(operand).collect::<Vec<_>>().join(", ");
+                writeln!(out, "    {} = {}({});", place(dest), func, args).unwrap();
+            }
+            Operation::Drop(p) => {
+                if p.ty().needs_drop() {
+                    let drop = self.drop(&p.ty());
+                    writeln!(out, "    {}(&{});", drop, place(p)).unwrap();
+                }
+            }
+            Operation::StorageLive(_) | Operation::StorageDead(_) | Operation::Noop => {}
+        }
+    }
+
+    // Returns the name of a function that frees the boxes owned by a value of a type and zeroes
+    // them, declaring it if needed.
+    fn drop(&mut self, ty: &Type) -> String {
+        let name = format!("drop_{}", mangle(ty));
+        if self.declared.contains(&name) {
+            return name;
+        }
+        let c = self.ty(ty);
+        let mut body = String::new();
+        match ty {
+            Type::Box(t) => {
+                body.push_str("    if (*v) {\n");
+                if t.needs_drop() {
+                    let drop = self.drop(t);
+                    writeln!(body, "        {}(*v);", drop).unwrap();
+                }
+                body.push_str("        free(*v);\n        *v = NULL;\n    }\n");
+            }
+            Type::Tuple(tys) => {
+                for (i, t) in tys.iter().enumerate().filter(|(_, t)| t.needs_drop()) {
+                    let drop = self.drop(t);
+                    writeln!(body, "    {}(&v->_{});", drop, i).unwrap();
+                }
+            }
+            // A closure is the tuple of its captures.
+            Type::Closure(c) => return self.drop(&Type::Tuple(c.captures.clone())),
+            Type::Struct(st) => {
+                for (f, t) in st.fields.iter().filter(|(_, t)| t.needs_drop()) {
+                    let drop = self.drop(t);
+                    writeln!(body, "    {}(&v->f_{});", drop, f).unwrap();
+                }
+            }
+            Type::Array(t, n) => {
+                let drop = self.drop(t);
+                writeln!(
+                    body,
+                    "    for (uint64_t i = 0; i < {}; i++) {}(&v->a[i]);",
+                    n, drop
+                )
+                .unwrap();
+            }
+            Type::Enum(e) => {
+                body.push_str("    switch (v->tag) {\n");
+                for (d, (v, t)) in e.variants.iter().enumerate() {
+                    if t.needs_drop() {
+                        let drop = self.drop(t);
+                        writeln!(body, "    case {}: {}(&v->u.v_{}); break;", d, drop, v).unwrap();
+                    }
+                }
+                body.push_str("    }\n");
+            }
+            // Strings are literals, which are never freed.
+            Type::String => {}
+            _ => unreachable!("{:?} does not need drop", ty),
+        }
+        self.declared.insert(name.clone());
+        writeln!(
+            self.decls,
+            "\nstatic void {}({} *v) {{\n{}}}",
+            name, c, body
+        )
+        .unwrap();
+        name
+    }
+
+    // Returns the C type of a type, declaring its struct if it is a tuple or struct.
+    fn ty(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Int => "int32_t".to_string(),
+            Type::Integer(t) => int_ty(*t).to_string(),
+            Type::Bool => "bool".to_string(),
+            // A value of the never type is never produced, but its local needs a type.
+            Type::Unit | Type::Never => "unit".to_string(),
+            Type::String => "const char *".to_string(),
+            Type::Tuple(tys) if tys.is_empty() => "unit".to_string(),
+            Type::Closure(c) => self.ty(&Type::Tuple(c.captures.clone())),
+            Type::Tuple(tys) => {
+                let name = format!("tuple_{}", mangle(ty));
+                if self.declared.insert(name.clone()) {
+                    let fields = tys
+                        .iter()
+                        .enumerate()
+                        .map(|(i, t)| format!("    {} _{};\n", self.ty(t), i))
+                        .collect::<String>();
+                    writeln!(self.decls, "\ntypedef struct {{\n{}}} {};", fields, name).unwrap();
+                }
+                name
+            }
+            Type::Struct(st) => {
+                let name = format!("struct_{}", st.id);
+                if self.declared.insert(name.clone()) {
+                    let fields = st
+                        .fields
+                        .iter()
+                        .map(|(f, t)| format!("    {} f_{};\n", self.ty(t), f))
+                        .collect::<String>();
+                    writeln!(self.decls, "\ntypedef struct {{\n{}}} {};", fields, name).unwrap();
+                }
+                name
+            }
+            Type::Array(t, n) => {
+                let name = format!("array_{}", mangle(ty));
+                if self.declared.insert(name.clone()) {
+                    let t = self.ty(t);
+                    writeln!(
+                        self.decls,
+                        "\ntypedef struct {{\n    {} a[{}];\n}} {};",
+                        t, n, name
+                    )
+                    .unwrap();
+                }
+                name
+            }
+            Type::Enum(e) => {
+                let name = format!("enum_{}", e.id);
+                if self.declared.insert(name.clone()) {
+                    let variants = e
+                        .variants
+                        .iter()
+                        .map(|(v, t)| format!("        {} v_{};\n", self.ty(t), v))
+                        .collect::<String>();
+                    writeln!(
+                        self.decls,
+                        "\ntypedef struct {{\n    int32_t tag;\n    union {{\n{}    }} u;\n}} {};",
+                        variants, name
+                    )
+                    .unwrap();
+                }
+                name
+            }
+            Type::Ref(_, ty) | Type::RefMut(_, ty) if matches!(ty.as_ref(), Type::Slice(_)) => {
+                self.ty(ty)
+            }
+            Type::Ref(_, ty) | Type::RefMut(_, ty) | Type::Box(ty) => format!("{} *", self.ty(ty)),
+            // A slice is unsized, so this is the type of references to it.
+            Type::Slice(t) => {
+                let name = format!("slice_{}", mangle(t));
+                if self.declared.insert(name.clone()) {
+                    let t = self.ty(t);
+                    writeln!(
+                        self.decls,
+                        "\ntypedef struct {{\n    {} *ptr;\n    uint64_t len;\n}} {};",
+                        t, name
+                    )
+                    .unwrap();
+                }
+                name
+            }
+            Type::Unknown | Type::Var(_) => {
+                panic!("Cannot generate C for a value of unknown type")
+            }
+            Type::Param(id) => panic!("Cannot generate C for a value of type parameter {}", id),
+            Type::Cell(..) | Type::Guard(..) | Type::GuardMut(..) => {
+                panic!("Cannot generate C for a value of type {}", ty)
+            }
+        }
+    }
+
+    // Returns the name of a function that prints a value of a type without a trailing newline,
+    // declaring it if needed.
+    fn print(&mut self, ty: &Type) -> String {
+        let name = format!("print_{}", mangle(ty));
+        if self.declared.contains(&name) {
+            return name;
+        }
+        let c = self.ty(ty);
+        let body = match ty {
+            Type::Int => "    printf(\"%d\", v);\n".to_string(),
+            Type::Integer(t) if t.is_signed() => {
+                "    printf(\"%lld\", (long long)v);\n".to_string()
+            }
+            Type::Integer(_) => "    printf(\"%llu\", (unsigned long long)v);\n".to_string(),
+            Type::Bool => "    printf(\"%s\", v ? \"true\" : \"false\");\n".to_string(),
+            Type::Unit => "    (void)v;\n    printf(\"()\");\n".to_string(),
+            Type::String => "    printf(\"%s\", v);\n".to_string(),
+            Type::Tuple(tys) => {
+                let mut body = "    printf(\"(\");\n".to_string();
+                for (i, t) in tys.iter().enumerate() {
+                    if i > 0 {
+                        body.push_str("    printf(\", \");\n");
+                    }
+                    let print = self.print(t);
+                    writeln!(body, "    {}(v._{});", print, i).unwrap();
+                }
+                body.push_str("    printf(\")\");\n");
+                body
+            }
+            // The interpreter stores structs as tuples, so they are printed like tuples.
+            Type::Struct(st) => {
+                let mut body = "    printf(\"(\");\n".to_string();
+                for (i, (f, t)) in st.fields.iter().enumerate() {
+                    if i > 0 {
+                        body.push_str("    printf(\", \");\n");
+                    }
+                    let print = self.print(t);
+                    writeln!(body, "    {}(v.f_{});", print, f).unwrap();
+                }
+                body.push_str("    printf(\")\");\n");
+                body
+            }
+            // The interpreter stores arrays as tuples, so they are printed like tuples.
+            Type::Array(t, n) => {
+                let print = self.print(t);
+                let mut body = "    printf(\"(\");\n".to_string();
+                writeln!(body, "    for (uint64_t i = 0; i < {}; i++) {{", n).unwrap();
+                body.push_str("        if (i > 0) printf(\", \");\n");
+                writeln!(body, "        {}(v.a[i]);", print).unwrap();
+                body.push_str("    }\n    printf(\")\");\n");
+                body
+            }
+            // Like the interpreter, the discriminant is printed before the payload.
+            Type::Enum(e) => {
+                let mut body = "    switch (v.tag) {\n".to_string();
+                for (d, (v, t)) in e.variants.iter().enumerate() {
+                    let print = self.print(t);
+                    writeln!(
+                        body,
+                        "    case {}: printf(\"#{}\"); {}(v.u.v_{}); break;",
+                        d, d, print, v
+                    )
+                    .unwrap();
+                }
+                body.push_str("    }\n");
+                body
+            }
+            // The interpreter prints references nested in values by allocation, which has no
+            // counterpart in C.
+            Type::Ref(_, t) | Type::RefMut(_, t) if matches!(t.as_ref(), Type::Slice(_)) => {
+                "    printf(\"&%p\", (void *)v.ptr);\n".to_string()
+            }
+            Type::Ref(..) | Type::RefMut(..) => "    printf(\"&%p\", (void *)v);\n".to_string(),
+            Type::Box(..) => "    printf(\"box %p\", (void *)v);\n".to_string(),
+            Type::Closure(c) => return self.print(&Type::Tuple(c.captures.clone())),
+            Type::Unknown | Type::Param(_) | Type::Var(_) | Type::Never | Type::Slice(_) => {
+                unreachable!()
+            }
+            Type::Cell(..) | Type::Guard(..) | Type::GuardMut(..) => unreachable!(),
+        };
+        self.declared.insert(name.clone());
+        writeln!(self.decls, "\nstatic void {}({} v) {{\n{}}}", name, c, body).unwrap();
+        name
+    }
+}
+
+fn int_ty(t: IntTy) -> &'static str {
+    match t {
+        IntTy::I8 => "int8_t",
+        IntTy::I16 => "int16_t",
+        IntTy::I32 => "int32_t",
+        IntTy::I64 => "int64_t",
+        IntTy::U8 => "uint8_t",
+        IntTy::U16 => "uint16_t",
+        IntTy::U32 => "uint32_t",
+        IntTy::U64 | IntTy::Usize => "uint64_t",
+    }
+}
+
+// Returns a name for a type which is unique and a valid C identifier.
+fn mangle(ty: &Type) -> String {
+    match ty {
+        Type::Int => "i".to_string(),
+        Type::Integer(t) => format!("I{}", t.name()),
+        Type::Bool => "b".to_string(),
+        Type::Unit => "u".to_string(),
+        Type::Never => "n".to_string(),
+        Type::String => "s".to_string(),
+        Type::Tuple(tys) if tys.is_empty() => "u".to_string(),
+        Type::Tuple(tys) => format!("T{}E", tys.iter().map(mangle).collect::<String>()),
+        Type::Closure(c) => mangle(&Type::Tuple(c.captures.clone())),
+        Type::Struct(s) => format!("S{}{}", s.id.len(), s.id),
+        Type::Enum(e) => format!("N{}{}", e.id.len(), e.id),
+        Type::Ref(_, ty) | Type::RefMut(_, ty) => format!("R{}", mangle(ty)),
+        Type::Box(ty) => format!("B{}", mangle(ty)),
+        Type::Array(ty, n) => format!("A{}{}", n, mangle(ty)),
+        Type::Slice(ty) => format!("L{}", mangle(ty)),
+        Type::Unknown | Type::Var(_) => "x".to_string(),
+        Type::Param(id) => format!("P{}{}", id.len(), id),
+        Type::Cell(kind, ty) => format!("C{}{}", kind.name(), mangle(ty)),
+        Type::Guard(_, ty) | Type::GuardMut(_, ty) => format!("G{}", mangle(ty)),
+    }
+}
+
+// User functions are prefixed so they cannot clash with C functions, e.g., `main`. Instances of
+// generic functions, e.g., `id::<i32>`, have characters that C identifiers cannot have.
+fn function_name(id: &str) -> String {
+    let id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("fn_{}", id)
+}
+
+// Temporaries are already named `_n`. Source variables are prefixed so they cannot clash with C
+// keywords.
+fn local(l: &Local) -> String {
+    if l.id.starts_with('_') {
+        l.id.clone()
+    } else {
+        format!("v_{}", l.id)
+    }
+}
+
+fn place(p: &Place) -> String {
+    match slice(p) {
+        (s, None) => s,
+        (_, Some(_)) => panic!(
+            "Cannot generate C for the slice {} other than by reference",
+            p
+        ),
+    }
+}
+
+// Returns the C of a place, or the pointer to the first element and the number of elements if the
+// place is a slice. Indices into slices, and into arrays at runtime, are checked.
+fn slice(p: &Place) -> (String, Option<(String, String)>) {
+    let mut s = local(&p.local);
+    let mut window: Option<(String, String)> = None;
+    for (k, elem) in p.elems.iter().enumerate() {
+        let prefix = Place {
+            local: p.local.clone(),
+            elems: p.elems[..k].to_vec(),
+        };
+        // The elements that an index or subslice projects from, which are those of an array
+        // unless the place is at a slice.
+        let elements = match (window.take(), prefix.ty()) {
+            (Some(window), _) => Some(window),
+            (None, Type::Array(_, n)) => Some((format!("{}.a", s), n.to_string())),
+            (None, _) => None,
+        };
+        s = match (elem, elements) {
+            (PlaceElem::TupleIndex(i), _) => format!("{}._{}", s, i),
+            (PlaceElem::Field(f), _) => format!("{}.f_{}", s, f),
+            (PlaceElem::Downcast(v), _) => format!("{}.u.v_{}", s, v),
+            (PlaceElem::Deref, _) => match prefix.ty() {
+                Type::Ref(_, t) | Type::RefMut(_, t) if matches!(t.as_ref(), Type::Slice(_)) => {
+                    window = Some((format!("{}.ptr", s), format!("{}.len", s)));
+                    s
+                }
+                _ => format!("(*{})", s),
+            },
+            (PlaceElem::ConstantIndex(i), _) if matches!(prefix.ty(), Type::Array(..)) => {
+                format!("{}.a[{}]", s, i)
+            }
+            (PlaceElem::ConstantIndex(i), Some((ptr, len))) => {
+                format!("{}[check_index({}, {})]", ptr, i, len)
+            }
+            (PlaceElem::Index(l), Some((ptr, len))) => {
+                format!("{}[check_index({}, {})]", ptr, local(l), len)
+            }
+            (PlaceElem::Subslice { from, to }, Some((ptr, len))) => {
+                let to = to.map_or(len.clone(), |to| to.to_string());
+                window = Some((
+                    format!("{} + {}", ptr, from),
+                    format!("subslice_len({}, {}, {})", from, to, len),
+                ));
+                s
+            }
+            (elem, _) => unreachable!("{:?} of {:?}", elem, prefix.ty()),
+        };
+    }
+    (s, window)
+}
+
+fn operand(o: &Operand) -> String {
+    match o {
+        Operand::Constant(c) => constant(c),
+        Operand::Copy(p) | Operand::Move(p) => place(p),
+        Operand::Function(name) => function_name(name),
+    }
+}
+
+// Returns the places an operation moves out of.
+fn moved(op: &Operation) -> Vec<&Place> {
+    let operands = match op {
+        Operation::Assign(_, Rvalue::Use(o)) => vec![o],
+        Operation::Call { args, .. } => args.iter().collect(),
+        _ => vec![],
+    };
+    operands
+        .into_iter()
+        .filter_map(|o| match o {
+            Operand::Move(p) => Some(p),
+            _ => None,
+        })
+        .collect()
+}
+
+fn operand_ty(o: &Operand) -> Type {
+    match o {
+        Operand::Constant(Constant::Int(_)) => Type::Int,
+        Operand::Constant(Constant::Integer(t, _)) => Type::Integer(*t),
+        Operand::Constant(Constant::Bool(_)) => Type::Bool,
+        Operand::Constant(Constant::String(_)) => Type::String,
+        Operand::Constant(Constant::Unit) => Type::Unit,
+        Operand::Copy(p) | Operand::Move(p) => p.ty().clone(),
+        Operand::Function(_) => Type::Unknown,
+    }
+}
+
+fn constant(c: &Constant) -> String {
+    match c {
+        // The literal `-2147483648` is the negation of a value which does not fit in an int.
+        Constant::Int(i32::MIN) => "(-2147483647 - 1)".to_string(),
+        Constant::Int(i) => format!("{}", i),
+        // Like for i32, the minimum of i64 is written as a difference.
+        Constant::Integer(t, i) if *i == IntTy::I64.min() => {
+            format!("(({})(-9223372036854775807ll - 1))", int_ty(*t))
+        }
+        Constant::Integer(t, i) if t.is_signed() => format!("(({}){}ll)", int_ty(*t), i),
+        Constant::Integer(t, i) => format!("(({}){}ull)", int_ty(*t), i),
+        Constant::Bool(b) => format!("{}", b),
+        Constant::String(s) => {
+            let mut out = "\"".to_string();
+            for b in s.bytes() {
+                match b {
+                    b'"' => out.push_str("\\\""),
+                    b'\\' => out.push_str("\\\\"),
+                    b' '..=b'~' => out.push(b as char),
+                    _ => write!(out, "\\{:03o}", b).unwrap(),
+                }
+            }
+            out.push('"');
+            out
+        }
+        Constant::Unit => "0".to_string(),
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ast::ArithOp;
+use crate::ast::LocalId;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Type;
+use crate::mir::BlockId;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+const PAGE_SIZE: u32 = 65536;
+
+// Strings are laid out from this address, so that no string is at address 0.
+const DATA_START: u32 = 8;
+
+// Emits the WebAssembly text format for a MIR function.
+//
+// Every local lives in a frame on a shadow stack in linear memory, which grows downwards from the
+// end of memory and is pointed to by the global `$sp`. Values are laid out as follows:
+// * `i32` and `bool` take 4 bytes.
+// * `()` takes no space.
+// * A `String` is the 4-byte address of its length, which is followed by its UTF-8 bytes. String
+//   constants are stored in a data segment.
+// * A tuple is its fields one after another.
+// * A reference is the 4-byte address of its referent.
+// * An enum is its 4-byte discriminant followed by room for its largest payload.
+// * A box is the 4-byte address of its contents. Boxes are allocated upwards from the end of the
+//   data segment by bumping the global `$hp`, and are never freed.
+//
+// The function is exported under its own name and takes and returns its parameters and result as
+// one i32 for every 4 bytes. The host must provide `env.print(address, length)`, which prints a
+// string from memory followed by a newline.
+struct Generator<'a> {
+    f: &'a Function,
+    out: String,
+    indent: usize,
+    // The offset of each local in the frame.
+    offsets: HashMap<LocalId, u32>,
+    frame_size: u32,
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+}
+
+impl Function {
+    pub fn to_wat(&self) -> String {
+        // Structured control flow is recovered from the dominator tree and loops, which are only
+        // meaningful for reachable blocks.
+        let mut f = self.clone();
+        f.remove_unreachable();
+        f.compute_cfg();
+        assert!(
+            f.loops.is_reducible(),
+            "Cannot generate WebAssembly for an irreducible CFG"
+        );
+        let mut g = Generator {
+            f: &f,
+            out: String::new(),
+            indent: 1,
+            offsets: HashMap::new(),
+            frame_size: 0,
+            data: vec![],
+            strings: HashMap::new(),
+        };
+        for l in f.params.iter().chain(f.locals.iter()) {
+            if !g.offsets.contains_key(&l.id) {
+                g.offsets.insert(l.id.clone(), g.frame_size);
+                g.frame_size += size(&l.ty);
+            }
+        }
+        g.function();
+        g.module()
+    }
+}
+
+impl Generator<'_> {
+    fn module(&self) -> String {
+        let data_end = DATA_START + self.data.len() as u32;
+        let pages = data_end / PAGE_SIZE + 2;
+        let mut out = String::new();
+        writeln!(out, "(module").unwrap();
+        writeln!(
+            out,
+            "  (import \"env\" \"print\" (func $print (param i32 i32)))"
+        )
+        .unwrap();
+        writeln!(out, "  (memory (export \"memory\") {})", pages).unwrap();
+        writeln!(
+            out,
+            "  (global $sp (mut i32) (i32.const {}))",
+            pages * PAGE_SIZE
+        )
+        .unwrap();
+        writeln!(out, "  (global $hp (mut i32) (i32.const {}))", data_end).unwrap();
+        // Arithmetic traps on overflow like in the interpreter. It is done on i64s, in which the
+        // result of an operation on two i32s always fits.
+        for op in ArithOp::ALL {
+            writeln!(
+                out,
+                "  (func ${} (param $a i32) (param $b i32) (result i32)",
+                op.name()
+            )
+            .unwrap();
+            writeln!(out, "    (local $c i64)").unwrap();
+            writeln!(out, "    (local.set $c (i64.{} (i64.extend_i32_s (local.get $a)) (i64.extend_i32_s (local.get $b))))", op.name()).unwrap();
+            writeln!(
+                out,
+                "    (if (i64.ne (local.get $c) (i64.extend_i32_s (i32.wrap_i64 (local.get $c))))"
+            )
+            .unwrap();
+            writeln!(out, "      (then unreachable))").unwrap();
+            writeln!(out, "    (i32.wrap_i64 (local.get $c)))").unwrap();
+        }
+        if !self.data.is_empty() {
+            let bytes = self
+                .data
+                .iter()
+                .map(|b| format!("\\{:02x}", b))
+                .collect::<String>();
+            writeln!(out, "  (data (i32.const {}) \"{}\")", DATA_START, bytes).unwrap();
+        }
+        out.push_str(&self.out);
+        writeln!(out, ")").unwrap();
+        out
+    }
+
+    fn function(&mut self) {
+        let f = self.f;
+        let mut signature = format!("(func $fn_{} (export \"{}\")", f.id, f.id);
+        let mut params = vec![];
+        for p in &f.params {
+            for k in 0..size(&p.ty) / 4 {
+                let name = format!("$p{}", params.len());
+                write!(signature, " (param {} i32)", name).unwrap();
+                params.push((self.offsets[&p.id] + 4 * k, name));
+            }
+        }
+        for _ in 0..size(&f.ty) / 4 {
+            signature.push_str(" (result i32)");
+        }
+        self.line(&signature);
+        self.indent += 1;
+        self.line("(local $fp i32)");
+        self.line("(local $tmp i32)");
+        self.line(&format!(
+            "(local.set $fp (i32.sub (global.get $sp) (i32.const {})))",
+            self.frame_size
+        ));
+        self.line("(global.set $sp (local.get $fp))");
+        for (offset, name) in params {
+            self.line(&format!(
+                "(i32.store offset={} (local.get $fp) (local.get {}))",
+                offset, name
+            ));
+        }
+        self.do_tree(0);
+        // Every path has already returned, but the validator does not know that.
+        self.line("unreachable");
+        self.indent -= 1;
+        self.line(")");
+    }
+
+    fn do_tree(&mut self, b: BlockId) {
+        let mut merge_nodes = self.f.domtree[b]
+            .iter()
+            .copied()
+            .filter(|&b1| self.is_merge_node(b1))
+            .collect::<Vec<_>>();
+        merge_nodes.sort_by_key(|&b1| self.f.reverse_postorder_number[b1]);
+
+        if self.f.loops.is_header(b) {
+            self.line(&format!("loop $l{}", b));
+            self.indent += 1;
+            self.node_within(b, merge_nodes);
+            self.indent -= 1;
+            self.line("end");
+        } else {
+            self.node_within(b, merge_nodes);
+        }
+    }
+
+    // Emits `b` followed by the merge nodes it immediately dominates. Every merge node is placed
+    // after a `block` that is exited by branching to the merge node.
+    fn node_within(&mut self, b: BlockId, mut merge_nodes: Vec<BlockId>) {
+        if let Some(merge_node) = merge_nodes.pop() {
+            self.line(&format!("block $b{}", merge_node));
+            self.indent += 1;
+            self.node_within(b, merge_nodes);
+            self.indent -= 1;
+            self.line("end");
+            self.do_tree(merge_node);
+        } else {
+            for stmt in &self.f.blocks[b].stmts {
+                self.operation(&stmt.op);
+            }
+            match &self.f.blocks[b].terminator {
+                Some(Terminator::Return) => {
+                    let ret = Place::from(self.f.locals[0].clone());
+                    let n = size(&ret.local.ty) / 4;
+                    for leaf in self.leaves(&Operand::Copy(ret), n) {
+                        self.line(&leaf);
+                    }
+                    self.line(&format!(
+                        "(global.set $sp (i32.add (local.get $fp) (i32.const {})))",
+                        self.frame_size
+                    ));
+                    self.line("return");
+                }
+                Some(Terminator::Goto(b1)) => self.do_branch(b, *b1),
+                Some(Terminator::ConditionalGoto(cond, b1, b2)) => {
+                    let cond = self.leaves(cond, 1).remove(0);
+                    self.line(&cond);
+                    self.line("if");
+                    self.indent += 1;
+                    self.do_branch(b, *b1);
+                    self.indent -= 1;
+                    self.line("else");
+                    self.indent += 1;
+                    self.do_branch(b, *b2);
+                    self.indent -= 1;
+                    self.line("end");
+                }
+                // Becomes a chain of comparisons, ending with the otherwise branch.
+                Some(Terminator::SwitchInt(discr, targets, otherwise)) => {
+                    let discr = self.leaves(discr, 1).remove(0);
+                    for (v, b1) in targets {
+                        self.line(&format!("(i32.eq {} (i32.const {}))", discr, v));
+                        self.line("if");
+                        self.indent += 1;
+                        self.do_branch(b, *b1);
+                        self.indent -= 1;
+                        self.line("else");
+                        self.indent += 1;
+                    }
+                    self.do_branch(b, *otherwise);
+                    for _ in targets {
+                        self.indent -= 1;
+                        self.line("end");
+                    }
+                }
+                None => self.line("unreachable"),
+            }
+        }
+    }
+
+    fn is_merge_node(&self, b: BlockId) -> bool {
+        self.f.predecessors[b].len() > 1
+    }
+
+    fn do_branch(&mut self, source: BlockId, target: BlockId) {
+        if self.f.loops.is_back_edge(source, target) {
+            self.line(&format!("br $l{}", target));
+        } else if self.is_merge_node(target) {
+            self.line(&format!("br $b{}", target));
+        } else {
+            self.do_tree(target);
+        }
+    }
+
+    fn operation(&mut self, op: &Operation) {
+        match op {
+            Operation::Assign(dest, Rvalue::Use(o)) => {
+                let (base, offset) = self.address(dest);
+                let n = size(&dest.ty()) / 4;
+                for (k, leaf) in self.leaves(o, n).into_iter().enumerate() {
+                    self.line(&format!(
+                        "(i32.store offset={} {} {})",
+                        offset + 4 * k as u32,
+                        base,
+                        leaf
+                    ));
+                }
+            }
+            Operation::Assign(dest, Rvalue::Ref { place, .. }) => {
+                let (base, offset) = self.address(dest);
+                let (base1, offset1) = self.address(place);
+                self.line(&format!(
+                    "(i32.store offset={} {} (i32.add {} (i32.const {})))",
+                    offset, base, base1, offset1
+                ));
+            }
+            Operation::Assign(dest, Rvalue::Discriminant(place)) => {
+                let (base, offset) = self.address(dest);
+                let (base1, offset1) = self.address(place);
+                self.line(&format!(
+                    "(i32.store offset={} {} (i32.load offset={} {}))",
+                    offset, base, offset1, base1
+                ));
+            }
+            Operation::SetDiscriminant(dest, d) => {
+                let (base, offset) = self.address(dest);
+                self.line(&format!(
+                    "(i32.store offset={} {} (i32.const {}))",
+                    offset, base, d
+                ));
+            }
+            Operation::Call {
+                func: Operand::Function(name),
+                args,
+                ..
+            } if name == "print" && args.len() == 1 => {
+                // Like in the interpreter, a reference is printed as its referent.
+                let s = self.leaves(&args[0], 1).remove(0);
+                let s = match operand_ty(&args[0]) {
+                    Type::Ref(..) | Type::RefMut(..) => format!("(i32.load {})", s),
+                    _ => s,
+                };
+                self.line(&format!("(local.set $tmp {})", s));
+                self.line("(call $print (i32.add (local.get $tmp) (i32.const 4)) (i32.load (local.get $tmp)))");
+            }
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if ArithOp::from_name(name).is_some() && args.len() == 2 => {
+                let (base, offset) = self.address(dest);
+                let a = self.leaves(&args[0], 1).remove(0);
+                let b = self.leaves(&args[1], 1).remove(0);
+                self.line(&format!(
+                    "(i32.store offset={} {} (call ${} {} {}))",
+                    offset, base, name, a, b
+                ));
+            }
+            Operation::Call {
+                dest,
+                func: Operand::Function(name),
+                args,
+            } if name == "box" && args.len() == 1 => {
+                let n = size(&operand_ty(&args[0]));
+                let leaves = self.leaves(&args[0], n / 4);
+                self.line("(local.set $tmp (global.get $hp))");
+                self.line(&format!(
+                    "(global.set $hp (i32.add (global.get $hp) (i32.const {})))",
+                    n
+                ));
+                for (k, leaf) in leaves.into_iter().enumerate() {
+                    self.line(&format!(
+                        "(i32.store offset={} (local.get $tmp) {})",
+                        4 * k,
+                        leaf
+                    ));
+                }
+                let (base, offset) = self.address(dest);
+                self.line(&format!(
+                    "(i32.store offset={} {} (local.get $tmp))",
+                    offset, base
+                ));
+            }
+            Operation::Call { func, .. } => {
+                panic!("Cannot generate WebAssembly for a call to {:?}", func)
+            }
+            Operation::StorageLive(_)
+            | Operation::StorageDead(_)
+            | Operation::Drop(_)
+            | Operation::Noop => {}
+        }
+    }
+
+    // Returns an address as an expression and a constant offset from it.
+    fn address(&self, p: &Place) -> (String, u32) {
+        let mut base = "(local.get $fp)".to_string();
+        let mut offset = self.offsets[&p.local.id];
+        let mut ty = p.local.ty.clone();
+        for elem in &p.elems {
+            match (elem, ty) {
+                (PlaceElem::TupleIndex(i), Type::Tuple(tys)) => {
+                    offset += tys[..*i].iter().map(size).sum::<u32>();
+                    ty = tys[*i].clone();
+                }
+                (PlaceElem::Field(f), Type::Struct(s)) => {
+                    let (i, t) = s.field(f).expect("Unknown field");
+                    offset += s.fields[..i].iter().map(|(_, t)| size(t)).sum::<u32>();
+                    ty = t.clone();
+                }
+                (PlaceElem::Downcast(v), Type::Enum(e)) => {
+                    let (_, t) = e.variant(v).expect("Unknown variant");
+                    offset += 4;
+                    ty = t.clone();
+                }
+                (PlaceElem::ConstantIndex(i), Type::Array(t, _)) => {
+                    offset += size(&t) * *i as u32;
+                    ty = t.as_ref().clone();
+                }
+                (PlaceElem::Index(_), _) => {
+                    panic!("Cannot generate WebAssembly for an index that is only known at runtime")
+                }
+                (PlaceElem::Deref, Type::Ref(_, t) | Type::RefMut(_, t) | Type::Box(t)) => {
+                    base = format!("(i32.load offset={} {})", offset, base);
+                    offset = 0;
+                    ty = t.as_ref().clone();
+                }
+                (elem, ty) => panic!("Cannot project {:?} from {:?}", elem, ty),
+            }
+        }
+        (base, offset)
+    }
+
+    // Returns the `n` i32s an operand consists of.
+    fn leaves(&mut self, o: &Operand, n: u32) -> Vec<String> {
+        match o {
+            Operand::Constant(Constant::Int(i)) => vec![format!("(i32.const {})", i)],
+            Operand::Constant(Constant::Bool(b)) => vec![format!("(i32.const {})", *b as i32)],
+            Operand::Constant(Constant::String(s)) => {
+                vec![format!("(i32.const {})", self.string(s))]
+            }
+            Operand::Constant(Constant::Unit) => vec![],
+            Operand::Constant(Constant::Integer(t, _)) => {
+                panic!(
+                    "Cannot generate WebAssembly for a value of type {}",
+                    t.name()
+                )
+            }
+            Operand::Copy(p) | Operand::Move(p) => {
+                let (base, offset) = self.address(p);
+                (0..n)
+                    .map(|k| format!("(i32.load offset={} {})", offset + 4 * k, base))
+                    .collect()
+            }
+            Operand::Function(name) => panic!("Cannot use function {} as a value", name),
+        }
+    }
+
+    // Returns the address of a string constant, adding it to the data segment if needed.
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(address) = self.strings.get(s) {
+            return *address;
+        }
+        let address = DATA_START + self.data.len() as u32;
+        self.data.extend((s.len() as u32).to_le_bytes());
+        self.data.extend(s.as_bytes());
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        self.strings.insert(s.to_string(), address);
+        address
+    }
+
+    fn line(&mut self, s: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(s);
+        self.out.push('\n');
+    }
+}
+
+// Returns the number of bytes a value of a type takes in memory.
+fn size(ty: &Type) -> u32 {
+    match ty {
+        Type::Int | Type::Bool | Type::String => 4,
+        Type::Ref(..) | Type::RefMut(..) | Type::Box(_) => 4,
+        Type::Unit | Type::Never => 0,
+        Type::Tuple(tys) => tys.iter().map(size).sum(),
+        Type::Array(t, n) => size(t) * *n as u32,
+        Type::Struct(s) => s.fields.iter().map(|(_, t)| size(t)).sum(),
+        Type::Enum(e) => 4 + e.variants.iter().map(|(_, t)| size(t)).max().unwrap_or(0),
+        Type::Unknown | Type::Var(_) => {
+            panic!("Cannot generate WebAssembly for a value of unknown type")
+        }
+        Type::Slice(_) => panic!("Cannot generate WebAssembly for a slice"),
+        Type::Closure(_) => panic!("Cannot generate WebAssembly for a closure"),
+        Type::Cell(..) | Type::Guard(..) | Type::GuardMut(..) => {
+            panic!("Cannot generate WebAssembly for a value of type {}", ty)
+        }
+        Type::Param(id) => {
+            panic!(
+                "Cannot generate WebAssembly for a value of type parameter {}",
+                id
+            )
+        }
+        Type::Integer(t) => {
+            panic!(
+                "Cannot generate WebAssembly for a value of type {}",
+                t.name()
+            )
+        }
+    }
+}
+
+fn operand_ty(o: &Operand) -> Type {
+    match o {
+        Operand::Copy(p) | Operand::Move(p) => p.ty().clone(),
+        Operand::Constant(Constant::Int(_)) => Type::Int,
+        Operand::Constant(Constant::Integer(t, _)) => Type::Integer(*t),
+        Operand::Constant(Constant::Bool(_)) => Type::Bool,
+        Operand::Constant(Constant::String(_)) => Type::String,
+        Operand::Constant(Constant::Unit) => Type::Unit,
+        Operand::Function(_) => Type::Unknown,
+    }
+}
@@ -0,0 +1,239 @@
+use std::rc::Rc;
+
+use crate::ast::Closure;
+use crate::ast::Enum;
+use crate::ast::Loan;
+use crate::ast::Local;
+use crate::ast::Name;
+use crate::ast::Place;
+use crate::ast::Struct;
+use crate::ast::Type;
+use crate::infer::infer_type_args;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+const INSTANCE_LIMIT: usize = 256;
+
+// Monomorphization. A generic function is type checked once, but every call of it is redirected to
+// an instance of it, e.g., `id::<i32>`, in which its type parameters are replaced by the types
+// that the call infers for them. The generic functions themselves are left out, so borrowck and
+// the backends only ever see concrete types.
+//
+// A generic function that calls itself with ever larger type arguments, e.g., `f((x, 1))` in
+// `f<T>(x: T)`, would have infinitely many instances, so their number is limited.
+pub fn monomorphize(functions: Vec<Function>) -> Vec<Function> {
+    let (generic, mut out): (Vec<_>, Vec<_>) = functions
+        .into_iter()
+        .partition(|f| !f.type_params.is_empty());
+    let mut i = 0;
+    while i < out.len() {
+        let mut instances = vec![];
+        for block in &mut out[i].blocks {
+            for stmt in &mut block.stmts {
+                let Operation::Call {
+                    func: Operand::Function(name),
+                    args,
+                    ..
+                } = &mut stmt.op
+                else {
+                    continue;
+                };
+                let Some(g) = generic.iter().find(|g| g.id == *name) else {
+                    continue;
+                };
+                let ts = args.iter().map(operand_ty).collect::<Vec<_>>();
+                // The loans of the caller mean nothing in the instance.
+                let type_args = infer_type_args(&g.type_params, &g.params, &ts)
+                    .iter()
+                    .map(erase_loans)
+                    .collect::<Vec<_>>();
+                *name = instance_id(g, &type_args);
+                if !instances.iter().any(|f: &Function| f.id == *name) {
+                    instances.push(g.instance(name, &type_args));
+                }
+            }
+        }
+        for f in instances {
+            if !out.iter().any(|g| g.id == f.id) {
+                if out.len() >= INSTANCE_LIMIT {
+                    panic!("Monomorphize: too many instances, e.g., {}", f.id);
+                }
+                out.push(f);
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+impl Function {
+    // A copy of a generic function with its type parameters replaced. Values that turn out to be
+    // Copy are copied rather than moved, and drops of values that own nothing are removed.
+    fn instance(&self, id: &str, type_args: &[Type]) -> Function {
+        let subst = |t: &Type| t.with_type_args(&self.type_params, type_args);
+        let mut f = self.clone();
+        f.id = id.to_string();
+        f.type_params = vec![];
+        f.ty = subst(&f.ty);
+        for l in f.params.iter_mut().chain(f.locals.iter_mut()) {
+            l.ty = subst(&l.ty);
+        }
+        for block in &mut f.blocks {
+            for stmt in &mut block.stmts {
+                match &mut stmt.op {
+                    Operation::Assign(p, rvalue) => {
+                        place(p, &subst);
+                        match rvalue {
+                            Rvalue::Use(o) => operand(o, &subst),
+                            Rvalue::Ref { place: p, .. } | Rvalue::Discriminant(p) => {
+                                place(p, &subst)
+                            }
+                        }
+                    }
+                    Operation::StorageLive(l) | Operation::StorageDead(l) => l.ty = subst(&l.ty),
+                    Operation::Call { dest, args, .. } => {
+                        place(dest, &subst);
+                        for o in args {
+                            operand(o, &subst);
+                        }
+                    }
+                    Operation::SetDiscriminant(p, _) => place(p, &subst),
+                    Operation::Drop(p) => {
+                        place(p, &subst);
+                        if !p.ty().needs_drop() {
+                            stmt.op = Operation::Noop;
+                        }
+                    }
+                    Operation::Noop => {}
+                }
+            }
+            match &mut block.terminator {
+                Some(Terminator::ConditionalGoto(o, _, _))
+                | Some(Terminator::SwitchInt(o, _, _)) => operand(o, &subst),
+                _ => {}
+            }
+        }
+        f
+    }
+}
+
+impl Type {
+    // Replaces the type parameters in a type with the corresponding type arguments.
+    pub fn with_type_args(&self, type_params: &[Name], type_args: &[Type]) -> Type {
+        let subst = |t: &Type| t.with_type_args(type_params, type_args);
+        let loans = |loans: &[Loan]| {
+            loans
+                .iter()
+                .map(|loan| Loan {
+                    place: Place {
+                        local: Local {
+                            ty: subst(&loan.place.local.ty),
+                            ..loan.place.local.clone()
+                        },
+                        elems: loan.place.elems.clone(),
+                    },
+                    mutable: loan.mutable,
+                })
+                .collect()
+        };
+        match self {
+            Type::Param(id) => match type_params.iter().position(|p| p == id) {
+                Some(i) => type_args[i].clone(),
+                None => self.clone(),
+            },
+            Type::Ref(ls, t) => Type::Ref(loans(ls), Rc::new(subst(t))),
+            Type::RefMut(ls, t) => Type::RefMut(loans(ls), Rc::new(subst(t))),
+            Type::Guard(ls, t) => Type::Guard(loans(ls), Rc::new(subst(t))),
+            Type::GuardMut(ls, t) => Type::GuardMut(loans(ls), Rc::new(subst(t))),
+            Type::Box(t) => Type::Box(Rc::new(subst(t))),
+            Type::Cell(k, t) => Type::Cell(*k, Rc::new(subst(t))),
+            Type::Array(t, n) => Type::Array(Rc::new(subst(t)), *n),
+            Type::Slice(t) => Type::Slice(Rc::new(subst(t))),
+            Type::Tuple(ts) => Type::Tuple(ts.iter().map(subst).collect()),
+            Type::Closure(c) => Type::Closure(Rc::new(Closure {
+                id: c.id.clone(),
+                kind: c.kind,
+                params: c.params.iter().map(subst).collect(),
+                ty: subst(&c.ty),
+                captures: c.captures.iter().map(subst).collect(),
+            })),
+            _ => self.clone(),
+        }
+    }
+}
+
+fn instance_id(f: &Function, type_args: &[Type]) -> Name {
+    let type_args = type_args.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    format!("{}::<{}>", f.id, type_args.join(", "))
+}
+
+fn erase_loans(t: &Type) -> Type {
+    match t {
+        Type::Ref(_, t) => Type::Ref(vec![], Rc::new(erase_loans(t))),
+        Type::RefMut(_, t) => Type::RefMut(vec![], Rc::new(erase_loans(t))),
+        Type::Guard(_, t) => Type::Guard(vec![], Rc::new(erase_loans(t))),
+        Type::GuardMut(_, t) => Type::GuardMut(vec![], Rc::new(erase_loans(t))),
+        Type::Box(t) => Type::Box(Rc::new(erase_loans(t))),
+        Type::Cell(k, t) => Type::Cell(*k, Rc::new(erase_loans(t))),
+        Type::Array(t, n) => Type::Array(Rc::new(erase_loans(t)), *n),
+        Type::Slice(t) => Type::Slice(Rc::new(erase_loans(t))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(erase_loans).collect()),
+        Type::Struct(s) => Type::Struct(Rc::new(Struct {
+            id: s.id.clone(),
+            fields: s
+                .fields
+                .iter()
+                .map(|(f, t)| (f.clone(), erase_loans(t)))
+                .collect(),
+        })),
+        Type::Enum(e) => Type::Enum(Rc::new(Enum {
+            id: e.id.clone(),
+            variants: e
+                .variants
+                .iter()
+                .map(|(v, t)| (v.clone(), erase_loans(t)))
+                .collect(),
+        })),
+        Type::Closure(c) => Type::Closure(Rc::new(Closure {
+            id: c.id.clone(),
+            kind: c.kind,
+            params: c.params.iter().map(erase_loans).collect(),
+            ty: erase_loans(&c.ty),
+            captures: c.captures.iter().map(erase_loans).collect(),
+        })),
+        _ => t.clone(),
+    }
+}
+
+fn place(p: &mut Place, subst: &impl Fn(&Type) -> Type) {
+    p.local.ty = subst(&p.local.ty);
+}
+
+fn operand(o: &mut Operand, subst: &impl Fn(&Type) -> Type) {
+    match o {
+        Operand::Copy(p) => place(p, subst),
+        Operand::Move(p) => {
+            place(p, subst);
+            if p.ty().is_copy() {
+                *o = Operand::Copy(p.clone());
+            }
+        }
+        Operand::Constant(_) | Operand::Function(_) => {}
+    }
+}
+
+fn operand_ty(o: &Operand) -> Type {
+    match o {
+        Operand::Constant(Constant::Int(_)) => Type::Int,
+        Operand::Constant(Constant::Integer(t, _)) => Type::Integer(*t),
+        Operand::Constant(Constant::Bool(_)) => Type::Bool,
+        Operand::Constant(Constant::String(_)) => Type::String,
+        Operand::Constant(Constant::Unit) => Type::Unit,
+        Operand::Copy(p) | Operand::Move(p) => p.ty().clone(),
+        Operand::Function(_) => Type::Unknown,
+    }
+}
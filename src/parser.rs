@@ -1,22 +1,60 @@
+use crate::ast::ArithOp;
+use crate::ast::Arm;
+use crate::ast::Binding;
+use crate::ast::BindingMode;
 use crate::ast::Block;
+use crate::ast::CellKind;
+use crate::ast::Enum;
 use crate::ast::Expr;
 use crate::ast::Function;
+use crate::ast::Impl;
+use crate::ast::IntTy;
+use crate::ast::Lambda;
+use crate::ast::Lifetime;
 use crate::ast::Loan;
 use crate::ast::Local;
+use crate::ast::Name;
+use crate::ast::Pattern;
 use crate::ast::Place;
 use crate::ast::PlaceElem;
+use crate::ast::Program;
+use crate::ast::Signature;
 use crate::ast::Stmt;
+use crate::ast::Struct;
+use crate::ast::Trait;
 use crate::ast::Type;
 use std::rc::Rc;
 
 pub struct Parser<'a> {
     input: &'a str,
     pos: usize,
+    // Structs and enums declared so far, which later declarations and the function can refer to.
+    structs: Vec<Rc<Struct>>,
+    enums: Vec<Rc<Enum>>,
+    // Type parameters of the function being parsed.
+    type_params: Vec<Name>,
+    // The type that `Self` stands for in a trait or impl, and the lifetime of references without
+    // one in the signature of a method, which is that of its receiver.
+    self_ty: Option<Type>,
+    elided: Option<Name>,
+    // Labels of the enclosing loops, and the number of labels so far, which identifies the next.
+    labels: Vec<(String, usize)>,
+    label_count: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
-        Self { input, pos: 0 }
+        Self {
+            input,
+            pos: 0,
+            structs: vec![],
+            enums: vec![],
+            type_params: vec![],
+            self_ty: None,
+            elided: None,
+            labels: vec![],
+            label_count: 0,
+        }
     }
 
     fn parse_expr(&mut self) -> Option<Expr> {
@@ -24,21 +62,43 @@ impl<'a> Parser<'a> {
         if self.at("if") {
             self.parse_ifelse()
         } else if self.at("while") {
-            self.parse_while()
-        } else if self.at("loop") {
+            self.parse_while(None)
+        } else if self.at("loop") || self.at("'") {
             self.parse_loop()
         } else if self.at("break") {
             self.parse_break()
         } else if self.at("continue") {
             self.parse_continue()
+        } else if self.at("return") {
+            self.parse_return()
+        } else if self.at("match") {
+            self.parse_match()
         } else if self.at("print") {
             self.consume("print")?;
             self.consume("(")?;
             let expr = self.parse_expr()?;
             self.consume(")")?;
             Some(Expr::Print(Type::Unknown, Rc::new(expr)))
-        } else if self.at("add") {
-            self.parse_add()
+        } else if self.consume("box(").is_some() {
+            let expr = self.parse_expr()?;
+            self.consume(")")?;
+            Some(Expr::Box(Type::Unknown, Rc::new(expr)))
+        } else if let Some(kind) = self.consume_cell("::new(") {
+            let expr = self.parse_expr()?;
+            self.consume(")")?;
+            Some(Expr::Call(
+                Type::Unknown,
+                format!("{}::new", kind.name()),
+                vec![expr],
+            ))
+        } else if self.consume("cast(").is_some() {
+            let expr = self.parse_expr()?;
+            self.consume(",")?;
+            let ty = self.parse_type()?;
+            self.consume(")")?;
+            Some(Expr::Cast(ty, Rc::new(expr)))
+        } else if let Some(op) = self.consume_arith() {
+            self.parse_arith(op)
         } else if self.at("assign") {
             self.consume("assign(")?;
             let place = self.parse_place()?;
@@ -48,6 +108,8 @@ impl<'a> Parser<'a> {
             Some(Expr::Assign(Type::Unknown, place, Rc::new(value)))
         } else if self.at("(") {
             self.parse_tuple_expr()
+        } else if self.at("[") {
+            self.parse_array_expr()
         } else if self.consume("&mut").is_some() {
             let place = self.parse_place()?;
             Some(Expr::RefMut(Type::Unknown, place))
@@ -61,10 +123,27 @@ impl<'a> Parser<'a> {
             self.consume(")")?;
             Some(Expr::Seq(Type::Unknown, Rc::new(first), Rc::new(second)))
         } else {
-            if let Some(lit) = self.parse_literal() {
+            if let Some(closure) = self.parse_closure() {
+                Some(closure)
+            } else if let Some(lit) = self.parse_literal() {
                 Some(lit)
+            } else if let Some(lit) = self.parse_struct_expr() {
+                Some(lit)
+            } else if let Some(variant) = self.parse_variant_expr() {
+                Some(variant)
+            } else if let Some(call) = self.parse_call_expr() {
+                Some(call)
             } else {
-                if let Some(place) = self.parse_place() {
+                if let Some(mut place) = self.parse_place() {
+                    // A field followed by arguments is a method of the place before it.
+                    if let (true, Some(PlaceElem::Field(_))) = (self.at("("), place.elems.last()) {
+                        let Some(PlaceElem::Field(m)) = place.elems.pop() else {
+                            unreachable!()
+                        };
+                        self.consume("(")?;
+                        let args = self.parse_args()?;
+                        return Some(Expr::MethodCall(Type::Unknown, place, m, args));
+                    }
                     Some(Expr::Place(Type::Unknown, place))
                 } else {
                     panic!("Invalid expression");
@@ -113,37 +192,94 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn parse_while(&mut self) -> Option<Expr> {
+    fn parse_while(&mut self, label: Option<usize>) -> Option<Expr> {
         self.consume("while")?;
         let cond = self.parse_expr()?;
         let body = self.parse_block()?;
-        Some(Expr::While(Type::Unknown, Rc::new(cond), Rc::new(body)))
+        Some(Expr::While(
+            Type::Unknown,
+            label,
+            Rc::new(cond),
+            Rc::new(body),
+        ))
     }
 
+    // Parses a `loop`, or a `while` with a label.
     fn parse_loop(&mut self) -> Option<Expr> {
-        self.consume("loop")?;
-        let body = self.parse_block()?;
-        Some(Expr::Loop(Type::Unknown, None, Rc::new(body)))
+        let label = if self.at("'") {
+            let id = self.parse_lifetime()?;
+            self.consume(":")?;
+            self.label_count += 1;
+            self.labels.push((id, self.label_count - 1));
+            Some(self.label_count - 1)
+        } else {
+            None
+        };
+        let expr = if self.at("while") {
+            self.parse_while(label)
+        } else {
+            self.consume("loop")
+                .and_then(|_| self.parse_block())
+                .map(|body| Expr::Loop(Type::Unknown, label, Rc::new(body)))
+        };
+        if label.is_some() {
+            self.labels.pop();
+        }
+        expr
     }
 
     fn parse_continue(&mut self) -> Option<Expr> {
         self.consume("continue")?;
-        Some(Expr::Continue(Type::Unknown, None))
+        let label = self.parse_label_use()?;
+        Some(Expr::Continue(Type::Unknown, label))
     }
 
     fn parse_break(&mut self) -> Option<Expr> {
         self.consume("break")?;
-        Some(Expr::Break(Type::Unknown, None))
+        let label = self.parse_label_use()?;
+        let value = match self.parse_value() {
+            Some(e) => Some(Rc::new(e?)),
+            None => None,
+        };
+        Some(Expr::Break(Type::Unknown, label, value))
     }
 
-    fn parse_add(&mut self) -> Option<Expr> {
-        self.consume("add")?;
-        self.consume("(")?;
+    // Parses the label after a `break` or `continue`, if any, and resolves it to the enclosing loop
+    // with that label. An undeclared label does not parse.
+    fn parse_label_use(&mut self) -> Option<Option<usize>> {
+        if !self.at("'") {
+            return Some(None);
+        }
+        let id = self.parse_lifetime()?;
+        let (_, label) = self.labels.iter().rev().find(|(l, _)| *l == id)?;
+        Some(Some(*label))
+    }
+
+    fn parse_return(&mut self) -> Option<Expr> {
+        self.consume("return")?;
+        let value = self
+            .parse_value()
+            .unwrap_or(Some(Expr::Unit(Type::Unknown)))?;
+        Some(Expr::Return(Type::Unknown, Rc::new(value)))
+    }
+
+    // Parses the optional value of a `break` or `return`, which is absent if the expression ends
+    // right after the keyword.
+    fn parse_value(&mut self) -> Option<Option<Expr>> {
+        self.skip_whitespace();
+        match self.peek_char() {
+            None | Some(';' | '}' | ')' | ',') => None,
+            _ => Some(self.parse_expr()),
+        }
+    }
+
+    // Parses the operands of `add(`, `sub(` or `mul(`.
+    fn parse_arith(&mut self, op: ArithOp) -> Option<Expr> {
         let lhs = self.parse_expr()?;
         self.consume(",")?;
         let rhs = self.parse_expr()?;
         self.consume(")")?;
-        Some(Expr::Add(Type::Unknown, Rc::new(lhs), Rc::new(rhs)))
+        Some(Expr::Arith(Type::Unknown, op, Rc::new(lhs), Rc::new(rhs)))
     }
 
     fn parse_place(&mut self) -> Option<Place> {
@@ -158,7 +294,33 @@ impl<'a> Parser<'a> {
             } else if self.consume(".index(").is_some() {
                 let index = self.parse_int()? as usize;
                 self.consume(")")?;
-                elems.push(PlaceElem::Index(index));
+                elems.push(PlaceElem::TupleIndex(index));
+            } else if self.consume(".downcast(").is_some() {
+                let variant = self.parse_identifier()?;
+                self.consume(")")?;
+                elems.push(PlaceElem::Downcast(variant));
+            } else if self.consume(".").is_some() {
+                let field = self.parse_identifier()?;
+                elems.push(PlaceElem::Field(field));
+            } else if self.consume("[").is_some() {
+                // A constant index, a constant range of a subslice, e.g., `1..3`, `1..` or `..`,
+                // or a local that holds the index.
+                let index = self.parse_int();
+                if self.consume("..").is_some() {
+                    let from = index.unwrap_or(0) as usize;
+                    let to = self.parse_int().map(|to| to as usize);
+                    elems.push(PlaceElem::Subslice { from, to });
+                } else if let Some(index) = index {
+                    elems.push(PlaceElem::ConstantIndex(index as usize));
+                } else {
+                    let local = Local {
+                        id: self.parse_identifier()?,
+                        ty: Type::Unknown,
+                        mutable: false,
+                    };
+                    elems.push(PlaceElem::Index(local));
+                }
+                self.consume("]")?;
             } else {
                 // Base case: a simple name
                 let local = Local {
@@ -172,8 +334,15 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_literal(&mut self) -> Option<Expr> {
-        if let Some(value) = self.parse_int() {
-            Some(Expr::Int(Type::Int, value))
+        if let Some(value) = self.parse_signed_int() {
+            // Without a suffix, the type of a literal is inferred.
+            let ty = match self.peek_char() {
+                Some(c) if c.is_alphabetic() => {
+                    Type::int(IntTy::from_name(&self.parse_identifier()?)?)
+                }
+                _ => Type::Unknown,
+            };
+            Some(Expr::Int(ty, value))
         } else if let Some(value) = self.parse_bool() {
             Some(Expr::Bool(Type::Bool, value))
         } else if let Some(value) = self.parse_string() {
@@ -183,18 +352,288 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parses `Name { a: e, b: e }` where `Name` is a declared struct. Backtracks otherwise.
+    fn parse_struct_expr(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        let Some(def) = self.parse_struct_name() else {
+            self.pos = start;
+            return None;
+        };
+        if !self.at("{") {
+            self.pos = start;
+            return None;
+        }
+        self.consume("{")?;
+        let mut fields = Vec::new();
+        while self.consume("}").is_none() {
+            let name = self.parse_identifier()?;
+            self.consume(":")?;
+            let expr = self.parse_expr()?;
+            fields.push((name, expr));
+            if self.consume(",").is_none() {
+                self.consume("}")?;
+                break;
+            }
+        }
+        Some(Expr::Struct(Type::Struct(def), fields))
+    }
+
+    fn parse_struct_name(&mut self) -> Option<Rc<Struct>> {
+        let name = self.parse_identifier()?;
+        self.structs.iter().find(|s| s.id == name).cloned()
+    }
+
+    // Parses `Name::Variant` or `Name::Variant(e, ..)` where `Name` is a declared enum.
+    // Backtracks otherwise.
+    // Parses `f(e0, e1, ..)`. Backtracks if the name is not followed by arguments, as it is then
+    // a place.
+    fn parse_call_expr(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        let Some(id) = self.parse_identifier() else {
+            self.pos = start;
+            return None;
+        };
+        if self.consume("(").is_none() {
+            self.pos = start;
+            return None;
+        }
+        let args = self.parse_args()?;
+        Some(Expr::Call(Type::Unknown, id, args))
+    }
+
+    // Parses the arguments of a call up to the closing parenthesis.
+    fn parse_args(&mut self) -> Option<Vec<Expr>> {
+        let mut args = Vec::new();
+        while self.consume(")").is_none() {
+            args.push(self.parse_expr()?);
+            if self.consume(",").is_none() {
+                self.consume(")")?;
+                break;
+            }
+        }
+        Some(args)
+    }
+
+    // Parses `move? |x, mut y: T| body`. The captures are inferred later.
+    fn parse_closure(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        let is_move = self.consume("move").is_some();
+        if self.consume("|").is_none() {
+            self.pos = start;
+            return None;
+        }
+        let mut params = Vec::new();
+        while self.consume("|").is_none() {
+            self.skip_whitespace();
+            let mutable = self.consume("mut ").is_some();
+            self.skip_whitespace();
+            let id = self.parse_identifier()?;
+            let ty = if self.consume(":").is_some() {
+                self.parse_type()?
+            } else {
+                Type::Unknown
+            };
+            params.push(Local { id, ty, mutable });
+            if self.consume(",").is_none() {
+                self.consume("|")?;
+                break;
+            }
+        }
+        let body = if self.at("{") {
+            Expr::Block(Type::Unknown, Rc::new(self.parse_block()?))
+        } else {
+            self.parse_expr()?
+        };
+        Some(Expr::Closure(
+            Type::Unknown,
+            Rc::new(Lambda {
+                is_move,
+                params,
+                body,
+                captures: vec![],
+                function: None,
+            }),
+        ))
+    }
+
+    fn parse_variant_expr(&mut self) -> Option<Expr> {
+        let start = self.pos;
+        let Some(def) = self.parse_enum_name() else {
+            self.pos = start;
+            return None;
+        };
+        self.consume("::")?;
+        let variant = self.parse_identifier()?;
+        let mut args = Vec::new();
+        if self.consume("(").is_some() {
+            while self.consume(")").is_none() {
+                args.push(self.parse_expr()?);
+                if self.consume(",").is_none() {
+                    self.consume(")")?;
+                    break;
+                }
+            }
+        }
+        Some(Expr::Variant(Type::Enum(def), variant, args))
+    }
+
+    fn parse_enum_name(&mut self) -> Option<Rc<Enum>> {
+        let name = self.parse_identifier()?;
+        self.enums.iter().find(|e| e.id == name).cloned()
+    }
+
+    fn parse_match(&mut self) -> Option<Expr> {
+        self.consume("match")?;
+        let place = self.parse_place()?;
+        self.consume("{")?;
+        let mut arms = Vec::new();
+        while self.consume("}").is_none() {
+            let pattern = self.parse_pattern()?;
+            self.consume("=>")?;
+            let block = self.parse_block()?;
+            arms.push(Arm {
+                pattern,
+                block: Rc::new(block),
+            });
+            self.consume(",");
+        }
+        Some(Expr::Match(Type::Unknown, place, arms))
+    }
+
+    fn parse_pattern(&mut self) -> Option<Pattern> {
+        let name = self.parse_identifier()?;
+        if name == "_" {
+            return Some(Pattern::Wildcard);
+        }
+        self.consume("::")?;
+        let variant = self.parse_identifier()?;
+        let mut bindings = Vec::new();
+        if self.consume("(").is_some() {
+            while self.consume(")").is_none() {
+                bindings.push(self.parse_binding()?);
+                if self.consume(",").is_none() {
+                    self.consume(")")?;
+                    break;
+                }
+            }
+        }
+        Some(Pattern::Variant(name, variant, bindings))
+    }
+
+    // Parses `x`, `mut x`, `ref x`, `ref mut x` or `_`.
+    fn parse_binding(&mut self) -> Option<Option<Binding>> {
+        let mut id = self.parse_identifier()?;
+        let mut mode = BindingMode::Value;
+        let mut mutable = false;
+        if id == "ref" {
+            id = self.parse_identifier()?;
+            mode = BindingMode::Ref;
+            if id == "mut" {
+                id = self.parse_identifier()?;
+                mode = BindingMode::RefMut;
+            }
+        } else if id == "mut" {
+            id = self.parse_identifier()?;
+            mutable = true;
+        }
+        if id == "_" {
+            return Some(None);
+        }
+        let local = Local {
+            id,
+            ty: Type::Unknown,
+            mutable,
+        };
+        Some(Some(Binding { mode, local }))
+    }
+
+    pub fn parse_enum(&mut self) -> Option<Rc<Enum>> {
+        self.consume("enum")?;
+        let id = self.parse_identifier()?;
+        self.consume("{")?;
+        let mut variants = Vec::new();
+        while self.consume("}").is_none() {
+            let name = self.parse_identifier()?;
+            let mut tys = Vec::new();
+            if self.consume("(").is_some() {
+                while self.consume(")").is_none() {
+                    tys.push(self.parse_type()?);
+                    if self.consume(",").is_none() {
+                        self.consume(")")?;
+                        break;
+                    }
+                }
+            }
+            variants.push((name, Type::Tuple(tys)));
+            if self.consume(",").is_none() {
+                self.consume("}")?;
+                break;
+            }
+        }
+        let e = Rc::new(Enum { id, variants });
+        self.enums.push(e.clone());
+        Some(e)
+    }
+
+    pub fn parse_struct(&mut self) -> Option<Rc<Struct>> {
+        self.consume("struct")?;
+        let id = self.parse_identifier()?;
+        self.consume("{")?;
+        let mut fields = Vec::new();
+        while self.consume("}").is_none() {
+            let name = self.parse_identifier()?;
+            self.consume(":")?;
+            let ty = self.parse_type()?;
+            fields.push((name, ty));
+            if self.consume(",").is_none() {
+                self.consume("}")?;
+                break;
+            }
+        }
+        let s = Rc::new(Struct { id, fields });
+        self.structs.push(s.clone());
+        Some(s)
+    }
+
     pub fn parse_function(&mut self) -> Option<Function> {
+        let (signature, type_params) = self.parse_signature()?;
+        let body = self.parse_block()?;
+        self.type_params.clear();
+        self.elided = None;
+        Some(Function {
+            id: signature.id,
+            lifetimes: signature.lifetimes,
+            type_params,
+            params: signature.params,
+            ty: signature.ty,
+            block: body,
+        })
+    }
+
+    // Parses `fn id<..>(params) -> ty`, where the first parameter of a method can be its receiver.
+    fn parse_signature(&mut self) -> Option<(Signature, Vec<Name>)> {
         self.consume("fn")?;
         self.skip_whitespace();
         let id = self.parse_identifier()?;
+        let (mut lifetimes, type_params) = self.parse_generics()?;
+        self.type_params = type_params.clone();
         self.consume("(")?;
         let mut params = Vec::new();
         while self.consume(")").is_none() {
-            let mutable = self.consume("mut").is_some();
-            let id = self.parse_identifier()?;
-            self.consume(":")?;
-            let ty = self.parse_type()?;
-            params.push(Local { id, ty, mutable });
+            let receiver = if params.is_empty() {
+                self.parse_receiver(&mut lifetimes)
+            } else {
+                None
+            };
+            if let Some(receiver) = receiver {
+                params.push(receiver);
+            } else {
+                let mutable = self.consume("mut").is_some();
+                let id = self.parse_identifier()?;
+                self.consume(":")?;
+                let ty = self.parse_type()?;
+                params.push(Local { id, ty, mutable });
+            }
             if self.consume(",").is_none() {
                 self.consume(")")?;
                 break;
@@ -206,16 +645,112 @@ impl<'a> Parser<'a> {
         } else {
             Type::Unit
         };
-        let body = self.parse_block()?;
-        Some(Function {
+        let signature = Signature {
             id,
+            lifetimes,
             params,
             ty,
-            block: body,
+        };
+        Some((signature, type_params))
+    }
+
+    // Parses the receiver of a method: `self`, `mut self`, `&self`, `&mut self` or `&'a self`. A
+    // reference without a lifetime is given the lifetime `'this`, like an elided one in Rust.
+    fn parse_receiver(&mut self, lifetimes: &mut Vec<Lifetime>) -> Option<Local> {
+        let self_ty = self.self_ty.clone()?;
+        let start = self.pos;
+        let by_ref = self.consume("&").is_some();
+        let lifetime = if by_ref && self.at("'") {
+            Some(self.parse_lifetime()?)
+        } else {
+            None
+        };
+        let mutable = self.consume("mut").is_some();
+        if self.parse_identifier().as_deref() != Some("self") {
+            self.pos = start;
+            return None;
+        }
+        if !by_ref {
+            return Some(Local {
+                id: "self".to_string(),
+                ty: self_ty,
+                mutable,
+            });
+        }
+        let id = match lifetime {
+            Some(id) => id,
+            None => {
+                let id = "'this".to_string();
+                if !lifetimes.iter().any(|l| l.id == id) {
+                    lifetimes.push(Lifetime {
+                        id: id.clone(),
+                        bounds: vec![],
+                    });
+                }
+                id
+            }
+        };
+        self.elided = Some(id.clone());
+        let loans = vec![Loan::lifetime(&id, mutable)];
+        let ty = Rc::new(self_ty);
+        Some(Local {
+            id: "self".to_string(),
+            ty: if mutable {
+                Type::RefMut(loans, ty)
+            } else {
+                Type::Ref(loans, ty)
+            },
+            mutable: false,
         })
     }
 
-    fn parse_int(&mut self) -> Option<i32> {
+    // Parses `trait Foo { fn get(&self) -> &i32; .. }`.
+    fn parse_trait(&mut self) -> Option<Trait> {
+        self.consume("trait")?;
+        let id = self.parse_identifier()?;
+        self.consume("{")?;
+        self.self_ty = Some(Type::Param("Self".to_string()));
+        let mut methods = Vec::new();
+        while self.consume("}").is_none() {
+            let (signature, type_params) = self.parse_signature()?;
+            if !type_params.is_empty() {
+                return None;
+            }
+            self.consume(";")?;
+            self.type_params.clear();
+            self.elided = None;
+            methods.push(signature);
+        }
+        self.self_ty = None;
+        Some(Trait { id, methods })
+    }
+
+    // Parses `impl Foo for S { fn get(&self) -> &i32 { .. } .. }`, whose methods become functions
+    // of the program.
+    fn parse_impl(&mut self) -> Option<(Impl, Vec<Function>)> {
+        self.consume("impl")?;
+        let trait_id = self.parse_identifier()?;
+        self.consume("for")?;
+        let ty = self.parse_type()?;
+        self.consume("{")?;
+        self.self_ty = Some(ty.clone());
+        let mut imp = Impl {
+            trait_id,
+            ty,
+            methods: vec![],
+        };
+        let mut functions = Vec::new();
+        while self.consume("}").is_none() {
+            let mut f = self.parse_function()?;
+            imp.methods.push(f.id.clone());
+            f.id = imp.function_id(&f.id);
+            functions.push(f);
+        }
+        self.self_ty = None;
+        Some((imp, functions))
+    }
+
+    fn parse_int(&mut self) -> Option<i128> {
         self.skip_whitespace();
         let start = self.pos;
         while let Some(c) = self.peek_char() {
@@ -228,6 +763,21 @@ impl<'a> Parser<'a> {
         self.input[start..self.pos].parse().ok()
     }
 
+    // Parses an integer with an optional minus sign, like a negated literal in Rust. Backtracks if
+    // there is no integer.
+    fn parse_signed_int(&mut self) -> Option<i128> {
+        let start = self.pos;
+        let negative = self.consume("-").is_some();
+        match self.parse_int() {
+            Some(i) if negative => Some(-i),
+            Some(i) => Some(i),
+            None => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
     fn parse_bool(&mut self) -> Option<bool> {
         if self.consume("true").is_some() {
             Some(true)
@@ -286,28 +836,59 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parses `[e0, e1, ..]`.
+    fn parse_array_expr(&mut self) -> Option<Expr> {
+        self.consume("[")?;
+        let mut elements = Vec::new();
+        while self.consume("]").is_none() {
+            elements.push(self.parse_expr()?);
+            if self.consume(",").is_none() {
+                self.consume("]")?;
+                break;
+            }
+        }
+        Some(Expr::Array(Type::Unknown, elements))
+    }
+
     fn parse_type(&mut self) -> Option<Type> {
         if self.consume("&").is_some() {
+            // `&'a T` and `&'a mut T` borrow for a lifetime parameter.
+            if self.at("'") {
+                let id = self.parse_lifetime()?;
+                let mutable = self.consume("mut").is_some();
+                let loans = vec![Loan::lifetime(&id, mutable)];
+                let t = Rc::new(self.parse_type()?);
+                return Some(if mutable {
+                    Type::RefMut(loans, t)
+                } else {
+                    Type::Ref(loans, t)
+                });
+            }
+            // In the signature of a method, like the lifetime of its receiver.
+            if !self.at("{") {
+                if let Some(id) = self.elided.clone() {
+                    let mutable = self.consume("mut").is_some();
+                    let loans = vec![Loan::lifetime(&id, mutable)];
+                    let t = Rc::new(self.parse_type()?);
+                    return Some(if mutable {
+                        Type::RefMut(loans, t)
+                    } else {
+                        Type::Ref(loans, t)
+                    });
+                }
+            }
             self.consume("{")?;
             let mut loans = Vec::new();
 
             loop {
                 if self.consume("mut").is_some() {
                     self.consume("(")?;
-                    let place = self.parse_place()?;
+                    loans.push(self.parse_loan(true)?);
                     self.consume(")")?;
-                    loans.push(Loan {
-                        place,
-                        mutable: true,
-                    });
                 } else if self.consume("shared").is_some() {
                     self.consume("(")?;
-                    let place = self.parse_place()?;
+                    loans.push(self.parse_loan(false)?);
                     self.consume(")")?;
-                    loans.push(Loan {
-                        place,
-                        mutable: false,
-                    });
                 } else {
                     break;
                 }
@@ -327,8 +908,23 @@ impl<'a> Parser<'a> {
                 let t = Rc::new(self.parse_type()?);
                 Some(Type::Ref(loans, t))
             }
-        } else if self.consume("i32").is_some() {
-            Some(Type::Int)
+        } else if self.consume("Box<").is_some() {
+            let t = Rc::new(self.parse_type()?);
+            self.consume(">")?;
+            Some(Type::Box(t))
+        } else if let Some(kind) = self.consume_cell("<") {
+            let t = Rc::new(self.parse_type()?);
+            self.consume(">")?;
+            Some(Type::Cell(kind, t))
+        } else if self.consume("[").is_some() {
+            let t = Rc::new(self.parse_type()?);
+            if self.consume("]").is_some() {
+                return Some(Type::Slice(t));
+            }
+            self.consume(";")?;
+            let n = self.parse_int()? as usize;
+            self.consume("]")?;
+            Some(Type::Array(t, n))
         } else if self.consume("String").is_some() {
             Some(Type::String)
         } else if self.consume("bool").is_some() {
@@ -355,10 +951,89 @@ impl<'a> Parser<'a> {
                 Some(Type::Tuple(types))
             }
         } else {
+            let start = self.pos;
+            if let Some(id) = self.parse_identifier() {
+                if self.type_params.contains(&id) {
+                    return Some(Type::Param(id));
+                }
+                if let (true, Some(t)) = (id == "Self", &self.self_ty) {
+                    return Some(t.clone());
+                }
+                if let Some(t) = IntTy::from_name(&id) {
+                    return Some(Type::int(t));
+                }
+            }
+            self.pos = start;
+            if let Some(s) = self.parse_struct_name() {
+                return Some(Type::Struct(s));
+            }
+            self.pos = start;
+            if let Some(e) = self.parse_enum_name() {
+                return Some(Type::Enum(e));
+            }
+            self.pos = start;
             None
         }
     }
 
+    // Consumes `Cell` or `RefCell` followed by `suffix`.
+    fn consume_arith(&mut self) -> Option<ArithOp> {
+        ArithOp::ALL
+            .into_iter()
+            .find(|op| self.consume(&format!("{}(", op.name())).is_some())
+    }
+
+    fn consume_cell(&mut self, suffix: &str) -> Option<CellKind> {
+        CellKind::ALL
+            .into_iter()
+            .find(|k| self.consume(&format!("{}{}", k.name(), suffix)).is_some())
+    }
+
+    fn parse_loan(&mut self, mutable: bool) -> Option<Loan> {
+        if self.at("'") {
+            let id = self.parse_lifetime()?;
+            return Some(Loan::lifetime(&id, mutable));
+        }
+        let place = self.parse_place()?;
+        Some(Loan { place, mutable })
+    }
+
+    // Parses `'a`, including the quote.
+    fn parse_lifetime(&mut self) -> Option<String> {
+        self.consume("'")?;
+        let id = self.parse_identifier()?;
+        Some(format!("'{}", id))
+    }
+
+    // Parses `<'a, 'b: 'a + 'c, T>`, if present, into lifetime and type parameters.
+    fn parse_generics(&mut self) -> Option<(Vec<Lifetime>, Vec<Name>)> {
+        let mut lifetimes = Vec::new();
+        let mut type_params = Vec::new();
+        if self.consume("<").is_none() {
+            return Some((lifetimes, type_params));
+        }
+        while self.consume(">").is_none() {
+            if self.at("'") {
+                let id = self.parse_lifetime()?;
+                let mut bounds = Vec::new();
+                if self.consume(":").is_some() {
+                    bounds.push(self.parse_lifetime()?);
+                    while self.consume("+").is_some() {
+                        bounds.push(self.parse_lifetime()?);
+                    }
+                }
+                lifetimes.push(Lifetime { id, bounds });
+            } else {
+                type_params.push(self.parse_identifier()?);
+            }
+            if self.consume(",").is_none() {
+                self.consume(">")?;
+                break;
+            }
+        }
+        Some((lifetimes, type_params))
+    }
+
     fn parse_identifier(&mut self) -> Option<String> {
         self.skip_whitespace();
         if let Some(c) = self.peek_char() {
@@ -443,6 +1118,52 @@ impl<'a> Parser<'a> {
 impl Function {
     pub fn parse(input: &str) -> Option<Self> {
         let mut parser = Parser::new(input);
+        loop {
+            if parser.at("struct") {
+                parser.parse_struct()?;
+            } else if parser.at("enum") {
+                parser.parse_enum()?;
+            } else {
+                break;
+            }
+        }
         parser.parse_function()
     }
 }
+
+impl Program {
+    // Parses declarations of structs, enums, traits, impls and functions up to the end of the
+    // input.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parser = Parser::new(input);
+        let mut functions = Vec::new();
+        let mut traits = Vec::new();
+        let mut impls = Vec::new();
+        loop {
+            if parser.at("struct") {
+                parser.parse_struct()?;
+            } else if parser.at("enum") {
+                parser.parse_enum()?;
+            } else if parser.at("trait") {
+                traits.push(parser.parse_trait()?);
+            } else if parser.at("impl") {
+                let (imp, methods) = parser.parse_impl()?;
+                impls.push(imp);
+                functions.extend(methods);
+            } else if parser.at("fn") {
+                functions.push(parser.parse_function()?);
+            } else {
+                break;
+            }
+        }
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return None;
+        }
+        Some(Program {
+            functions,
+            traits,
+            impls,
+        })
+    }
+}
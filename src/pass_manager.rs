@@ -0,0 +1,285 @@
+use crate::mir::Function;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Analysis {
+    Predecessors,
+    Successors,
+    Postorder,
+    Preorder,
+    ReversePostorderNumber,
+    Dominators,
+    Loops,
+    Liveness,
+}
+
+impl Analysis {
+    pub const ALL: &'static [Analysis] = &[
+        Analysis::Predecessors,
+        Analysis::Successors,
+        Analysis::Postorder,
+        Analysis::Preorder,
+        Analysis::ReversePostorderNumber,
+        Analysis::Dominators,
+        Analysis::Loops,
+        Analysis::Liveness,
+    ];
+
+    // Analyses that must be up to date before this analysis can be computed.
+    pub fn requires(self) -> &'static [Analysis] {
+        match self {
+            Analysis::Predecessors => &[],
+            Analysis::Successors => &[],
+            Analysis::Postorder => &[Analysis::Successors],
+            Analysis::Preorder => &[Analysis::Successors],
+            Analysis::ReversePostorderNumber => &[Analysis::Postorder],
+            Analysis::Dominators => &[Analysis::Predecessors],
+            Analysis::Loops => &[
+                Analysis::Predecessors,
+                Analysis::Successors,
+                Analysis::Dominators,
+            ],
+            Analysis::Liveness => &[],
+        }
+    }
+
+    fn compute(self, f: &mut Function) {
+        match self {
+            Analysis::Predecessors => f.compute_predecessors(),
+            Analysis::Successors => f.compute_successors(),
+            Analysis::Postorder => f.compute_postorder(),
+            Analysis::Preorder => f.compute_preorder(),
+            Analysis::ReversePostorderNumber => f.compute_reverse_postorder_number(),
+            Analysis::Dominators => f.compute_dominators(),
+            Analysis::Loops => f.compute_loops(),
+            Analysis::Liveness => f.compute_liveness(),
+        }
+    }
+}
+
+// Analyses that depend on the shape of the CFG.
+const CFG: &[Analysis] = Analysis::ALL;
+
+// Analyses that depend on the statements of blocks, but not on the shape of the CFG.
+const STMTS: &[Analysis] = &[Analysis::Liveness];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pass {
+    pub name: &'static str,
+    pub requires: &'static [Analysis],
+    // Analyses that are stale after the pass has changed the function.
+    pub invalidates: &'static [Analysis],
+    // Runs the pass, and returns whether it changed the function.
+    pub run: fn(&mut Function) -> bool,
+}
+
+impl Pass {
+    pub const MERGE_BLOCKS: Pass = Pass {
+        name: "merge_blocks",
+        requires: &[],
+        invalidates: CFG,
+        run: Function::merge_blocks,
+    };
+
+    pub const REMOVE_UNREACHABLE: Pass = Pass {
+        name: "remove_unreachable",
+        requires: &[],
+        invalidates: CFG,
+        run: Function::remove_unreachable,
+    };
+
+    pub const CONSTANT_PROPAGATION: Pass = Pass {
+        name: "constant_propagation",
+        requires: &[],
+        invalidates: CFG,
+        run: Function::constant_propagation,
+    };
+
+    pub const COPY_PROPAGATION: Pass = Pass {
+        name: "copy_propagation",
+        requires: &[],
+        invalidates: STMTS,
+        run: Function::copy_propagation,
+    };
+
+    pub const DEAD_STORE_ELIMINATION: Pass = Pass {
+        name: "dead_store_elimination",
+        requires: &[Analysis::Liveness],
+        invalidates: STMTS,
+        run: Function::dead_store_elimination,
+    };
+
+    pub const REMOVE_UNUSED_VARIABLES: Pass = Pass {
+        name: "remove_unused_variables",
+        requires: &[],
+        invalidates: STMTS,
+        run: Function::remove_unused_variables,
+    };
+
+    pub const ELABORATE_DROPS: Pass = Pass {
+        name: "elaborate_drops",
+        requires: &[],
+        invalidates: CFG,
+        run: Function::elaborate_drops,
+    };
+
+    pub const BORROWCK: Pass = Pass {
+        name: "borrowck",
+        requires: &[Analysis::Liveness],
+        invalidates: &[],
+        run: |f| {
+            f.borrowck();
+            false
+        },
+    };
+
+    pub const ALL: &'static [Pass] = &[
+        Pass::MERGE_BLOCKS,
+        Pass::REMOVE_UNREACHABLE,
+        Pass::CONSTANT_PROPAGATION,
+        Pass::COPY_PROPAGATION,
+        Pass::DEAD_STORE_ELIMINATION,
+        Pass::REMOVE_UNUSED_VARIABLES,
+        Pass::ELABORATE_DROPS,
+        Pass::BORROWCK,
+    ];
+
+    pub fn lookup(name: &str) -> Option<Pass> {
+        Pass::ALL.iter().find(|p| p.name == name).copied()
+    }
+}
+
+pub struct PassManager {
+    passes: Vec<Pass>,
+    valid: Vec<Analysis>,
+    dump: bool,
+    validate: bool,
+    // The name of each pass that has run, and whether it changed the function.
+    pub changes: Vec<(&'static str, bool)>,
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager {
+            passes: vec![],
+            valid: vec![],
+            dump: false,
+            validate: false,
+            changes: vec![],
+        }
+    }
+
+    // Creates a pass manager from a comma-separated list of pass names, e.g.,
+    // "copy_propagation,dead_store_elimination". Fails on the first name that is not a pass.
+    pub fn from_pipeline(pipeline: &str) -> Result<PassManager, String> {
+        let mut pm = PassManager::new();
+        for name in pipeline.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match Pass::lookup(name) {
+                Some(pass) => pm.passes.push(pass),
+                None => return Err(format!("Unknown pass: {}", name)),
+            }
+        }
+        Ok(pm)
+    }
+
+    pub fn with_pass(mut self, pass: Pass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    // Prints the MIR after every pass.
+    pub fn with_dump(mut self) -> Self {
+        self.dump = true;
+        self
+    }
+
+    // Validates the MIR before the first pass and after every pass. Only has an effect in debug
+    // builds.
+    pub fn with_validation(mut self) -> Self {
+        self.validate = true;
+        self
+    }
+
+    // Runs every pass on `f`. The analyses that are up to date are tracked across calls, so a pass
+    // manager must only be used for a single function.
+    pub fn run(&mut self, f: &mut Function) {
+        self.check(f, "lowering");
+        for pass in self.passes.clone() {
+            self.run_pass(f, pass);
+        }
+    }
+
+    // Runs a single pass after computing the analyses it requires, and invalidates the analyses
+    // it makes stale if it changed the function.
+    pub fn run_pass(&mut self, f: &mut Function, pass: Pass) -> bool {
+        for &a in pass.requires {
+            self.require(f, a);
+        }
+        let changed = (pass.run)(f);
+        if changed {
+            self.invalidate(pass.invalidates);
+        }
+        self.changes.push((pass.name, changed));
+        if self.dump {
+            println!("// after {}", pass.name);
+            println!("{}", f);
+        }
+        self.check(f, pass.name);
+        changed
+    }
+
+    fn check(&self, f: &Function, after: &str) {
+        if self.validate && cfg!(debug_assertions) {
+            if let Err(errors) = f.validate() {
+                panic!("Invalid MIR after {}:\n{}\n{}", after, errors.join("\n"), f);
+            }
+        }
+    }
+
+    // Computes an analysis, and the analyses it depends on, unless they are already up to date.
+    pub fn require(&mut self, f: &mut Function, a: Analysis) {
+        if self.valid.contains(&a) {
+            return;
+        }
+        for &dep in a.requires() {
+            self.require(f, dep);
+        }
+        a.compute(f);
+        self.valid.push(a);
+    }
+
+    fn invalidate(&mut self, analyses: &[Analysis]) {
+        let mut stale = analyses.to_vec();
+        // An analysis is also stale if any analysis it was computed from is stale.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &a in &self.valid {
+                if !stale.contains(&a) && a.requires().iter().any(|dep| stale.contains(dep)) {
+                    stale.push(a);
+                    changed = true;
+                }
+            }
+        }
+        self.valid.retain(|a| !stale.contains(a));
+    }
+
+    pub fn is_valid(&self, a: Analysis) -> bool {
+        self.valid.contains(&a)
+    }
+}
+
+impl Function {
+    // Runs a pipeline that is known to be valid, and panics otherwise.
+    pub fn with_pipeline(mut self, pipeline: &str) -> Self {
+        PassManager::from_pipeline(pipeline)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .run(&mut self);
+        self
+    }
+}
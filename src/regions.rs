@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Enum;
+use crate::ast::Function;
+use crate::ast::Loan;
+use crate::ast::Name;
+use crate::ast::Place;
+use crate::ast::PlaceElem;
+use crate::ast::Struct;
+use crate::ast::Type;
+
+// Region checking. References in a signature name lifetime parameters, e.g., `&'a i32`, which
+// stand for loans of places outside the function. Within the body, references carry the loans of
+// the places they borrow, so the body is checked against the signature by tracing the loans of
+// the returned value back to lifetime parameters. A call instantiates the lifetimes of the callee
+// with the loans of its arguments.
+impl Function {
+    // Panics if a lifetime in the signature is not a parameter of the function.
+    pub fn check_lifetimes(&self) {
+        let mut ids = vec![];
+        for l in &self.params {
+            lifetimes(&l.ty, &mut ids);
+        }
+        lifetimes(&self.ty, &mut ids);
+        ids.extend(self.lifetimes.iter().flat_map(|l| l.bounds.iter().cloned()));
+        if let Some(id) = ids.iter().find(|id| self.lifetime(id).is_none()) {
+            panic!("Function: undeclared lifetime {}", id);
+        }
+    }
+
+    // Whether `'a` outlives `'b`, directly or through the bounds of other lifetimes.
+    pub fn outlives(&self, a: &str, b: &str) -> bool {
+        let mut seen = vec![a.to_string()];
+        let mut i = 0;
+        while let Some(l) = seen.get(i) {
+            if l == b {
+                return true;
+            }
+            if let Some(l) = self.lifetime(l) {
+                for bound in &l.bounds {
+                    if !seen.contains(bound) {
+                        seen.push(bound.clone());
+                    }
+                }
+            }
+            i += 1;
+        }
+        false
+    }
+
+    // The lifetimes that a loan is valid for. A loan of a place behind a reference is valid for as
+    // long as the reference, while a loan of a place of the function ends when the function
+    // returns, and so the place is returned as the error.
+    pub fn regions(&self, loan: &Loan) -> Result<Vec<Name>, Place> {
+        if let Some(id) = loan.lifetime_id() {
+            return Ok(vec![id.clone()]);
+        }
+        let p = &loan.place;
+        for k in 0..p.elems.len() {
+            let prefix = Place {
+                local: p.local.clone(),
+                elems: p.elems[..k].to_vec(),
+            };
+            if p.elems[k] != PlaceElem::Deref {
+                continue;
+            }
+            if let Type::Ref(loans, _) | Type::RefMut(loans, _) = prefix.ty() {
+                let mut regions = vec![];
+                for loan in loans {
+                    regions.extend(self.regions(&loan)?);
+                }
+                return Ok(regions);
+            }
+        }
+        Err(p.clone())
+    }
+
+    // Panics unless the body of the function can return a value of the given type, i.e., every
+    // loan of the value is in the signature or outlives a lifetime of it.
+    pub fn check_return(&self, actual: &Type) {
+        if !actual.same_shape(&self.ty) {
+            panic!("Function: mismatched types: {:?} != {:?}", actual, self.ty);
+        }
+        self.check_outlives(&self.ty, actual);
+    }
+
+    fn check_outlives(&self, expected: &Type, actual: &Type) {
+        match (expected, actual) {
+            (Type::Ref(expected_loans, t1), Type::Ref(loans, t2))
+            | (Type::RefMut(expected_loans, t1), Type::RefMut(loans, t2))
+            | (Type::Guard(expected_loans, t1), Type::Guard(loans, t2))
+            | (Type::GuardMut(expected_loans, t1), Type::GuardMut(loans, t2)) => {
+                for loan in loans {
+                    if !self.permits(expected_loans, loan) {
+                        panic!("Function: {} does not live long enough", loan.place);
+                    }
+                }
+                self.check_outlives(t1, t2);
+            }
+            (Type::Box(t1), Type::Box(t2))
+            | (Type::Cell(_, t1), Type::Cell(_, t2))
+            | (Type::Array(t1, _), Type::Array(t2, _))
+            | (Type::Slice(t1), Type::Slice(t2)) => self.check_outlives(t1, t2),
+            (Type::Tuple(ts1), Type::Tuple(ts2)) => {
+                for (t1, t2) in ts1.iter().zip(ts2) {
+                    self.check_outlives(t1, t2);
+                }
+            }
+            (Type::Struct(s1), Type::Struct(s2)) => {
+                for ((_, t1), (_, t2)) in s1.fields.iter().zip(&s2.fields) {
+                    self.check_outlives(t1, t2);
+                }
+            }
+            (Type::Enum(e1), Type::Enum(e2)) => {
+                for ((_, t1), (_, t2)) in e1.variants.iter().zip(&e2.variants) {
+                    self.check_outlives(t1, t2);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn permits(&self, expected: &[Loan], loan: &Loan) -> bool {
+        if expected.iter().any(|l| l.place == loan.place) {
+            return true;
+        }
+        let Ok(regions) = self.regions(loan) else {
+            return false;
+        };
+        regions.iter().all(|r| {
+            expected
+                .iter()
+                .filter_map(|l| l.lifetime_id())
+                .any(|l| self.outlives(r, l))
+        })
+    }
+
+    // The type of a call with arguments of the given types. Each lifetime of the return type is
+    // replaced with the loans of the arguments at its positions in the parameters, and with those
+    // of the lifetimes that outlive it.
+    pub fn instantiate(&self, args: &[Type]) -> Type {
+        let mut subst: HashMap<Name, Vec<Loan>> = HashMap::new();
+        for (param, arg) in self.params.iter().zip(args) {
+            bind(&param.ty, arg, &mut subst);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for l in &self.lifetimes {
+                let loans = subst.get(&l.id).cloned().unwrap_or_default();
+                for bound in &l.bounds {
+                    let bound_loans = subst.entry(bound.clone()).or_default();
+                    for loan in &loans {
+                        if !bound_loans.contains(loan) {
+                            bound_loans.push(loan.clone());
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        substitute(&self.ty, &subst)
+    }
+}
+
+fn lifetimes(t: &Type, ids: &mut Vec<Name>) {
+    for loan in t.loans() {
+        if let Some(id) = loan.lifetime_id() {
+            ids.push(id.clone());
+        }
+    }
+}
+
+// Collects the loans of an argument at the positions of the lifetimes of its parameter.
+fn bind(param: &Type, arg: &Type, subst: &mut HashMap<Name, Vec<Loan>>) {
+    match (param, arg) {
+        (Type::Ref(lifetimes, t1), Type::Ref(loans, t2))
+        | (Type::RefMut(lifetimes, t1), Type::RefMut(loans, t2))
+        | (Type::Guard(lifetimes, t1), Type::Guard(loans, t2))
+        | (Type::GuardMut(lifetimes, t1), Type::GuardMut(loans, t2)) => {
+            for id in lifetimes.iter().filter_map(|l| l.lifetime_id()) {
+                let bound = subst.entry(id.clone()).or_default();
+                for loan in loans {
+                    if !bound.contains(loan) {
+                        bound.push(loan.clone());
+                    }
+                }
+            }
+            bind(t1, t2, subst);
+        }
+        (Type::Box(t1), Type::Box(t2))
+        | (Type::Cell(_, t1), Type::Cell(_, t2))
+        | (Type::Array(t1, _), Type::Array(t2, _))
+        | (Type::Slice(t1), Type::Slice(t2)) => bind(t1, t2, subst),
+        (Type::Tuple(ts1), Type::Tuple(ts2)) => {
+            for (t1, t2) in ts1.iter().zip(ts2) {
+                bind(t1, t2, subst);
+            }
+        }
+        (Type::Struct(s1), Type::Struct(s2)) => {
+            for ((_, t1), (_, t2)) in s1.fields.iter().zip(&s2.fields) {
+                bind(t1, t2, subst);
+            }
+        }
+        (Type::Enum(e1), Type::Enum(e2)) => {
+            for ((_, t1), (_, t2)) in e1.variants.iter().zip(&e2.variants) {
+                bind(t1, t2, subst);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn substitute(t: &Type, subst: &HashMap<Name, Vec<Loan>>) -> Type {
+    let loans = |loans: &[Loan]| {
+        let mut out: Vec<Loan> = vec![];
+        for loan in loans {
+            let loans = match loan.lifetime_id() {
+                Some(id) => subst.get(id).cloned().unwrap_or_default(),
+                None => vec![loan.clone()],
+            };
+            for loan in loans {
+                if !out.contains(&loan) {
+                    out.push(loan);
+                }
+            }
+        }
+        out
+    };
+    match t {
+        Type::Ref(ls, t) => Type::Ref(loans(ls), Rc::new(substitute(t, subst))),
+        Type::RefMut(ls, t) => Type::RefMut(loans(ls), Rc::new(substitute(t, subst))),
+        Type::Guard(ls, t) => Type::Guard(loans(ls), Rc::new(substitute(t, subst))),
+        Type::GuardMut(ls, t) => Type::GuardMut(loans(ls), Rc::new(substitute(t, subst))),
+        Type::Box(t) => Type::Box(Rc::new(substitute(t, subst))),
+        Type::Cell(k, t) => Type::Cell(*k, Rc::new(substitute(t, subst))),
+        Type::Array(t, n) => Type::Array(Rc::new(substitute(t, subst)), *n),
+        Type::Slice(t) => Type::Slice(Rc::new(substitute(t, subst))),
+        Type::Tuple(ts) => Type::Tuple(ts.iter().map(|t| substitute(t, subst)).collect()),
+        Type::Struct(s) => Type::Struct(Rc::new(Struct {
+            id: s.id.clone(),
+            fields: s
+                .fields
+                .iter()
+                .map(|(f, t)| (f.clone(), substitute(t, subst)))
+                .collect(),
+        })),
+        Type::Enum(e) => Type::Enum(Rc::new(Enum {
+            id: e.id.clone(),
+            variants: e
+                .variants
+                .iter()
+                .map(|(v, t)| (v.clone(), substitute(t, subst)))
+                .collect(),
+        })),
+        _ => t.clone(),
+    }
+}
@@ -2,9 +2,13 @@ use crate::mir;
 use crate::mir::Terminator;
 
 impl mir::Function {
-    pub fn remove_unreachable(&mut self) {
+    // Returns whether any block was removed.
+    pub fn remove_unreachable(&mut self) -> bool {
         let mut visited = vec![false; self.blocks.len()];
         self.dfs(&mut visited, 0);
+        if visited.iter().all(|&v| v) {
+            return false;
+        }
 
         let mut block_map = vec![0; self.blocks.len()];
         let mut new_blocks = Vec::with_capacity(visited.iter().filter(|&&v| v).count());
@@ -26,12 +30,19 @@ impl mir::Function {
                     *b0 = block_map[*b0];
                     *b1 = block_map[*b1];
                 }
+                Some(Terminator::SwitchInt(_, targets, otherwise)) => {
+                    for (_, b) in targets {
+                        *b = block_map[*b];
+                    }
+                    *otherwise = block_map[*otherwise];
+                }
                 _ => {}
             }
         }
 
         self.successors = vec![];
         self.predecessors = vec![];
+        true
     }
 
     fn dfs(&self, visited: &mut Vec<bool>, b: usize) {
@@ -48,6 +59,12 @@ impl mir::Function {
                 self.dfs(visited, b0);
                 self.dfs(visited, b1);
             }
+            Some(Terminator::SwitchInt(_, targets, otherwise)) => {
+                for (_, b) in targets {
+                    self.dfs(visited, *b);
+                }
+                self.dfs(visited, *otherwise);
+            }
             _ => {}
         }
     }
@@ -1,16 +1,29 @@
 use std::collections::HashSet;
 
+use crate::ast::LocalId;
 use crate::ast::Place;
+use crate::ast::PlaceElem;
 use crate::mir::Function;
 use crate::mir::Operand;
 use crate::mir::Operation;
 use crate::mir::Rvalue;
+use crate::mir::Terminator;
 
 impl Function {
-    pub fn remove_unused_variables(&mut self) {
-        let mut used = HashSet::new();
+    // Returns whether any local or statement was removed.
+    pub fn remove_unused_variables(&mut self) -> bool {
+        let mut used: HashSet<LocalId> = HashSet::new();
 
-        used.insert(Place::from(self.locals[0].clone()));
+        used.insert(self.locals[0].id.clone());
+
+        // Conditions decide control flow, and so are always used.
+        for block in &self.blocks {
+            match &block.terminator {
+                Some(Terminator::ConditionalGoto(cond, _, _)) => used.extend(operand_locals(cond)),
+                Some(Terminator::SwitchInt(discr, _, _)) => used.extend(operand_locals(discr)),
+                _ => {}
+            }
+        }
 
         let mut changed = true;
         while changed {
@@ -19,26 +32,37 @@ impl Function {
             for block in &self.blocks {
                 for stmt in &block.stmts {
                     match &stmt.op {
-                        Operation::Assign(dest, Rvalue::Use(operand)) => {
-                            if used.contains(dest) {
-                                for p in operand_places(operand) {
-                                    changed = used.insert(p);
+                        Operation::Assign(dest, rvalue) => {
+                            // Writes through a reference are visible to the referent.
+                            if used.contains(&dest.local.id) || is_indirect(dest) {
+                                let mut locals = place_locals(dest);
+                                locals.extend(match rvalue {
+                                    Rvalue::Use(operand) => operand_locals(operand),
+                                    Rvalue::Ref { place, .. } => place_locals(place),
+                                    Rvalue::Discriminant(place) => place_locals(place),
+                                });
+                                for l in locals {
+                                    changed |= used.insert(l);
                                 }
                             }
                         }
-                        Operation::Assign(dest, Rvalue::Ref { mutable: _, place }) => {
-                            if used.contains(dest) {
-                                changed = used.insert(place.clone());
-                            }
-                        }
                         Operation::Call { dest, func, args } => {
-                            changed = used.insert(Place::from(dest.clone()));
-                            for p in operand_places(func) {
-                                changed = used.insert(p);
+                            for l in place_locals(dest) {
+                                changed |= used.insert(l);
+                            }
+                            for l in operand_locals(func) {
+                                changed |= used.insert(l);
                             }
                             for arg in args {
-                                for p in operand_places(arg) {
-                                    changed = used.insert(p);
+                                for l in operand_locals(arg) {
+                                    changed |= used.insert(l);
+                                }
+                            }
+                        }
+                        Operation::SetDiscriminant(dest, _) | Operation::Drop(dest) => {
+                            if used.contains(&dest.local.id) || is_indirect(dest) {
+                                for l in place_locals(dest) {
+                                    changed |= used.insert(l);
                                 }
                             }
                         }
@@ -49,18 +73,24 @@ impl Function {
             }
         }
 
-        self.locals
-            .retain(|l| used.contains(&Place::from(l.clone())));
+        let len = self.locals.len();
+        self.locals.retain(|l| used.contains(&l.id));
+        let mut changed = self.locals.len() != len;
 
         for block in &mut self.blocks {
+            let len = block.stmts.len();
             block.stmts.retain(|stmt| match &stmt.op {
-                Operation::Assign(dest, _) => used.contains(dest),
-                Operation::StorageLive(local) => used.contains(&Place::from(local.clone())),
-                Operation::StorageDead(local) => used.contains(&Place::from(local.clone())),
-                Operation::Call { dest, .. } => used.contains(dest),
+                Operation::Assign(dest, _) => used.contains(&dest.local.id),
+                Operation::StorageLive(local) => used.contains(&local.id),
+                Operation::StorageDead(local) => used.contains(&local.id),
+                Operation::Call { dest, .. } => used.contains(&dest.local.id),
+                Operation::SetDiscriminant(dest, _) => used.contains(&dest.local.id),
+                Operation::Drop(place) => used.contains(&place.local.id),
                 _ => true,
             });
+            changed |= block.stmts.len() != len;
         }
+        changed
     }
 
     pub fn with_remove_unused_variables(mut self) -> Self {
@@ -69,11 +99,22 @@ impl Function {
     }
 }
 
-fn operand_places(op: &Operand) -> Vec<Place> {
+fn is_indirect(p: &Place) -> bool {
+    p.elems.contains(&PlaceElem::Deref)
+}
+
+// The local of a place and the locals that index into it.
+fn place_locals(p: &Place) -> Vec<LocalId> {
+    let mut ls = vec![p.local.id.clone()];
+    ls.extend(p.index_locals().map(|l| l.id.clone()));
+    ls
+}
+
+fn operand_locals(op: &Operand) -> Vec<LocalId> {
     match op {
         Operand::Constant(_) => vec![],
-        Operand::Copy(p) => vec![p.clone()],
-        Operand::Move(p) => vec![p.clone()],
+        Operand::Copy(p) => place_locals(p),
+        Operand::Move(p) => place_locals(p),
         Operand::Function(_) => vec![],
     }
 }
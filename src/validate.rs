@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::ArithOp;
+use crate::ast::LocalId;
+use crate::ast::Place;
+use crate::ast::Type;
+use crate::mir::BlockId;
+use crate::mir::Constant;
+use crate::mir::Function;
+use crate::mir::Operand;
+use crate::mir::Operation;
+use crate::mir::Rvalue;
+use crate::mir::Terminator;
+
+struct Validator<'a> {
+    func: &'a Function,
+    // The functions that calls are checked against, besides builtins.
+    functions: &'a [Function],
+    errors: Vec<String>,
+}
+
+impl Function {
+    // Checks the structural invariants of the MIR and returns all violations that were found.
+    //
+    // Storage and type checks are only performed on blocks that are reachable from the entry,
+    // since code after a jump is allowed to be ill-formed until it is removed.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        self.validate_with_functions(&[])
+    }
+
+    // Like `validate`, but also checks that the arguments and destinations of calls of
+    // `functions` match their signatures.
+    pub fn validate_with_functions(&self, functions: &[Function]) -> Result<(), Vec<String>> {
+        let mut v = Validator {
+            func: self,
+            functions,
+            errors: vec![],
+        };
+        v.check_blocks();
+        if v.errors.is_empty() {
+            let reachable = v.reachable();
+            v.check_types(&reachable);
+            v.check_storage(&reachable);
+        }
+        if v.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(v.errors)
+        }
+    }
+}
+
+impl Validator<'_> {
+    fn error(&mut self, msg: String) {
+        self.errors.push(msg);
+    }
+
+    fn targets(&self, b: BlockId) -> Vec<BlockId> {
+        match &self.func.blocks[b].terminator {
+            Some(Terminator::Goto(b1)) => vec![*b1],
+            Some(Terminator::ConditionalGoto(_, b1, b2)) => vec![*b1, *b2],
+            Some(Terminator::SwitchInt(_, targets, otherwise)) => targets
+                .iter()
+                .map(|(_, b)| *b)
+                .chain([*otherwise])
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn check_blocks(&mut self) {
+        let n = self.func.blocks.len();
+        if n == 0 {
+            self.error("function has no blocks".to_string());
+        }
+        for (i, block) in self.func.blocks.iter().enumerate() {
+            if block.id != i {
+                self.error(format!("bb{} has id {}", i, block.id));
+            }
+            if block.terminator.is_none() {
+                self.error(format!("bb{} has no terminator", i));
+            }
+            for target in self.targets(i) {
+                if target >= n {
+                    self.error(format!("bb{} jumps to non-existent bb{}", i, target));
+                }
+            }
+            for stmt in &block.stmts {
+                for l in op_locals(&stmt.op) {
+                    self.check_declared(i, &l);
+                }
+            }
+            if let Some(
+                Terminator::ConditionalGoto(cond, _, _) | Terminator::SwitchInt(cond, _, _),
+            ) = &block.terminator
+            {
+                for l in operand_locals(cond) {
+                    self.check_declared(i, &l);
+                }
+            }
+        }
+    }
+
+    fn check_declared(&mut self, b: BlockId, id: &LocalId) {
+        let declared = self
+            .func
+            .params
+            .iter()
+            .chain(self.func.locals.iter())
+            .any(|l| l.id == *id);
+        if !declared {
+            self.error(format!("bb{} uses undeclared local {}", b, id));
+        }
+    }
+
+    fn reachable(&self) -> Vec<bool> {
+        let mut visited = vec![false; self.func.blocks.len()];
+        let mut worklist = vec![0];
+        while let Some(b) = worklist.pop() {
+            if visited[b] {
+                continue;
+            }
+            visited[b] = true;
+            worklist.extend(self.targets(b));
+        }
+        visited
+    }
+
+    fn check_types(&mut self, reachable: &[bool]) {
+        for (b, block) in self.func.blocks.iter().enumerate() {
+            if !reachable[b] {
+                continue;
+            }
+            for stmt in &block.stmts {
+                if let Operation::Call {
+                    dest,
+                    func: Operand::Function(name),
+                    args,
+                } = &stmt.op
+                {
+                    self.check_call(b, dest, name, args);
+                }
+                if let Operation::Assign(dest, rvalue) = &stmt.op {
+                    let ty = match rvalue {
+                        Rvalue::Use(op) => operand_ty(op),
+                        Rvalue::Ref { mutable, place } => {
+                            let t = Rc::new(place.ty().clone());
+                            if *mutable {
+                                Type::RefMut(vec![], t)
+                            } else {
+                                Type::Ref(vec![], t)
+                            }
+                        }
+                        Rvalue::Discriminant(place) => {
+                            if !matches!(place.ty(), Type::Enum(_)) {
+                                self.error(format!("bb{}: discriminant of non-enum {}", b, place));
+                            }
+                            Type::Int
+                        }
+                    };
+                    if !dest.ty().same_shape(&ty) {
+                        self.error(format!(
+                            "bb{}: cannot assign {:?} to {} of type {:?}",
+                            b,
+                            ty,
+                            dest,
+                            dest.ty()
+                        ));
+                    }
+                }
+            }
+            if let Some(Terminator::ConditionalGoto(cond, _, _)) = &block.terminator {
+                if !operand_ty(cond).same_shape(&Type::Bool) {
+                    self.error(format!("bb{}: condition is not a bool", b));
+                }
+            }
+            if let Some(Terminator::SwitchInt(discr, _, _)) = &block.terminator {
+                if !operand_ty(discr).same_shape(&Type::Int) {
+                    self.error(format!("bb{}: switch on a non-integer", b));
+                }
+            }
+        }
+    }
+
+    // Builtins other than arithmetic are generic, so only their callers' types are known.
+    fn check_call(&mut self, b: BlockId, dest: &Place, name: &str, args: &[Operand]) {
+        let (params, ret) = if ArithOp::from_name(name).is_some() {
+            (vec![dest.ty(); args.len()], dest.ty())
+        } else if let Some(callee) = self.functions.iter().find(|f| f.id == name) {
+            let params = callee.params.iter().map(|l| l.ty.clone()).collect();
+            (params, callee.locals[0].ty.clone())
+        } else {
+            return;
+        };
+        if params.len() != args.len() {
+            self.error(format!(
+                "bb{}: {} takes {} arguments, found {}",
+                b,
+                name,
+                params.len(),
+                args.len()
+            ));
+            return;
+        }
+        for (i, (arg, param)) in args.iter().zip(&params).enumerate() {
+            let ty = operand_ty(arg);
+            if !ty.same_shape(param) {
+                self.error(format!(
+                    "bb{}: argument {} of {} has type {:?}, expected {:?}",
+                    b, i, name, ty, param
+                ));
+            }
+        }
+        if !dest.ty().same_shape(&ret) {
+            self.error(format!(
+                "bb{}: cannot assign result of {} of type {:?} to {} of type {:?}",
+                b,
+                name,
+                ret,
+                dest,
+                dest.ty()
+            ));
+        }
+    }
+
+    // Every local that has storage statements must be live exactly once between a StorageLive and
+    // a StorageDead, must agree on whether it is live where paths join, and must be dead when
+    // returning.
+    fn check_storage(&mut self, reachable: &[bool]) {
+        let tracked = self
+            .func
+            .blocks
+            .iter()
+            .flat_map(|b| b.stmts.iter())
+            .filter_map(|s| match &s.op {
+                Operation::StorageLive(l) | Operation::StorageDead(l) => Some(l.id.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut entry: Vec<Option<HashMap<LocalId, bool>>> = vec![None; self.func.blocks.len()];
+        entry[0] = Some(tracked.iter().map(|l| (l.clone(), false)).collect());
+        let mut worklist = vec![0];
+
+        while let Some(b) = worklist.pop() {
+            let mut live = entry[b].clone().unwrap();
+            for stmt in &self.func.blocks[b].stmts {
+                match &stmt.op {
+                    Operation::StorageLive(l) if live.insert(l.id.clone(), true) == Some(true) => {
+                        self.error(format!("bb{}: StorageLive({}) of live local", b, l.id));
+                        return;
+                    }
+                    Operation::StorageDead(l)
+                        if live.insert(l.id.clone(), false) == Some(false) =>
+                    {
+                        self.error(format!("bb{}: StorageDead({}) of dead local", b, l.id));
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(Terminator::Return) = &self.func.blocks[b].terminator {
+                let mut still_live = live
+                    .iter()
+                    .filter(|(_, live)| **live)
+                    .map(|(l, _)| l.clone())
+                    .collect::<Vec<_>>();
+                still_live.sort();
+                if !still_live.is_empty() {
+                    self.error(format!(
+                        "bb{}: returns with live storage for {}",
+                        b,
+                        still_live.join(", ")
+                    ));
+                    return;
+                }
+            }
+            for target in self.targets(b) {
+                if !reachable[target] {
+                    continue;
+                }
+                match &entry[target] {
+                    None => {
+                        entry[target] = Some(live.clone());
+                        worklist.push(target);
+                    }
+                    Some(other) if *other != live => {
+                        self.error(format!(
+                            "bb{}: storage differs between predecessors (from bb{})",
+                            target, b
+                        ));
+                        return;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+}
+
+fn operand_ty(op: &Operand) -> Type {
+    match op {
+        Operand::Constant(c) => match c {
+            Constant::Int(_) => Type::Int,
+            Constant::Integer(t, _) => Type::Integer(*t),
+            Constant::Bool(_) => Type::Bool,
+            Constant::String(_) => Type::String,
+            Constant::Unit => Type::Unit,
+        },
+        Operand::Copy(p) | Operand::Move(p) => p.ty().clone(),
+        Operand::Function(_) => Type::Unknown,
+    }
+}
+
+fn place_locals(p: &Place) -> Vec<LocalId> {
+    let mut ls = vec![p.local.id.clone()];
+    ls.extend(p.index_locals().map(|l| l.id.clone()));
+    ls
+}
+
+fn operand_locals(op: &Operand) -> Vec<LocalId> {
+    match op {
+        Operand::Copy(p) | Operand::Move(p) => place_locals(p),
+        Operand::Constant(_) | Operand::Function(_) => vec![],
+    }
+}
+
+fn op_locals(op: &Operation) -> Vec<LocalId> {
+    match op {
+        Operation::Assign(dest, rvalue) => {
+            let mut ls = place_locals(dest);
+            match rvalue {
+                Rvalue::Use(op) => ls.extend(operand_locals(op)),
+                Rvalue::Ref { place, .. } | Rvalue::Discriminant(place) => {
+                    ls.extend(place_locals(place))
+                }
+            }
+            ls
+        }
+        Operation::SetDiscriminant(dest, _) | Operation::Drop(dest) => place_locals(dest),
+        Operation::Call { dest, func, args } => {
+            let mut ls = place_locals(dest);
+            ls.extend(operand_locals(func));
+            for arg in args {
+                ls.extend(operand_locals(arg));
+            }
+            ls
+        }
+        Operation::StorageLive(l) | Operation::StorageDead(l) => vec![l.id.clone()],
+        Operation::Noop => vec![],
+    }
+}
@@ -0,0 +1,166 @@
+use ownership_v4::ast::Function;
+use ownership_v4::ast::IntTy;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run_function;
+
+fn usize(i: i128) -> Value {
+    Value::Integer(IntTy::Usize, i)
+}
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse(
+        r#"fn f(i: usize) -> i32 {
+               let a: [i32; 3] = [1, 2, 3];
+               add(a[0], a[i])
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+    let s = f.to_string();
+    assert!(s.contains("a: [i32; 3]"), "{}", s);
+    assert!(s.contains("[1, 2, 3]"), "{}", s);
+    assert!(s.contains("add(a[0], a[i])"), "{}", s);
+}
+
+#[test]
+fn test_indexing() {
+    let s = r#"fn f(i: usize) -> i32 {
+                   let mut a = [1, 2, 3];
+                   assign(a[i], 10);
+                   let j = 2;
+                   add(a[0], a[j])
+               }"#;
+    assert_eq!(run_function(s, vec![usize(0)]).0, Ok(Value::Int(13)));
+    assert_eq!(run_function(s, vec![usize(2)]).0, Ok(Value::Int(11)));
+}
+
+#[test]
+fn test_nested() {
+    let s = r#"fn f() -> i32 {
+                   let mut a = [[1, 2], [3, 4]];
+                   let r = &mut a[1];
+                   assign(r.deref[0], 5);
+                   add(a[0][1], a[1][0])
+               }"#;
+    assert_eq!(run_function(s, vec![]).0, Ok(Value::Int(7)));
+}
+
+#[test]
+fn test_strings() {
+    let s = r#"fn f(i: usize) {
+                   let mut a = ["a", "b", "c"];
+                   assign(a[i], "z");
+                   let r = &a[2];
+                   print(&a[0]);
+                   print(r);
+               }"#;
+    assert_eq!(run_function(s, vec![usize(2)]).1, "a\nz\n");
+}
+
+#[test]
+fn test_err_out_of_bounds() {
+    let s = "fn f(i: usize) -> i32 { let a = [1, 2, 3]; a[i] }";
+    assert_eq!(
+        run_function(s, vec![usize(3)]).0,
+        Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+    );
+}
+
+#[test]
+#[should_panic(expected = "Index: index 3 is out of bounds of a")]
+fn test_err_constant_out_of_bounds() {
+    Function::parse("fn f() -> i32 { let a = [1, 2, 3]; a[3] }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Index: mismatched types")]
+fn test_err_index_type() {
+    Function::parse("fn f(i: i32) -> i32 { let a = [1, 2, 3]; a[i] }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "cannot move out of a[0]")]
+fn test_err_move_out_of_array() {
+    Function::parse(r#"fn f() -> String { let a = ["a", "b"]; a[0] }"#)
+        .unwrap()
+        .infer();
+}
+
+#[test]
+fn test_disjoint_constant_indices() {
+    borrowck(
+        r#"fn f() {
+               let mut a = ["a", "b"];
+               let x = &mut a[0];
+               let y = &mut a[1];
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_same_constant_index() {
+    borrowck(
+        r#"fn f() {
+               let mut a = ["a", "b"];
+               let x = &mut a[0];
+               let y = &a[0];
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_runtime_indices() {
+    borrowck(
+        r#"fn f(i: usize, j: usize) {
+               let mut a = ["a", "b"];
+               let x = &mut a[i];
+               let y = &mut a[j];
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_runtime_and_constant_index() {
+    borrowck(
+        r#"fn f(i: usize) {
+               let mut a = ["a", "b"];
+               let x = &mut a[i];
+               let y = &a[0];
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_whole_array() {
+    borrowck(
+        r#"fn f() {
+               let mut a = ["a", "b"];
+               let x = &mut a[1];
+               let y = &a;
+               print(x);
+               print(&y.deref[0]);
+           }"#,
+    );
+}
@@ -1,4 +1,9 @@
 use ownership_v4::ast::Function;
+use ownership_v4::eval::Evaluator;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir::BasicBlock;
+use ownership_v4::mir::Operation;
+use ownership_v4::mir::Terminator;
 
 macro_rules! check {
     ($a:expr, $b:expr) => {
@@ -67,3 +72,71 @@ fn test3() {
         ""}
     );
 }
+
+// Nothing after a return is lowered into the block that returns, so it cannot overwrite the
+// return value.
+#[test]
+fn test_return_early() {
+    let f = Function::parse("fn f(x: bool) -> i32 { if x { return 1 } else { }; 2 }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    assert_eq!(f.interpret(vec![Value::Bool(true)]), Ok(Value::Int(1)));
+    assert_eq!(f.interpret(vec![Value::Bool(false)]), Ok(Value::Int(2)));
+}
+
+// The block lowered after the return is unreachable, which decompiling must not trip over.
+#[test]
+fn test_return_early_into_ast() {
+    let mut mir = Function::parse("fn f(x: bool) -> i32 { if x { return 1 } else { }; 2 }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    mir.compute_cfg();
+    let f = mir.into_ast();
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+        evaluator.run(&f, vec![Value::Bool(true)]),
+        Ok(Value::Int(1))
+    );
+    assert_eq!(
+        evaluator.run(&f, vec![Value::Bool(false)]),
+        Ok(Value::Int(2))
+    );
+}
+
+// Jumping out of scopes ends the storage of their locals, but not of those outside the loop.
+#[test]
+fn test_jump_out_of_scopes() {
+    let f = Function::parse(
+        "fn f(x: bool) -> i32 { let a = 1; loop { let y = 2; if x { return y } else { break } }; a }",
+    )
+    .unwrap()
+    .infer()
+    .into_mir();
+    println!("{}", f);
+    assert_eq!(f.validate(), Ok(()));
+    // `a` is `_1` and `y` is `_2`.
+    let dead = |b: &BasicBlock, id: &str| {
+        b.stmts
+            .iter()
+            .any(|s| matches!(&s.op, Operation::StorageDead(l) if l.id == id))
+    };
+    let returns = f
+        .blocks
+        .iter()
+        .filter(|b| matches!(b.terminator, Some(Terminator::Return)))
+        .collect::<Vec<_>>();
+    assert_eq!(returns.len(), 2);
+    assert!(returns.iter().all(|b| dead(b, "_1")));
+    assert!(returns.iter().any(|b| dead(b, "_2")));
+    let gotos = f
+        .blocks
+        .iter()
+        .filter(|b| matches!(b.terminator, Some(Terminator::Goto(_))) && dead(b, "_2"))
+        .collect::<Vec<_>>();
+    assert!(!gotos.is_empty());
+    assert!(gotos.iter().all(|b| !dead(b, "_1")));
+    assert_eq!(f.interpret(vec![Value::Bool(true)]), Ok(Value::Int(2)));
+    assert_eq!(f.interpret(vec![Value::Bool(false)]), Ok(Value::Int(1)));
+}
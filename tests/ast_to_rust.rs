@@ -0,0 +1,339 @@
+use std::process::Command;
+
+use ownership_v4::ast::Function;
+use ownership_v4::ast::Program;
+
+// Compiles Rust source as a library and returns whether rustc accepted it.
+fn rustc(name: &str, src: &str) -> bool {
+    let dir = std::env::temp_dir().join(format!("ast_to_rust_{}_{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.rs"), src).unwrap();
+    let output = Command::new(std::env::var("RUSTC").unwrap_or("rustc".to_string()))
+        .arg("--edition=2021")
+        .arg("--crate-type=lib")
+        .arg("--emit=metadata")
+        .arg("-Awarnings")
+        .arg("--out-dir")
+        .arg(&dir)
+        .arg(dir.join("lib.rs"))
+        .output()
+        .expect("Should run rustc");
+    std::fs::remove_dir_all(&dir).unwrap();
+    output.status.success()
+}
+
+// Checks that our borrow checker and rustc agree on whether a program is accepted.
+fn check(name: &str, s: &str) -> bool {
+    let f = Function::parse(s).unwrap().infer();
+    let src = f.rust().to_string();
+    let mir = f.into_mir().with_liveness();
+    let ours = std::panic::catch_unwind(|| mir.borrowck()).is_ok();
+    assert_eq!(ours, rustc(name, &src), "{}", src);
+    ours
+}
+
+#[test]
+fn test_print() {
+    let f = Function::parse(
+        r#"fn f(a: i32) -> (i32, bool) {
+               let mut x = ("a", (a, true));
+               let y = &mut x.index(1);
+               assign(y.deref.index(0), add(y.deref.index(0), 1));
+               print(&x.index(0));
+               x.index(1)
+           }"#,
+    )
+    .unwrap()
+    .infer();
+    assert_eq!(
+        f.rust().to_string(),
+        r#"fn f(a: i32) -> (i32, bool) {
+    let mut x: (String, (i32, bool)) = (String::from("a"), (a, true));
+    let y: &mut (i32, bool) = &mut x.1;
+    (*y).0 = ((*y).0 + 1);
+    println!("{}", &x.0);
+    x.1
+}"#
+    );
+}
+
+#[test]
+fn test_accepted() {
+    assert!(check(
+        "shared",
+        r#"fn f() {
+               let x = "hello";
+               let a = &x;
+               let b = &a.deref;
+               print(a);
+               print(b);
+           }"#,
+    ));
+    assert!(check(
+        "mutable",
+        r#"fn f() {
+               let mut x = ("a", "b");
+               let a = &mut x.index(0);
+               let b = &mut x.index(1);
+               print(a);
+               print(b);
+           }"#,
+    ));
+    assert!(check(
+        "struct_fields",
+        r#"struct Pair { a: String, b: String }
+           struct View { r: &{} String }
+           fn f() {
+               let mut p = Pair { a: "a", b: "b" };
+               let a = &mut p.a;
+               let v = View { r: &p.b };
+               print(a);
+               print(v.r);
+           }"#,
+    ));
+    assert!(check(
+        "enum_payload",
+        r#"enum Message { Text(String), Quit }
+           fn f() {
+               let mut m = Message::Text("hello");
+               let mut q = "quit";
+               let r = match m {
+                   Message::Text(ref mut t) => { t },
+                   Message::Quit => { &mut q },
+               };
+               print(r);
+               assign(m, Message::Quit);
+           }"#,
+    ));
+    assert!(check(
+        "box",
+        r#"fn f() {
+               let b = box("a");
+               let r = &b.deref;
+               print(r);
+               let c = b;
+               print(&c.deref);
+           }"#,
+    ));
+}
+
+#[test]
+fn test_rejected() {
+    assert!(!check(
+        "conflict",
+        r#"fn f() {
+               let mut x = "hello";
+               let a = &x;
+               let b = &mut x;
+               print(a);
+               print(b);
+           }"#,
+    ));
+    assert!(!check(
+        "two_mutable",
+        r#"fn f() {
+               let mut x = "hello";
+               let a = &mut x;
+               let b = &mut x;
+               print(a);
+               print(b);
+           }"#,
+    ));
+    assert!(!check(
+        "struct_field",
+        r#"struct Pair { a: String, b: String }
+           fn f() {
+               let mut p = Pair { a: "a", b: "b" };
+               let a = &mut p.a;
+               let b = &p.a;
+               print(a);
+               print(b);
+           }"#,
+    ));
+    assert!(!check(
+        "enum_payload",
+        r#"enum Message { Text(String), Quit }
+           fn f() {
+               let mut m = Message::Text("hello");
+               let mut q = "quit";
+               let r = match m {
+                   Message::Text(ref mut t) => { t },
+                   Message::Quit => { &mut q },
+               };
+               assign(m, Message::Quit);
+               print(r);
+           }"#,
+    ));
+    assert!(!check(
+        "box_moved",
+        r#"fn f() {
+               let b = box("a");
+               let r = &b.deref;
+               let c = b;
+               print(r);
+           }"#,
+    ));
+    assert!(!check(
+        "box_dropped",
+        r#"fn f() {
+               let s = "s";
+               let r = if true {
+                   let b = box("a");
+                   &b.deref
+               } else {
+                   &s
+               };
+               print(r);
+           }"#,
+    ));
+}
+
+#[test]
+fn test_decompiled_mir() {
+    let s = r#"fn f(n: i32) -> i32 {
+                   let mut sum = 0;
+                   let mut go = true;
+                   while go {
+                       assign(sum, add(sum, n));
+                       assign(go, false);
+                   };
+                   sum
+               }"#;
+    let mut mir = Function::parse(s).unwrap().infer().into_mir();
+    mir.remove_unreachable();
+    mir.compute_cfg();
+    let src = mir.into_ast().rust().to_string();
+    assert!(rustc("decompiled", &src), "{}", src);
+}
+
+#[test]
+fn test_decompiled_labeled_loops() {
+    let s = r#"fn f(c: bool, d: bool) -> i32 {
+                   let mut sum = 0;
+                   'outer: loop {
+                       loop {
+                           assign(sum, add(sum, 1));
+                           if c { break 'outer; } else { };
+                           if d { continue 'outer; } else { };
+                       };
+                   };
+                   sum
+               }"#;
+    let mut mir = Function::parse(s).unwrap().infer().into_mir();
+    mir.remove_unreachable();
+    mir.compute_cfg();
+    let src = mir.into_ast().rust().to_string();
+    assert!(src.contains("break 'l"), "{}", src);
+    assert!(rustc("decompiled_labeled_loops", &src), "{}", src);
+}
+
+// Like `check`, for a whole program.
+fn check_program(name: &str, s: &str) -> bool {
+    let p = Program::parse(s).unwrap().infer();
+    let src = p.rust().to_string();
+    let ours = std::panic::catch_unwind(|| {
+        for f in p.clone().into_mir() {
+            f.with_liveness().borrowck();
+        }
+    })
+    .is_ok();
+    assert_eq!(ours, rustc(name, &src), "{}", src);
+    ours
+}
+
+// Closures are printed with their captures left to rustc, which must capture the same places.
+#[test]
+fn test_closures() {
+    assert!(check_program(
+        "closure_disjoint",
+        r#"struct P { x: String, y: String }
+           fn f() {
+               let mut p = P { x: "a", y: "b" };
+               let mut n = 1;
+               let mut c = |k: i32| { assign(p.x, "c"); assign(n, add(n, k)) };
+               let r = &p.y;
+               c(2);
+               print(r);
+               let d = move || { let t = p.x; print(&t); n };
+               d();
+           }"#,
+    ));
+    assert!(!check_program(
+        "closure_conflict",
+        r#"fn f() -> i32 {
+               let mut n = 1;
+               let mut c = || assign(n, 2);
+               let r = &n;
+               c();
+               r.deref
+           }"#,
+    ));
+}
+
+// Method calls are printed as calls of the methods' paths, with the receiver borrowed explicitly.
+#[test]
+fn test_traits() {
+    let decls = r#"struct S { x: i32 }
+                   trait Get { fn get(&self) -> &i32; }
+                   trait Inc { fn inc(&mut self, n: i32); }
+                   impl Get for S { fn get(&self) -> &i32 { &self.deref.x } }
+                   impl Inc for S {
+                       fn inc(&mut self, n: i32) { assign(self.deref.x, add(self.deref.x, n)) }
+                   }"#;
+    assert!(check_program(
+        "traits_ok",
+        &format!(
+            r#"{}
+               fn f() -> i32 {{
+                   let mut s = S {{ x: 1 }};
+                   let r = &s;
+                   let x = r.get();
+                   let y = x.deref;
+                   s.inc(y);
+                   s.x
+               }}"#,
+            decls
+        ),
+    ));
+    assert!(!check_program(
+        "traits_conflict",
+        &format!(
+            r#"{}
+               fn f() -> i32 {{
+                   let mut s = S {{ x: 1 }};
+                   let x = s.get();
+                   s.inc(1);
+                   x.deref
+               }}"#,
+            decls
+        ),
+    ));
+}
+
+// Borrowck and rustc agree that a RefCell can be borrowed through shared references, but not
+// replaced while a guard of it is live.
+#[test]
+fn test_cells() {
+    assert!(check_program(
+        "cells_ok",
+        r#"fn f() -> i32 {
+               let c = Cell::new(1);
+               let r = &c;
+               c.set(add(r.deref.get(), 1));
+               let d = RefCell::new(c.get());
+               let mut g = d.borrow_mut();
+               assign(g.deref, add(g.deref, 1));
+               let x = g.deref;
+               x
+           }"#,
+    ));
+    assert!(!check_program(
+        "cells_conflict",
+        r#"fn f() -> i32 {
+               let mut d = RefCell::new(1);
+               let g = d.borrow();
+               assign(d, RefCell::new(2));
+               g.deref
+           }"#,
+    ));
+}
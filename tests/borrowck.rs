@@ -188,6 +188,45 @@ fn test_reborrow() {
     );
 }
 
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_use_after_move() {
+    check(
+        r#"fn example() {
+               let x = "hello";
+               let y = x;
+               print(&x);
+           }"#,
+    );
+}
+
+// The second iteration moves what the first one moved out of.
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_move_in_loop() {
+    check(
+        r#"fn example() {
+               let x = "hello";
+               loop {
+                   let y = x;
+               }
+           }"#,
+    );
+}
+
+#[test]
+fn test_move_and_assign_in_loop() {
+    check(
+        r#"fn example() {
+               let mut x = "hello";
+               loop {
+                   let y = x;
+                   assign(x, "again");
+               }
+           }"#,
+    );
+}
+
 #[test]
 fn test_function0() {
     check(
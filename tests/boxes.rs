@@ -0,0 +1,104 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run_function;
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse(
+        r#"fn f(b: Box<String>) {
+               let c = box(box("a"));
+               print(&c.deref.deref);
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+    let s = f.to_string();
+    assert!(s.contains("box(box(\"a\"))"), "{}", s);
+    assert!(s.contains("Box<String>"), "{}", s);
+    let mir = f.into_mir().to_string();
+    assert!(mir.contains("Drop("), "{}", mir);
+}
+
+#[test]
+fn test_move_and_deref() {
+    let s = r#"fn f() -> i32 {
+                   let mut b = box(1);
+                   assign(b.deref, add(b.deref, 1));
+                   let c = b;
+                   let d = box((c, "x"));
+                   print(&d.deref.index(1));
+                   d.deref.index(0).deref
+               }"#;
+    assert_eq!(
+        run_function(s, vec![]),
+        (Ok(Value::Int(2)), "x\n".to_string())
+    );
+}
+
+#[test]
+fn test_move_out_of_box() {
+    let s = r#"fn f() {
+                   let b = box("hello");
+                   let s = b.deref;
+                   print(&s);
+               }"#;
+    assert_eq!(
+        run_function(s, vec![]),
+        (Ok(Value::Unit), "hello\n".to_string())
+    );
+}
+
+#[test]
+fn test_borrow_contents() {
+    borrowck(
+        r#"fn f() {
+               let mut b = box("a");
+               let r = &mut b.deref;
+               assign(r.deref, "b");
+               print(&b.deref);
+           }"#,
+    );
+}
+
+// Without the borrow checker, the reference to the contents of the box dangles after the box is
+// dropped at the end of the branch.
+const DANGLING: &str = r#"fn f() {
+                              let s = "s";
+                              let r = if true {
+                                  let b = box("a");
+                                  &b.deref
+                              } else {
+                                  &s
+                              };
+                              print(r);
+                          }"#;
+
+#[test]
+fn test_drop_frees() {
+    let f = Function::parse(DANGLING).unwrap().infer().into_mir();
+    assert!(matches!(f.interpret(vec![]), Err(Error::UseAfterFree(_))));
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_borrow_outlives_box() {
+    borrowck(DANGLING);
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_move_while_borrowed() {
+    borrowck(
+        r#"fn f() {
+               let b = box("a");
+               let r = &b.deref;
+               let c = b;
+               print(r);
+           }"#,
+    );
+}
@@ -0,0 +1,214 @@
+use ownership_v4::ast::Program;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+#[test]
+fn test_parse_and_display() {
+    let p = Program::parse(
+        r#"fn main() -> i32 {
+               let c: RefCell<i32> = RefCell::new(1);
+               let g = c.borrow();
+               g.deref
+           }"#,
+    )
+    .expect("Should parse");
+    let s = p.to_string();
+    assert!(s.contains("c: RefCell<i32> = RefCell::new(1)"), "{}", s);
+    assert!(s.contains("c.borrow()"), "{}", s);
+    let s = p.infer().to_string();
+    assert!(
+        s.contains("g: Ref<{shared(c)} i32> = RefCell::borrow(&c)"),
+        "{}",
+        s
+    );
+}
+
+// A Cell is mutated through shared references, which can be live at the same time.
+#[test]
+fn test_cell_through_shared_refs() {
+    let s = r#"fn main() -> i32 {
+                   let c = Cell::new(1);
+                   let r1 = &c;
+                   let r2 = &c;
+                   r1.deref.set(add(r2.deref.get(), 1));
+                   r2.deref.set(add(r1.deref.get(), 1));
+                   c.get()
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(3)));
+    borrowck(s);
+}
+
+#[test]
+fn test_cell_set_drops_old_value() {
+    let s = r#"fn main() {
+                   let c = Cell::new(box(1));
+                   c.set(box(2));
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Unit));
+    borrowck(s);
+}
+
+#[test]
+fn test_refcell_borrow_mut() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   let r = &c;
+                   let mut g = r.deref.borrow_mut();
+                   assign(g.deref, add(g.deref, 2));
+                   let x = g.deref;
+                   x
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(3)));
+    borrowck(s);
+}
+
+#[test]
+fn test_refcell_shared_borrows() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   let g1 = c.borrow();
+                   let g2 = c.borrow();
+                   add(g1.deref, g2.deref)
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(2)));
+    borrowck(s);
+}
+
+// The borrow of a guard ends when it goes out of scope, and that of a temporary at the end of its
+// statement.
+#[test]
+fn test_refcell_release() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   if true {
+                       let mut g = c.borrow_mut();
+                       assign(g.deref, 2);
+                   } else {
+                   };
+                   c.borrow_mut();
+                   let g = c.borrow();
+                   let h = c.borrow();
+                   add(g.deref, h.deref)
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(4)));
+    borrowck(s);
+}
+
+// A moved guard ends its borrow only once, when its new owner goes out of scope.
+#[test]
+fn test_refcell_moved_guard() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   let mut g = if true {
+                       let h = c.borrow_mut();
+                       h
+                   } else {
+                       c.borrow_mut()
+                   };
+                   assign(g.deref, 5);
+                   g.deref
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(5)));
+    borrowck(s);
+}
+
+// Borrowck accepts these, since all of the borrows are shared, but the RefCell rejects them.
+#[test]
+fn test_refcell_borrow_mut_while_borrowed() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   let g = c.borrow();
+                   let h = c.borrow_mut();
+                   g.deref
+               }"#;
+    assert_eq!(run(s).0, Err(Error::BorrowMutError));
+    borrowck(s);
+}
+
+#[test]
+fn test_refcell_borrow_while_mutably_borrowed() {
+    let s = r#"fn main() -> i32 {
+                   let c = RefCell::new(1);
+                   let g = c.borrow_mut();
+                   let h = c.borrow();
+                   h.deref
+               }"#;
+    assert_eq!(run(s).0, Err(Error::BorrowError));
+    borrowck(s);
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_assign_refcell_while_borrowed() {
+    borrowck(
+        r#"fn main() -> i32 {
+               let mut c = RefCell::new(1);
+               let g = c.borrow();
+               assign(c, RefCell::new(2));
+               g.deref
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_reference_outlives_guard() {
+    borrowck(
+        r#"fn main() -> i32 {
+               let c = RefCell::new(1);
+               let r = if true {
+                   let g = c.borrow();
+                   &g.deref
+               } else {
+                   let h = c.borrow();
+                   &h.deref
+               };
+               r.deref
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "cannot assign to immutable variable")]
+fn test_assign_through_shared_guard() {
+    Program::parse(
+        r#"fn main() {
+               let c = RefCell::new(1);
+               let g = c.borrow();
+               assign(g.deref, 2);
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Method: Cell::get needs a Copy type, found String")]
+fn test_get_non_copy() {
+    Program::parse(
+        r#"fn main() {
+               let c = Cell::new("a");
+               let s = c.get();
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Method: no method borrow for Cell<String>")]
+fn test_no_method() {
+    Program::parse(
+        r#"fn main() {
+               let c = Cell::new("a");
+               let g = c.borrow();
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
@@ -0,0 +1,268 @@
+use ownership_v4::ast::Program;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+#[test]
+fn test_parse_and_display() {
+    let p = Program::parse(
+        r#"fn main() -> i32 {
+               let mut n = 1;
+               let mut c = |x: i32, y| assign(n, add(x, y));
+               c(1, 2);
+               let d = move || { n };
+               d()
+           }"#,
+    )
+    .expect("Should parse");
+    let s = p.to_string();
+    assert!(s.contains("|x: i32, y| n = add(x, y)"), "{}", s);
+    assert!(s.contains("move || {"), "{}", s);
+    let s = p.infer().to_string();
+    assert!(
+        s.contains("c: main::{closure#0} = |x: i32, y: i32|"),
+        "{}",
+        s
+    );
+    assert!(s.contains("d: main::{closure#1} = move ||"), "{}", s);
+}
+
+#[test]
+fn test_capture_by_ref() {
+    let s = r#"fn main() -> i32 {
+                   let n = 1;
+                   let s = "hello";
+                   let c = |x: i32| { print(&s); add(x, n) };
+                   let a = c(2);
+                   let b = c(3);
+                   print(&s);
+                   add(a, b)
+               }"#;
+    assert_eq!(
+        run(s),
+        (Ok(Value::Int(7)), "hello\nhello\nhello\n".to_string())
+    );
+    borrowck(s);
+}
+
+#[test]
+fn test_capture_by_mut_ref() {
+    let s = r#"fn main() -> i32 {
+                   let mut n = 1;
+                   let mut c = |x: i32| assign(n, add(n, x));
+                   c(2);
+                   c(3);
+                   n
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(6)));
+    borrowck(s);
+}
+
+#[test]
+fn test_capture_by_move() {
+    let s = r#"fn main() {
+                   let s = "moved";
+                   let c = || { let t = s; print(&t) };
+                   c();
+               }"#;
+    assert_eq!(run(s).1, "moved\n");
+    borrowck(s);
+}
+
+// A `move` closure holds a copy of `n`, so assigning to `n` does not change what it sees.
+#[test]
+fn test_move_closure() {
+    let s = r#"fn main() -> i32 {
+                   let mut n = 1;
+                   let c = move || n;
+                   assign(n, 5);
+                   add(c(), n)
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(6)));
+    borrowck(s);
+}
+
+// Only `p.x` is captured, so `p.y` can still be borrowed while the closure is live.
+#[test]
+fn test_disjoint_fields() {
+    let s = r#"struct P { x: String, y: String }
+               fn main() {
+                   let mut p = P { x: "a", y: "b" };
+                   let mut c = || assign(p.x, "c");
+                   let r = &p.y;
+                   c();
+                   print(r);
+                   print(&p.x);
+               }"#;
+    assert_eq!(run(s).1, "b\nc\n");
+    borrowck(s);
+}
+
+#[test]
+fn test_capture_through_reference() {
+    let s = r#"fn main() -> i32 {
+                   let mut n = 1;
+                   let r = &mut n;
+                   let mut c = || assign(r.deref, 5);
+                   c();
+                   n
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(5)));
+    borrowck(s);
+}
+
+#[test]
+fn test_nested_closures() {
+    let s = r#"fn main() -> i32 {
+                   let mut n = 1;
+                   let k = 2;
+                   let mut c = || {
+                       let mut d = || assign(n, add(n, k));
+                       d();
+                       d()
+                   };
+                   c();
+                   n
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(5)));
+    borrowck(s);
+}
+
+#[test]
+fn test_capture_in_generic_function() {
+    let s = r#"fn apply<T>(x: T) -> T {
+                   let c = move || x;
+                   c()
+               }
+               fn main() -> i32 {
+                   apply(3)
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(3)));
+    borrowck(s);
+}
+
+#[test]
+fn test_assign_after_last_call() {
+    borrowck(
+        r#"fn main() -> i32 {
+               let mut n = 1;
+               let mut c = || assign(n, 2);
+               c();
+               assign(n, 3);
+               n
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_assign_while_captured() {
+    borrowck(
+        r#"fn main() -> i32 {
+               let mut n = 1;
+               let c = || n;
+               assign(n, 3);
+               c()
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_borrow_while_mutably_captured() {
+    borrowck(
+        r#"fn main() -> i32 {
+               let mut n = 1;
+               let mut c = || assign(n, 2);
+               let r = &n;
+               c();
+               r.deref
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_move_while_captured() {
+    borrowck(
+        r#"fn main() {
+               let s = "a";
+               let c = || print(&s);
+               let t = s;
+               c();
+           }"#,
+    );
+}
+
+// Each branch calls the FnOnce closure at most once.
+#[test]
+fn test_call_fn_once_in_branches() {
+    let s = r#"fn main() {
+                   let s = "moved";
+                   let c = || { let t = s; print(&t) };
+                   if true {
+                       c();
+                   } else {
+                       c();
+                   };
+               }"#;
+    assert_eq!(run(s).1, "moved\n");
+    borrowck(s);
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_call_fn_once_twice() {
+    borrowck(
+        r#"fn main() {
+               let s = "a";
+               let c = || { let t = s; print(&t) };
+               c();
+               c();
+           }"#,
+    );
+}
+
+// The second iteration calls the closure that the first one moved.
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_call_fn_once_in_loop() {
+    borrowck(
+        r#"fn main() {
+               let s = "a";
+               let c = move || { let t = s; print(&t) };
+               loop {
+                   c();
+               }
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_use_after_move_closure() {
+    borrowck(
+        r#"fn main() {
+               let s = "a";
+               let c = move || print(&s);
+               c();
+               print(&s);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Call: n is not a closure")]
+fn test_call_non_closure() {
+    Program::parse(
+        r#"fn main() -> i32 {
+               let n = 1;
+               n(2)
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
@@ -0,0 +1,49 @@
+// Helpers shared by the tests of language features. Not every test file uses all of them.
+#![allow(dead_code)]
+
+use ownership_v4::ast::Function;
+use ownership_v4::ast::Program;
+use ownership_v4::eval::Evaluator;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+
+// Checks every function of a program, including the instances of generic functions and the
+// functions of closures.
+pub fn borrowck(s: &str) {
+    let p = Program::parse(s).expect("Should parse").infer();
+    println!("{}", p);
+    for mut f in p.into_mir() {
+        f.compute_liveness();
+        println!("{}", f.verbose());
+        f.borrowck();
+    }
+}
+
+// Runs `main` with both the evaluator and the interpreter, which must agree.
+pub fn run(s: &str) -> (Result<Value, Error>, String) {
+    let p = Program::parse(s).expect("Should parse").infer();
+    let main = p.function("main").expect("Should have main");
+    let mut evaluator = Evaluator::new().with_functions(&p.functions);
+    let expected = evaluator.run(main, vec![]);
+    let mir = p.into_mir();
+    let main = mir.iter().find(|f| f.id == "main").unwrap();
+    let mut interp = Interpreter::new(main).with_functions(&mir);
+    let actual = interp.run(vec![]);
+    assert_eq!(expected, actual);
+    assert_eq!(evaluator.stdout, interp.stdout);
+    (actual, interp.stdout)
+}
+
+// Like `run`, for a single function with the given arguments.
+pub fn run_function(s: &str, args: Vec<Value>) -> (Result<Value, Error>, String) {
+    let f = Function::parse(s).expect("Should parse").infer();
+    let mut evaluator = Evaluator::new();
+    let expected = evaluator.run(&f, args.clone());
+    let mir = f.into_mir();
+    let mut interp = Interpreter::new(&mir);
+    let actual = interp.run(args);
+    assert_eq!(expected, actual);
+    assert_eq!(evaluator.stdout, interp.stdout);
+    (actual, interp.stdout)
+}
@@ -0,0 +1,159 @@
+use ownership_v4::ast::Function;
+use ownership_v4::mir::Constant;
+use ownership_v4::mir::Operand;
+use ownership_v4::mir::Operation;
+use ownership_v4::mir::Rvalue;
+
+macro_rules! check {
+    ($a:expr, $b:expr) => {
+        assert!($a == $b, "\n{}\n{}", $b, $a);
+    };
+}
+
+#[test]
+fn test_copy_propagation0() {
+    let f = Function::parse("fn f(a: i32) -> i32 { let x = a; let y = x; let z = 5; y }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_copy_propagation()
+        .with_liveness()
+        .with_dead_store_elimination();
+    check!(
+        f.to_string(),
+        indoc::indoc! {
+        "fn f(a: i32) -> i32 {
+             let _0: i32;
+             let _1: i32;
+             let _2: i32;
+             let _3: i32;
+             bb0: {
+                 StorageLive(_1);
+                 StorageLive(_2);
+                 StorageLive(_3);
+                 _0 = copy a;
+                 StorageDead(_3);
+                 StorageDead(_2);
+                 StorageDead(_1);
+                 return;
+             }
+         }"}
+    );
+}
+
+#[test]
+fn test_copy_propagation_borrowed() {
+    let f = Function::parse("fn f() -> i32 { let x = 1; let y = x; let r = &x; y }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_copy_propagation();
+    check!(
+        f.to_string(),
+        indoc::indoc! {
+        "fn f() -> i32 {
+             let _0: i32;
+             let _1: i32;
+             let _2: i32;
+             let _3: &{shared(_1)} i32;
+             let _4: &{shared(_1)} i32;
+             bb0: {
+                 StorageLive(_1);
+                 _1 = const 1;
+                 StorageLive(_2);
+                 _2 = copy _1;
+                 StorageLive(_3);
+                 _3 = &_1;
+                 StorageLive(_4);
+                 _4 = copy _3;
+                 _0 = copy _2;
+                 StorageDead(_4);
+                 StorageDead(_3);
+                 StorageDead(_2);
+                 StorageDead(_1);
+                 return;
+             }
+         }"}
+    );
+}
+
+#[test]
+fn test_dead_store_elimination_reassign() {
+    let f = Function::parse("fn f() -> i32 { let mut x = 1; assign(x, 2); x }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_predecessors()
+        .with_successors()
+        .with_liveness()
+        .with_dead_store_elimination();
+    check!(
+        f.to_string(),
+        indoc::indoc! {
+        "fn f() -> i32 {
+             let _0: i32;
+             let _1: i32;
+             bb0: {
+                 StorageLive(_1);
+                 _1 = const 2;
+                 _0 = copy _1;
+                 StorageDead(_1);
+                 return;
+             }
+         }"}
+    );
+}
+
+// The condition is read by the terminator, so it is live after the last statement of the block,
+// which keeps its definition, but not out of the block.
+#[test]
+fn test_condition_live_into_terminator() {
+    let f = Function::parse("fn f(x: i32) -> i32 { let c = true; if c { x } else { 2 } }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_liveness();
+    let block = &f.blocks[0];
+    let cond = block.stmts.iter().find_map(|s| match &s.op {
+        Operation::Assign(p, Rvalue::Use(Operand::Constant(Constant::Bool(true)))) => Some(p),
+        _ => None,
+    });
+    let cond = cond.expect("Should assign the condition").clone();
+    assert!(block.stmts.last().unwrap().live_out.contains(&cond));
+    assert!(!block.live_out.contains(&cond));
+    assert!(block.live_out.iter().any(|p| p.local.id == "x"));
+    let f = f.with_dead_store_elimination();
+    assert!(f.blocks[0]
+        .stmts
+        .iter()
+        .any(|s| matches!(&s.op, Operation::Assign(p, _) if *p == cond)));
+}
+
+#[test]
+fn test_copy_propagation_discriminant() {
+    let f = Function::parse(
+        r#"enum E { A(i32), B }
+           fn f(e: E) -> i32 {
+               let r = &e;
+               let s = r;
+               match s.deref {
+                   E::A(x) => { 1 },
+                   E::B => { 2 },
+               }
+           }"#,
+    )
+    .unwrap()
+    .infer()
+    .into_mir()
+    .with_copy_propagation();
+    let discriminants = f
+        .blocks
+        .iter()
+        .flat_map(|b| &b.stmts)
+        .filter_map(|s| match &s.op {
+            Operation::Assign(_, Rvalue::Discriminant(p)) => Some(p.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(discriminants, vec!["_1.deref".to_string()], "{}", f);
+}
@@ -0,0 +1,129 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir;
+
+mod common;
+
+use common::borrowck;
+
+fn lower(s: &str) -> mir::Function {
+    let f = Function::parse(s).expect("Should parse").infer();
+    let mir = f.into_mir();
+    println!("{}", mir);
+    mir
+}
+
+fn drops(f: &mir::Function) -> Vec<String> {
+    f.to_string()
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("Drop("))
+        .map(str::to_string)
+        .collect()
+}
+
+// Runs a function once for each value of its condition. The interpreter rejects drops of places
+// that have been moved out of, and double frees of boxes.
+fn run_both(f: &mir::Function) {
+    for c in [true, false] {
+        let mut interp = Interpreter::new(f);
+        interp
+            .run(vec![Value::Bool(c)])
+            .unwrap_or_else(|e| panic!("Should interpret with {}: {:?}", c, e));
+    }
+}
+
+#[test]
+fn test_drop_at_scope_end() {
+    let f = lower(
+        r#"fn f() {
+               let s = "a";
+               print(&s);
+           }"#,
+    );
+    assert_eq!(drops(&f), vec!["Drop(_1);"]);
+}
+
+#[test]
+fn test_moved_is_not_dropped() {
+    let f = lower(
+        r#"fn f(c: bool) {
+               let s = "a";
+               let t = s;
+               print(&t);
+           }"#,
+    );
+    assert_eq!(drops(&f).len(), 1);
+    run_both(&f);
+}
+
+#[test]
+fn test_drop_flag() {
+    let f = lower(
+        r#"fn f(c: bool) {
+               let b = box(1);
+               if c {
+                   let d = b;
+               } else {
+               };
+           }"#,
+    );
+    let mir = f.to_string();
+    assert!(mir.contains("bool"), "{}", mir);
+    assert_eq!(drops(&f).len(), 2);
+    run_both(&f);
+}
+
+#[test]
+fn test_partial_move() {
+    let f = lower(
+        r#"fn f(c: bool) {
+               let t = ("a", box(1));
+               let s = t.index(0);
+               print(&s);
+           }"#,
+    );
+    assert!(
+        drops(&f).iter().any(|d| d.ends_with(".1);")),
+        "{:?}",
+        drops(&f)
+    );
+    run_both(&f);
+}
+
+#[test]
+fn test_drop_on_break() {
+    let f = lower(
+        r#"fn f(c: bool) {
+               loop {
+                   let b = box(1);
+                   if c {
+                       break;
+                   } else {
+                   };
+                   let d = b;
+                   break;
+               }
+           }"#,
+    );
+    assert_eq!(drops(&f).len(), 2);
+    run_both(&f);
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_borrow_outlives_string() {
+    borrowck(
+        r#"fn f() {
+               let s = "s";
+               let r = if true {
+                   let t = "t";
+                   &t
+               } else {
+                   &s
+               };
+               print(r);
+           }"#,
+    );
+}
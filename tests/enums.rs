@@ -0,0 +1,113 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run_function;
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse(
+        r#"enum Shape { Circle(i32), Rect(i32, i32), Empty }
+           fn f() -> i32 {
+               let s = Shape::Rect(2, 3);
+               match s {
+                   Shape::Circle(r) => { r },
+                   Shape::Rect(w, _) => { w },
+                   _ => { 0 },
+               }
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+    let s = f.to_string();
+    assert!(s.contains("Shape::Rect(2, 3)"), "{}", s);
+    assert!(s.contains("Shape::Rect(w, _) =>"), "{}", s);
+    assert!(s.contains("_ =>"), "{}", s);
+}
+
+#[test]
+fn test_match_by_value() {
+    let s = r#"enum Shape { Circle(i32), Rect(i32, i32), Empty }
+               fn f() -> i32 {
+                   let s = Shape::Rect(2, 3);
+                   let t = Shape::Empty;
+                   let a = match s {
+                       Shape::Circle(r) => { r },
+                       Shape::Rect(w, h) => { add(w, h) },
+                       Shape::Empty => { 0 },
+                   };
+                   let b = match t {
+                       Shape::Circle(r) => { r },
+                       _ => { 100 },
+                   };
+                   add(a, b)
+               }"#;
+    assert_eq!(run_function(s, vec![]).0, Ok(Value::Int(105)));
+}
+
+#[test]
+fn test_match_by_ref() {
+    let s = r#"enum Message { Text(String), Quit }
+               fn f() {
+                   let mut m = Message::Text("hello");
+                   match m {
+                       Message::Text(ref mut t) => { assign(t.deref, "bye") },
+                       Message::Quit => { },
+                   };
+                   match m {
+                       Message::Text(ref t) => { print(t) },
+                       Message::Quit => { },
+                   };
+               }"#;
+    assert_eq!(
+        run_function(s, vec![]),
+        (Ok(Value::Unit), "bye\n".to_string())
+    );
+}
+
+#[test]
+fn test_borrow_payload_after_match() {
+    borrowck(
+        r#"enum Message { Text(String), Quit }
+           fn f() {
+               let m = Message::Text("hello");
+               let q = "quit";
+               let r = match m {
+                   Message::Text(ref t) => { t },
+                   Message::Quit => { &q },
+               };
+               print(r);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_write_while_payload_borrowed() {
+    borrowck(
+        r#"enum Message { Text(String), Quit }
+           fn f() {
+               let mut m = Message::Text("hello");
+               let mut q = "quit";
+               let r = match m {
+                   Message::Text(ref mut t) => { t },
+                   Message::Quit => { &mut q },
+               };
+               assign(m, Message::Quit);
+               print(r);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Match: variant Text of Message is not covered")]
+fn test_err_non_exhaustive() {
+    Function::parse(
+        r#"enum Message { Text(String), Quit }
+           fn f() { let m = Message::Quit; match m { Message::Quit => { } }; }"#,
+    )
+    .unwrap()
+    .infer();
+}
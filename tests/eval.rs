@@ -0,0 +1,163 @@
+use ownership_v4::ast::Function;
+use ownership_v4::eval::Evaluator;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+
+const PIPELINE: &str = "copy_propagation, dead_store_elimination, merge_blocks, remove_unused_variables";
+
+// Runs a program with the AST evaluator and the MIR interpreter, before and after optimization,
+// and checks that they all agree.
+fn check(s: &str, args: Vec<Value>) -> (Value, String) {
+    let f = Function::parse(s).unwrap().infer();
+    let mut evaluator = Evaluator::new();
+    let expected = evaluator.run(&f, args.clone()).expect("Should evaluate");
+
+    let mir = f.into_mir();
+    let mut interp = Interpreter::new(&mir);
+    let actual = interp.run(args.clone()).expect("Should interpret");
+    assert_eq!(expected, actual);
+    assert_eq!(evaluator.stdout, interp.stdout);
+
+    let mir = mir.with_pipeline(PIPELINE);
+    let mut interp = Interpreter::new(&mir);
+    let actual = interp.run(args).expect("Should interpret optimized");
+    assert_eq!(expected, actual, "\n{}", mir);
+    assert_eq!(evaluator.stdout, interp.stdout);
+
+    (expected, evaluator.stdout)
+}
+
+#[test]
+fn test_arith() {
+    let (v, _) = check("fn f(a: i32) -> i32 { let x = a; let y = add(x, 2); y }", vec![Value::Int(1)]);
+    assert_eq!(v, Value::Int(3));
+}
+
+#[test]
+fn test_ifelse() {
+    let s = "fn f(c: bool) -> i32 { let x = if c { 1 } else { 2 }; add(x, 10) }";
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(11));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(12));
+}
+
+#[test]
+fn test_tuples() {
+    let s = "fn f() -> i32 { let x = (1, (2, 3)); let y = x.index(1); add(x.index(0), y.index(1)) }";
+    assert_eq!(check(s, vec![]).0, Value::Int(4));
+}
+
+#[test]
+fn test_references() {
+    let s = r#"fn f() -> i32 {
+                   let mut x = (1, 2);
+                   let r = &mut x.index(1);
+                   assign(r.deref, 5);
+                   add(x.index(0), x.index(1))
+               }"#;
+    assert_eq!(check(s, vec![]).0, Value::Int(6));
+}
+
+#[test]
+fn test_print() {
+    let s = r#"fn f(c: bool) {
+                   let x = "a";
+                   let y = "b";
+                   if c { print(&x) } else { print(&y) };
+                   print(&x);
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(false)]).1, "b\na\n");
+}
+
+#[test]
+fn test_loop_break_continue() {
+    let s = r#"fn f() -> i32 {
+                   let mut n = 0;
+                   let mut done = false;
+                   loop {
+                       if done { break } else { assign(done, true) };
+                       assign(n, add(n, 1));
+                       continue
+                   };
+                   n
+               }"#;
+    assert_eq!(check(s, vec![]).0, Value::Int(1));
+}
+
+#[test]
+fn test_deferred_init() {
+    let s = r#"fn f(c: bool) -> i32 {
+                   let mut x;
+                   if c { assign(x, 1) } else { assign(x, 2) };
+                   add(x, 10)
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(11));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(12));
+}
+
+#[test]
+fn test_break_value() {
+    let s = r#"fn f(c: bool) -> i32 {
+                   let mut i = 0;
+                   let x = loop {
+                       if c { break add(i, 10); } else { };
+                       assign(i, add(i, 1));
+                       if true { break i; } else { };
+                   };
+                   x
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(10));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(1));
+}
+
+#[test]
+fn test_early_return() {
+    let s = r#"fn f(c: bool) -> i32 {
+                   if c { return 1; } else { };
+                   2
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(1));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(2));
+}
+
+#[test]
+fn test_labeled_loops() {
+    let s = r#"fn f(c: bool) -> i32 {
+                   let mut sum = 0;
+                   let mut first = true;
+                   'outer: loop {
+                       loop {
+                           assign(sum, add(sum, 1));
+                           if first { assign(first, false); continue 'outer; } else { };
+                           if c { break 'outer; } else { };
+                           break;
+                       };
+                       assign(sum, add(sum, 10));
+                       break;
+                   };
+                   sum
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(2));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(12));
+}
+
+#[test]
+fn test_labeled_while() {
+    let s = r#"fn f(c: bool) -> i32 {
+                   let mut sum = 0;
+                   let mut go = true;
+                   let mut first = true;
+                   'outer: while go {
+                       assign(sum, add(sum, 1));
+                       loop {
+                           if first { assign(first, false); continue 'outer; } else { };
+                           if c { break 'outer; } else { };
+                           assign(go, false);
+                           break;
+                       };
+                       assign(sum, add(sum, 10));
+                   };
+                   sum
+               }"#;
+    assert_eq!(check(s, vec![Value::Bool(true)]).0, Value::Int(2));
+    assert_eq!(check(s, vec![Value::Bool(false)]).0, Value::Int(12));
+}
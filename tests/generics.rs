@@ -0,0 +1,143 @@
+use ownership_v4::ast::Program;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+fn lower(s: &str) -> Vec<mir::Function> {
+    let p = Program::parse(s).expect("Should parse").infer();
+    println!("{}", p);
+    let fs = p.into_mir();
+    for f in &fs {
+        println!("{}", f);
+    }
+    fs
+}
+
+const SWAP: &str = r#"fn swap<'a, 'b, T>(a: &'a mut T, b: &'b mut T) {
+                          let t = a.deref;
+                          assign(a.deref, b.deref);
+                          assign(b.deref, t);
+                      }"#;
+
+#[test]
+fn test_parse_and_display() {
+    let p = Program::parse(
+        r#"fn id<T>(x: T) -> T {
+               x
+           }"#,
+    )
+    .expect("Should parse");
+    let s = p.to_string();
+    assert!(s.contains("fn id<T>(x: T) -> T"), "{}", s);
+}
+
+#[test]
+fn test_swap() {
+    let s = format!(
+        r#"{}
+           fn main() {{
+               let mut x = "x";
+               let mut y = "y";
+               swap(&mut x, &mut y);
+               print(&x);
+               let mut i = 1;
+               let mut j = 2;
+               swap(&mut i, &mut j);
+           }}"#,
+        SWAP
+    );
+    let fs = lower(&s);
+    let ids = fs.iter().map(|f| f.id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["main", "swap::<String>", "swap::<i32>"]);
+    borrowck(&s);
+    assert_eq!(run(&s), (Ok(Value::Unit), "y\n".to_string()));
+}
+
+#[test]
+fn test_instance_is_concrete() {
+    let fs = lower(
+        r#"fn consume<T>(x: T) {
+           }
+
+           fn main() {
+               consume(1);
+               consume(box(1));
+           }"#,
+    );
+    let drops = |id: &str| {
+        let f = fs.iter().find(|f| f.id == id).unwrap();
+        f.to_string().matches("Drop(").count()
+    };
+    assert_eq!(drops("consume::<i32>"), 0);
+    assert_eq!(drops("consume::<Box<i32>>"), 1);
+}
+
+#[test]
+fn test_nested_instances() {
+    let s = r#"fn id<T>(x: T) -> T {
+                   x
+               }
+
+               fn twice<T>(x: T) -> T {
+                   id(id(x))
+               }
+
+               fn main() {
+                   let s = twice("a");
+                   print(&s);
+               }"#;
+    let fs = lower(s);
+    assert!(fs.iter().all(|f| f.type_params.is_empty()));
+    assert!(fs.iter().any(|f| f.id == "id::<String>"));
+    borrowck(s);
+    assert_eq!(run(s), (Ok(Value::Unit), "a\n".to_string()));
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_result_borrows_argument() {
+    borrowck(
+        r#"fn id<T>(x: T) -> T {
+               x
+           }
+
+           fn main() {
+               let mut s = "a";
+               let r = id(&s);
+               assign(s, "b");
+               print(r);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Call: mismatched types: expected i32, found bool")]
+fn test_err_mismatched_type_args() {
+    lower(
+        r#"fn pick<T>(x: T, y: T) -> T {
+               x
+           }
+
+           fn main() {
+               pick(1, true);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Call: cannot infer type parameter T")]
+fn test_err_cannot_infer() {
+    lower(
+        r#"fn f<T>(x: i32) -> i32 {
+               x
+           }
+
+           fn main() {
+               f(1);
+           }"#,
+    );
+}
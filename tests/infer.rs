@@ -1,4 +1,8 @@
 use ownership_v4::ast::Function;
+use ownership_v4::ast::Local;
+use ownership_v4::ast::Place;
+use ownership_v4::ast::PlaceElem;
+use ownership_v4::ast::Type;
 
 #[test]
 fn test1() {
@@ -21,3 +25,124 @@ fn test3() {
         .infer();
     println!("{}", f.verbose());
 }
+
+#[test]
+fn test_infer_from_later_use() {
+    let f = Function::parse("fn f() -> i32 { let mut x; assign(x, 1); x }")
+        .unwrap()
+        .infer();
+    let s = f.verbose().to_string();
+    assert!(s.contains("let x: i32"), "{}", s);
+}
+
+#[test]
+fn test_infer_loans_of_every_assignment() {
+    let f = Function::parse(
+        r#"fn f(c: bool) {
+               let a = "a";
+               let b = "b";
+               let mut r;
+               if c {
+                   assign(r, &a);
+               } else {
+                   assign(r, &b);
+               };
+               print(r);
+           }"#,
+    )
+    .unwrap()
+    .infer();
+    let s = f.to_string();
+    assert!(s.contains("let r: &{shared(a), shared(b)} String"), "{}", s);
+    let mut mir = f.into_mir();
+    mir.compute_liveness();
+    mir.borrowck();
+}
+
+#[test]
+#[should_panic(expected = "Let: mismatched types: expected i32, found bool")]
+fn test_err_annotation() {
+    Function::parse("fn f() { let x: i32 = true; }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Add: mismatched types: expected i32, found String")]
+fn test_err_add() {
+    Function::parse(r#"fn f() -> i32 { add(1, "a") }"#)
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "cannot infer the type of x")]
+fn test_err_cannot_infer() {
+    Function::parse("fn f() { let x; }").unwrap().infer();
+}
+
+#[test]
+fn test_never() {
+    let f =
+        Function::parse("fn f(c: bool) -> i32 { let x = if c { 1 } else { return 2 }; loop { } }")
+            .unwrap()
+            .infer();
+    let s = f.verbose().to_string();
+    assert!(s.contains("let x: i32"), "{}", s);
+    assert_eq!(f.block.ty().to_string(), "!");
+}
+
+#[test]
+#[should_panic(expected = "Return: mismatched types: expected i32, found bool")]
+fn test_err_return() {
+    Function::parse("fn f() -> i32 { return true; }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Break: mismatched types: expected i32, found String")]
+fn test_err_break() {
+    Function::parse(r#"fn f(c: bool) { let x = loop { if c { break 1; } else { break "a"; } }; }"#)
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Break: only a loop can break with a value")]
+fn test_err_break_while() {
+    Function::parse("fn f() { while true { break 1; } }")
+        .unwrap()
+        .infer();
+}
+
+// The elements of a place project out of the type of its local from the outside in.
+#[test]
+fn test_place_ty() {
+    let inner = Type::Tuple(vec![Type::Int, Type::Bool]);
+    let local = Local {
+        id: "t".to_string(),
+        ty: Type::Tuple(vec![inner, Type::Unit]),
+        mutable: false,
+    };
+    let place = Place {
+        local,
+        elems: vec![PlaceElem::TupleIndex(0), PlaceElem::TupleIndex(1)],
+    };
+    assert_eq!(place.ty(), Type::Bool);
+}
+
+// The place is mutable through the mutable reference in the tuple, even though the tuple is not.
+#[test]
+fn test_assign_through_ref_in_tuple() {
+    Function::parse(
+        r#"fn f() -> i32 {
+               let mut a = 1;
+               let t = (2, &mut a);
+               assign(t.index(1).deref, 3);
+               a
+           }"#,
+    )
+    .unwrap()
+    .infer();
+}
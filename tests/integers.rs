@@ -0,0 +1,180 @@
+use ownership_v4::ast::Function;
+use ownership_v4::ast::IntTy;
+use ownership_v4::eval::Evaluator;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+
+// Runs a function with both the evaluator and the interpreter, which must agree.
+fn run(s: &str, args: Vec<Value>) -> Result<Value, Error> {
+    let f = Function::parse(s).expect("Should parse").infer();
+    let mut evaluator = Evaluator::new();
+    let expected = evaluator.run(&f, args.clone());
+    let mir = f.into_mir();
+    let mut interp = Interpreter::new(&mir);
+    let actual = interp.run(args);
+    assert_eq!(expected, actual);
+    actual
+}
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse("fn f(x: u64) -> usize { let y = 255u8; cast(y, usize) }")
+        .expect("Should parse")
+        .infer();
+    let s = f.to_string();
+    assert!(s.contains("fn f(x: u64) -> usize"), "{}", s);
+    assert!(s.contains("255u8"), "{}", s);
+    assert!(s.contains("cast(y, usize)"), "{}", s);
+}
+
+#[test]
+fn test_default_and_inferred_literals() {
+    let f = Function::parse("fn f(a: u8) -> u8 { let x = 1; let y = 2; add(a, y) }")
+        .unwrap()
+        .infer();
+    let s = f.verbose().to_string();
+    assert!(s.contains("let x: i32"), "{}", s);
+    assert!(s.contains("let y: u8"), "{}", s);
+}
+
+#[test]
+fn test_wide_types() {
+    let s = "fn f(a: u64) -> u64 { add(a, 4000000000) }";
+    let v = run(s, vec![Value::Integer(IntTy::U64, 4000000000)]);
+    assert_eq!(v, Ok(Value::Integer(IntTy::U64, 8000000000)));
+}
+
+#[test]
+fn test_overflow() {
+    let s = "fn f(a: u8) -> u8 { add(a, 1) }";
+    assert_eq!(
+        run(s, vec![Value::Integer(IntTy::U8, 254)]),
+        Ok(Value::Integer(IntTy::U8, 255))
+    );
+    assert_eq!(
+        run(s, vec![Value::Integer(IntTy::U8, 255)]),
+        Err(Error::Overflow)
+    );
+}
+
+#[test]
+fn test_casts() {
+    let s = "fn f() -> i8 { let x = cast(300, u8); cast(add(x, 211u8), i8) }";
+    assert_eq!(run(s, vec![]), Ok(Value::Integer(IntTy::I8, -1)));
+    let s = "fn f(c: bool) -> i64 { cast(c, i64) }";
+    assert_eq!(
+        run(s, vec![Value::Bool(true)]),
+        Ok(Value::Integer(IntTy::I64, 1))
+    );
+}
+
+#[test]
+fn test_constant_folding() {
+    let f = Function::parse("fn f() -> u16 { let x = 300u16; add(x, 1) }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_pipeline("constant_propagation");
+    let mir = f.to_string();
+    assert!(mir.contains("301u16"), "{}", mir);
+    assert!(!mir.contains("add("), "{}", mir);
+}
+
+// A constant operation that overflows is not folded, so that it overflows at runtime.
+#[test]
+fn test_constant_overflow() {
+    let f = Function::parse("fn f() -> u8 { let x = 200u8; add(x, 100) }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_pipeline("constant_propagation");
+    let mir = f.to_string();
+    assert!(mir.contains("add("), "{}", mir);
+    assert_eq!(f.interpret(vec![]), Err(Error::Overflow));
+}
+
+#[test]
+fn test_sub_and_mul() {
+    let s = "fn f(a: i64) -> i64 { mul(sub(a, 10), -3) }";
+    let v = run(s, vec![Value::Integer(IntTy::I64, 4)]);
+    assert_eq!(v, Ok(Value::Integer(IntTy::I64, 18)));
+    let s = "fn f(a: u8) -> u8 { sub(a, 1) }";
+    assert_eq!(
+        run(s, vec![Value::Integer(IntTy::U8, 0)]),
+        Err(Error::Overflow)
+    );
+}
+
+#[test]
+fn test_negative_literals() {
+    let f = Function::parse("fn f() -> i64 { add(-5i64, -2) }")
+        .expect("Should parse")
+        .infer();
+    let s = f.to_string();
+    assert!(s.contains("-5i64"), "{}", s);
+    assert_eq!(run(&s, vec![]), Ok(Value::Integer(IntTy::I64, -7)));
+}
+
+// Every operation is checked at the bounds of every type, both when it is folded and when it is
+// interpreted.
+#[test]
+fn test_min_max() {
+    for t in IntTy::ALL {
+        let (name, min, max) = (t.name(), t.min(), t.max());
+        let cases = [
+            (format!("add({}{}, 0)", max, name), Some(max)),
+            (format!("add({}{}, 1)", max, name), None),
+            (format!("sub({}{}, 0)", min, name), Some(min)),
+            (format!("sub({}{}, 1)", min, name), None),
+            (format!("sub({}{}, 1)", min + 1, name), Some(min)),
+            (format!("mul({}{}, 1)", max, name), Some(max)),
+            (format!("mul({}{}, 2)", max, name), None),
+            (format!("mul({}{}, {})", max, name, max), None),
+        ];
+        for (e, expected) in cases {
+            let s = format!("fn f() -> {} {{ let x = {}; x }}", name, e);
+            let expected = expected.map(|i| Value::int(t, i)).ok_or(Error::Overflow);
+            assert_eq!(run(&s, vec![]), expected, "{}", s);
+            let f = Function::parse(&s)
+                .unwrap()
+                .infer()
+                .into_mir()
+                .with_pipeline("constant_propagation");
+            assert_eq!(f.interpret(vec![]), expected, "{}", f);
+            assert_eq!(f.to_string().contains(&e[..3]), expected.is_err(), "{}", f);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "Int: literal -1 out of range for u8")]
+fn test_err_negative_unsigned() {
+    Function::parse("fn f() { let x: u8 = -1; }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Int: literal 256 out of range for u8")]
+fn test_err_literal_out_of_range() {
+    Function::parse("fn f() { let x: u8 = 256; }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Add: mismatched types: expected u8, found i64")]
+fn test_err_mixed_types() {
+    Function::parse("fn f() { let x = add(1u8, 1i64); }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "Cast: cannot cast String to u8")]
+fn test_err_cast_string() {
+    Function::parse(r#"fn f() { let x = cast("a", u8); }"#)
+        .unwrap()
+        .infer();
+}
@@ -0,0 +1,133 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+
+// Runs a program that borrowck accepts and returns its output.
+fn run(s: &str) -> String {
+    let f = Function::parse(s)
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_liveness();
+    f.borrowck();
+    let mut interp = Interpreter::new(&f);
+    interp.run(vec![]).expect("Should run");
+    interp.stdout
+}
+
+#[test]
+fn test_print() {
+    assert_eq!(run(r#"fn f() { let x = "hello"; print(&x); }"#), "hello\n");
+}
+
+#[test]
+fn test_mutable_borrow() {
+    let out = run(r#"fn f() {
+                         let mut x = "hello";
+                         let y = &mut x;
+                         assign(y.deref, "world");
+                         print(y);
+                     }"#);
+    assert_eq!(out, "world\n");
+}
+
+#[test]
+fn test_multiple_immutable_borrows() {
+    let out = run(r#"fn f() {
+                         let x = "hello";
+                         let a = &x;
+                         let b = &x;
+                         print(a);
+                         print(b);
+                     }"#);
+    assert_eq!(out, "hello\nhello\n");
+}
+
+#[test]
+fn test_reborrow() {
+    let out = run(r#"fn f() {
+                         let x = "hello";
+                         let a = &x;
+                         let b = &a.deref;
+                         print(b);
+                         print(a);
+                     }"#);
+    assert_eq!(out, "hello\nhello\n");
+}
+
+#[test]
+fn test_disjoint_fields() {
+    let f = Function::parse(
+        "fn f() -> i32 {
+             let mut x = (1, 2);
+             let a = &mut x.index(0);
+             let b = &mut x.index(1);
+             assign(a.deref, 5);
+             assign(b.deref, 6);
+             add(x.index(0), x.index(1))
+         }",
+    )
+    .unwrap()
+    .infer()
+    .into_mir();
+    assert_eq!(f.interpret(vec![]), Ok(Value::Int(11)));
+}
+
+#[test]
+fn test_loop() {
+    let f = Function::parse("fn f(c: bool) -> i32 { let mut x = 0; while c { assign(x, 1); }; x }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    assert_eq!(f.interpret(vec![Value::Bool(false)]), Ok(Value::Int(0)));
+    let mut interp = Interpreter::new(&f).with_fuel(100);
+    assert_eq!(interp.run(vec![Value::Bool(true)]), Err(Error::StepLimit));
+}
+
+#[test]
+fn test_err_missing_terminator() {
+    let mut f = Function::parse("fn f() -> i32 { 1 }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    f.blocks[0].terminator = None;
+    assert_eq!(f.interpret(vec![]), Err(Error::MissingTerminator(0)));
+}
+
+#[test]
+fn test_err_aliasing() {
+    // Rejected by borrowck, so we only lower it.
+    let f = Function::parse(
+        "fn f() -> i32 {
+             let mut x = 1;
+             let a = &mut x;
+             let b = &mut x;
+             assign(a.deref, 2);
+             0
+         }",
+    )
+    .unwrap()
+    .infer()
+    .into_mir();
+    assert!(matches!(
+        f.interpret(vec![]),
+        Err(Error::AliasingViolation { write: true, .. })
+    ));
+}
+
+#[test]
+fn test_err_use_after_move() {
+    let f = Function::parse(
+        r#"fn f() {
+               let mut x = "hello";
+               let a = &mut x;
+               let b = a;
+               print(a);
+           }"#,
+    )
+    .unwrap()
+    .infer()
+    .into_mir();
+    assert!(matches!(f.interpret(vec![]), Err(Error::UseOfUninit(_))));
+}
@@ -0,0 +1,162 @@
+use ownership_v4::ast::Program;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+const LONGEST: &str = r#"fn longest<'a>(x: &'a String, y: &'a String) -> &'a String {
+                             if true {
+                                 x
+                             } else {
+                                 y
+                             }
+                         }"#;
+
+#[test]
+fn test_parse_and_display() {
+    let p = Program::parse(
+        r#"fn first<'a, 'b: 'a>(x: &'a i32, y: &'b mut i32) -> &'a i32 {
+               x
+           }"#,
+    )
+    .expect("Should parse");
+    let s = p.to_string();
+    assert!(
+        s.contains("fn first<'a, 'b: 'a>(x: &'a i32, y: &'b mut i32) -> &'a i32"),
+        "{}",
+        s
+    );
+}
+
+#[test]
+fn test_call() {
+    let s = format!(
+        r#"{}
+           fn main() {{
+               let s = "a";
+               let t = "b";
+               let r = longest(&s, &t);
+               print(r);
+           }}"#,
+        LONGEST
+    );
+    borrowck(&s);
+    assert_eq!(run(&s), (Ok(Value::Unit), "a\n".to_string()));
+}
+
+#[test]
+fn test_reborrow() {
+    borrowck(
+        r#"fn get<'a>(x: &'a (i32, i32)) -> &'a i32 {
+               &x.deref.index(1)
+           }"#,
+    );
+}
+
+#[test]
+fn test_outlives() {
+    borrowck(
+        r#"fn second<'a, 'b: 'a>(x: &'a i32, y: &'b i32) -> &'a i32 {
+               y
+           }"#,
+    );
+}
+
+#[test]
+fn test_unrelated_lifetime() {
+    borrowck(
+        r#"fn first<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {
+               x
+           }
+
+           fn main() {
+               let a = 1;
+               let mut b = 2;
+               let r = first(&a, &b);
+               assign(b, 3);
+               let c = r;
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Function: 'b does not live long enough")]
+fn test_err_missing_bound() {
+    borrowck(
+        r#"fn second<'a, 'b>(x: &'a i32, y: &'b i32) -> &'a i32 {
+               y
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Function: y does not live long enough")]
+fn test_err_return_local() {
+    borrowck(
+        r#"fn dangle<'a>(x: &'a i32) -> &'a i32 {
+               let y = 1;
+               &y
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Function: undeclared lifetime 'b")]
+fn test_err_undeclared_lifetime() {
+    borrowck(
+        r#"fn f<'a>(x: &'b i32) -> &'a i32 {
+               x
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_assign_while_borrowed() {
+    borrowck(&format!(
+        r#"{}
+           fn main() {{
+               let s = "a";
+               let mut t = "b";
+               let r = longest(&s, &t);
+               assign(t, "c");
+               print(r);
+           }}"#,
+        LONGEST
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_assign_outliving_argument() {
+    borrowck(
+        r#"fn second<'a, 'b: 'a>(x: &'a i32, y: &'b i32) -> &'a i32 {
+               y
+           }
+
+           fn main() {
+               let a = 1;
+               let mut b = 2;
+               let r = second(&a, &b);
+               assign(b, 3);
+               let c = r;
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "does not live long enough")]
+fn test_err_return_local_early() {
+    borrowck(
+        r#"fn dangle<'a>(x: &'a i32, c: bool) -> &'a i32 {
+               let y = 1;
+               if c {
+                   return &y;
+               } else {
+               };
+               x
+           }"#,
+    );
+}
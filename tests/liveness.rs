@@ -1,4 +1,8 @@
 use ownership_v4::ast::Function;
+use ownership_v4::ast::Place;
+use ownership_v4::ast::PlaceElem;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir::Operation;
 
 #[test]
 fn test() {
@@ -7,3 +11,25 @@ fn test() {
     let f = f.into_mir().with_liveness();
     println!("{f}");
 }
+
+// Writing through a reference reads the reference, so the write is not a dead store.
+#[test]
+fn test_write_through_reference() {
+    let s = "fn f() -> i32 { let mut x = 1; let r = &mut x; assign(r.deref, 2); x }";
+    let f = Function::parse(s)
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_liveness();
+    let (stmt, r) = f.blocks[0]
+        .stmts
+        .iter()
+        .find_map(|s| match &s.op {
+            Operation::Assign(p, _) if p.elems.contains(&PlaceElem::Deref) => Some((s, &p.local)),
+            _ => None,
+        })
+        .expect("Should write through the reference");
+    assert!(stmt.live_in.contains(&Place::from(r.clone())), "{}", f);
+    let f = f.with_dead_store_elimination();
+    assert_eq!(f.interpret(vec![]), Ok(Value::Int(2)));
+}
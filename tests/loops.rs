@@ -0,0 +1,97 @@
+use ownership_v4::ast::Function;
+use ownership_v4::eval::Evaluator;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir;
+use ownership_v4::mir::Operation;
+use ownership_v4::mir::Terminator;
+
+fn analyse(s: &str) -> mir::Function {
+    Function::parse(s)
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_predecessors()
+        .with_successors()
+        .with_dominators()
+        .with_loops()
+}
+
+#[test]
+fn test_no_loops() {
+    let f = analyse("fn f() -> i32 { if true { 1 } else { 2 } }");
+    assert!(f.loops.loops.is_empty());
+    assert!(f.loops.is_reducible());
+}
+
+#[test]
+fn test_while() {
+    let f = analyse("fn f(x: bool) { while x { } }");
+    assert_eq!(f.loops.loops.len(), 1);
+    let l = &f.loops.loops[0];
+    assert_eq!(l.header, 1);
+    assert_eq!(l.latches, vec![2]);
+    assert!(l.body.contains(&1) && l.body.contains(&2));
+    assert_eq!(l.exits, vec![3]);
+    assert_eq!(l.depth, 1);
+    assert!(f.loops.is_back_edge(2, 1));
+    assert!(!f.loops.is_back_edge(0, 1));
+    assert!(f.loops.is_exit(1, 3));
+}
+
+#[test]
+fn test_nested() {
+    let f = analyse("fn f(x: bool) { while x { while x { } } }");
+    assert_eq!(f.loops.loops.len(), 2);
+    let outer = &f.loops.loops[0];
+    let inner = &f.loops.loops[1];
+    assert_eq!(outer.parent, None);
+    assert_eq!(inner.parent, Some(0));
+    assert_eq!(inner.depth, 2);
+    assert!(outer.body.contains(&inner.header));
+    assert_eq!(f.loops.depth(inner.header), 2);
+    assert_eq!(f.loops.depth(0), 0);
+}
+
+// Jumping back from the `then` branch adds a loop but keeps the number of blocks, so the loop
+// forest that was computed before is stale, and into_ast must not decompile from it. Otherwise
+// the loop is silently left out.
+#[test]
+fn test_into_ast_after_adding_loop() {
+    let s = r#"fn f(x: bool) -> i32 {
+                   let mut go = x;
+                   let mut n = 0;
+                   if true { } else { };
+                   assign(n, add(n, 1));
+                   if go { assign(go, false); } else { };
+                   n
+               }"#;
+    let mut f = analyse(s);
+    assert!(f.loops.loops.is_empty());
+    let (header, then) = f
+        .blocks
+        .iter()
+        .rev()
+        .find_map(|b| match b.terminator {
+            Some(Terminator::ConditionalGoto(_, then, _)) => Some((b.id, then)),
+            _ => None,
+        })
+        .unwrap();
+    f.blocks[then].terminator = Some(Terminator::Goto(header));
+    // The locals are declared up front instead, since the new loop crosses their scopes.
+    for b in &mut f.blocks {
+        b.stmts
+            .retain(|s| !matches!(s.op, Operation::StorageLive(_) | Operation::StorageDead(_)));
+    }
+    f.compute_predecessors();
+    f.compute_successors();
+    f.compute_dominators();
+    println!("{}", f);
+    assert_eq!(f.interpret(vec![Value::Bool(true)]), Ok(Value::Int(2)));
+    let f = f.into_ast();
+    println!("{}", f);
+    let mut evaluator = Evaluator::new();
+    assert_eq!(
+        evaluator.run(&f, vec![Value::Bool(true)]),
+        Ok(Value::Int(2))
+    );
+}
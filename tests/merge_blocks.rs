@@ -0,0 +1,56 @@
+use ownership_v4::ast::Function;
+
+macro_rules! check {
+    ($a:expr, $b:expr) => {
+        assert!($a == $b, "\n{}\n{}", $b, $a);
+    };
+}
+
+#[test]
+fn test_merge_blocks0() {
+    let f = Function::parse("fn f(x: bool) { loop { if x { break } else { continue } } }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_merge_blocks();
+    check!(
+        f.to_string(),
+        indoc::indoc! {
+        "fn f(x: bool) -> () {
+             let _0: ();
+             let _1: !;
+             bb0: {
+                 goto bb1;
+             }
+             bb1: {
+                 StorageLive(_1);
+                 if copy x goto bb2 else goto bb3;
+             }
+             bb2: {
+                 StorageDead(_1);
+                 _0 = const ();
+                 return;
+             }
+             bb3: {
+                 StorageDead(_1);
+                 goto bb1;
+             }
+         }"}
+    );
+    assert_eq!(f.validate(), Ok(()));
+    assert_eq!(f.predecessors, vec![vec![], vec![0, 3], vec![1], vec![1]]);
+    assert_eq!(f.successors, vec![vec![1], vec![2, 3], vec![], vec![1]]);
+    assert_eq!(f.domtree, vec![vec![1], vec![2, 3], vec![], vec![]]);
+}
+
+#[test]
+fn test_merge_blocks_infinite_loop() {
+    let f = Function::parse("fn f() { loop { } }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_merge_blocks();
+    assert_eq!(f.blocks.len(), 2);
+    assert_eq!(f.predecessors, vec![vec![], vec![0, 1]]);
+    assert_eq!(f.validate(), Ok(()));
+}
@@ -0,0 +1,273 @@
+use std::process::Command;
+
+use ownership_v4::ast::Function;
+use ownership_v4::ast::IntTy;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir::Constant;
+
+// Whether `cc` can be run. A missing `cc` fails the tests that need it, unless OWNERSHIP_SKIP_CC
+// is set, in which case they are skipped.
+fn has_cc() -> bool {
+    if Command::new("cc").arg("--version").output().is_ok() {
+        return true;
+    }
+    if std::env::var_os("OWNERSHIP_SKIP_CC").is_none() {
+        panic!("cc is not on PATH; set OWNERSHIP_SKIP_CC=1 to skip the tests that need it");
+    }
+    eprintln!("skipping: cc is not on PATH");
+    false
+}
+
+// Compiles a C file, runs it, and returns what it printed.
+fn compile_and_run(name: &str, c: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("mir_to_c_{}_{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let src = dir.join("main.c");
+    let exe = dir.join("main");
+    std::fs::write(&src, c).unwrap();
+    let status = Command::new("cc")
+        .arg("-std=c99")
+        .arg("-o")
+        .arg(&exe)
+        .arg(&src)
+        .status()
+        .expect("Should run the C compiler");
+    assert!(status.success(), "{}", c);
+    let output = Command::new(&exe).output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Checks that a program prints the same when compiled to C as when interpreted.
+fn check(name: &str, s: &str, args: Vec<Constant>) -> String {
+    let f = Function::parse(s).unwrap().infer().into_mir();
+    let values = args
+        .iter()
+        .map(|c| match c {
+            Constant::Int(i) => Value::Int(*i),
+            Constant::Integer(t, i) => Value::Integer(*t, *i),
+            Constant::Bool(b) => Value::Bool(*b),
+            Constant::String(s) => Value::String(s.clone()),
+            Constant::Unit => Value::Unit,
+        })
+        .collect();
+    let mut interp = Interpreter::new(&f);
+    interp.run(values).expect("Should interpret");
+    let stdout = compile_and_run(name, &f.to_c_with_main(&args));
+    assert_eq!(stdout, interp.stdout);
+    stdout
+}
+
+// Checks that a function without parameters returns the same integer when compiled to C as when
+// interpreted.
+fn check_int(name: &str, s: &str) -> i32 {
+    let f = Function::parse(s).unwrap().infer().into_mir();
+    let Value::Int(expected) = Interpreter::new(&f).run(vec![]).unwrap() else {
+        panic!("Expected an integer");
+    };
+    let c = format!(
+        "{}\nint main(void) {{\n    printf(\"%d\", fn_{}());\n    return 0;\n}}\n",
+        f.to_c(),
+        f.id
+    );
+    let actual = compile_and_run(name, &c).parse::<i32>().unwrap();
+    assert_eq!(actual, expected);
+    actual
+}
+
+#[test]
+fn test_print() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() { let x = "hello world"; print(&x); }"#;
+    assert_eq!(check("print", s, vec![]), "hello world\n");
+}
+
+#[test]
+fn test_ifelse() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f(c: bool) {
+                   let x = "a";
+                   let y = "b";
+                   if c { print(&x) } else { print(&y) };
+                   print(&x);
+               }"#;
+    assert_eq!(
+        check("ifelse_true", s, vec![Constant::Bool(true)]),
+        "a\na\n"
+    );
+    assert_eq!(
+        check("ifelse_false", s, vec![Constant::Bool(false)]),
+        "b\na\n"
+    );
+}
+
+#[test]
+fn test_tuples_and_references() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() -> i32 {
+                   let mut x = (1, (2, "a"));
+                   let r = &mut x.index(1).index(0);
+                   assign(r.deref, 5);
+                   add(x.index(0), x.index(1).index(0))
+               }"#;
+    assert_eq!(check_int("tuples", s), 6);
+}
+
+#[test]
+fn test_structs() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"struct Point { x: i32, y: i32 }
+               struct Line { a: Point, b: Point }
+               fn f() -> i32 {
+                   let mut l = Line { b: Point { x: 3, y: 4 }, a: Point { x: 1, y: 2 } };
+                   let r = &mut l.a;
+                   assign(r.deref.y, 10);
+                   add(l.a.y, l.b.x)
+               }"#;
+    assert_eq!(check_int("structs", s), 13);
+}
+
+#[test]
+fn test_enums() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"enum Shape { Circle(i32), Rect(i32, i32), Empty }
+               fn f() -> i32 {
+                   let mut s = Shape::Circle(1);
+                   match s {
+                       Shape::Circle(ref mut r) => { assign(r.deref, 7) },
+                       _ => { },
+                   };
+                   let a = match s {
+                       Shape::Circle(r) => { r },
+                       Shape::Rect(w, h) => { add(w, h) },
+                       Shape::Empty => { 0 },
+                   };
+                   let t = Shape::Rect(2, 3);
+                   match t {
+                       Shape::Rect(w, h) => { add(a, add(w, h)) },
+                       _ => { 0 },
+                   }
+               }"#;
+    assert_eq!(check_int("enums", s), 12);
+}
+
+#[test]
+fn test_boxes() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() -> i32 {
+                   let mut b = box((1, box(2)));
+                   assign(b.deref.index(1).deref, 5);
+                   let c = b;
+                   let s = box(box("moved"));
+                   let t = s;
+                   add(c.deref.index(0), c.deref.index(1).deref)
+               }"#;
+    assert_eq!(check_int("boxes", s), 6);
+}
+
+#[test]
+fn test_loop() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() -> i32 {
+                   let mut n = 0;
+                   let mut done = false;
+                   while true {
+                       assign(n, add(n, 1));
+                       if done { break } else { assign(done, true) };
+                   };
+                   n
+               }"#;
+    assert_eq!(check_int("loop", s), 2);
+}
+
+#[test]
+fn test_arithmetic() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() -> i32 {
+                   let a = mul(sub(4, 10), -3);
+                   let b = mul(sub(4i64, 10), -3000000000);
+                   add(a, cast(sub(b, 18000000000), i32))
+               }"#;
+    assert_eq!(check_int("arithmetic", s), 18);
+}
+
+#[test]
+fn test_string_escapes() {
+    let f = Function::parse(r#"fn f() { let x = "a\b"; print(&x); }"#)
+        .unwrap()
+        .infer()
+        .into_mir();
+    assert!(f.to_c().contains(r#""a\\b""#));
+}
+
+#[test]
+fn test_integer_types() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f() -> i32 {
+                   let a = 1u64;
+                   let b = add(a, 10000000000);
+                   let c = cast(b, i8);
+                   let d = add(cast(true, u8), 254u8);
+                   add(cast(c, i32), cast(d, i32))
+               }"#;
+    assert_eq!(check_int("integer_types", s), 256);
+}
+
+#[test]
+fn test_arrays() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f(i: usize) {
+                   let mut a = [(1, "a"), (2, "b"), (3, "c")];
+                   assign(a[i].index(1), "z");
+                   let b = [box(1), box(2)];
+                   print(&a[0].index(1));
+                   print(&a[i].index(1));
+               }"#;
+    assert_eq!(
+        check("arrays", s, vec![Constant::Integer(IntTy::Usize, 2)]),
+        "a\nz\n"
+    );
+}
+
+#[test]
+fn test_slices() {
+    if !has_cc() {
+        return;
+    }
+    let s = r#"fn f(i: usize) {
+                   let mut a = ["a", "b", "c", "d"];
+                   let x = &mut a[..2];
+                   let y = &mut a[2..];
+                   let z = &mut y.deref[1..];
+                   assign(x.deref[i], "x");
+                   assign(z.deref[0], "z");
+                   print(&a[1]);
+                   print(&a[3]);
+               }"#;
+    assert_eq!(
+        check("slices", s, vec![Constant::Integer(IntTy::Usize, 1)]),
+        "x\nz\n"
+    );
+}
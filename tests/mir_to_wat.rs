@@ -0,0 +1,207 @@
+use std::process::Command;
+
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Interpreter;
+use ownership_v4::interpreter::Value;
+
+// Instantiates a module, calls the function `f` with the given arguments, and prints what it
+// printed followed by what it returned.
+const HOST: &str = r#"
+const fs = require("fs");
+const [wasm, ...args] = process.argv.slice(2);
+let memory;
+let stdout = "";
+const env = {
+    print(address, length) {
+        stdout += Buffer.from(memory.buffer, address, length).toString("utf8") + "\n";
+    },
+};
+WebAssembly.instantiate(fs.readFileSync(wasm), { env }).then(({ instance }) => {
+    memory = instance.exports.memory;
+    const result = instance.exports.f(...args.map(Number));
+    process.stdout.write(stdout + "=" + (result === undefined ? "" : result));
+});
+"#;
+
+// Whether `node` can be run. A missing `node` fails the tests that need it, unless
+// OWNERSHIP_SKIP_NODE is set, in which case they are skipped.
+fn has_node() -> bool {
+    if Command::new("node").arg("--version").output().is_ok() {
+        return true;
+    }
+    if std::env::var_os("OWNERSHIP_SKIP_NODE").is_none() {
+        panic!("node is not on PATH; set OWNERSHIP_SKIP_NODE=1 to skip the tests that need it");
+    }
+    eprintln!("skipping: node is not on PATH");
+    false
+}
+
+// Checks that a program prints and returns the same when compiled to WebAssembly as when
+// interpreted. Arguments and results are integers or booleans.
+fn check(name: &str, s: &str, args: Vec<Value>) -> (String, String) {
+    let f = Function::parse(s).unwrap().infer().into_mir();
+    let mut interp = Interpreter::new(&f);
+    let result = interp.run(args.clone()).expect("Should interpret");
+
+    let wat = f.to_wat();
+    let wasm = wat::parse_str(&wat).unwrap_or_else(|e| panic!("{}\n{}", e, wat));
+    let dir = std::env::temp_dir().join(format!("mir_to_wat_{}_{}", std::process::id(), name));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("main.wasm"), wasm).unwrap();
+    std::fs::write(dir.join("host.js"), HOST).unwrap();
+    let output = Command::new("node")
+        .arg(dir.join("host.js"))
+        .arg(dir.join("main.wasm"))
+        .args(args.iter().map(|v| match v {
+            Value::Int(i) => i.to_string(),
+            Value::Bool(b) => (*b as i32).to_string(),
+            v => panic!("Unsupported argument {}", v),
+        }))
+        .output()
+        .expect("Should run node");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stderr),
+        wat
+    );
+    let output = String::from_utf8(output.stdout).unwrap();
+    let (stdout, actual) = output.rsplit_once('=').unwrap();
+    let expected = match result {
+        Value::Int(i) => i.to_string(),
+        Value::Bool(b) => (b as i32).to_string(),
+        Value::Unit => String::new(),
+        v => panic!("Unsupported result {}", v),
+    };
+    assert_eq!(actual, expected, "{}", wat);
+    assert_eq!(stdout, interp.stdout, "{}", wat);
+    (stdout.to_string(), actual.to_string())
+}
+
+#[test]
+fn test_print() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"fn f() { let x = "hello"; let y = "wörld"; print(&x); print(&y); }"#;
+    assert_eq!(check("print", s, vec![]).0, "hello\nwörld\n");
+}
+
+#[test]
+fn test_ifelse() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"fn f(c: bool) -> i32 {
+                   let x = "a";
+                   let y = "b";
+                   if c { print(&x) } else { print(&y) };
+                   if c { 1 } else { 2 }
+               }"#;
+    assert_eq!(
+        check("ifelse_true", s, vec![Value::Bool(true)]),
+        ("a\n".to_string(), "1".to_string())
+    );
+    assert_eq!(
+        check("ifelse_false", s, vec![Value::Bool(false)]),
+        ("b\n".to_string(), "2".to_string())
+    );
+}
+
+#[test]
+fn test_tuples_and_references() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"fn f(a: i32) -> i32 {
+                   let mut x = (a, (true, (2, "a")));
+                   let r = &mut x.index(1).index(1).index(0);
+                   assign(r.deref, add(r.deref, 3));
+                   let y = x;
+                   add(y.index(0), y.index(1).index(1).index(0))
+               }"#;
+    assert_eq!(check("tuples", s, vec![Value::Int(10)]).1, "15");
+}
+
+#[test]
+fn test_arithmetic() {
+    if !has_node() {
+        return;
+    }
+    let s = "fn f(a: i32) -> i32 { mul(sub(a, 10), -3) }";
+    assert_eq!(check("arithmetic", s, vec![Value::Int(4)]).1, "18");
+}
+
+#[test]
+fn test_enums() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"enum Shape { Circle(i32), Rect(i32, i32), Empty }
+               fn f(a: i32) -> i32 {
+                   let s = Shape::Rect(a, 3);
+                   let t = Shape::Empty;
+                   let x = match s {
+                       Shape::Circle(r) => { r },
+                       Shape::Rect(w, h) => { add(w, h) },
+                       Shape::Empty => { 0 },
+                   };
+                   match t {
+                       Shape::Circle(r) => { r },
+                       _ => { add(x, 100) },
+                   }
+               }"#;
+    assert_eq!(check("enums", s, vec![Value::Int(2)]).1, "105");
+}
+
+#[test]
+fn test_boxes() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"fn f(a: i32) -> i32 {
+                   let mut b = box((a, box("x")));
+                   assign(b.deref.index(0), add(b.deref.index(0), 1));
+                   let c = b;
+                   print(&c.deref.index(1).deref);
+                   c.deref.index(0)
+               }"#;
+    assert_eq!(
+        check("boxes", s, vec![Value::Int(2)]),
+        ("x\n".to_string(), "3".to_string())
+    );
+}
+
+#[test]
+fn test_nested_loops() {
+    if !has_node() {
+        return;
+    }
+    let s = r#"fn f(n: i32) -> i32 {
+                   let mut sum = 0;
+                   let mut outer = true;
+                   while outer {
+                       let mut inner = true;
+                       while inner {
+                           assign(sum, add(sum, n));
+                           assign(inner, false);
+                       };
+                       loop {
+                           if outer { assign(outer, false); continue } else { break }
+                       };
+                   };
+                   sum
+               }"#;
+    assert_eq!(check("loops", s, vec![Value::Int(7)]).1, "7");
+}
+
+#[test]
+fn test_overflow_traps() {
+    let f = Function::parse("fn f(a: i32) -> i32 { add(a, 1) }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    assert!(wat::parse_str(f.to_wat()).is_ok());
+    assert!(f.to_wat().contains("unreachable"));
+}
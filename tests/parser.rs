@@ -41,3 +41,26 @@ fn test_parse7() {
     let f = Function::parse("fn f() { loop { loop { loop { } } } }").unwrap();
     println!("{f}");
 }
+
+#[test]
+fn test_parse_labels() {
+    let f =
+        Function::parse("fn f() { 'a: loop { 'b: loop { continue 'a; }; break 'a; } }").unwrap();
+    let s = f.to_string();
+    assert!(s.contains("'l0: loop"), "{}", s);
+    assert!(s.contains("continue 'l0"), "{}", s);
+}
+
+#[test]
+fn test_parse_labeled_while() {
+    let f = Function::parse("fn f(c: bool) { 'a: while c { loop { break 'a; } } }").unwrap();
+    let s = f.to_string();
+    assert!(s.contains("'l0: while"), "{}", s);
+    assert!(s.contains("break 'l0"), "{}", s);
+}
+
+#[test]
+fn test_err_label_out_of_scope() {
+    let f = Function::parse("fn f() { 'a: loop { 'b: loop { break 'a; }; continue 'b; } }");
+    assert!(f.is_none());
+}
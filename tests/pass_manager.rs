@@ -0,0 +1,101 @@
+use ownership_v4::ast::Function;
+use ownership_v4::pass_manager::Analysis;
+use ownership_v4::pass_manager::Pass;
+use ownership_v4::pass_manager::PassManager;
+
+#[test]
+fn test_pipeline() {
+    let mut f = Function::parse("fn f(a: i32) -> i32 { let x = a; let y = x; y }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    let mut pm = PassManager::from_pipeline(
+        "copy_propagation, dead_store_elimination, remove_unused_variables, borrowck",
+    )
+    .unwrap();
+    pm.run(&mut f);
+    assert_eq!(
+        pm.changes,
+        vec![
+            ("copy_propagation", true),
+            ("dead_store_elimination", true),
+            ("remove_unused_variables", true),
+            ("borrowck", false),
+        ]
+    );
+    assert_eq!(f.locals.len(), 1);
+}
+
+#[test]
+fn test_invalidation() {
+    let mut f = Function::parse("fn f(x: bool) { loop { if x { break } else { continue } } }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    let mut pm = PassManager::new();
+    for &a in Analysis::ALL {
+        pm.require(&mut f, a);
+    }
+
+    // A pass that changes nothing invalidates nothing.
+    let unchanged = Pass {
+        name: "unchanged",
+        requires: &[],
+        invalidates: Analysis::ALL,
+        run: |_| false,
+    };
+    assert!(!pm.run_pass(&mut f, unchanged));
+    assert!(Analysis::ALL.iter().all(|&a| pm.is_valid(a)));
+
+    // Analyses computed from an invalidated analysis are stale too, but no others.
+    let preds = Pass {
+        name: "preds",
+        requires: &[],
+        invalidates: &[Analysis::Predecessors],
+        run: |_| true,
+    };
+    assert!(pm.run_pass(&mut f, preds));
+    let stale = [
+        Analysis::Predecessors,
+        Analysis::Dominators,
+        Analysis::Loops,
+    ];
+    for &a in Analysis::ALL {
+        assert_eq!(pm.is_valid(a), !stale.contains(&a), "{:?}", a);
+    }
+    pm.require(&mut f, Analysis::Loops);
+    assert!(Analysis::ALL.iter().all(|&a| pm.is_valid(a)));
+
+    assert!(pm.run_pass(&mut f, Pass::MERGE_BLOCKS));
+    assert!(Analysis::ALL.iter().all(|&a| !pm.is_valid(a)));
+}
+
+#[test]
+fn test_invalidation_stmts() {
+    let mut f = Function::parse("fn f(a: i32) -> i32 { let x = a; let y = x; y }")
+        .unwrap()
+        .infer()
+        .into_mir();
+    let mut pm = PassManager::new();
+    for &a in Analysis::ALL {
+        pm.require(&mut f, a);
+    }
+    assert!(pm.run_pass(&mut f, Pass::COPY_PROPAGATION));
+    for &a in Analysis::ALL {
+        assert_eq!(pm.is_valid(a), a != Analysis::Liveness, "{:?}", a);
+    }
+    // Liveness is recomputed before dead store elimination needs it.
+    assert!(pm.run_pass(&mut f, Pass::DEAD_STORE_ELIMINATION));
+    assert!(!pm.is_valid(Analysis::Liveness));
+    assert!(!pm.run_pass(&mut f, Pass::DEAD_STORE_ELIMINATION));
+    assert!(pm.is_valid(Analysis::Liveness));
+}
+
+#[test]
+fn test_unknown_pass() {
+    let pm = PassManager::from_pipeline("copy_propagation, inline_everything");
+    assert_eq!(
+        pm.err(),
+        Some("Unknown pass: inline_everything".to_string())
+    );
+}
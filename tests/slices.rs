@@ -0,0 +1,185 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Error;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+// Splits a slice like `split_at_mut` with a constant midpoint, which borrowck can prove to be
+// disjoint.
+const SPLIT: &str = r#"fn split<'a>(s: &'a mut [String]) {
+                           let x = &mut s.deref[..1];
+                           let y = &mut s.deref[1..];
+                           assign(x.deref[0], "x");
+                           assign(y.deref[0], "y");
+                       }"#;
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse(
+        r#"fn f<'a>(s: &'a [i32]) -> i32 {
+               let a = [1, 2, 3];
+               let t = &a[1..3];
+               let u = &t.deref[1..];
+               add(s.deref[0], u.deref[0])
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+    let s = f.to_string();
+    assert!(s.contains("s: &'a [i32]"), "{}", s);
+    assert!(s.contains("&a[1..3]"), "{}", s);
+    assert!(s.contains("&t.deref[1..]"), "{}", s);
+}
+
+#[test]
+fn test_subslices() {
+    let s = r#"fn main() -> i32 {
+                   let mut a = [1, 2, 3, 4];
+                   let s = &mut a[1..];
+                   let t = &mut s.deref[1..3];
+                   assign(t.deref[0], 10);
+                   add(a[2], a[3])
+               }"#;
+    assert_eq!(run(s).0, Ok(Value::Int(14)));
+}
+
+#[test]
+fn test_split() {
+    let s = format!(
+        r#"{}
+           fn main() {{
+               let mut a = ["a", "b", "c"];
+               split(&mut a[1..]);
+               print(&a[0]);
+               print(&a[1]);
+               print(&a[2]);
+           }}"#,
+        SPLIT
+    );
+    borrowck(&s);
+    assert_eq!(run(&s).1, "a\nx\ny\n");
+}
+
+#[test]
+fn test_disjoint_ranges() {
+    let s = r#"fn main() {
+                   let mut a = ["a", "b", "c", "d"];
+                   let x = &mut a[0..2];
+                   let y = &mut a[2..3];
+                   let z = &mut a[3];
+                   assign(x.deref[1], "x");
+                   assign(y.deref[0], "y");
+                   assign(z.deref, "z");
+                   print(&a[1]);
+                   print(&a[2]);
+                   print(&a[3]);
+               }"#;
+    borrowck(s);
+    assert_eq!(run(s).1, "x\ny\nz\n");
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_overlapping_ranges() {
+    borrowck(
+        r#"fn main() {
+               let mut a = ["a", "b", "c", "d"];
+               let x = &mut a[0..3];
+               let y = &a[2..];
+               print(&x.deref[0]);
+               print(&y.deref[0]);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_overlapping_subslices() {
+    borrowck(
+        r#"fn main() {
+               let mut a = ["a", "b", "c", "d"];
+               let x = &mut a[1..];
+               let y = &a[0..3];
+               print(&x.deref[0]);
+               print(&y.deref[0]);
+           }"#,
+    );
+}
+
+// Without knowing the length of a slice, a range from its start cannot be proven to be disjoint
+// from a range up to its end.
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_overlapping_slice_ranges() {
+    borrowck(
+        r#"fn split<'a>(s: &'a mut [String]) {
+               let x = &mut s.deref[..2];
+               let y = &mut s.deref[1..];
+               print(&x.deref[0]);
+               print(&y.deref[0]);
+           }"#,
+    );
+}
+
+// An index that is only known at runtime may be anywhere in the slice, which is why splitting
+// at a runtime midpoint, like `split_at_mut` does, cannot be checked.
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_runtime_index_and_range() {
+    borrowck(
+        r#"fn split<'a>(s: &'a mut [String], i: usize) {
+               let x = &mut s.deref[i];
+               let y = &mut s.deref[1..];
+               print(x);
+               print(&y.deref[0]);
+           }"#,
+    );
+}
+
+#[test]
+fn test_err_index_out_of_bounds() {
+    let s = r#"fn main() -> i32 {
+                   let a = [1, 2, 3];
+                   let s = &a[1..];
+                   let i = 2;
+                   s.deref[i]
+               }"#;
+    assert_eq!(run(s).0, Err(Error::IndexOutOfBounds { index: 2, len: 2 }));
+}
+
+#[test]
+fn test_err_range_out_of_bounds() {
+    let s = r#"fn main() -> i32 {
+                   let a = [1, 2, 3];
+                   let s = &a[1..];
+                   let t = &s.deref[1..3];
+                   t.deref[0]
+               }"#;
+    assert_eq!(
+        run(s).0,
+        Err(Error::RangeOutOfBounds {
+            from: 1,
+            to: 3,
+            len: 2
+        })
+    );
+}
+
+#[test]
+#[should_panic(expected = "Index: range 2..5 is out of bounds of a")]
+fn test_err_constant_range_out_of_bounds() {
+    Function::parse("fn f() { let a = [1, 2, 3]; let s = &a[2..5]; }")
+        .unwrap()
+        .infer();
+}
+
+#[test]
+#[should_panic(expected = "a[1..] is a slice, which can only be used through a reference")]
+fn test_err_unsized() {
+    Function::parse("fn f() { let a = [1, 2, 3]; let s = a[1..]; }")
+        .unwrap()
+        .infer();
+}
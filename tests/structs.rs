@@ -0,0 +1,122 @@
+use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run_function;
+
+#[test]
+fn test_parse_and_display() {
+    let f = Function::parse(
+        r#"struct Point { x: i32, y: i32 }
+           struct Line { a: Point, b: Point, }
+           fn f() -> i32 {
+               let l = Line { a: Point { x: 1, y: 2 }, b: Point { y: 4, x: 3 } };
+               l.b.x
+           }"#,
+    )
+    .expect("Should parse")
+    .infer();
+    let s = f.to_string();
+    assert!(
+        s.contains("Line { a: Point { x: 1, y: 2 }, b: Point { y: 4, x: 3 } }"),
+        "{}",
+        s
+    );
+    assert!(s.contains("l.b.x"), "{}", s);
+}
+
+#[test]
+fn test_fields() {
+    let s = r#"struct Point { x: i32, y: i32 }
+               struct Line { a: Point, b: Point }
+               fn f() -> i32 {
+                   let mut l = Line { b: Point { x: 3, y: 4 }, a: Point { x: 1, y: 2 } };
+                   assign(l.a.y, 10);
+                   add(l.a.y, l.b.x)
+               }"#;
+    assert_eq!(run_function(s, vec![]).0, Ok(Value::Int(13)));
+}
+
+#[test]
+fn test_references_to_fields() {
+    let s = r#"struct Person { name: String, greeting: String }
+               fn f() {
+                   let mut p = Person { name: "Ferris", greeting: "hello" };
+                   let g = &mut p.greeting;
+                   assign(g.deref, "hi");
+                   print(&p.greeting);
+                   print(&p.name);
+               }"#;
+    assert_eq!(
+        run_function(s, vec![]),
+        (Ok(Value::Unit), "hi\nFerris\n".to_string())
+    );
+}
+
+#[test]
+fn test_disjoint_fields() {
+    borrowck(
+        r#"struct Pair { a: String, b: String }
+           fn f() {
+               let mut p = Pair { a: "a", b: "b" };
+               let x = &mut p.a;
+               let y = &mut p.b;
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_same_field() {
+    borrowck(
+        r#"struct Pair { a: String, b: String }
+           fn f() {
+               let mut p = Pair { a: "a", b: "b" };
+               let x = &mut p.a;
+               let y = &p.a;
+               print(x);
+               print(y);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_err_field_and_whole() {
+    borrowck(
+        r#"struct Pair { a: String, b: String }
+           fn f() {
+               let mut p = Pair { a: "a", b: "b" };
+               let x = &mut p.a;
+               let y = &p;
+               print(x);
+               print(&y.deref.b);
+           }"#,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Struct: field b of Pair must be given once")]
+fn test_err_missing_field() {
+    Function::parse(
+        r#"struct Pair { a: String, b: String }
+           fn f() { let p = Pair { a: "a" }; }"#,
+    )
+    .unwrap()
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Struct: Pair has no field c")]
+fn test_err_unknown_field() {
+    Function::parse(
+        r#"struct Pair { a: String, b: String }
+           fn f() { let p = Pair { a: "a", b: "b", c: "c" }; }"#,
+    )
+    .unwrap()
+    .infer();
+}
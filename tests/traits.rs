@@ -0,0 +1,200 @@
+use ownership_v4::ast::Program;
+use ownership_v4::interpreter::Value;
+
+mod common;
+
+use common::borrowck;
+use common::run;
+
+const S: &str = r#"struct S { x: i32 }
+                   trait Get { fn get(&self) -> &i32; }
+                   trait Inc { fn inc(&mut self, n: i32); }
+                   impl Get for S { fn get(&self) -> &i32 { &self.deref.x } }
+                   impl Inc for S {
+                       fn inc(&mut self, n: i32) { assign(self.deref.x, add(self.deref.x, n)) }
+                   }"#;
+
+#[test]
+fn test_parse_and_display() {
+    let p = Program::parse(&format!(
+        "{}\nfn main() -> i32 {{ let s = S {{ x: 1 }}; let r = s.get(); r.deref }}",
+        S
+    ))
+    .expect("Should parse");
+    assert_eq!(p.traits.len(), 2);
+    assert_eq!(p.traits[0].methods[0].lifetimes[0].id, "'this");
+    assert_eq!(p.impls[1].methods, vec!["inc".to_string()]);
+    let s = p.to_string();
+    assert!(
+        s.contains("fn <S as Get>::get<'this>(self: &'this S)"),
+        "{}",
+        s
+    );
+    assert!(s.contains("s.get()"), "{}", s);
+    let s = p.infer().to_string();
+    assert!(s.contains("<S as Get>::get(&s)"), "{}", s);
+}
+
+#[test]
+fn test_auto_ref() {
+    let s = format!(
+        "{}\nfn main() -> i32 {{ let s = S {{ x: 1 }}; let r = s.get(); r.deref }}",
+        S
+    );
+    assert_eq!(run(&s).0, Ok(Value::Int(1)));
+    borrowck(&s);
+}
+
+#[test]
+fn test_auto_ref_mut() {
+    let s = format!(
+        "{}\nfn main() -> i32 {{ let mut s = S {{ x: 1 }}; s.inc(2); s.inc(3); s.x }}",
+        S
+    );
+    assert_eq!(run(&s).0, Ok(Value::Int(6)));
+    borrowck(&s);
+}
+
+// The receiver is dereferenced until a type with the method is found.
+#[test]
+fn test_auto_deref() {
+    let s = format!(
+        r#"{}
+           fn main() -> i32 {{
+               let s = S {{ x: 1 }};
+               let r = &s;
+               let rr = &r;
+               let mut b = box(S {{ x: 2 }});
+               b.inc(1);
+               let x = rr.get();
+               let y = b.get();
+               add(x.deref, y.deref)
+           }}"#,
+        S
+    );
+    assert_eq!(run(&s).0, Ok(Value::Int(4)));
+    borrowck(&s);
+}
+
+#[test]
+fn test_self_by_value() {
+    let s = r#"struct T { s: String }
+               trait Take { fn take(self) -> String; }
+               impl Take for T { fn take(self) -> String { self.s } }
+               fn main() {
+                   let t = T { s: "taken" };
+                   let s = t.take();
+                   print(&s);
+               }"#;
+    assert_eq!(run(s).1, "taken\n");
+    borrowck(s);
+}
+
+#[test]
+fn test_explicit_lifetime() {
+    let s = r#"struct P { x: String, y: String }
+               trait First { fn first<'a>(&'a self, other: &'a String) -> &'a String; }
+               impl First for P {
+                   fn first<'a>(&'a self, other: &'a String) -> &'a String { &self.deref.x }
+               }
+               fn main() {
+                   let p = P { x: "x", y: "y" };
+                   let o = "o";
+                   let r = p.first(&o);
+                   print(r);
+               }"#;
+    assert_eq!(run(s).1, "x\n");
+    borrowck(s);
+}
+
+// The borrow of the receiver ends once the returned reference is no longer used.
+#[test]
+fn test_mutate_after_last_use() {
+    borrowck(&format!(
+        r#"{}
+           fn main() -> i32 {{
+               let mut s = S {{ x: 1 }};
+               let r = s.get();
+               let x = r.deref;
+               s.inc(x);
+               s.x
+           }}"#,
+        S
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_assign_while_borrowed_by_method() {
+    borrowck(&format!(
+        r#"{}
+           fn main() -> i32 {{
+               let mut s = S {{ x: 1 }};
+               let r = s.get();
+               assign(s.x, 2);
+               r.deref
+           }}"#,
+        S
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Borrowck error")]
+fn test_mutable_method_while_borrowed() {
+    borrowck(&format!(
+        r#"{}
+           fn main() -> i32 {{
+               let mut s = S {{ x: 1 }};
+               let r = s.get();
+               s.inc(1);
+               r.deref
+           }}"#,
+        S
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Method: cannot borrow r.deref as mutable")]
+fn test_mutable_method_through_shared_ref() {
+    Program::parse(&format!(
+        "{}\nfn main() {{ let mut s = S {{ x: 1 }}; let r = &s; r.inc(1) }}",
+        S
+    ))
+    .expect("Should parse")
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Method: no method len for S")]
+fn test_no_method() {
+    Program::parse(&format!(
+        "{}\nfn main() -> i32 {{ let s = S {{ x: 1 }}; s.len() }}",
+        S
+    ))
+    .expect("Should parse")
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Impl: method get of Get is missing for S")]
+fn test_missing_method() {
+    Program::parse(
+        r#"struct S { x: i32 }
+           trait Get { fn get(&self) -> &i32; }
+           impl Get for S { }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
+
+#[test]
+#[should_panic(expected = "Impl: <S as Get>::get does not match its signature in Get")]
+fn test_mismatched_signature() {
+    Program::parse(
+        r#"struct S { x: i32 }
+           trait Get { fn get(&self) -> &i32; }
+           impl Get for S { fn get(&self) -> i32 { self.deref.x } }"#,
+    )
+    .expect("Should parse")
+    .infer();
+}
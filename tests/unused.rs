@@ -1,4 +1,9 @@
 use ownership_v4::ast::Function;
+use ownership_v4::interpreter::Value;
+use ownership_v4::mir::Constant;
+use ownership_v4::mir::Operand;
+use ownership_v4::mir::Operation;
+use ownership_v4::mir::Rvalue;
 
 #[test]
 fn test_unused0() {
@@ -11,3 +16,58 @@ fn test_unused0() {
         .with_remove_unused_variables()
         .inspect();
 }
+
+// A tuple is initialized one field at a time, and those writes are used if the tuple is.
+#[test]
+fn test_unused_fields() {
+    let f = Function::parse("fn f() -> (i32, i32) { let t = (1, 2); let u = 3; t }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_remove_unused_variables();
+    assert_eq!(
+        f.interpret(vec![]),
+        Ok(Value::Tuple(vec![Value::Int(1), Value::Int(2)]))
+    );
+    let three = f.blocks.iter().flat_map(|b| &b.stmts).any(|s| {
+        matches!(
+            &s.op,
+            Operation::Assign(_, Rvalue::Use(Operand::Constant(Constant::Int(3))))
+        )
+    });
+    assert!(!three, "{}", f);
+}
+
+// A write through a reference is a write to its referent.
+#[test]
+fn test_unused_write_through_reference() {
+    let f =
+        Function::parse("fn f() -> i32 { let mut x = 1; let r = &mut x; assign(r.deref, 2); x }")
+            .unwrap()
+            .infer()
+            .into_mir()
+            .with_remove_unused_variables();
+    assert_eq!(f.interpret(vec![]), Ok(Value::Int(2)));
+}
+
+// A variable that is only used as a condition decides control flow, and so is used.
+#[test]
+fn test_unused_condition() {
+    let f = Function::parse("fn f(c: bool) -> i32 { let d = c; if d { 1 } else { 2 } }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_remove_unused_variables();
+    assert_eq!(f.interpret(vec![Value::Bool(false)]), Ok(Value::Int(2)));
+}
+
+// Each copy makes the next variable of the chain used, which takes one more round to see.
+#[test]
+fn test_unused_chain() {
+    let f = Function::parse("fn f() -> i32 { let a = 1; let b = a; let c = b; c }")
+        .unwrap()
+        .infer()
+        .into_mir()
+        .with_remove_unused_variables();
+    assert_eq!(f.interpret(vec![]), Ok(Value::Int(1)));
+}
@@ -0,0 +1,146 @@
+use ownership_v4::ast::Function;
+use ownership_v4::ast::Place;
+use ownership_v4::ast::Program;
+use ownership_v4::ast::Type;
+use ownership_v4::mir;
+use ownership_v4::mir::Operation;
+use ownership_v4::mir::Terminator;
+
+fn lower(s: &str) -> mir::Function {
+    Function::parse(s).unwrap().infer().into_mir()
+}
+
+#[test]
+fn test_valid0() {
+    let f = lower("fn f(a: i32) -> i32 { let x = a; let y = x; y }");
+    assert_eq!(f.validate(), Ok(()));
+}
+
+#[test]
+fn test_valid_break_continue() {
+    let f = lower("fn f(x: bool) { loop { let y = 1; if x { break } else { continue } } }");
+    assert_eq!(f.validate(), Ok(()));
+}
+
+#[test]
+fn test_invalid_target() {
+    let mut f = lower("fn f() -> i32 { if true { 1 } else { 2 } }");
+    f.blocks[0].terminator = Some(Terminator::Goto(42));
+    assert_eq!(
+        f.validate(),
+        Err(vec!["bb0 jumps to non-existent bb42".to_string()])
+    );
+}
+
+#[test]
+fn test_invalid_id() {
+    let mut f = lower("fn f() -> i32 { if true { 1 } else { 2 } }");
+    f.blocks[1].id = 2;
+    assert_eq!(f.validate(), Err(vec!["bb1 has id 2".to_string()]));
+}
+
+#[test]
+fn test_invalid_undeclared() {
+    let mut f = lower("fn f() -> i32 { let x = 1; x }");
+    f.locals.retain(|l| l.id != "_1");
+    assert!(f.validate().is_err());
+}
+
+#[test]
+fn test_invalid_storage() {
+    let mut f = lower("fn f() -> i32 { let x = 1; x }");
+    f.blocks[0]
+        .stmts
+        .retain(|s| !matches!(s.op, Operation::StorageDead(_)));
+    assert_eq!(
+        f.validate(),
+        Err(vec!["bb0: returns with live storage for _1".to_string()])
+    );
+}
+
+#[test]
+fn test_invalid_type() {
+    let mut f = lower("fn f() -> i32 { let x = 1; x }");
+    let stmt = f.blocks[0]
+        .stmts
+        .iter_mut()
+        .find(|s| matches!(s.op, Operation::Assign(..)))
+        .unwrap();
+    if let Operation::Assign(_, rvalue) = &mut stmt.op {
+        *rvalue = mir::Rvalue::Use(mir::Operand::Constant(mir::Constant::Bool(true)));
+    }
+    assert!(f.validate().is_err());
+}
+
+#[test]
+fn test_valid_nested_tuple_place() {
+    let f = lower("fn f() -> bool { let t = ((1, true), 2); t.index(0).index(1) }");
+    assert_eq!(f.validate(), Ok(()));
+}
+
+fn lower_program(s: &str) -> Vec<mir::Function> {
+    Program::parse(s).unwrap().infer().into_mir()
+}
+
+fn call_args(f: &mut mir::Function) -> (&mut Place, &mut Vec<mir::Operand>) {
+    f.blocks
+        .iter_mut()
+        .flat_map(|b| b.stmts.iter_mut())
+        .find_map(|s| match &mut s.op {
+            Operation::Call { dest, args, .. } => Some((dest, args)),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_valid_call() {
+    let fs = lower_program(
+        r#"fn g(a: i32, b: bool) -> i32 { a }
+           fn f() -> i32 { let x = add(1, 2); g(x, true) }"#,
+    );
+    for f in &fs {
+        assert_eq!(f.validate_with_functions(&fs), Ok(()));
+    }
+}
+
+#[test]
+fn test_invalid_call_argument() {
+    let mut fs = lower_program(
+        r#"fn g(a: i32, b: bool) -> i32 { a }
+           fn f() -> i32 { g(1, true) }"#,
+    );
+    let mut f = fs.pop().unwrap();
+    let (_, args) = call_args(&mut f);
+    args.swap(0, 1);
+    assert_eq!(f.validate(), Ok(()));
+    assert_eq!(
+        f.validate_with_functions(&fs),
+        Err(vec![
+            "bb0: argument 0 of g has type Bool, expected Int".to_string(),
+            "bb0: argument 1 of g has type Int, expected Bool".to_string(),
+        ])
+    );
+    let (_, args) = call_args(&mut f);
+    args.pop();
+    assert_eq!(
+        f.validate_with_functions(&fs),
+        Err(vec!["bb0: g takes 2 arguments, found 1".to_string()])
+    );
+}
+
+#[test]
+fn test_invalid_builtin_call() {
+    let mut f = lower("fn f() -> i32 { let x = add(1, 2); x }");
+    let (_, args) = call_args(&mut f);
+    args[1] = mir::Operand::Constant(mir::Constant::Bool(true));
+    assert_eq!(
+        f.validate(),
+        Err(vec![
+            "bb0: argument 1 of add has type Bool, expected Int".to_string()
+        ])
+    );
+    let (dest, _) = call_args(&mut f);
+    dest.local.ty = Type::Bool;
+    assert!(f.validate().is_err());
+}